@@ -32,6 +32,10 @@ use winapi::um::winuser::{
 use super::util::{FromWide, ToWide};
 use crate::clipboard::{ClipboardFormat, FormatId};
 
+/// The name of the native format for HTML, which [`ClipboardFormat::HTML`]
+/// is mapped to.
+const CF_HTML: &str = "HTML Format";
+
 #[derive(Debug, Clone, Default)]
 pub struct Clipboard;
 
@@ -133,6 +137,9 @@ impl Clipboard {
                     ptr::copy_nonoverlapping(locked, dest.as_mut_ptr(), size);
                     dest.set_len(size);
                     GlobalUnlock(handle);
+                    if format == ClipboardFormat::HTML {
+                        return html_fragment(&dest);
+                    }
                     Some(dest)
                 } else {
                     None
@@ -176,20 +183,79 @@ unsafe fn make_handle(format: &ClipboardFormat) -> HANDLE {
         GlobalUnlock(handle);
         handle
     } else {
-        let handle = GlobalAlloc(GMEM_MOVEABLE, format.data.len() * mem::size_of::<CHAR>());
+        let html;
+        let data = if format.identifier == ClipboardFormat::HTML {
+            html = wrap_html_fragment(&format.data);
+            &html
+        } else {
+            &format.data
+        };
+        let handle = GlobalAlloc(GMEM_MOVEABLE, data.len() * mem::size_of::<CHAR>());
         let locked = GlobalLock(handle) as *mut u8;
-        ptr::copy_nonoverlapping(format.data.as_ptr(), locked, format.data.len());
+        ptr::copy_nonoverlapping(data.as_ptr(), locked, data.len());
         GlobalUnlock(handle);
         handle
     }
 }
 
+/// Wrap an HTML fragment in the header and the document required by the
+/// native "HTML Format".
+///
+/// See <https://docs.microsoft.com/en-us/windows/win32/dataxchg/html-clipboard-format>.
+fn wrap_html_fragment(fragment: &[u8]) -> Vec<u8> {
+    const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+    let header = |start_html: usize, end_html: usize, start_fragment: usize, end_fragment| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\n\
+             StartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_fragment, end_fragment
+        )
+    };
+    // the offsets have a fixed width, so the header's length does not depend on them.
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+
+    let mut data = header(start_html, end_html, start_fragment, end_fragment).into_bytes();
+    data.extend_from_slice(PREFIX.as_bytes());
+    data.extend_from_slice(fragment);
+    data.extend_from_slice(SUFFIX.as_bytes());
+    data
+}
+
+/// Extract the fragment from data in the native "HTML Format".
+fn html_fragment(data: &[u8]) -> Option<Vec<u8>> {
+    let offset = |key: &str| -> Option<usize> {
+        let start = find_bytes(data, key.as_bytes())? + key.len();
+        let digits = data[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        std::str::from_utf8(&data[start..start + digits])
+            .ok()?
+            .parse()
+            .ok()
+    };
+    let start = offset("StartFragment:")?;
+    let end = offset("EndFragment:")?;
+    data.get(start..end).map(<[u8]>::to_vec)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
 fn get_format_id(format: FormatId) -> Option<UINT> {
     if let Some((id, _)) = STANDARD_FORMATS.iter().find(|(_, s)| s == &format) {
         return Some(*id);
     }
     match format {
         ClipboardFormat::TEXT => Some(CF_UNICODETEXT),
+        ClipboardFormat::HTML => register_identifier(CF_HTML),
         other => register_identifier(other),
     }
 }
@@ -326,3 +392,19 @@ fn get_standard_format_name(format: UINT) -> Option<&'static str> {
         .find(|(id, _)| *id == format)
         .map(|(_, s)| *s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_format_round_trip() {
+        let fragment = "<b>bold</b> &amp; caf\u{e9}";
+        let data = wrap_html_fragment(fragment.as_bytes());
+        let text = std::str::from_utf8(&data).unwrap();
+        assert!(text.starts_with("Version:0.9\r\nStartHTML:"));
+        assert!(text.ends_with("</body></html>"));
+        assert_eq!(html_fragment(&data).unwrap(), fragment.as_bytes());
+        assert!(html_fragment(b"<b>no header</b>").is_none());
+    }
+}
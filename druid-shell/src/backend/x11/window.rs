@@ -44,7 +44,7 @@ use x11rb::xcb_ffi::XCBConnection;
 #[cfg(feature = "raw-win-handle")]
 use raw_window_handle::{unix::XcbHandle, HasRawWindowHandle, RawWindowHandle};
 
-use crate::common_util::IdleCallback;
+use crate::common_util::{ClickCounter, IdleCallback};
 use crate::dialog::FileDialogOptions;
 use crate::error::Error as ShellError;
 use crate::keyboard::{KeyState, Modifiers};
@@ -470,6 +470,7 @@ impl WindowBuilder {
             present_data: RefCell::new(present_data),
             buffers,
             active_text_field: Cell::new(None),
+            click_counter: ClickCounter::default(),
         });
 
         window.set_title(&self.title);
@@ -588,6 +589,8 @@ pub(crate) struct Window {
     present_data: RefCell<Option<PresentData>>,
     buffers: RefCell<Buffers>,
     active_text_field: Cell<Option<TextFieldToken>>,
+    /// X11 doesn't report multi-clicks, so we count them ourselves.
+    click_counter: ClickCounter,
 }
 
 /// A collection of pixmaps for rendering to. This gets used in two different ways: if the present
@@ -1055,14 +1058,14 @@ impl Window {
     ) -> Result<(), Error> {
         let button = mouse_button(button_press.detail);
         let scale = self.scale.get();
+        let pos = Point::new(button_press.event_x as f64, button_press.event_y as f64).to_dp(scale);
         let mouse_event = MouseEvent {
-            pos: Point::new(button_press.event_x as f64, button_press.event_y as f64).to_dp(scale),
+            pos,
             // The xcb state field doesn't include the newly pressed button, but
             // druid wants it to be included.
             buttons: mouse_buttons(button_press.state).with(button),
             mods: key_mods(button_press.state),
-            // TODO: detect the count
-            count: 1,
+            count: self.click_counter.count_for_click(pos),
             focus: false,
             button,
             wheel_delta: Vec2::ZERO,
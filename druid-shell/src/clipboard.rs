@@ -220,6 +220,7 @@ cfg_if::cfg_if! {
             pub const PDF: &'static str = "com.adobe.pdf";
             pub const TEXT: &'static str = "public.utf8-plain-text";
             pub const SVG: &'static str = "public.svg-image";
+            pub const HTML: &'static str = "public.html";
         }
     } else {
         impl ClipboardFormat {
@@ -233,6 +234,9 @@ cfg_if::cfg_if! {
             }
             pub const PDF: &'static str = "application/pdf";
             pub const SVG: &'static str = "image/svg+xml";
+            // On Windows this is mapped to the native "HTML Format"; the
+            // header that it requires is added and removed by the backend.
+            pub const HTML: &'static str = "text/html";
         }
     }
 }
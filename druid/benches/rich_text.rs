@@ -0,0 +1,95 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks for laying out rich text.
+//!
+//! Run with `cargo bench -p druid --bench rich_text`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use druid::piet::Device;
+use druid::text::{RichText, RichTextBuilder, TextLayout};
+use druid::{theme, Color, Env, FontDescriptor, FontFamily, RenderContext};
+
+/// The number of tokens in the highlighted text.
+const TOKENS: usize = 10_000;
+
+/// Text highlighted one token at a time, as by a syntax highlighter, with
+/// the color changing every hundred tokens.
+fn highlighted_text() -> RichText {
+    let keyword = Color::rgb8(0xcc, 0x78, 0x32);
+    let mut builder = RichTextBuilder::new();
+    for i in 0..TOKENS {
+        let color = if (i / 100) % 2 == 0 {
+            Color::WHITE
+        } else {
+            keyword.clone()
+        };
+        builder.push("token ").text_color(color);
+    }
+    builder.build()
+}
+
+fn env() -> Env {
+    Env::empty()
+        .adding(
+            theme::UI_FONT,
+            FontDescriptor::new(FontFamily::SYSTEM_UI).with_size(15.0),
+        )
+        .adding(theme::TEXT_COLOR, Color::BLACK)
+}
+
+fn coalesce(c: &mut Criterion) {
+    let text = highlighted_text();
+    c.bench_function("coalesce 10k token spans", |b| {
+        b.iter(|| {
+            let mut text = text.clone();
+            text.coalesce_attributes();
+            text
+        })
+    });
+}
+
+fn layout(c: &mut Criterion) {
+    let mut device = Device::new().unwrap();
+    let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+    let mut piet = target.render_context();
+    let env = env();
+
+    let text = highlighted_text();
+    let mut coalesced = text.clone();
+    coalesced.coalesce_attributes();
+
+    let mut group = c.benchmark_group("layout 10k highlighted tokens");
+    group.sample_size(20);
+    group.bench_function("per-token spans", |b| {
+        b.iter(|| {
+            let mut layout = TextLayout::<RichText>::from_text(text.clone());
+            layout.rebuild_if_needed(piet.text(), &env);
+            layout
+        })
+    });
+    group.bench_function("coalesced spans", |b| {
+        b.iter(|| {
+            let mut layout = TextLayout::<RichText>::from_text(coalesced.clone());
+            layout.rebuild_if_needed(piet.text(), &env);
+            layout
+        })
+    });
+    group.finish();
+
+    piet.finish().unwrap();
+}
+
+criterion_group!(benches, coalesce, layout);
+criterion_main!(benches);
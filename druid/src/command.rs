@@ -222,6 +222,16 @@ pub mod sys {
     pub const CONFIGURE_WINDOW: Selector<WindowConfig> =
         Selector::new("druid-builtin.configure-window");
 
+    /// Set the title of a window, replacing the title it was created with.
+    ///
+    /// If the window was created with a localized or dynamic title, it will
+    /// no longer be updated.
+    ///
+    /// The command must target a specific window.
+    /// When calling `submit_command` on a `Widget`s context, passing `None` as target
+    /// will automatically target the window containing the widget.
+    pub const SET_WINDOW_TITLE: Selector<String> = Selector::new("druid-builtin.set-window-title");
+
     /// Display a context (right-click) menu. The payload must be the [`ContextMenu`]
     /// object to be displayed.
     ///
@@ -437,6 +447,18 @@ impl Command {
         self.target
     }
 
+    /// Returns the name of the [`Selector`] this `Command` was created with.
+    #[cfg(feature = "serde")]
+    pub(crate) fn selector_name(&self) -> &'static str {
+        self.symbol
+    }
+
+    /// Returns the payload of this `Command` if it has type `T`, regardless
+    /// of its selector.
+    pub(crate) fn payload<T: Any>(&self) -> Option<&T> {
+        self.payload.downcast_ref()
+    }
+
     /// Returns `true` if `self` matches this `selector`.
     pub fn is<T>(&self, selector: Selector<T>) -> bool {
         self.symbol == selector.symbol()
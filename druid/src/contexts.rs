@@ -31,9 +31,9 @@ use crate::shell::text::Event as ImeInvalidation;
 use crate::shell::Region;
 use crate::text::{ImeHandlerRef, TextFieldRegistration};
 use crate::{
-    commands, sub_window::SubWindowDesc, widget::Widget, Affine, Command, Cursor, Data, Env,
-    ExtEventSink, Insets, Menu, Notification, Point, Rect, SingleUse, Size, Target, TimerToken,
-    Vec2, WidgetId, WindowConfig, WindowDesc, WindowHandle, WindowId,
+    commands, sub_window::SubWindowDesc, widget::Widget, Affine, Application, Clipboard, Command,
+    Cursor, Data, Env, ExtEventSink, Insets, Menu, Notification, Point, Rect, SingleUse, Size,
+    Target, TimerToken, Vec2, WidgetId, WindowConfig, WindowDesc, WindowHandle, WindowId,
 };
 
 /// A macro for implementing methods on multiple contexts.
@@ -59,6 +59,8 @@ pub(crate) struct ContextState<'a> {
     pub(crate) text: PietText,
     /// The id of the widget that currently has focus.
     pub(crate) focus_widget: Option<WidgetId>,
+    /// `true` if the focus change being routed moves backwards through the focus chain.
+    pub(crate) focus_reversed: bool,
     pub(crate) root_app_data_type: TypeId,
 }
 
@@ -112,6 +114,9 @@ pub struct LayoutCtx<'a, 'b> {
     pub(crate) state: &'a mut ContextState<'b>,
     pub(crate) widget_state: &'a mut WidgetState,
     pub(crate) mouse_pos: Option<Point>,
+    /// `true` if this layout pass is only measuring widgets, in which case
+    /// their layout state is restored once they have been laid out.
+    pub(crate) measuring: bool,
 }
 
 /// Z-order paint operations with transformations.
@@ -537,6 +542,30 @@ impl EventCtx<'_, '_> {
         // TODO: plumb mouse grab through to platform (through druid-shell)
     }
 
+    /// Returns a handle to the system clipboard.
+    ///
+    /// This can be used to copy and paste text, or data in custom formats.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use druid::EventCtx;
+    /// fn copy(ctx: &mut EventCtx, text: &str) {
+    ///     ctx.clipboard().put_string(text);
+    /// }
+    ///
+    /// fn paste(ctx: &mut EventCtx) -> Option<String> {
+    ///     ctx.clipboard().get_string()
+    /// }
+    /// ```
+    ///
+    /// Pasting is also delivered to the focused widget as [`Event::Paste`].
+    ///
+    /// [`Event::Paste`]: crate::Event::Paste
+    pub fn clipboard(&self) -> Clipboard {
+        Application::global().clipboard()
+    }
+
     /// Create a new window.
     /// `T` must be the application's root `Data` type (the type provided to [`AppLauncher::launch`]).
     ///
@@ -752,6 +781,18 @@ impl LifeCycleCtx<'_, '_> {
         self.widget_state.focus_chain.push(self.widget_id());
     }
 
+    /// Returns `true` if the current [`LifeCycle::FocusChanged`] event was
+    /// caused by moving focus backwards through the focus chain, for instance
+    /// with Shift+Tab.
+    ///
+    /// Widgets with several focusable parts can use this to decide which
+    /// part to focus first.
+    ///
+    /// [`LifeCycle::FocusChanged`]: enum.LifeCycle.html#variant.FocusChanged
+    pub fn is_focus_reversed(&self) -> bool {
+        self.state.focus_reversed
+    }
+
     /// Register this widget as accepting text input.
     pub fn register_text_input(&mut self, document: impl ImeHandlerRef + 'static) {
         let registration = TextFieldRegistration {
@@ -899,6 +940,7 @@ impl<'a> ContextState<'a> {
             window,
             window_id,
             focus_widget,
+            focus_reversed: false,
             text: window.text(),
             root_app_data_type: TypeId::of::<T>(),
         }
@@ -163,6 +163,19 @@ pub struct WidgetState {
     pub(crate) text_registrations: Vec<TextFieldRegistration>,
 }
 
+/// The parts of a [`WidgetState`] that are set during layout, which are saved
+/// and restored when a widget is only being measured.
+#[derive(Clone, Copy)]
+struct LayoutState {
+    size: Size,
+    origin: Point,
+    paint_insets: Insets,
+    baseline_offset: f64,
+    needs_layout: bool,
+    needs_window_origin: bool,
+    is_expecting_set_origin_call: bool,
+}
+
 /// Methods by which a widget can attempt to change focus state.
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum FocusChange {
@@ -273,6 +286,9 @@ impl<T, W: Widget<T>> WidgetPod<T, W> {
     /// [`Size`]: struct.Size.html
     /// [`LifeCycle::Size`]: enum.LifeCycle.html#variant.Size
     pub fn set_origin(&mut self, ctx: &mut LayoutCtx, data: &T, env: &Env, origin: Point) {
+        if ctx.measuring {
+            return;
+        }
         self.state.origin = origin;
         self.state.is_expecting_set_origin_call = false;
         let layout_rect = self.layout_rect();
@@ -569,6 +585,7 @@ impl<T: Data, W: Widget<T>> WidgetPod<T, W> {
             return Size::ZERO;
         }
 
+        let saved = ctx.measuring.then(|| self.state.layout_state());
         self.state.needs_layout = false;
         self.state.needs_window_origin = false;
         self.state.is_expecting_set_origin_call = true;
@@ -582,8 +599,16 @@ impl<T: Data, W: Widget<T>> WidgetPod<T, W> {
             widget_state: &mut self.state,
             state: ctx.state,
             mouse_pos: child_mouse_pos,
+            measuring: ctx.measuring,
         };
 
+        if let Some(saved) = saved {
+            let size = self.inner.layout(&mut child_ctx, bc, data, env);
+            child_ctx.widget_state.restore_layout_state(saved);
+            ctx.widget_state.merge_up(&mut child_ctx.widget_state);
+            return size;
+        }
+
         let new_size = self.inner.layout(&mut child_ctx, bc, data, env);
         if new_size != prev_size {
             let mut child_ctx = LifeCycleCtx {
@@ -605,6 +630,45 @@ impl<T: Data, W: Widget<T>> WidgetPod<T, W> {
         new_size
     }
 
+    /// Compute the size a widget would have under the given constraints,
+    /// without laying it out.
+    ///
+    /// Container widgets can call this from their [`layout`] method to
+    /// measure a child before calling [`layout`] on it. See
+    /// [`Widget::intrinsic_size`] for more information.
+    ///
+    /// [`layout`]: trait.Widget.html#tymethod.layout
+    /// [`Widget::intrinsic_size`]: trait.Widget.html#method.intrinsic_size
+    pub fn intrinsic_size(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &T,
+        env: &Env,
+    ) -> Size {
+        if !self.is_initialized() {
+            debug_panic!(
+                "{:?}: intrinsic_size method called before receiving WidgetAdded.",
+                ctx.widget_id()
+            );
+            return Size::ZERO;
+        }
+
+        let child_mouse_pos = ctx
+            .mouse_pos
+            .map(|pos| pos - self.layout_rect().origin().to_vec2() + self.viewport_offset());
+        let saved = self.state.layout_state();
+        let mut child_ctx = LayoutCtx {
+            widget_state: &mut self.state,
+            state: ctx.state,
+            mouse_pos: child_mouse_pos,
+            measuring: true,
+        };
+        let size = self.inner.intrinsic_size(&mut child_ctx, bc, data, env);
+        self.state.restore_layout_state(saved);
+        size
+    }
+
     fn log_layout_issues(&self, size: Size) {
         if size.width.is_infinite() {
             let name = self.widget().type_name();
@@ -1272,6 +1336,28 @@ impl WidgetState {
     /// This will also clear some requests in the child state.
     ///
     /// This method is idempotent and can be called multiple times.
+    fn layout_state(&self) -> LayoutState {
+        LayoutState {
+            size: self.size,
+            origin: self.origin,
+            paint_insets: self.paint_insets,
+            baseline_offset: self.baseline_offset,
+            needs_layout: self.needs_layout,
+            needs_window_origin: self.needs_window_origin,
+            is_expecting_set_origin_call: self.is_expecting_set_origin_call,
+        }
+    }
+
+    fn restore_layout_state(&mut self, saved: LayoutState) {
+        self.size = saved.size;
+        self.origin = saved.origin;
+        self.paint_insets = saved.paint_insets;
+        self.baseline_offset = saved.baseline_offset;
+        self.needs_layout = saved.needs_layout;
+        self.needs_window_origin = saved.needs_window_origin;
+        self.is_expecting_set_origin_call = saved.is_expecting_set_origin_call;
+    }
+
     fn merge_up(&mut self, child_state: &mut WidgetState) {
         trace!(
             "merge_up self.id={:?} child.id={:?}",
@@ -88,10 +88,20 @@ struct EnvImpl {
 /// [`Env`]: struct.Env.html
 #[derive(Clone, Debug, PartialEq, Data)]
 pub struct Key<T> {
-    key: &'static str,
+    key: KeyName,
     value_type: PhantomData<T>,
 }
 
+/// The string value of a [`Key`].
+///
+/// Keys are normally created from a `&'static str`, but keys that are only
+/// known at runtime (for instance when deserializing rich text) own their name.
+#[derive(Clone)]
+enum KeyName {
+    Static(&'static str),
+    Owned(ArcStr),
+}
+
 /// A dynamic type representing all values that can be stored in an environment.
 #[derive(Clone, Data)]
 #[allow(missing_docs)]
@@ -263,10 +273,10 @@ impl Env {
     pub fn try_get<V: ValueType>(&self, key: impl Borrow<Key<V>>) -> Result<V, MissingKeyError> {
         self.0
             .map
-            .get(key.borrow().key)
+            .get(key.borrow().name())
             .map(|value| value.to_inner_unchecked())
-            .ok_or(MissingKeyError {
-                key: key.borrow().key.into(),
+            .ok_or_else(|| MissingKeyError {
+                key: key.borrow().name().into(),
             })
     }
 
@@ -297,9 +307,12 @@ impl Env {
     ///
     /// [`Value`]: enum.Value.html
     pub fn try_get_untyped<V>(&self, key: impl Borrow<Key<V>>) -> Result<&Value, MissingKeyError> {
-        self.0.map.get(key.borrow().key).ok_or(MissingKeyError {
-            key: key.borrow().key.into(),
-        })
+        self.0
+            .map
+            .get(key.borrow().name())
+            .ok_or_else(|| MissingKeyError {
+                key: key.borrow().name().into(),
+            })
     }
 
     /// Gets the entire contents of the `Env`, in key-value pairs.
@@ -396,10 +409,51 @@ impl<T> Key<T> {
     /// ```
     pub const fn new(key: &'static str) -> Self {
         Key {
-            key,
+            key: KeyName::Static(key),
             value_type: PhantomData,
         }
     }
+
+    /// Create a `Key` with a name that is only known at runtime.
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    pub(crate) fn from_name(name: impl Into<ArcStr>) -> Self {
+        Key {
+            key: KeyName::Owned(name.into()),
+            value_type: PhantomData,
+        }
+    }
+
+    /// Return this key's string value.
+    pub(crate) fn name(&self) -> &str {
+        self.key.as_str()
+    }
+}
+
+impl KeyName {
+    fn as_str(&self) -> &str {
+        match self {
+            KeyName::Static(name) => name,
+            KeyName::Owned(name) => name,
+        }
+    }
+}
+
+impl PartialEq for KeyName {
+    fn eq(&self, other: &KeyName) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Data for KeyName {
+    fn same(&self, other: &KeyName) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Debug for KeyName {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
 }
 
 impl Key<()> {
@@ -412,7 +466,7 @@ impl Key<()> {
     /// [`Env`]: struct.Env.html
     pub const fn untyped(key: &'static str) -> Self {
         Key {
-            key,
+            key: KeyName::Static(key),
             value_type: PhantomData,
         }
     }
@@ -421,8 +475,8 @@ impl Key<()> {
     ///
     /// This should only be needed for things like debugging or for building
     /// other tooling that needs to inspect keys.
-    pub const fn raw(&self) -> &'static str {
-        self.key
+    pub fn raw(&self) -> &str {
+        self.key.as_str()
     }
 }
 
@@ -548,7 +602,10 @@ impl Env {
 
 impl<T> From<Key<T>> for ArcStr {
     fn from(src: Key<T>) -> ArcStr {
-        ArcStr::from(src.key)
+        match src.key {
+            KeyName::Static(name) => name.into(),
+            KeyName::Owned(name) => name,
+        }
     }
 }
 
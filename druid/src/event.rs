@@ -67,7 +67,14 @@ pub enum Event {
     /// that the window *will* close just because this event is received; for instance, you should
     /// avoid destructive side effects such as cleaning up resources.
     ///
+    /// Handling this event only vetoes the current request. To close the
+    /// window later, for instance after asking the user to save their work,
+    /// stop handling the event and submit [`CLOSE_WINDOW`] again. Once a
+    /// request is not vetoed, the window is closing, and this event is not
+    /// sent again.
+    ///
     /// [`set_handled`]: crate::EventCtx::set_handled
+    /// [`CLOSE_WINDOW`]: crate::commands::CLOSE_WINDOW
     WindowCloseRequested,
     /// Sent to all widgets in a given window when the system is going to close that window.
     ///
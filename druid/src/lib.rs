@@ -98,6 +98,8 @@
 //! * `svg` - Scalable Vector Graphics for icons and other scalable images using the [`usvg` crate].
 //! * `image` - Bitmap image support using the [`image` crate].
 //! * `x11` - Work-in-progress X11 Linux backend instead of GTK.
+//! * `markdown` - Converting Markdown to [`RichText`] using the [`pulldown-cmark` crate].
+//! * `serde` - Serialization and deserialization of [`RichText`] using [`serde`].
 //!
 //! Features can be added with `cargo`. For example, in your `Cargo.toml`:
 //! ```no_compile
@@ -125,6 +127,9 @@
 //! [`im` module]: im/index.html
 //! [`usvg` crate]: https://crates.io/crates/usvg
 //! [`image` crate]: https://crates.io/crates/image
+//! [`pulldown-cmark` crate]: https://crates.io/crates/pulldown-cmark
+//! [`serde`]: https://crates.io/crates/serde
+//! [`RichText`]: text::RichText
 
 #![deny(
     broken_intra_doc_links,
@@ -213,7 +218,7 @@ pub use env::{Env, Key, KeyOrValue, Value, ValueType, ValueTypeError};
 pub use event::{Event, InternalEvent, InternalLifeCycle, LifeCycle};
 pub use ext_event::{ExtEventError, ExtEventSink};
 pub use lens::{Lens, LensExt};
-pub use localization::LocalizedString;
+pub use localization::{LocalizedRichString, LocalizedString};
 pub use menu::{sys as platform_menus, Menu, MenuItem};
 pub use mouse::MouseEvent;
 pub use util::Handled;
@@ -27,19 +27,26 @@
 //! At runtime, you resolve your [`LocalizedString`] into an actual string,
 //! passing it the current [`Env`] and [`Data`].
 //!
+//! A [`LocalizedRichString`] works the same way, but resolves to [`RichText`],
+//! with attributes applied to the text of some of its arguments.
+//!
 //!
 //! [Fluent]: https://projectfluent.org
 //! [fluent-rs]: https://github.com/projectfluent/fluent-rs
 //! [`LocalizedString`]: struct.LocalizedString.html
+//! [`LocalizedRichString`]: struct.LocalizedRichString.html
+//! [`RichText`]: text/struct.RichText.html
 //! [`Env`]: struct.Env.html
 //! [`Data`]: trait.Data.html
 
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
 use std::{fs, io};
 
 use tracing::{debug, error, warn};
 
+use crate::text::{Attribute, RichText, RichTextBuilder};
 use crate::{Application, ArcStr, Env};
 
 use fluent_bundle::{
@@ -55,6 +62,11 @@ use unic_langid::LanguageIdentifier;
 // the path dynamically.
 static FALLBACK_STRINGS: &str = include_str!("../resources/i18n/en-US/builtin.ftl");
 
+// fluent inserts bidi controls around interpolated values.
+// https://www.w3.org/International/questions/qa-bidi-unicode-controls#basedirection
+const START_ISOLATE: char = '\u{2068}';
+const END_ISOLATE: char = '\u{2069}';
+
 /// Provides access to the localization strings for the current locale.
 #[allow(dead_code)]
 pub(crate) struct L10nManager {
@@ -96,6 +108,41 @@ pub struct LocalizedString<T> {
     resolved_lang: Option<LanguageIdentifier>,
 }
 
+/// A localized string whose arguments can be styled, producing [`RichText`].
+///
+/// This is like a [`LocalizedString`], but each argument can be given
+/// [`Attribute`]s with [`with_arg_attribute`]. When the string is resolved,
+/// the attributes are applied wherever the text of that argument appears in
+/// the formatted message, so they follow the argument when a translation
+/// moves it.
+///
+/// Arguments are found using the bidi isolation marks that fluent places
+/// around interpolated values, and matched by their text; only string and
+/// number arguments can be styled. If two styled arguments format to the same
+/// text, both are styled with the attributes of each.
+///
+/// # Examples
+///
+/// ```
+/// # use druid::{FontWeight, LocalizedRichString};
+/// # use druid::text::Attribute;
+/// # struct User { name: String }
+/// let greeting: LocalizedRichString<User> = LocalizedRichString::new("welcome-user")
+///     .with_placeholder("Welcome!")
+///     .with_arg("name", |user: &User, _env| user.name.clone().into())
+///     .with_arg_attribute("name", Attribute::weight(FontWeight::BOLD));
+/// ```
+///
+/// [`RichText`]: crate::text::RichText
+/// [`with_arg_attribute`]: LocalizedRichString::with_arg_attribute
+#[derive(Debug, Clone)]
+pub struct LocalizedRichString<T> {
+    string: LocalizedString<T>,
+    styles: Vec<(&'static str, Vec<Attribute>)>,
+    /// The resolved text, and the ranges of it styled by each entry in `styles`.
+    resolved: Option<(ArcStr, Vec<(Range<usize>, usize)>)>,
+}
+
 /// A stack of localization resources, used for fallback.
 struct BundleStack(Vec<FluentBundle<Arc<FluentResource>>>);
 
@@ -251,14 +298,34 @@ impl L10nManager {
         args: impl Into<Option<&'args FluentArgs<'args>>>,
     ) -> Option<ArcStr> {
         let args = args.into();
-        let value = match self
+        let result = self.format(key, args)?;
+
+        // the bidi controls can cause rendering issues; for now we just
+        // strip them.
+        if args.is_some() && result.chars().any(|c| c == START_ISOLATE) {
+            Some(strip_isolates(&result).0.into())
+        } else {
+            Some(result.into())
+        }
+    }
+
+    /// Fetch a localized string, along with the ranges of the text that were
+    /// interpolated into it.
+    ///
+    /// The ranges include the whole string, and may be nested.
+    pub(crate) fn localize_placeables(
+        &self,
+        key: &str,
+        args: Option<&FluentArgs>,
+    ) -> Option<(String, Vec<Range<usize>>)> {
+        self.format(key, args).map(|result| strip_isolates(&result))
+    }
+
+    fn format(&self, key: &str, args: Option<&FluentArgs>) -> Option<String> {
+        let value = self
             .current_bundle
             .get_message(key)
-            .and_then(|msg| msg.value())
-        {
-            Some(v) => v,
-            None => return None,
-        };
+            .and_then(|msg| msg.value())?;
         let mut errs = Vec::new();
         let result = self
             .current_bundle
@@ -266,23 +333,7 @@ impl L10nManager {
         for err in errs {
             warn!("localization error {:?}", err);
         }
-
-        // fluent inserts bidi controls when interpolating, and they can
-        // cause rendering issues; for now we just strip them.
-        // https://www.w3.org/International/questions/qa-bidi-unicode-controls#basedirection
-        const START_ISOLATE: char = '\u{2068}';
-        const END_ISOLATE: char = '\u{2069}';
-        if args.is_some() && result.chars().any(|c| c == START_ISOLATE) {
-            Some(
-                result
-                    .chars()
-                    .filter(|c| c != &START_ISOLATE && c != &END_ISOLATE)
-                    .collect::<String>()
-                    .into(),
-            )
-        } else {
-            Some(result.into())
-        }
+        Some(result)
     }
     //TODO: handle locale change
 }
@@ -370,6 +421,161 @@ impl<T> LocalizedString<T> {
     }
 }
 
+impl<T> LocalizedRichString<T> {
+    /// Create a new `LocalizedRichString` with the given key.
+    pub fn new(key: &'static str) -> Self {
+        LocalizedRichString {
+            string: LocalizedString::new(key),
+            styles: Vec::new(),
+            resolved: None,
+        }
+    }
+
+    /// Add a placeholder value. This will be used, without any attributes,
+    /// if localization fails.
+    ///
+    /// See [`LocalizedString::with_placeholder`].
+    pub fn with_placeholder(mut self, placeholder: impl Into<ArcStr>) -> Self {
+        self.string = self.string.with_placeholder(placeholder);
+        self
+    }
+
+    /// Add a named argument and a corresponding closure.
+    ///
+    /// See [`LocalizedString::with_arg`].
+    pub fn with_arg(
+        mut self,
+        key: &'static str,
+        f: impl Fn(&T, &Env) -> FluentValue<'static> + 'static,
+    ) -> Self {
+        self.string = self.string.with_arg(key, f);
+        self
+    }
+
+    /// Add an [`Attribute`] to the text of the named argument.
+    pub fn with_arg_attribute(mut self, key: &'static str, attr: Attribute) -> Self {
+        match self.styles.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, attrs)) => attrs.push(attr),
+            None => self.styles.push((key, vec![attr])),
+        }
+        self
+    }
+
+    /// Return the localized text, with the attributes of its arguments.
+    ///
+    /// If the string has not been resolved, this returns the placeholder, or
+    /// the key if there is no placeholder, without any attributes.
+    pub fn rich_text(&self) -> RichText {
+        match &self.resolved {
+            Some((text, spans)) => {
+                let mut builder = RichTextBuilder::new();
+                builder.push(text);
+                for (range, idx) in spans {
+                    let mut adder = builder.add_attributes_for_range(range.clone());
+                    for attr in &self.styles[*idx].1 {
+                        adder.add_attr(attr.clone());
+                    }
+                }
+                builder.build()
+            }
+            None => RichText::new(self.string.localized_str()),
+        }
+    }
+
+    /// Lazily compute the localized value for this string based on the provided
+    /// environment and data.
+    ///
+    /// Returns `true` if the current value of the string has changed.
+    pub fn resolve(&mut self, data: &T, env: &Env) -> bool {
+        match env.localization_manager() {
+            Some(manager) => self.resolve_with(manager, data, env),
+            None => false,
+        }
+    }
+
+    fn resolve_with(&mut self, manager: &L10nManager, data: &T, env: &Env) -> bool {
+        let string = &mut self.string;
+        if string.args.is_none() && string.resolved_lang.as_ref() == Some(&manager.current_locale) {
+            return false;
+        }
+
+        let values: Vec<_> = string
+            .args
+            .iter()
+            .flatten()
+            .map(|(k, v)| (*k, (v.0)(data, env)))
+            .collect();
+        let styled: Vec<_> = self
+            .styles
+            .iter()
+            .map(|(key, _)| {
+                values
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .and_then(|(_, value)| placeable_text(value))
+            })
+            .collect();
+        let args: Option<FluentArgs> = string.args.as_ref().map(|_| values.into_iter().collect());
+
+        string.resolved_lang = Some(manager.current_locale.clone());
+        let next =
+            manager
+                .localize_placeables(string.key, args.as_ref())
+                .map(|(text, placeables)| {
+                    let spans = styled_spans(&text, &placeables, &styled);
+                    (text.into(), spans)
+                });
+        let result = next != self.resolved;
+        self.resolved = next;
+        result
+    }
+}
+
+/// Remove the bidi isolation marks from a formatted string, returning the
+/// ranges they enclosed, and the range of the whole string.
+fn strip_isolates(s: &str) -> (String, Vec<Range<usize>>) {
+    let mut text = String::with_capacity(s.len());
+    let mut starts = Vec::new();
+    let mut ranges = Vec::new();
+    for c in s.chars() {
+        match c {
+            START_ISOLATE => starts.push(text.len()),
+            END_ISOLATE => ranges.extend(starts.pop().map(|start| start..text.len())),
+            c => text.push(c),
+        }
+    }
+    ranges.push(0..text.len());
+    (text, ranges)
+}
+
+/// The text of an argument, as it is formatted by fluent.
+fn placeable_text(value: &FluentValue) -> Option<String> {
+    match value {
+        FluentValue::String(s) => Some(s.to_string()),
+        FluentValue::Number(n) => Some(n.as_string().to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the ranges of `placeables` whose text is that of one of the
+/// `styled` arguments, with the index of that argument.
+fn styled_spans(
+    text: &str,
+    placeables: &[Range<usize>],
+    styled: &[Option<String>],
+) -> Vec<(Range<usize>, usize)> {
+    let mut spans = Vec::new();
+    for range in placeables.iter().filter(|range| !range.is_empty()) {
+        for (idx, arg) in styled.iter().enumerate() {
+            let span = (range.clone(), idx);
+            if arg.as_deref() == Some(&text[range.clone()]) && !spans.contains(&span) {
+                spans.push(span);
+            }
+        }
+    }
+    spans
+}
+
 impl<T> std::fmt::Debug for ArgSource<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "Arg Resolver {:p}", self.0)
@@ -397,6 +603,8 @@ impl<'a, T: std::fmt::Display> std::fmt::Display for PrintLocales<'a, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::piet::TextStorage as _;
+    use crate::text::AttributeKind;
     use test_env_log::test;
 
     #[test]
@@ -437,4 +645,100 @@ mod tests {
         assert_eq!(resmgr.resolve_locales(cn_hk), vec![en_us.clone()]);
         assert_eq!(resmgr.resolve_locales(pt_pt), vec![en_us]);
     }
+
+    fn manager(source: &str) -> L10nManager {
+        let en_us: LanguageIdentifier = "en-US".parse().unwrap();
+        let res = FluentResource::try_new(source.to_string()).unwrap_or_else(|(res, _)| res);
+        let mut bundle = FluentBundle::new(vec![en_us.clone()]);
+        bundle.add_resource(Arc::new(res)).unwrap();
+        L10nManager {
+            res_mgr: ResourceManager {
+                resources: HashMap::new(),
+                locales: vec![en_us.clone()],
+                default_locale: en_us.clone(),
+                path_scheme: String::new(),
+            },
+            resources: Vec::new(),
+            current_bundle: BundleStack(vec![bundle]),
+            current_locale: en_us,
+        }
+    }
+
+    #[test]
+    fn strip_isolates() {
+        let (text, ranges) = super::strip_isolates("a \u{2068}b \u{2068}c\u{2069}\u{2069}!");
+        assert_eq!(text, "a b c!");
+        assert_eq!(ranges, vec![4..5, 2..5, 0..6]);
+    }
+
+    #[test]
+    fn rich_string_follows_args() {
+        struct Inbox {
+            name: &'static str,
+            count: usize,
+        }
+
+        let en = manager(
+            "inbox = Hello { $name }, you have { $count ->
+                [one] one message
+               *[other] { $count } messages
+            }.",
+        );
+        // a translation that moves the arguments around
+        let reordered = manager("inbox = { $count } new for { $name }");
+        let mut string = LocalizedRichString::new("inbox")
+            .with_arg("name", |inbox: &Inbox, _| inbox.name.into())
+            .with_arg("count", |inbox: &Inbox, _| inbox.count.into())
+            .with_arg_attribute("name", Attribute::underline(true))
+            .with_arg_attribute("count", Attribute::size(20.0));
+        let env = Env::empty();
+        let spans = |string: &LocalizedRichString<Inbox>| -> Vec<_> {
+            let text = string.rich_text();
+            let mut spans: Vec<_> = text
+                .spans()
+                .map(|(range, attr)| (range, attr.kind()))
+                .collect();
+            spans.sort_by_key(|(range, _)| range.start);
+            spans
+        };
+
+        let data = Inbox {
+            name: "Ann",
+            count: 12,
+        };
+        assert!(string.resolve_with(&en, &data, &env));
+        assert_eq!(
+            string.rich_text().as_str(),
+            "Hello Ann, you have 12 messages."
+        );
+        assert_eq!(
+            spans(&string),
+            vec![
+                (6..9, AttributeKind::Underline),
+                (20..22, AttributeKind::FontSize)
+            ]
+        );
+        assert!(!string.resolve_with(&en, &data, &env));
+
+        let data = Inbox {
+            name: "Bo",
+            count: 1,
+        };
+        assert!(string.resolve_with(&en, &data, &env));
+        assert_eq!(
+            string.rich_text().as_str(),
+            "Hello Bo, you have one message."
+        );
+        assert_eq!(spans(&string), vec![(6..8, AttributeKind::Underline)]);
+
+        assert!(string.resolve_with(&reordered, &data, &env));
+        assert_eq!(string.rich_text().as_str(), "1 new for Bo");
+        assert_eq!(
+            spans(&string),
+            vec![
+                (0..1, AttributeKind::FontSize),
+                (10..12, AttributeKind::Underline)
+            ]
+        );
+    }
 }
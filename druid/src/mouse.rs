@@ -46,6 +46,11 @@ pub struct MouseEvent {
     pub mods: Modifiers,
     /// The number of mouse clicks associated with this event. This will always
     /// be `0` for a mouse-up and mouse-move events.
+    ///
+    /// A mouse-down that follows the previous one closely enough, in both time
+    /// and distance, has a count one higher than it; for instance the second
+    /// mouse-down of a double-click has a count of `2`. The thresholds are
+    /// taken from the platform where it provides them.
     pub count: u8,
     /// Focus is `true` on macOS when the mouse-down event (or its companion mouse-up event)
     /// with `MouseButton::Left` was the event that caused the window to gain focus.
@@ -273,3 +273,57 @@ fn aspect_ratio() {
         assert_eq!(state.layout_rect().size(), Size::new(1000., 500.));
     });
 }
+
+#[test]
+fn intrinsic_size_matches_layout() {
+    let sizes = Rc::new(Cell::new((Size::ZERO, Size::ZERO)));
+    let sizes_2 = sizes.clone();
+    let child = SizedBox::new(Label::new("hello!")).width(100.).padding(10.);
+    let widget = ModularWidget::new(WidgetPod::new(child))
+        .lifecycle_fn(|child, ctx, event, data, env| child.lifecycle(ctx, event, data, env))
+        .layout_fn(move |child, ctx, bc, data, env| {
+            let child_bc = bc.loosen();
+            let intrinsic = child.intrinsic_size(ctx, &child_bc, data, env);
+            let size = child.layout(ctx, &child_bc, data, env);
+            child.set_origin(ctx, data, env, Point::ZERO);
+            sizes_2.set((intrinsic, size));
+            bc.max()
+        });
+
+    Harness::create_simple((), widget, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+        let (intrinsic, size) = sizes.get();
+        assert_eq!(intrinsic, size);
+        assert_eq!(intrinsic.width, 120.);
+    });
+}
+
+#[test]
+fn intrinsic_size_does_not_change_layout() {
+    let id = WidgetId::next();
+    let measured = Rc::new(Cell::new(Size::ZERO));
+    let measured_2 = measured.clone();
+    let flex = Flex::row()
+        .with_child(SizedBox::empty().width(40.).height(10.).with_id(id))
+        .with_child(SizedBox::empty().width(60.).height(20.));
+    let widget = ModularWidget::new(WidgetPod::new(flex))
+        .lifecycle_fn(|child, ctx, event, data, env| child.lifecycle(ctx, event, data, env))
+        .layout_fn(move |child, ctx, bc, data, env| {
+            let size = child.layout(ctx, &bc.loosen(), data, env);
+            child.set_origin(ctx, data, env, Point::ZERO);
+            let tight = BoxConstraints::tight(Size::new(500., 500.));
+            measured_2.set(child.intrinsic_size(ctx, &tight, data, env));
+            assert_eq!(child.layout_rect().size(), size);
+            bc.max()
+        });
+
+    Harness::create_simple((), widget, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+        assert_eq!(measured.get(), Size::new(500., 500.));
+        // measuring with a taller height would have centered the child
+        let state = harness.get_state(id);
+        assert_eq!(state.layout_rect(), Rect::new(0., 5., 40., 15.));
+    });
+}
@@ -24,7 +24,7 @@ mod invalidation_tests;
 #[cfg(test)]
 mod layout_tests;
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -961,3 +961,249 @@ fn notifications() {
         assert!(saw_notification(&grandparent_rec));
     });
 }
+
+/// Build a two-link label text, recording the index of each activated link.
+#[cfg(test)]
+fn two_link_text(activated: &Rc<RefCell<Vec<usize>>>) -> crate::text::RichText {
+    let mut builder = crate::text::RichTextBuilder::new();
+    let record = activated.clone();
+    builder
+        .push("one")
+        .on_click(move |_, _| record.borrow_mut().push(0));
+    builder.push(" ");
+    let record = activated.clone();
+    builder
+        .push("two")
+        .on_click(move |_, _| record.borrow_mut().push(1));
+    builder.build()
+}
+
+/// Wrap `child` in a widget that gives focus to the widget named by `FOCUS_WIDGET`.
+#[cfg(test)]
+fn focus_setter<T: Data>(child: impl Widget<T> + 'static) -> impl Widget<T> {
+    ModularWidget::new(WidgetPod::new(child))
+        .event_fn(|child, ctx, event, data, env| {
+            if let Event::Command(cmd) = event {
+                if let Some(id) = cmd.get(FOCUS_WIDGET) {
+                    ctx.set_focus(*id);
+                }
+            }
+            child.event(ctx, event, data, env);
+        })
+        .lifecycle_fn(|child, ctx, event, data, env| child.lifecycle(ctx, event, data, env))
+        .update_fn(|child, ctx, _, data, env| child.update(ctx, data, env))
+        .layout_fn(|child, ctx, bc, data, env| {
+            let size = child.layout(ctx, bc, data, env);
+            child.set_origin(ctx, data, env, Point::ORIGIN);
+            size
+        })
+}
+
+#[cfg(test)]
+const FOCUS_WIDGET: Selector<WidgetId> = Selector::new("druid-tests.focus-widget");
+
+#[cfg(test)]
+fn key_down(mods: Modifiers, key: KbKey) -> Event {
+    Event::KeyDown(KeyEvent::for_test(mods, key))
+}
+
+/// Tab and Shift+Tab move between the links of a label before moving focus,
+/// and Enter or Space activates the focused link.
+#[test]
+fn label_link_keyboard_navigation() {
+    let activated = Rc::new(RefCell::new(Vec::new()));
+    let [id_1, id_2] = widget_ids();
+    let labels = Flex::row()
+        .with_child(RawLabel::new().with_id(id_1))
+        .with_child(RawLabel::new().with_id(id_2));
+
+    let tab = || key_down(Modifiers::empty(), KbKey::Tab);
+    let shift_tab = || key_down(Modifiers::SHIFT, KbKey::Tab);
+    let enter = || key_down(Modifiers::empty(), KbKey::Enter);
+    let space = || key_down(Modifiers::empty(), KbKey::Character(" ".into()));
+
+    let text = two_link_text(&activated);
+    Harness::create_simple(text, focus_setter(labels), |harness| {
+        harness.send_initial_events();
+        assert_eq!(harness.window().focus_chain(), &[id_1, id_2]);
+
+        // gaining focus directly starts on the first link
+        harness.submit_command(FOCUS_WIDGET.with(id_1));
+        assert_eq!(harness.window().focus, Some(id_1));
+        harness.event(enter());
+        assert_eq!(activated.borrow().as_slice(), &[0]);
+
+        // tab moves to the next link without leaving the label
+        harness.event(tab());
+        assert_eq!(harness.window().focus, Some(id_1));
+        harness.event(space());
+        assert_eq!(activated.borrow().as_slice(), &[0, 1]);
+
+        // tab past the last link focuses the next label, on its first link
+        harness.event(tab());
+        assert_eq!(harness.window().focus, Some(id_2));
+        harness.event(enter());
+        assert_eq!(activated.borrow().as_slice(), &[0, 1, 0]);
+
+        // shift+tab before the first link returns to the previous label,
+        // on its last link
+        harness.event(shift_tab());
+        assert_eq!(harness.window().focus, Some(id_1));
+        harness.event(enter());
+        assert_eq!(activated.borrow().as_slice(), &[0, 1, 0, 1]);
+
+        harness.event(shift_tab());
+        assert_eq!(harness.window().focus, Some(id_1));
+        harness.event(enter());
+        assert_eq!(activated.borrow().as_slice(), &[0, 1, 0, 1, 0]);
+    });
+}
+
+/// Keys are ignored by a label that isn't focused.
+#[test]
+fn label_link_needs_focus() {
+    let activated = Rc::new(RefCell::new(Vec::new()));
+    let label = RawLabel::new();
+
+    let text = two_link_text(&activated);
+    Harness::create_simple(text, focus_setter(label), |harness| {
+        harness.send_initial_events();
+        harness.event(key_down(Modifiers::empty(), KbKey::Enter));
+        assert!(activated.borrow().is_empty());
+    });
+}
+
+/// Helper function to construct a left button press or release at a position.
+#[cfg(test)]
+fn left_click(p: impl Into<Point>, count: u8) -> MouseEvent {
+    MouseEvent {
+        buttons: MouseButtons::default().with(MouseButton::Left),
+        count,
+        button: MouseButton::Left,
+        ..move_mouse(p)
+    }
+}
+
+#[cfg(test)]
+fn selected_text<T: Data>(harness: &mut Harness<T>, id: WidgetId) -> Option<String> {
+    // the label's state is nested in those of the widgets wrapping it
+    fn find_selection(state: &crate::debug_state::DebugState) -> Option<String> {
+        state
+            .other_values
+            .get("selection")
+            .cloned()
+            .or_else(|| state.children.iter().find_map(find_selection))
+    }
+    find_selection(&harness.get_debug_state(id))
+}
+
+#[test]
+fn label_drag_selection() {
+    let [label_id] = widget_ids();
+    let label = RawLabel::new().with_selectable(true).with_id(label_id);
+
+    Harness::create_simple(ArcStr::from("hello world"), label, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+        assert_eq!(selected_text(harness, label_id), None);
+
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseMove(left_click((1000.0, 5.0), 1)));
+        harness.event(Event::MouseUp(left_click((1000.0, 5.0), 1)));
+        assert_eq!(
+            selected_text(harness, label_id).as_deref(),
+            Some("hello world")
+        );
+        assert_eq!(harness.window().focus, Some(label_id));
+
+        // a single click collapses the selection
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseUp(left_click((0.0, 5.0), 1)));
+        assert_eq!(selected_text(harness, label_id), None);
+    });
+}
+
+#[test]
+fn label_double_click_selects_word() {
+    let [label_id] = widget_ids();
+    let label = RawLabel::new().with_selectable(true).with_id(label_id);
+
+    Harness::create_simple(ArcStr::from("hello world"), label, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseUp(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 2)));
+        harness.event(Event::MouseUp(left_click((0.0, 5.0), 2)));
+        assert_eq!(selected_text(harness, label_id).as_deref(), Some("hello"));
+    });
+}
+
+#[test]
+fn label_selection_across_wrapped_lines() {
+    let [label_id] = widget_ids();
+    let label = RawLabel::new()
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .with_selectable(true)
+        .with_id(label_id);
+    let text = "one two three four";
+
+    // the label is aligned so that the window doesn't force it to its own size
+    let root = Align::new(UnitPoint::TOP_LEFT, label.fix_width(60.0));
+    Harness::create_simple(ArcStr::from(text), root, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+
+        // dragging down to the start of the last line selects every line above it
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseMove(left_click((0.0, 1000.0), 1)));
+        harness.event(Event::MouseUp(left_click((0.0, 1000.0), 1)));
+        let selection = selected_text(harness, label_id).expect("text should wrap");
+        assert!(selection.len() < text.len());
+        assert!(text.starts_with(&selection));
+        assert!(selection.ends_with(' '));
+
+        // and dragging to the far corner selects everything
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseMove(left_click((1000.0, 1000.0), 1)));
+        harness.event(Event::MouseUp(left_click((1000.0, 1000.0), 1)));
+        assert_eq!(selected_text(harness, label_id).as_deref(), Some(text));
+    });
+}
+
+/// A focused label leaves the copy command to its ancestors when nothing is
+/// selected. (Copying a selection writes to the system clipboard, which needs
+/// a running `Application` and so can't be exercised from the `Harness`.)
+#[test]
+fn label_copy_without_selection() {
+    let [label_id] = widget_ids();
+    let label = RawLabel::new().with_selectable(true).with_id(label_id);
+    let copy_handled = Rc::new(Cell::new(None));
+    let handled = copy_handled.clone();
+    let root = ModularWidget::new(WidgetPod::new(label))
+        .event_fn(move |child, ctx, event, data, env| {
+            child.event(ctx, event, data, env);
+            if matches!(event, Event::Command(cmd) if cmd.is(commands::COPY)) {
+                handled.set(Some(ctx.is_handled()));
+            }
+        })
+        .lifecycle_fn(|child, ctx, event, data, env| child.lifecycle(ctx, event, data, env))
+        .update_fn(|child, ctx, _, data, env| child.update(ctx, data, env))
+        .layout_fn(|child, ctx, bc, data, env| {
+            let size = child.layout(ctx, bc, data, env);
+            child.set_origin(ctx, data, env, Point::ORIGIN);
+            size
+        });
+
+    Harness::create_simple(ArcStr::from("hello world"), root, |harness| {
+        harness.send_initial_events();
+        harness.just_layout();
+        harness.event(Event::MouseDown(left_click((0.0, 5.0), 1)));
+        harness.event(Event::MouseUp(left_click((0.0, 5.0), 1)));
+        assert_eq!(harness.window().focus, Some(label_id));
+
+        harness.submit_command(commands::COPY);
+        assert_eq!(copy_handled.get(), Some(false));
+        assert_eq!(selected_text(harness, label_id), None);
+    });
+}
@@ -0,0 +1,299 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Converting text containing ANSI escape sequences to [`RichText`].
+
+use std::convert::TryFrom;
+
+use super::{AttributesAdder, RichText, RichTextBuilder};
+use crate::piet::{Color, FontStyle, FontWeight};
+
+const ESC: char = '\x1b';
+
+/// The sixteen base colors, using the values from xterm.
+const BASE_COLORS: [Color; 16] = [
+    Color::rgb8(0x00, 0x00, 0x00),
+    Color::rgb8(0xcd, 0x00, 0x00),
+    Color::rgb8(0x00, 0xcd, 0x00),
+    Color::rgb8(0xcd, 0xcd, 0x00),
+    Color::rgb8(0x00, 0x00, 0xee),
+    Color::rgb8(0xcd, 0x00, 0xcd),
+    Color::rgb8(0x00, 0xcd, 0xcd),
+    Color::rgb8(0xe5, 0xe5, 0xe5),
+    Color::rgb8(0x7f, 0x7f, 0x7f),
+    Color::rgb8(0xff, 0x00, 0x00),
+    Color::rgb8(0x00, 0xff, 0x00),
+    Color::rgb8(0xff, 0xff, 0x00),
+    Color::rgb8(0x5c, 0x5c, 0xff),
+    Color::rgb8(0xff, 0x00, 0xff),
+    Color::rgb8(0x00, 0xff, 0xff),
+    Color::rgb8(0xff, 0xff, 0xff),
+];
+
+/// The graphic rendition set by SGR sequences.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct AnsiStyle {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+impl RichText {
+    /// Create a new `RichText` from text containing ANSI escape sequences,
+    /// such as the output of a compiler or other command-line tool.
+    ///
+    /// The following SGR ("Select Graphic Rendition") parameters are
+    /// converted to attributes:
+    ///
+    /// - `1` and `22` turn bold on and off
+    /// - `3` and `23` turn italic on and off
+    /// - `4` and `24` turn underline on and off
+    /// - `30`–`37`, `90`–`97` and `39` set and reset the text color
+    /// - `40`–`47`, `100`–`107` and `49` set and reset the background color
+    /// - `38;5;n` and `48;5;n` select one of the 256 indexed colors
+    /// - `38;2;r;g;b` and `48;2;r;g;b` select a 24-bit color
+    /// - `0`, or an empty parameter list, resets all of the above
+    ///
+    /// The base colors use the same values as xterm. Other SGR parameters,
+    /// and all other escape sequences, are removed from the text without
+    /// affecting its style.
+    ///
+    /// This method is only available with the `ansi` feature.
+    pub fn from_ansi(text: &str) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        let mut style = AnsiStyle::default();
+        let mut run = String::new();
+        let mut rest = text;
+
+        while let Some(esc) = rest.find(ESC) {
+            run.push_str(&rest[..esc]);
+            let (len, sgr_params) = parse_escape(&rest[esc..]);
+            rest = &rest[esc + len..];
+
+            if let Some(params) = sgr_params {
+                let mut new_style = style.clone();
+                new_style.apply_sgr(params);
+                if new_style != style {
+                    if !run.is_empty() {
+                        style.add_attributes(&mut builder.push(&run));
+                        run.clear();
+                    }
+                    style = new_style;
+                }
+            }
+        }
+        run.push_str(rest);
+        if !run.is_empty() {
+            style.add_attributes(&mut builder.push(&run));
+        }
+
+        let mut text = builder.build();
+        text.coalesce_attributes();
+        text
+    }
+}
+
+impl AnsiStyle {
+    fn apply_sgr(&mut self, params: &str) {
+        // Parameters using ':' as a separator, such as `4:3` for curly
+        // underlines, are not supported and are skipped.
+        let mut codes = params.split(';').map(|param| {
+            if param.is_empty() {
+                Some(0)
+            } else {
+                param.parse::<u16>().ok()
+            }
+        });
+
+        while let Some(code) = codes.next() {
+            match code {
+                Some(0) => *self = AnsiStyle::default(),
+                Some(1) => self.bold = true,
+                Some(22) => self.bold = false,
+                Some(3) => self.italic = true,
+                Some(23) => self.italic = false,
+                Some(4) => self.underline = true,
+                Some(24) => self.underline = false,
+                Some(n @ 30..=37) => self.fg = Some(BASE_COLORS[n as usize - 30].clone()),
+                Some(n @ 90..=97) => self.fg = Some(BASE_COLORS[n as usize - 90 + 8].clone()),
+                Some(38) => {
+                    if let Some(color) = extended_color(&mut codes) {
+                        self.fg = Some(color);
+                    }
+                }
+                Some(39) => self.fg = None,
+                Some(n @ 40..=47) => self.bg = Some(BASE_COLORS[n as usize - 40].clone()),
+                Some(n @ 100..=107) => self.bg = Some(BASE_COLORS[n as usize - 100 + 8].clone()),
+                Some(48) => {
+                    if let Some(color) = extended_color(&mut codes) {
+                        self.bg = Some(color);
+                    }
+                }
+                Some(49) => self.bg = None,
+                _ => (),
+            }
+        }
+    }
+
+    fn add_attributes(&self, attrs: &mut AttributesAdder) {
+        if self.bold {
+            attrs.weight(FontWeight::BOLD);
+        }
+        if self.italic {
+            attrs.style(FontStyle::Italic);
+        }
+        if self.underline {
+            attrs.underline(true);
+        }
+        if let Some(color) = &self.fg {
+            attrs.text_color(color.clone());
+        }
+        if let Some(color) = &self.bg {
+            attrs.background_color(color.clone());
+        }
+    }
+}
+
+/// Parse the arguments of an extended color (SGR `38` or `48`), which are
+/// either `5;n` for an indexed color or `2;r;g;b` for a 24-bit color.
+fn extended_color(codes: &mut impl Iterator<Item = Option<u16>>) -> Option<Color> {
+    let mut next = || codes.next().flatten().and_then(|n| u8::try_from(n).ok());
+    match next()? {
+        5 => Some(indexed_color(next()?)),
+        2 => Some(Color::rgb8(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// Returns the color at `index` in the 256-color palette.
+fn indexed_color(index: u8) -> Color {
+    match index {
+        0..=15 => BASE_COLORS[index as usize].clone(),
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            Color::rgb8(level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => Color::grey8(8 + (index - 232) * 10),
+    }
+}
+
+/// Returns the length in bytes of the escape sequence at the start of `s`,
+/// and its parameters if it is an SGR sequence.
+///
+/// Sequences that are malformed or unterminated end before the first byte
+/// that cannot be part of them.
+fn parse_escape(s: &str) -> (usize, Option<&str>) {
+    let bytes = s.as_bytes();
+    match bytes.get(1) {
+        // Control sequence: parameter and intermediate bytes, then a final byte.
+        Some(b'[') => match bytes[2..].iter().position(|b| !(0x20..=0x3f).contains(b)) {
+            Some(pos) if (0x40..=0x7e).contains(&bytes[pos + 2]) => {
+                let params = if bytes[pos + 2] == b'm' {
+                    Some(&s[2..pos + 2])
+                } else {
+                    None
+                };
+                (pos + 3, params)
+            }
+            Some(pos) => (pos + 2, None),
+            None => (s.len(), None),
+        },
+        // String sequences, such as setting the window title, are terminated
+        // by BEL or by ST (`ESC \`).
+        Some(b']') | Some(b'P') | Some(b'X') | Some(b'^') | Some(b'_') => {
+            match s[2..].find(|c: char| c == '\x07' || c == ESC) {
+                Some(pos) if s[pos + 2..].starts_with('\x07') => (pos + 3, None),
+                Some(pos) if s[pos + 2..].starts_with("\x1b\\") => (pos + 4, None),
+                Some(pos) => (pos + 2, None),
+                None => (s.len(), None),
+            }
+        }
+        // Other sequences: any intermediate bytes, then a final byte.
+        Some(_) => match bytes[1..].iter().position(|b| !(0x20..=0x2f).contains(b)) {
+            Some(pos) if bytes[pos + 1].is_ascii() => (pos + 2, None),
+            Some(pos) => (pos + 1, None),
+            None => (s.len(), None),
+        },
+        None => (1, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piet::TextStorage as _;
+    use crate::text::Attribute;
+    use crate::KeyOrValue;
+
+    fn attrs_at(text: &RichText, idx: usize) -> Vec<Attribute> {
+        text.spans()
+            .filter(|(range, _)| range.contains(&idx))
+            .map(|(_, attr)| attr.clone())
+            .collect()
+    }
+
+    fn color_at(text: &RichText, idx: usize) -> Option<Color> {
+        attrs_at(text, idx).into_iter().find_map(|attr| match attr {
+            Attribute::TextColor(KeyOrValue::Concrete(color)) => Some(color),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn nested_bold_and_color() {
+        let text = RichText::from_ansi("\x1b[1mbold \x1b[31mred\x1b[39m bold\x1b[22m plain");
+        assert_eq!(text.as_str(), "bold red bold plain");
+
+        let spans: Vec<_> = text.spans().collect();
+        assert_eq!(spans.len(), 2);
+        assert!(matches!(
+            spans[0],
+            (ref range, Attribute::Weight(FontWeight::BOLD)) if *range == (0..13)
+        ));
+        assert_eq!(spans[1].0, 5..8);
+        assert_eq!(color_at(&text, 5), Some(BASE_COLORS[1].clone()));
+        assert_eq!(color_at(&text, 9), None);
+    }
+
+    #[test]
+    fn reset_mid_span() {
+        let text = RichText::from_ansi("\x1b[3;4;42mstyled\x1b[0mplain\x1b[mstill plain");
+        assert_eq!(text.as_str(), "styledplainstill plain");
+        assert_eq!(attrs_at(&text, 0).len(), 3);
+        assert!(attrs_at(&text, 6).is_empty());
+        assert!(attrs_at(&text, 12).is_empty());
+    }
+
+    #[test]
+    fn extended_colors() {
+        let text = RichText::from_ansi("\x1b[38;5;196ma\x1b[38;2;1;2;3mb\x1b[38;5;232mc\x1b[95md");
+        assert_eq!(text.as_str(), "abcd");
+        assert_eq!(color_at(&text, 0), Some(Color::rgb8(0xff, 0, 0)));
+        assert_eq!(color_at(&text, 1), Some(Color::rgb8(1, 2, 3)));
+        assert_eq!(color_at(&text, 2), Some(Color::grey8(8)));
+        assert_eq!(color_at(&text, 3), Some(BASE_COLORS[13].clone()));
+    }
+
+    #[test]
+    fn unsupported_sequences_are_stripped() {
+        let text = RichText::from_ansi(
+            "\x1b[2Ja\x1b]0;title\x07b\x1b(Bc\x1b[5m d\x1b]8;;url\x1b\\e\x1b[38;9mf\x1b[1",
+        );
+        assert_eq!(text.as_str(), "abc def");
+        assert_eq!(text.spans().count(), 0);
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Images and other content displayed inline with text.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::kurbo::{Rect, Size};
+use crate::piet::{ImageBuf, InterpolationMode};
+use crate::{Data, PaintCtx, RenderContext};
+
+/// The character that stands in for an [`InlineAttachment`] in the text.
+pub const OBJECT_REPLACEMENT_CHARACTER: char = '\u{FFFC}';
+
+/// The character an [`InlineAttachment`] is replaced with for layout.
+///
+/// An em space is, by definition, as wide as the font size, so a run of them
+/// at the right size reserves the space of the attachment; see
+/// [`InlineAttachment::placeholder`].
+pub(crate) const PLACEHOLDER: char = '\u{2003}';
+
+/// The position of the placeholder of one [`InlineAttachment`], both in the
+/// text and in the text that is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Placeholder {
+    /// The offset of the [`OBJECT_REPLACEMENT_CHARACTER`] in the text.
+    pub text_start: usize,
+    /// The offset of the placeholder in the laid out text.
+    pub layout_start: usize,
+    /// The length of the placeholder, in bytes.
+    pub layout_len: usize,
+}
+
+/// How an [`InlineAttachment`] is aligned vertically within its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttachmentAlignment {
+    /// The bottom of the attachment sits on the baseline of the text.
+    Baseline,
+    /// The attachment is centered vertically in the line.
+    Center,
+}
+
+/// An image, or other custom content, displayed inline with text.
+///
+/// An attachment is added to [`RichText`] as an [`Attribute::Attachment`]
+/// over an [`OBJECT_REPLACEMENT_CHARACTER`] in the text, usually with
+/// [`RichTextBuilder::push_attachment`]. The attachment takes up `size` in
+/// the layout, and wraps as a single character.
+///
+/// The attachment is laid out as a run of em spaces as wide as the
+/// attachment, with a font size no larger than its height, so the line it is
+/// on is about as tall as a line of text at that size.
+///
+/// [`RichText`]: super::RichText
+/// [`Attribute::Attachment`]: super::Attribute::Attachment
+/// [`RichTextBuilder::push_attachment`]: super::RichTextBuilder::push_attachment
+#[derive(Clone)]
+pub struct InlineAttachment {
+    size: Size,
+    alignment: AttachmentAlignment,
+    content: Content,
+}
+
+#[derive(Clone)]
+enum Content {
+    Image(ImageBuf),
+    Painter(Arc<dyn Fn(&mut PaintCtx, Rect)>),
+}
+
+impl InlineAttachment {
+    /// Create a new attachment of the given size, drawn by `paint`.
+    ///
+    /// `paint` is called with the rect that the attachment occupies, in the
+    /// coordinate space of the widget drawing the text.
+    pub fn new(size: impl Into<Size>, paint: impl Fn(&mut PaintCtx, Rect) + 'static) -> Self {
+        InlineAttachment {
+            size: size.into(),
+            alignment: AttachmentAlignment::Baseline,
+            content: Content::Painter(Arc::new(paint)),
+        }
+    }
+
+    /// Create a new attachment that draws `image`, scaled to `size`.
+    pub fn from_image(image: ImageBuf, size: impl Into<Size>) -> Self {
+        InlineAttachment {
+            size: size.into(),
+            alignment: AttachmentAlignment::Baseline,
+            content: Content::Image(image),
+        }
+    }
+
+    /// Builder-style method to set the [`AttachmentAlignment`].
+    ///
+    /// The default is [`AttachmentAlignment::Baseline`].
+    pub fn with_alignment(mut self, alignment: AttachmentAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// The size of the attachment.
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The vertical alignment of the attachment.
+    pub fn alignment(&self) -> AttachmentAlignment {
+        self.alignment
+    }
+
+    /// The text this attachment is laid out as, in place of its
+    /// [`OBJECT_REPLACEMENT_CHARACTER`].
+    ///
+    /// This is a run of em spaces that, at [`placeholder_font_size`], is as
+    /// wide as the attachment.
+    ///
+    /// [`placeholder_font_size`]: InlineAttachment::placeholder_font_size
+    pub fn placeholder(&self) -> String {
+        std::iter::repeat(PLACEHOLDER)
+            .take(self.placeholder_len())
+            .collect()
+    }
+
+    /// The font size of the [`placeholder`].
+    ///
+    /// This is no larger than the height of the attachment, unless the
+    /// attachment is narrower than it is tall, in which case it is the width.
+    ///
+    /// [`placeholder`]: InlineAttachment::placeholder
+    pub fn placeholder_font_size(&self) -> f64 {
+        self.size.width / self.placeholder_len() as f64
+    }
+
+    /// The number of em spaces in the placeholder.
+    fn placeholder_len(&self) -> usize {
+        if self.size.height > 0.0 && self.size.width > self.size.height {
+            (self.size.width / self.size.height).ceil() as usize
+        } else {
+            1
+        }
+    }
+
+    /// Returns the rect occupied by this attachment, given its horizontal
+    /// position `x`, and the top, `baseline` and bottom of its line.
+    pub(crate) fn rect(&self, x: f64, line_top: f64, baseline: f64, line_bottom: f64) -> Rect {
+        let y = match self.alignment {
+            AttachmentAlignment::Baseline => baseline - self.size.height,
+            AttachmentAlignment::Center => (line_top + line_bottom - self.size.height) / 2.0,
+        };
+        Rect::from_origin_size((x, y), self.size)
+    }
+
+    pub(crate) fn paint(&self, ctx: &mut PaintCtx, rect: Rect) {
+        match &self.content {
+            Content::Image(image) => {
+                let image = image.to_image(ctx.render_ctx);
+                ctx.draw_image(&image, rect, InterpolationMode::Bilinear);
+            }
+            Content::Painter(paint) => paint(ctx, rect),
+        }
+    }
+}
+
+/// Returns the [`Placeholder`]s of the `attachments` of `text`, in order.
+///
+/// Each [`OBJECT_REPLACEMENT_CHARACTER`] in the range of an attachment is
+/// replaced by the attachment's [`placeholder`].
+///
+/// [`placeholder`]: InlineAttachment::placeholder
+pub(crate) fn placeholders(
+    text: &str,
+    attachments: &[(Range<usize>, InlineAttachment)],
+) -> Vec<Placeholder> {
+    let mut placeholders = Vec::new();
+    let mut pos = 0;
+    let mut shift = 0;
+    for (range, attachment) in attachments {
+        let range = range.start.max(pos)..range.end.min(text.len());
+        if range.start >= range.end {
+            continue;
+        }
+        let len = attachment.placeholder().len();
+        for (offset, c) in text[range.clone()].char_indices() {
+            if c == OBJECT_REPLACEMENT_CHARACTER {
+                let text_start = range.start + offset;
+                placeholders.push(Placeholder {
+                    text_start,
+                    layout_start: text_start + shift,
+                    layout_len: len,
+                });
+                shift += len - c.len_utf8();
+            }
+        }
+        pos = range.end;
+    }
+    placeholders
+}
+
+/// Returns `text` with the [`OBJECT_REPLACEMENT_CHARACTER`] of each of the
+/// `placeholders` replaced by em spaces.
+pub(crate) fn replace_placeholders(text: &str, placeholders: &[Placeholder]) -> String {
+    let extra: usize = placeholders
+        .iter()
+        .map(|p| p.layout_len - OBJECT_REPLACEMENT_CHARACTER.len_utf8())
+        .sum();
+    let mut buffer = String::with_capacity(text.len() + extra);
+    let mut pos = 0;
+    for p in placeholders {
+        buffer.push_str(&text[pos..p.text_start]);
+        let count = p.layout_len / PLACEHOLDER.len_utf8();
+        buffer.extend(std::iter::repeat(PLACEHOLDER).take(count));
+        pos = p.text_start + OBJECT_REPLACEMENT_CHARACTER.len_utf8();
+    }
+    buffer.push_str(&text[pos..]);
+    buffer
+}
+
+/// Convert an offset in the text to the equivalent offset in the text with
+/// `placeholders`.
+pub(crate) fn to_layout_offset(placeholders: &[Placeholder], text_pos: usize) -> usize {
+    let idx = match placeholders.binary_search_by(|p| p.text_start.cmp(&text_pos)) {
+        Ok(idx) | Err(idx) => idx,
+    };
+    match idx.checked_sub(1).map(|idx| &placeholders[idx]) {
+        Some(p) => {
+            let text_end = p.text_start + OBJECT_REPLACEMENT_CHARACTER.len_utf8();
+            p.layout_start + p.layout_len + text_pos.saturating_sub(text_end)
+        }
+        None => text_pos,
+    }
+}
+
+/// Convert an offset in the text with `placeholders` to the equivalent
+/// offset in the text.
+///
+/// Offsets inside a placeholder go to the nearer end of the attachment.
+pub(crate) fn to_text_offset(placeholders: &[Placeholder], layout_pos: usize) -> usize {
+    let idx = match placeholders.binary_search_by(|p| p.layout_start.cmp(&layout_pos)) {
+        Ok(idx) | Err(idx) => idx,
+    };
+    match idx.checked_sub(1).map(|idx| &placeholders[idx]) {
+        Some(p) => {
+            let text_end = p.text_start + OBJECT_REPLACEMENT_CHARACTER.len_utf8();
+            let inside = layout_pos - p.layout_start;
+            if inside >= p.layout_len {
+                text_end + inside - p.layout_len
+            } else if inside * 2 < p.layout_len {
+                p.text_start
+            } else {
+                text_end
+            }
+        }
+        None => layout_pos,
+    }
+}
+
+impl PartialEq for InlineAttachment {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.alignment == other.alignment
+            && match (&self.content, &other.content) {
+                (Content::Image(one), Content::Image(two)) => one.same(two),
+                (Content::Painter(one), Content::Painter(two)) => Arc::ptr_eq(one, two),
+                _ => false,
+            }
+    }
+}
+
+impl fmt::Debug for InlineAttachment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let content = match self.content {
+            Content::Image(_) => "Image",
+            Content::Painter(_) => "Painter",
+        };
+        f.debug_struct("InlineAttachment")
+            .field("size", &self.size)
+            .field("alignment", &self.alignment)
+            .field("content", &content)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_alignment() {
+        let attachment = InlineAttachment::new((10.0, 8.0), |_, _| ());
+        let rect = attachment.rect(5.0, 0.0, 15.0, 20.0);
+        assert_eq!(rect, Rect::new(5.0, 7.0, 15.0, 15.0));
+
+        let attachment = attachment.with_alignment(AttachmentAlignment::Center);
+        let rect = attachment.rect(5.0, 0.0, 15.0, 20.0);
+        assert_eq!(rect, Rect::new(5.0, 6.0, 15.0, 14.0));
+    }
+
+    #[test]
+    fn placeholder_size() {
+        // the placeholder of a wide attachment is as tall as the attachment
+        let wide = InlineAttachment::new((50.0, 20.0), |_, _| ());
+        assert_eq!(wide.placeholder(), "\u{2003}".repeat(3));
+        let width = wide.placeholder_font_size() * 3.0;
+        assert!((width - 50.0).abs() < 1e-9);
+        assert!(wide.placeholder_font_size() <= 20.0);
+
+        let narrow = InlineAttachment::new((8.0, 20.0), |_, _| ());
+        assert_eq!(narrow.placeholder(), "\u{2003}");
+        assert_eq!(narrow.placeholder_font_size(), 8.0);
+    }
+
+    #[test]
+    fn placeholder_offsets() {
+        let text = "a\u{FFFC}b\u{FFFC}c";
+        let wide = InlineAttachment::new((40.0, 10.0), |_, _| ());
+        let placeholders = placeholders(text, &[(1..4, wide.clone()), (5..8, wide)]);
+        assert_eq!(
+            placeholders,
+            vec![
+                Placeholder {
+                    text_start: 1,
+                    layout_start: 1,
+                    layout_len: 12,
+                },
+                Placeholder {
+                    text_start: 5,
+                    layout_start: 14,
+                    layout_len: 12,
+                },
+            ]
+        );
+        let layout = replace_placeholders(text, &placeholders);
+        assert_eq!(layout, format!("a{0}b{0}c", "\u{2003}".repeat(4)));
+
+        for (text_pos, layout_pos) in [(0, 0), (1, 1), (4, 13), (5, 14), (8, 26), (9, 27)].iter() {
+            assert_eq!(to_layout_offset(&placeholders, *text_pos), *layout_pos);
+            assert_eq!(to_text_offset(&placeholders, *layout_pos), *text_pos);
+        }
+        // inside a placeholder, offsets go to the nearer end
+        assert_eq!(to_text_offset(&placeholders, 4), 1);
+        assert_eq!(to_text_offset(&placeholders, 10), 4);
+    }
+
+    #[test]
+    fn equality() {
+        let attachment = InlineAttachment::new((10.0, 8.0), |_, _| ());
+        assert_eq!(attachment, attachment.clone());
+        assert_ne!(attachment, InlineAttachment::new((10.0, 8.0), |_, _| ()));
+        assert_ne!(
+            attachment,
+            attachment
+                .clone()
+                .with_alignment(AttachmentAlignment::Center)
+        );
+    }
+}
@@ -14,34 +14,73 @@
 
 //! Text attributes and spans.
 
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt;
 use std::ops::Range;
+use std::sync::Arc;
 
-use crate::piet::{Color, FontFamily, FontStyle, FontWeight, TextAttribute as PietAttr};
-use crate::{Command, Env, FontDescriptor, KeyOrValue};
+use crate::piet::{
+    Color, FontFamily, FontStyle, FontWeight, TextAlignment, TextAttribute as PietAttr,
+};
+use crate::{ArcStr, Command, Env, EventCtx, FontDescriptor, KeyOrValue, Selector};
 
+use super::attachment::{self, InlineAttachment};
 use super::EnvUpdateCtx;
 
+/// The [`Selector`] of the [`Command`] carried by [`Link`]s that have been deserialized.
+///
+/// A [`Command`]'s payload cannot be serialized; when a [`Link`] is serialized
+/// (with the `serde` feature) only the name of its command's selector is stored.
+/// When it is deserialized, the link's command uses this selector and carries
+/// that name as its payload.
+#[cfg(feature = "serde")]
+pub const DESERIALIZED_LINK: Selector<String> = Selector::new("druid-builtin.deserialized-link");
+
+/// The factor by which the font size of synthesized small capitals is reduced.
+const SMALL_CAPS_SCALE: f64 = 0.8;
+
 /// A clickable range of text with an associated [`Command`].
-#[derive(Debug, Clone)]
+///
+/// A link can also carry a callback, which is called directly by the widget
+/// displaying the text when the link is clicked; see [`Link::new_with_callback`].
+#[derive(Clone)]
 pub struct Link {
     /// The range of text for the link.
     pub range: Range<usize>,
     /// A [`Command`] representing the link's payload.
     pub command: Command,
+    additional_commands: Vec<Command>,
+    on_click: Option<Arc<dyn Fn(&mut EventCtx, &Env)>>,
+    hover_color: Option<KeyOrValue<Color>>,
+    active_color: Option<KeyOrValue<Color>>,
+    tooltip: Option<ArcStr>,
+}
+
+/// The state of a [`Link`] that the mouse is interacting with.
+///
+/// This is passed to [`TextStorage::with_link_state`] so that the link can
+/// be restyled.
+///
+/// [`TextStorage::with_link_state`]: super::TextStorage::with_link_state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// The mouse is over the link.
+    Hovered,
+    /// The link is being pressed.
+    Pressed,
 }
 
 /// A collection of spans of attributes of various kinds.
 #[derive(Debug, Clone, Default)]
 pub struct AttributeSpans {
-    family: SpanSet<FontFamily>,
-    size: SpanSet<KeyOrValue<f64>>,
-    weight: SpanSet<FontWeight>,
-    fg_color: SpanSet<KeyOrValue<Color>>,
-    style: SpanSet<FontStyle>,
-    underline: SpanSet<bool>,
-    font_descriptor: SpanSet<KeyOrValue<FontDescriptor>>,
+    /// The spans of each kind of attribute, indexed by [`AttributeKind`].
+    sets: [SpanSet<Attribute>; KIND_COUNT],
 }
 
+/// The number of variants of [`AttributeKind`].
+const KIND_COUNT: usize = 23;
+
 /// A set of spans for a given attribute.
 ///
 /// Invariant: the spans are sorted and non-overlapping.
@@ -80,11 +119,51 @@ struct Span<T> {
 /// let theme_color = Attribute::text_color(theme::SELECTION_COLOR);
 /// ```
 ///
+/// An attribute that uses a [`Key`] is resolved again whenever the value of
+/// that key changes, so a color can follow the app's theme. Here the heading
+/// is recolored when the checkbox toggles dark mode:
+///
+/// ```
+/// use druid::text::{Attribute, RichText};
+/// use druid::widget::{Checkbox, Flex, RawLabel};
+/// use druid::{Color, Data, Key, Lens, Widget, WidgetExt};
+///
+/// const HEADING_COLOR: Key<Color> = Key::new("my-app.heading-color");
+///
+/// #[derive(Clone, Data, Lens)]
+/// struct AppState {
+///     dark_mode: bool,
+///     heading: RichText,
+/// }
+///
+/// fn ui() -> impl Widget<AppState> {
+///     Flex::column()
+///         .with_child(RawLabel::new().lens(AppState::heading))
+///         .with_child(Checkbox::new("Dark mode").lens(AppState::dark_mode))
+///         .env_scope(|env, data: &AppState| {
+///             let color = if data.dark_mode {
+///                 Color::rgb8(0xf0, 0xf0, 0xea)
+///             } else {
+///                 Color::rgb8(0x20, 0x20, 0x28)
+///             };
+///             env.set(HEADING_COLOR, color);
+///         })
+/// }
+///
+/// let state = AppState {
+///     dark_mode: false,
+///     heading: RichText::new("Themed text".into())
+///         .with_attribute(.., Attribute::text_color(HEADING_COLOR)),
+/// };
+/// # let _ = (ui(), state);
+/// ```
+///
+/// [`Key`]: crate::Key
 /// [`KeyOrValue`]: ../enum.KeyOrValue.html
 /// [`theme`]: ../theme
 /// [`Attribute::size`]: #method.size
 /// [`Attribute::text_color`]: #method.text_color
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Attribute {
     /// The font family.
     FontFamily(FontFamily),
@@ -94,268 +173,2197 @@ pub enum Attribute {
     Weight(FontWeight),
     /// The foreground color of the text.
     TextColor(KeyOrValue<Color>),
+    /// The background color of the text.
+    ///
+    /// This is not handled by [`piet`]; instead the widget displaying the
+    /// text fills the region behind the glyphs before drawing them.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    BackgroundColor(KeyOrValue<Color>),
     /// The [`FontStyle`]; either regular or italic.
     ///
     /// [`FontStyle`]: enum.FontStyle.html
     Style(FontStyle),
     /// Underline.
     Underline(bool),
+    /// Strikethrough.
+    ///
+    /// The line is drawn in the same color as the text it strikes through.
+    /// [`piet`] does not currently support drawing it in a different color,
+    /// so there is no attribute to override it; text that needs a line of
+    /// another color can be drawn over by the widget that displays it.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    Strikethrough(bool),
+    /// Extra space added between characters, in points.
+    ///
+    /// [`piet`] does not currently support tracking, so this attribute has no
+    /// effect on layout; it is preserved so that it can be applied once
+    /// backend support is available.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    LetterSpacing(KeyOrValue<f64>),
+    /// The height of a line, as a multiple of the font size.
+    ///
+    /// Line height is a property of a whole line: the span that covers the
+    /// first character of a line determines the height of that line, and any
+    /// other line height spans that start later in the same line are ignored.
+    ///
+    /// [`piet`] does not currently support setting the line height, so this
+    /// attribute has no effect on layout; it is preserved so that it can be
+    /// applied once backend support is available.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    LineHeight(KeyOrValue<f64>),
+    /// A vertical offset from the baseline, as a multiple of the font size.
+    ///
+    /// Positive values raise the text, and negative values lower it. This is
+    /// used for superscripts and subscripts; see [`AttributesAdder::superscript`].
+    /// The font size is that of the text before any [`Attribute::FontScale`]
+    /// is applied, so that the shift of a superscript does not depend on how
+    /// much it is reduced.
+    ///
+    /// [`piet`] does not support shifting the baseline, so the [`TextLayout`]
+    /// lays out the shifted text in place, where it takes up space but is
+    /// not drawn, and draws it separately at the shifted position. The height
+    /// of its line is not changed, and text that is shifted far enough may
+    /// overlap the lines above or below it. [`Link`]s can be clicked in
+    /// either position.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    /// [`TextLayout`]: super::TextLayout
+    /// [`AttributesAdder::superscript`]: super::AttributesAdder::superscript
+    BaselineShift(f64),
+    /// A factor by which the font size is multiplied.
+    ///
+    /// The scale applies to the font size of the text, whether that comes
+    /// from an [`Attribute::FontSize`] or from the default font of the
+    /// layout, and is resolved when the text is laid out. This is used for
+    /// superscripts and subscripts; see [`AttributesAdder::superscript`].
+    ///
+    /// [`AttributesAdder::superscript`]: super::AttributesAdder::superscript
+    FontScale(f64),
+    /// The [`TextAlignment`] of the paragraphs covered by this attribute.
+    ///
+    /// Alignment is a property of a whole paragraph, that is, of the text
+    /// between two line breaks; the span that covers the first character of
+    /// a paragraph determines the alignment of that paragraph, overriding the
+    /// alignment set on the widget.
+    ///
+    /// A [`TextLayout`] lays out text with paragraph attributes one paragraph
+    /// at a time. Text edited in a [`TextBox`] is laid out as a single
+    /// paragraph, and the alignment that covers the start of the text is used
+    /// for all of it.
+    ///
+    /// [`TextLayout`]: super::TextLayout
+    /// [`TextBox`]: crate::widget::TextBox
+    Alignment(TextAlignment),
+    /// The indent of the first line of a paragraph, in points.
+    ///
+    /// Like [`Attribute::Alignment`], this is a property of a whole
+    /// paragraph. The indent is at the start of the line, which is on the
+    /// right for right-to-left text.
+    ///
+    /// [`piet`] cannot indent a single line, so the first line of an indented
+    /// paragraph is laid out on its own; it is not justified.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    Indent(f64),
+    /// The indent of all lines of a paragraph except the first, in points.
+    ///
+    /// Like [`Attribute::Alignment`], this is a property of a whole
+    /// paragraph. If the paragraph starts with an [`Attribute::Marker`], the
+    /// marker is placed at the [`Attribute::Indent`], and all of the text
+    /// after it, including the rest of the first line, at the hanging indent.
+    HangingIndent(f64),
+    /// Extra space above a paragraph, in points.
+    ///
+    /// Like [`Attribute::Alignment`], this is a property of a whole paragraph.
+    /// The space between two paragraphs is the sum of the space after the
+    /// first and the space before the second.
+    SpaceBefore(f64),
+    /// Extra space below a paragraph, in points.
+    ///
+    /// Like [`Attribute::Alignment`], this is a property of a whole paragraph.
+    SpaceAfter(f64),
+    /// Marks the text at the start of a paragraph as its list marker, such as
+    /// a bullet or a number.
+    ///
+    /// The marker is laid out separately from the rest of the paragraph; see
+    /// [`Attribute::HangingIndent`].
+    Marker,
+    /// Marks text whose font size may be reduced, down to `min` points, when
+    /// the text would otherwise not fit in the width available to its layout.
+    ///
+    /// When a [`TextLayout`] with a finite wrap width would need to wrap text
+    /// that contains these spans, their font size is reduced until the text
+    /// fits on as many lines as it would with unlimited width; if the text
+    /// still does not fit at the minimum size, it wraps as usual. Text outside
+    /// of these spans keeps its size.
+    ///
+    /// [`TextLayout`]: super::TextLayout
+    ScaleToFit {
+        /// The smallest font size, in points, that the text may be reduced to.
+        min: f64,
+    },
+    /// A [`FontVariant`], such as small capitals.
+    ///
+    /// [`piet`] does not currently expose OpenType font features, so small
+    /// capitals are always synthesized: lowercase letters are replaced by
+    /// their uppercase forms, drawn at a reduced size. The result depends on
+    /// the font and may look different from a font's own small capitals, and
+    /// from platform to platform. Letters whose uppercase form has a
+    /// different length in UTF-8, such as `ß`, are left unchanged, so that
+    /// offsets into the text stay valid.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    FontVariant(FontVariant),
+    /// OpenType font features, such as [`FeatureTag::TABULAR_FIGURES`], each
+    /// with its value: `0` to turn the feature off, `1` to turn it on, or
+    /// the index of an alternate glyph for features that have several.
+    ///
+    /// Features are stored with the text, and kept through edits and
+    /// serialization, but [`piet`] does not currently expose OpenType font
+    /// features, so they do not yet change how the text is drawn on any
+    /// platform; text with features is drawn with the font's defaults.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    FontFeatures(Vec<(FeatureTag, u32)>),
+    /// An [`InlineAttachment`], such as an image, displayed in place of the
+    /// text in the range.
+    ///
+    /// The range should contain a single [`OBJECT_REPLACEMENT_CHARACTER`];
+    /// it is replaced by a placeholder with the width of the attachment for
+    /// layout. Other characters in the range are not affected.
+    ///
+    /// [`OBJECT_REPLACEMENT_CHARACTER`]: super::OBJECT_REPLACEMENT_CHARACTER
+    Attachment(InlineAttachment),
     /// A [`FontDescriptor`](struct.FontDescriptor.html).
     Descriptor(KeyOrValue<FontDescriptor>),
 }
 
-impl Link {
-    /// Create a new `Link`.
-    pub fn new(range: Range<usize>, command: Command) -> Self {
-        Self { range, command }
+/// An alternative form of the glyphs of a font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontVariant {
+    /// The font's normal glyphs.
+    Normal,
+    /// Small capitals: lowercase letters are drawn as smaller uppercase letters.
+    SmallCaps,
+}
+
+/// An OpenType font feature tag, such as `tnum` for tabular figures.
+///
+/// The features that a font supports are listed in the font itself; see the
+/// [OpenType feature registry] for the meaning of the registered tags.
+///
+/// [OpenType feature registry]: https://docs.microsoft.com/en-us/typography/opentype/spec/featurelist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FeatureTag([u8; 4]);
+
+impl FeatureTag {
+    /// Tabular figures (`tnum`): digits that all have the same width, so
+    /// that numbers line up in columns.
+    pub const TABULAR_FIGURES: FeatureTag = FeatureTag(*b"tnum");
+    /// Proportional figures (`pnum`): digits with their natural widths.
+    pub const PROPORTIONAL_FIGURES: FeatureTag = FeatureTag(*b"pnum");
+    /// Standard ligatures (`liga`), such as "fi"; these are usually on by
+    /// default, and can be turned off with a value of `0`.
+    pub const STANDARD_LIGATURES: FeatureTag = FeatureTag(*b"liga");
+    /// Discretionary ligatures (`dlig`).
+    pub const DISCRETIONARY_LIGATURES: FeatureTag = FeatureTag(*b"dlig");
+    /// Kerning (`kern`).
+    pub const KERNING: FeatureTag = FeatureTag(*b"kern");
+    /// Slashed zero (`zero`).
+    pub const SLASHED_ZERO: FeatureTag = FeatureTag(*b"zero");
+
+    /// Create a tag from its four characters, such as `"ss01"` for the
+    /// first stylistic set.
+    ///
+    /// Returns `None` if `tag` is not four printable ASCII characters.
+    pub fn new(tag: &str) -> Option<FeatureTag> {
+        let bytes: [u8; 4] = tag.as_bytes().try_into().ok()?;
+        if bytes.iter().all(|b| (0x20..0x7f).contains(b)) {
+            Some(FeatureTag(bytes))
+        } else {
+            None
+        }
     }
 
-    /// Get this `Link`'s range.
-    pub fn range(&self) -> Range<usize> {
-        self.range.clone()
+    /// The four characters of this tag.
+    pub fn as_str(&self) -> &str {
+        // tags only contain printable ASCII
+        std::str::from_utf8(&self.0).unwrap()
     }
 }
 
-impl AttributeSpans {
-    /// Create a new, empty `AttributeSpans`.
-    pub fn new() -> Self {
-        Default::default()
+impl fmt::Display for FeatureTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
     }
+}
 
-    /// Add a new [`Attribute`] over the provided [`Range`].
-    pub fn add(&mut self, range: Range<usize>, attr: Attribute) {
-        match attr {
-            Attribute::FontFamily(attr) => self.family.add(Span::new(range, attr)),
-            Attribute::FontSize(attr) => self.size.add(Span::new(range, attr)),
-            Attribute::Weight(attr) => self.weight.add(Span::new(range, attr)),
-            Attribute::TextColor(attr) => self.fg_color.add(Span::new(range, attr)),
-            Attribute::Style(attr) => self.style.add(Span::new(range, attr)),
-            Attribute::Underline(attr) => self.underline.add(Span::new(range, attr)),
-            Attribute::Descriptor(attr) => self.font_descriptor.add(Span::new(range, attr)),
+/// The kind of an [`Attribute`], without any associated value.
+///
+/// This is used to identify a class of attributes, for instance when
+/// removing attributes with [`AttributeSpans::remove`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttributeKind {
+    /// [`Attribute::FontFamily`].
+    FontFamily,
+    /// [`Attribute::FontSize`].
+    FontSize,
+    /// [`Attribute::Weight`].
+    Weight,
+    /// [`Attribute::TextColor`].
+    TextColor,
+    /// [`Attribute::BackgroundColor`].
+    BackgroundColor,
+    /// [`Attribute::Style`].
+    Style,
+    /// [`Attribute::Underline`].
+    Underline,
+    /// [`Attribute::Strikethrough`].
+    Strikethrough,
+    /// [`Attribute::LetterSpacing`].
+    LetterSpacing,
+    /// [`Attribute::LineHeight`].
+    LineHeight,
+    /// [`Attribute::BaselineShift`].
+    BaselineShift,
+    /// [`Attribute::FontScale`].
+    FontScale,
+    /// [`Attribute::Alignment`].
+    Alignment,
+    /// [`Attribute::Indent`].
+    Indent,
+    /// [`Attribute::HangingIndent`].
+    HangingIndent,
+    /// [`Attribute::SpaceBefore`].
+    SpaceBefore,
+    /// [`Attribute::SpaceAfter`].
+    SpaceAfter,
+    /// [`Attribute::Marker`].
+    Marker,
+    /// [`Attribute::ScaleToFit`].
+    ScaleToFit,
+    /// [`Attribute::FontVariant`].
+    FontVariant,
+    /// [`Attribute::FontFeatures`].
+    FontFeatures,
+    /// [`Attribute::Attachment`].
+    Attachment,
+    /// [`Attribute::Descriptor`].
+    Descriptor,
+}
+
+impl AttributeKind {
+    /// Every kind of attribute, in the order their spans are stored in an
+    /// [`AttributeSpans`].
+    const ALL: [AttributeKind; KIND_COUNT] = [
+        AttributeKind::FontFamily,
+        AttributeKind::FontSize,
+        AttributeKind::Weight,
+        AttributeKind::TextColor,
+        AttributeKind::BackgroundColor,
+        AttributeKind::Style,
+        AttributeKind::Underline,
+        AttributeKind::Strikethrough,
+        AttributeKind::LetterSpacing,
+        AttributeKind::LineHeight,
+        AttributeKind::BaselineShift,
+        AttributeKind::FontScale,
+        AttributeKind::Alignment,
+        AttributeKind::Indent,
+        AttributeKind::HangingIndent,
+        AttributeKind::SpaceBefore,
+        AttributeKind::SpaceAfter,
+        AttributeKind::Marker,
+        AttributeKind::ScaleToFit,
+        AttributeKind::FontVariant,
+        AttributeKind::FontFeatures,
+        AttributeKind::Attachment,
+        AttributeKind::Descriptor,
+    ];
+}
+
+/// The effective style of the text at a given position.
+///
+/// This is the result of layering all of the attributes that apply at that
+/// position on top of the default style; see [`RichText::resolved_style_at`].
+///
+/// [`RichText::resolved_style_at`]: super::RichText::resolved_style_at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedStyle {
+    /// The font family.
+    pub family: FontFamily,
+    /// The font size, in points.
+    pub size: f64,
+    /// The font weight.
+    pub weight: FontWeight,
+    /// The font style.
+    pub style: FontStyle,
+    /// The foreground color.
+    pub text_color: Color,
+    /// The background color, if any.
+    pub background_color: Option<Color>,
+    /// Whether the text is underlined.
+    pub underline: bool,
+    /// Whether the text is struck through.
+    pub strikethrough: bool,
+}
+
+/// The style of a paragraph of text.
+///
+/// This collects the [`Attribute`]s that apply to whole paragraphs; see
+/// [`TextStorage::paragraphs`].
+///
+/// [`TextStorage::paragraphs`]: super::TextStorage::paragraphs
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParagraphStyle {
+    /// The alignment of the paragraph, if it overrides the default.
+    pub alignment: Option<TextAlignment>,
+    /// The indent of the first line, in points.
+    pub indent: f64,
+    /// Extra space above the paragraph, in points.
+    pub space_before: f64,
+    /// Extra space below the paragraph, in points.
+    pub space_after: f64,
+    /// The indent of the lines after the first, in points.
+    pub hanging_indent: f64,
+    /// The length in bytes of the list marker at the start of the
+    /// paragraph, or `0` if it has none.
+    pub marker_len: usize,
+}
+
+/// How spans that contain an insertion point are updated when text is inserted.
+///
+/// Spans that start at or after the insertion point are always moved after
+/// the inserted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertBehavior {
+    /// Spans that contain the insertion point grow to include the inserted text.
+    Extend,
+    /// Like `Extend`, but spans that end at the insertion point also grow, so
+    /// that the inserted text takes on the attributes of the text before it.
+    ///
+    /// This matches the behaviour of typing in a text editor.
+    ExtendPreceding,
+    /// Spans that contain the insertion point are split in two, leaving the
+    /// inserted text uncovered.
+    Split,
+}
+
+impl Link {
+    /// Create a new `Link`.
+    pub fn new(range: Range<usize>, command: Command) -> Self {
+        Self {
+            range,
+            command,
+            additional_commands: Vec::new(),
+            on_click: None,
+            hover_color: None,
+            active_color: None,
+            tooltip: None,
         }
     }
 
-    pub(crate) fn to_piet_attrs(&self, env: &Env) -> Vec<(Range<usize>, PietAttr)> {
-        let mut items = Vec::new();
-        for Span { range, attr } in self.font_descriptor.iter() {
-            let font = attr.resolve(env);
-            items.push((range.clone(), PietAttr::FontFamily(font.family)));
-            items.push((range.clone(), PietAttr::FontSize(font.size)));
-            items.push((range.clone(), PietAttr::Weight(font.weight)));
-            items.push((range.clone(), PietAttr::Style(font.style)));
+    /// Create a new `Link` that calls the provided closure when clicked.
+    ///
+    /// This avoids having to route a [`Command`] through an [`AppDelegate`]
+    /// for simple actions. The `command` of such a link is [`Selector::NOOP`],
+    /// and is not submitted.
+    ///
+    /// [`AppDelegate`]: crate::AppDelegate
+    pub fn new_with_callback(
+        range: Range<usize>,
+        on_click: impl Fn(&mut EventCtx, &Env) + 'static,
+    ) -> Self {
+        Self {
+            range,
+            command: Selector::NOOP.into(),
+            additional_commands: Vec::new(),
+            on_click: Some(Arc::new(on_click)),
+            hover_color: None,
+            active_color: None,
+            tooltip: None,
         }
+    }
 
-        items.extend(
-            self.family
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::FontFamily(s.attr.clone()))),
-        );
-        items.extend(
-            self.size
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::FontSize(s.attr.resolve(env)))),
-        );
-        items.extend(
-            self.weight
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::Weight(s.attr))),
-        );
-        items.extend(
-            self.fg_color
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::TextColor(s.attr.resolve(env)))),
-        );
-        items.extend(
-            self.style
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::Style(s.attr))),
-        );
-        items.extend(
-            self.underline
-                .iter()
-                .map(|s| (s.range.clone(), PietAttr::Underline(s.attr))),
-        );
+    /// Builder-style method to add a [`Command`] that is submitted when this
+    /// link is clicked, in addition to the link's main `command`.
+    ///
+    /// Commands are submitted in the order they were added, after the main
+    /// `command`.
+    pub fn with_command(mut self, command: impl Into<Command>) -> Self {
+        self.additional_commands.push(command.into());
+        self
+    }
 
-        // sort by ascending start order; this is a stable sort
-        // so items that come from FontDescriptor will stay at the front
-        items.sort_by(|a, b| a.0.start.cmp(&b.0.start));
-        items
+    /// An iterator over all of the [`Command`]s submitted when this link is
+    /// clicked, starting with the main `command`.
+    ///
+    /// [`Selector::NOOP`] commands are skipped.
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        std::iter::once(&self.command)
+            .chain(&self.additional_commands)
+            .filter(|cmd| !cmd.is(Selector::NOOP))
     }
 
-    pub(crate) fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
-        self.size
-            .iter()
-            .any(|span_attr| ctx.env_key_changed(&span_attr.attr))
-            || self
-                .fg_color
-                .iter()
-                .any(|span_attr| ctx.env_key_changed(&span_attr.attr))
-            || self
-                .font_descriptor
-                .iter()
-                .any(|span_attr| ctx.env_key_changed(&span_attr.attr))
+    /// Builder-style method to set a human-readable description of this link,
+    /// such as its destination, to be shown while the mouse is over it.
+    ///
+    /// The tooltip is independent of the link's commands; it is up to the
+    /// widget displaying the text to show it.
+    pub fn with_tooltip(mut self, tooltip: impl Into<ArcStr>) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
     }
-}
 
-impl<T: Clone> SpanSet<T> {
-    fn iter(&self) -> impl Iterator<Item = &Span<T>> {
-        self.spans.iter()
+    /// The tooltip for this link, if one has been set.
+    pub fn tooltip(&self) -> Option<&ArcStr> {
+        self.tooltip.as_ref()
     }
 
-    /// Add a `Span` to this `SpanSet`.
+    /// Builder-style method to set the color of this link's text while the
+    /// mouse is over it.
     ///
-    /// Spans can be added in any order. existing spans will be updated
-    /// as required.
-    fn add(&mut self, span: Span<T>) {
-        let span_start = span.range.start;
-        let span_end = span.range.end;
-        let insert_idx = self
-            .spans
-            .iter()
-            .position(|x| x.range.start >= span.range.start)
-            .unwrap_or_else(|| self.spans.len());
+    /// If this is not set, the text keeps its color; in either case
+    /// [`theme::LINK_HOVER_COLOR`] is painted behind it.
+    ///
+    /// [`theme::LINK_HOVER_COLOR`]: crate::theme::LINK_HOVER_COLOR
+    pub fn with_hover_color(mut self, color: impl Into<KeyOrValue<Color>>) -> Self {
+        self.hover_color = Some(color.into());
+        self
+    }
 
-        // if we are inserting into the middle of an existing span we need
-        // to add the trailing portion back afterwards.
-        let mut prev_remainder = None;
+    /// Builder-style method to set the color of this link's text while it is
+    /// being pressed.
+    ///
+    /// If this is not set, the hover color is used.
+    pub fn with_active_color(mut self, color: impl Into<KeyOrValue<Color>>) -> Self {
+        self.active_color = Some(color.into());
+        self
+    }
 
-        if insert_idx > 0 {
-            // truncate the preceding item, if necessary
-            let before = self.spans.get_mut(insert_idx - 1).unwrap();
-            if before.range.end > span_end {
-                let mut remainder = before.clone();
-                remainder.range.start = span_end;
-                prev_remainder = Some(remainder);
-            }
-            before.range.end = before.range.end.min(span_start);
-        }
+    /// The color of this link's text while the mouse is over it, if one has
+    /// been set.
+    pub fn hover_color(&self) -> Option<&KeyOrValue<Color>> {
+        self.hover_color.as_ref()
+    }
 
-        self.spans.insert(insert_idx, span);
-        if let Some(remainder) = prev_remainder.take() {
-            self.spans.insert(insert_idx + 1, remainder);
-        }
+    /// The color of this link's text while it is being pressed, if one has
+    /// been set.
+    pub fn active_color(&self) -> Option<&KeyOrValue<Color>> {
+        self.active_color.as_ref()
+    }
 
-        // clip any existing spans as needed
-        for after in self.spans.iter_mut().skip(insert_idx + 1) {
-            after.range.start = after.range.start.max(span_end);
-            after.range.end = after.range.end.max(span_end);
+    /// The color of this link's text in the given `state`, if it should
+    /// change.
+    ///
+    /// A pressed link without an active color uses its hover color.
+    pub fn color_for_state(&self, state: LinkState) -> Option<&KeyOrValue<Color>> {
+        match state {
+            LinkState::Hovered => self.hover_color(),
+            LinkState::Pressed => self.active_color().or_else(|| self.hover_color()),
         }
+    }
 
-        // remove any spans that have been overwritten
-        self.spans.retain(|span| !span.is_empty());
+    /// Returns `true` if the [`Env`] key of this link's hover or active color
+    /// has changed.
+    pub(crate) fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
+        self.hover_color
+            .iter()
+            .chain(self.active_color.iter())
+            .any(|color| ctx.env_key_changed(color))
     }
 
-    /// Edit the spans, inserting empty space into the changed region if needed.
-    ///
-    /// This is used to keep the spans up to date as edits occur in the buffer.
+    /// Perform this link's action, in response to it being clicked.
     ///
-    /// `changed` is the range of the string that has been replaced; this can
-    /// be an empty range (eg, 10..10) for the insertion case.
+    /// This calls the link's callback, if it has one, and submits each of
+    /// its [`commands`].
     ///
-    /// `new_len` is the length of the inserted text.
-    //TODO: we could be smarter here about just extending the existing spans
-    //as requred for insertions in the interior of a span.
-    //TODO: this isn't currently used; it should be used if we use spans with
-    //some editable type.
-    // the branches are much more readable without sharing code
-    #[allow(dead_code, clippy::branches_sharing_code)]
-    fn edit(&mut self, changed: Range<usize>, new_len: usize) {
-        let old_len = changed.len();
-        let mut to_insert = None;
-
-        for (idx, Span { range, attr }) in self.spans.iter_mut().enumerate() {
-            if range.end <= changed.start {
-                continue;
-            } else if range.start < changed.start {
-                // we start before but end inside; truncate end
-                if range.end <= changed.end {
-                    range.end = changed.start;
-                // we start before and end after; this is a special case,
-                // we'll need to add a new span
-                } else {
-                    let new_start = changed.start + new_len;
-                    let new_end = range.end - old_len + new_len;
-                    let new_span = Span::new(new_start..new_end, attr.clone());
-                    to_insert = Some((idx + 1, new_span));
-                    range.end = changed.start;
-                }
-            // start inside
-            } else if range.start < changed.end {
-                range.start = changed.start + new_len;
-                // end inside; collapse
-                if range.end <= changed.end {
-                    range.end = changed.start + new_len;
-                // end outside: adjust by length delta
-                } else {
-                    range.end -= old_len;
-                    range.end += new_len;
-                }
-            // whole range is after:
-            } else {
-                range.start -= old_len;
-                range.start += new_len;
-                range.end -= old_len;
-                range.end += new_len;
-            }
+    /// [`commands`]: Link::commands
+    pub fn activate(&self, ctx: &mut EventCtx, env: &Env) {
+        if let Some(on_click) = &self.on_click {
+            on_click(ctx, env);
         }
-        if let Some((idx, span)) = to_insert.take() {
-            self.spans.insert(idx, span);
+        for command in self.commands() {
+            ctx.submit_command(command.clone());
         }
-
-        self.spans.retain(|span| !span.is_empty());
     }
-}
 
-impl<T> Span<T> {
-    fn new(range: Range<usize>, attr: T) -> Self {
-        Span { range, attr }
+    /// Get this `Link`'s range.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
     }
 
-    fn is_empty(&self) -> bool {
-        self.range.end <= self.range.start
+    /// Returns a copy of this `Link` with its range shifted by `offset`.
+    pub(crate) fn shifted(&self, offset: usize) -> Link {
+        let mut link = self.clone();
+        link.range = self.range.start + offset..self.range.end + offset;
+        link
     }
-}
 
-impl Attribute {
-    /// Create a new font size attribute.
-    pub fn size(size: impl Into<KeyOrValue<f64>>) -> Self {
-        Attribute::FontSize(size.into())
+    /// Returns this `Link` updated to account for `len` bytes of text being
+    /// inserted at `offset`, following the same rules as [`AttributeSpans::insert`].
+    pub(crate) fn inserted(
+        &self,
+        offset: usize,
+        len: usize,
+        behavior: InsertBehavior,
+    ) -> Vec<Link> {
+        let range = &self.range;
+        let ranges = if range.start >= offset {
+            vec![range.start + len..range.end + len]
+        } else if range.end > offset
+            || (behavior == InsertBehavior::ExtendPreceding && range.end == offset)
+        {
+            match behavior {
+                InsertBehavior::Split => vec![range.start..offset, offset + len..range.end + len],
+                _ => vec![range.start..range.end + len],
+            }
+        } else {
+            vec![range.clone()]
+        };
+        ranges
+            .into_iter()
+            .map(|range| Link {
+                range,
+                ..self.clone()
+            })
+            .collect()
     }
 
-    /// Create a new forground color attribute.
-    pub fn text_color(color: impl Into<KeyOrValue<Color>>) -> Self {
-        Attribute::TextColor(color.into())
+    /// Returns this `Link` updated to account for the text in `range` being
+    /// deleted, or `None` if the whole link was deleted.
+    pub(crate) fn deleted(&self, range: &Range<usize>) -> Option<Link> {
+        let map = |offset: usize| {
+            if offset <= range.start {
+                offset
+            } else if offset <= range.end {
+                range.start
+            } else {
+                offset - range.len()
+            }
+        };
+        let new_range = map(self.range.start)..map(self.range.end);
+        if new_range.start < new_range.end {
+            Some(Link {
+                range: new_range,
+                ..self.clone()
+            })
+        } else {
+            None
+        }
     }
 
-    /// Create a new font family attribute.
-    pub fn font_family(family: FontFamily) -> Self {
-        Attribute::FontFamily(family)
-    }
+    /// Returns this `Link` updated to account for the text in `changed` being
+    /// replaced with `new_len` bytes of new text.
+    ///
+    /// This follows the rules of [`AttributeSpans::edit`]; a link may be
+    /// removed, clipped, moved, or split in two.
+    pub(crate) fn edited(&self, changed: &Range<usize>, new_len: usize) -> Vec<Link> {
+        let new_end = changed.start + new_len;
+        let shift = |offset: usize| offset - changed.end + new_end;
+        let range = &self.range;
 
-    /// Create a new `FontWeight` attribute.
-    pub fn weight(weight: FontWeight) -> Self {
-        Attribute::Weight(weight)
-    }
+        let ranges = if range.end <= changed.start {
+            vec![range.clone()]
+        } else if range.start >= changed.end {
+            vec![shift(range.start)..shift(range.end)]
+        } else if range.start < changed.start && range.end > changed.end {
+            vec![range.start..changed.start, new_end..shift(range.end)]
+        } else if range.start < changed.start {
+            vec![range.start..changed.start]
+        } else if range.end > changed.end {
+            vec![new_end..shift(range.end)]
+        } else {
+            Vec::new()
+        };
 
-    /// Create a new `FontStyle` attribute.
-    pub fn style(style: FontStyle) -> Self {
-        Attribute::Style(style)
+        ranges
+            .into_iter()
+            .map(|range| Link {
+                range,
+                ..self.clone()
+            })
+            .collect()
     }
 
-    /// Create a new underline attribute.
+    /// Returns a copy of this `Link` with the ends of its range moved by `f`,
+    /// or `None` if its range becomes empty.
+    pub(crate) fn mapped(&self, f: impl Fn(usize) -> usize) -> Option<Link> {
+        let range = f(self.range.start)..f(self.range.end);
+        assert!(
+            range.start <= range.end,
+            "link range {:?} was mapped to {:?}",
+            self.range,
+            range
+        );
+        if range.is_empty() {
+            return None;
+        }
+        let mut link = self.clone();
+        link.range = range;
+        Some(link)
+    }
+
+    /// Returns a copy of this `Link` clipped to `range`, with its range made
+    /// relative to the start of `range`.
+    ///
+    /// Returns `None` if the link does not intersect `range`.
+    pub(crate) fn clipped(&self, range: &Range<usize>) -> Option<Link> {
+        let start = self.range.start.max(range.start);
+        let end = self.range.end.min(range.end);
+        if start >= end {
+            return None;
+        }
+        let mut link = self.clone();
+        link.range = start - range.start..end - range.start;
+        Some(link)
+    }
+}
+
+impl fmt::Debug for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Link")
+            .field("range", &self.range)
+            .field("command", &self.command)
+            .field("additional_commands", &self.additional_commands)
+            .field("on_click", &self.on_click.as_ref().map(|_| "Fn"))
+            .field("hover_color", &self.hover_color)
+            .field("active_color", &self.active_color)
+            .field("tooltip", &self.tooltip)
+            .finish()
+    }
+}
+
+impl AttributeSpans {
+    /// Create a new, empty `AttributeSpans`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The spans of the given kind of attribute.
+    fn spans(&self, kind: AttributeKind) -> &SpanSet<Attribute> {
+        &self.sets[kind as usize]
+    }
+
+    /// The spans of the given kind of attribute, mutably.
+    fn spans_mut(&mut self, kind: AttributeKind) -> &mut SpanSet<Attribute> {
+        &mut self.sets[kind as usize]
+    }
+
+    /// Returns the number of spans in this collection.
+    ///
+    /// Each attribute added to a range is counted separately; an attribute
+    /// that has been split by another attribute of the same kind counts
+    /// once for each remaining piece.
+    pub fn len(&self) -> usize {
+        self.sets.iter().map(|set| set.spans.len()).sum()
+    }
+
+    /// Returns `true` if this collection contains no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a new [`Attribute`] over the provided [`Range`].
+    pub fn add(&mut self, range: Range<usize>, attr: Attribute) {
+        self.spans_mut(attr.kind()).add(Span::new(range, attr));
+    }
+
+    /// Returns an iterator over all of the attributes in this collection,
+    /// along with the ranges they apply to.
+    ///
+    /// Items are yielded in order of ascending range start; attributes that
+    /// start at the same position are yielded in the order of their
+    /// [`AttributeKind`].
+    pub fn iter(&self) -> impl Iterator<Item = (Range<usize>, &Attribute)> {
+        // each set is already sorted, so we merge them, always taking the
+        // earliest span of the first kind that has one.
+        let mut sets: Vec<_> = self
+            .sets
+            .iter()
+            .filter(|set| !set.spans.is_empty())
+            .map(|set| set.iter().peekable())
+            .collect();
+        std::iter::from_fn(move || {
+            let (idx, _) = sets
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(idx, set)| set.peek().map(|span| (idx, span.range.start)))
+                .min_by_key(|(_, start)| *start)?;
+            sets[idx]
+                .next()
+                .map(|span| (span.range.clone(), &span.attr))
+        })
+    }
+    /// Returns the attributes that apply at `offset`.
+    ///
+    /// Attributes are returned in order of precedence, matching the order in
+    /// which they are applied to the text layout: where several attributes
+    /// affect the same property, such as an [`Attribute::Descriptor`] and an
+    /// [`Attribute::Weight`], the later one takes effect.
+    pub fn attributes_at(&self, offset: usize) -> Vec<Attribute> {
+        let mut items: Vec<_> = self
+            .iter()
+            .filter(|(range, _)| range.contains(&offset))
+            .collect();
+        // font descriptors are applied before other attributes starting
+        // at the same position; see `to_piet_attrs`.
+        items.sort_by_key(|(range, attr)| (range.start, attr.kind() != AttributeKind::Descriptor));
+        items.into_iter().map(|(_, attr)| attr.clone()).collect()
+    }
+
+    /// Divide the text, of length `len`, into maximal runs of uniform attributes.
+    ///
+    /// The runs are non-overlapping, cover the whole text, and are each paired
+    /// with their attributes, in the order returned by [`attributes_at`].
+    ///
+    /// [`attributes_at`]: AttributeSpans::attributes_at
+    pub(crate) fn runs(&self, len: usize) -> Vec<(Range<usize>, Vec<Attribute>)> {
+        // the spans in the order of `attributes_at`, and the same spans in
+        // order of their ends; a single sweep over both finds the spans that
+        // apply to each run.
+        let mut items: Vec<_> = self.iter().collect();
+        items.sort_by_key(|(range, attr)| (range.start, attr.kind() != AttributeKind::Descriptor));
+        let mut ends: Vec<usize> = (0..items.len()).collect();
+        ends.sort_by_key(|idx| items[*idx].0.end);
+
+        let mut runs: Vec<(Range<usize>, Vec<Attribute>)> = Vec::new();
+        let mut active = BTreeSet::new();
+        let (mut next_start, mut next_end) = (0, 0);
+        let mut pos = 0;
+        while pos < len {
+            while next_end < ends.len() && items[ends[next_end]].0.end <= pos {
+                active.remove(&ends[next_end]);
+                next_end += 1;
+            }
+            while next_start < items.len() && items[next_start].0.start <= pos {
+                active.insert(next_start);
+                next_start += 1;
+            }
+            let end = items
+                .get(next_start)
+                .map(|(range, _)| range.start)
+                .into_iter()
+                .chain(ends.get(next_end).map(|idx| items[*idx].0.end))
+                .fold(len, usize::min);
+            let attrs: Vec<_> = active.iter().map(|idx| items[*idx].1.clone()).collect();
+            match runs.last_mut() {
+                Some((range, prev)) if *prev == attrs => range.end = end,
+                _ => runs.push((pos..end, attrs)),
+            }
+            pos = end;
+        }
+        runs
+    }
+
+    /// Returns the attribute of the given [`AttributeKind`] if it applies,
+    /// with the same value, to the whole of `range`.
+    ///
+    /// Returns `None` if any part of the range has no attribute of this kind,
+    /// or if the value of the attribute varies within the range. For an empty
+    /// range, this returns the attribute at its start.
+    pub fn attribute_in_range(
+        &self,
+        range: Range<usize>,
+        kind: AttributeKind,
+    ) -> Option<Attribute> {
+        if range.start >= range.end {
+            return self
+                .attributes_at(range.start)
+                .into_iter()
+                .find(|attr| attr.kind() == kind);
+        }
+
+        let mut pos = range.start;
+        let mut found: Option<&Attribute> = None;
+        for Span { range: span, attr } in self.spans(kind).iter() {
+            if span.end <= pos || span.start >= range.end {
+                continue;
+            }
+            if span.start > pos || found.map(|f| f != attr).unwrap_or(false) {
+                return None;
+            }
+            pos = span.end;
+            found = Some(attr);
+        }
+        if pos < range.end {
+            return None;
+        }
+        found.cloned()
+    }
+
+    /// Remove all attributes of the given [`AttributeKind`] from the provided [`Range`].
+    ///
+    /// Spans that partially overlap the range are truncated, and spans that
+    /// contain the range are split in two.
+    pub fn remove(&mut self, range: Range<usize>, kind: AttributeKind) {
+        self.spans_mut(kind).remove(range);
+    }
+
+    /// Remove attributes of all kinds from the provided [`Range`].
+    ///
+    /// Spans that partially overlap the range are truncated, and spans that
+    /// contain the range are split in two.
+    pub fn clear_range(&mut self, range: Range<usize>) {
+        for set in self.sets.iter_mut() {
+            set.remove(range.clone());
+        }
+    }
+
+    /// Merge adjacent spans that have equal attributes.
+    ///
+    /// Text that is built up in many small pieces, such as by a syntax
+    /// highlighter, can otherwise produce a large number of tiny spans, each
+    /// of which is passed to the text layout separately. This does not change
+    /// the appearance of the text.
+    pub fn coalesce(&mut self) {
+        for kind in AttributeKind::ALL.iter() {
+            // adjacent attachments are separate objects, even if they are equal,
+            // and each is displayed in place of its own character.
+            if *kind != AttributeKind::Attachment {
+                self.spans_mut(*kind).coalesce();
+            }
+        }
+    }
+
+    /// Retain only the kinds of attributes for which the predicate returns `true`.
+    ///
+    /// All attributes of the other kinds are removed, over the whole text.
+    pub fn retain(&mut self, mut f: impl FnMut(AttributeKind) -> bool) {
+        for kind in AttributeKind::ALL.iter() {
+            if !f(*kind) {
+                *self.spans_mut(*kind) = SpanSet::default();
+            }
+        }
+    }
+
+    /// Returns the spans that intersect `range`, clipped to that range
+    /// and shifted so that they are relative to its start.
+    pub(crate) fn slice(&self, range: Range<usize>) -> AttributeSpans {
+        let mut sliced = AttributeSpans::new();
+        for (set, slice) in self.sets.iter().zip(sliced.sets.iter_mut()) {
+            *slice = set.slice(&range);
+        }
+        sliced
+    }
+
+    /// Append the spans in `other`, shifting them by `offset`.
+    ///
+    /// `offset` must be greater than or equal to the end of every span
+    /// currently in `self`; this is the case when `other` describes text
+    /// that is appended to the end of the text described by `self`.
+    pub(crate) fn append(&mut self, other: &AttributeSpans, offset: usize) {
+        for (set, other) in self.sets.iter_mut().zip(other.sets.iter()) {
+            set.append(other, offset);
+        }
+    }
+
+    /// Resolve the style of the text at `offset`, starting from the default
+    /// font and text color in the [`theme`].
+    ///
+    /// Attributes are layered in the same order they are applied to the
+    /// text layout.
+    ///
+    /// [`theme`]: crate::theme
+    pub(crate) fn resolved_style_at(&self, offset: usize, env: &Env) -> ResolvedStyle {
+        Self::resolve_style(&self.attributes_at(offset), env)
+    }
+
+    /// Resolve the style of text with `attributes`, in the order returned by
+    /// [`attributes_at`], starting from the default font and text color in
+    /// the [`theme`].
+    ///
+    /// [`attributes_at`]: AttributeSpans::attributes_at
+    /// [`theme`]: crate::theme
+    pub(crate) fn resolve_style(attributes: &[Attribute], env: &Env) -> ResolvedStyle {
+        let font = env.get(crate::theme::UI_FONT);
+        let mut resolved = ResolvedStyle {
+            family: font.family,
+            size: font.size,
+            weight: font.weight,
+            style: font.style,
+            text_color: env.get(crate::theme::TEXT_COLOR),
+            background_color: None,
+            underline: false,
+            strikethrough: false,
+        };
+        let mut scale = 1.0;
+        for attr in attributes {
+            match attr {
+                Attribute::Descriptor(font) => {
+                    let font = font.resolve(env);
+                    resolved.family = font.family;
+                    resolved.size = font.size;
+                    resolved.weight = font.weight;
+                    resolved.style = font.style;
+                }
+                Attribute::FontFamily(family) => resolved.family = family.clone(),
+                Attribute::FontSize(size) => resolved.size = size.resolve(env),
+                Attribute::FontScale(font_scale) => scale = *font_scale,
+                Attribute::Weight(weight) => resolved.weight = *weight,
+                Attribute::TextColor(color) => resolved.text_color = color.resolve(env),
+                Attribute::BackgroundColor(color) => {
+                    resolved.background_color = Some(color.resolve(env))
+                }
+                Attribute::Style(style) => resolved.style = *style,
+                Attribute::Underline(underline) => resolved.underline = *underline,
+                Attribute::Strikethrough(strikethrough) => resolved.strikethrough = *strikethrough,
+                _ => (),
+            }
+        }
+        resolved.size *= scale;
+        resolved
+    }
+
+    /// Update the spans to account for `len` bytes of text inserted at `offset`.
+    ///
+    /// Spans that start at or after `offset` are moved by `len`; the
+    /// [`InsertBehavior`] determines what happens to spans that contain
+    /// `offset`.
+    pub fn insert(&mut self, offset: usize, len: usize, behavior: InsertBehavior) {
+        for set in self.sets.iter_mut() {
+            set.insert(offset, len, behavior);
+        }
+    }
+
+    /// Update the spans to account for the text in `range` being deleted.
+    ///
+    /// Spans that overlap `range` shrink, and spans that lie entirely
+    /// within it are removed; spans after it are moved back.
+    pub fn delete(&mut self, range: Range<usize>) {
+        for set in self.sets.iter_mut() {
+            set.delete(&range);
+        }
+    }
+
+    /// Update the spans to account for the text in `changed` being replaced
+    /// with `new_len` bytes of new text.
+    ///
+    /// Spans that lie entirely within `changed` are removed, and spans that
+    /// start or end within it are clipped to its boundaries. Spans that
+    /// contain `changed` are split in two, and spans after it are moved to
+    /// account for the difference in length.
+    pub fn edit(&mut self, changed: Range<usize>, new_len: usize) {
+        for set in self.sets.iter_mut() {
+            set.edit(changed.clone(), new_len);
+        }
+    }
+
+    /// Move the start and end of every span with `f`.
+    ///
+    /// `f` must not decrease: spans keep their order, and spans that become
+    /// empty are removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` changes the order of the ends of any spans.
+    pub fn map_ranges(&mut self, f: impl Fn(usize) -> usize) {
+        for set in self.sets.iter_mut() {
+            set.map_ranges(&f);
+        }
+    }
+
+    /// Remove the spans that lie entirely within `range`, leaving those that
+    /// extend beyond it untouched.
+    pub(crate) fn remove_within(&mut self, range: Range<usize>) {
+        for set in self.sets.iter_mut() {
+            set.remove_within(&range);
+        }
+    }
+
+    pub(crate) fn to_piet_attrs(&self, env: &Env) -> Vec<(Range<usize>, PietAttr)> {
+        let mut items = Vec::new();
+        for Span { range, attr } in self.spans(AttributeKind::Descriptor).iter() {
+            if let Attribute::Descriptor(font) = attr {
+                let font = font.resolve(env);
+                items.push((range.clone(), PietAttr::FontFamily(font.family)));
+                items.push((range.clone(), PietAttr::FontSize(font.size)));
+                items.push((range.clone(), PietAttr::Weight(font.weight)));
+                items.push((range.clone(), PietAttr::Style(font.style)));
+            }
+        }
+
+        for Span { range, attr } in self.sets.iter().flat_map(|set| set.iter()) {
+            let attr = match attr {
+                Attribute::FontFamily(family) => PietAttr::FontFamily(family.clone()),
+                Attribute::FontSize(size) => PietAttr::FontSize(size.resolve(env)),
+                Attribute::Weight(weight) => PietAttr::Weight(*weight),
+                Attribute::TextColor(color) => PietAttr::TextColor(color.resolve(env)),
+                Attribute::Style(style) => PietAttr::Style(*style),
+                Attribute::Underline(underline) => PietAttr::Underline(*underline),
+                Attribute::Strikethrough(strikethrough) => PietAttr::Strikethrough(*strikethrough),
+                // attachments are laid out as a run of placeholders sized to fit the
+                // attachment; see `InlineAttachment::placeholder`.
+                Attribute::Attachment(attachment) => {
+                    PietAttr::FontSize(attachment.placeholder_font_size())
+                }
+                // letter spacing, line height, font variants and font features
+                // have no piet equivalent yet, and are not passed on. Font scales
+                // need the default font size, and are resolved beforehand; see
+                // `resolve_font_scales`. Shifted text is drawn separately; see
+                // `baseline_shifts`.
+                _ => continue,
+            };
+            items.push((range.clone(), attr));
+        }
+
+        // sort by ascending start order; this is a stable sort
+        // so items that come from FontDescriptor will stay at the front
+        items.sort_by(|a, b| a.0.start.cmp(&b.0.start));
+        items
+    }
+
+    /// Add the default styling for a link over `range`: an underline, and the
+    /// theme's [`LINK_COLOR`].
+    ///
+    /// These are only added to the parts of the range that have no underline
+    /// or text color attribute, so that explicit styling is preserved.
+    ///
+    /// [`LINK_COLOR`]: crate::theme::LINK_COLOR
+    pub(crate) fn add_link_style(&mut self, range: Range<usize>) {
+        self.spans_mut(AttributeKind::Underline)
+            .fill(&range, Attribute::underline(true));
+        self.spans_mut(AttributeKind::TextColor)
+            .fill(&range, Attribute::text_color(crate::theme::LINK_COLOR));
+    }
+
+    /// Returns the attribute of the given kind that covers `offset`, if any.
+    fn attribute_at(&self, kind: AttributeKind, offset: usize) -> Option<&Attribute> {
+        self.spans(kind)
+            .iter()
+            .find(|span| span.range.contains(&offset))
+            .map(|span| &span.attr)
+    }
+
+    /// Returns the alignment of the line starting at `offset`, if one is set.
+    pub(crate) fn alignment_at(&self, offset: usize) -> Option<TextAlignment> {
+        match self.attribute_at(AttributeKind::Alignment, offset) {
+            Some(Attribute::Alignment(alignment)) => Some(*alignment),
+            _ => None,
+        }
+    }
+
+    /// Returns the inline attachments, and the ranges they apply to.
+    pub(crate) fn attachments(&self) -> Vec<(Range<usize>, InlineAttachment)> {
+        self.spans(AttributeKind::Attachment)
+            .iter()
+            .filter_map(|span| match &span.attr {
+                Attribute::Attachment(attachment) => Some((span.range.clone(), attachment.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns `text` with each [`OBJECT_REPLACEMENT_CHARACTER`] that has an
+    /// attachment replaced by its [`placeholder`], and the positions of the
+    /// placeholders, or `None` if there are no attachments.
+    ///
+    /// [`OBJECT_REPLACEMENT_CHARACTER`]: super::OBJECT_REPLACEMENT_CHARACTER
+    /// [`placeholder`]: InlineAttachment::placeholder
+    pub(crate) fn attachment_placeholders(
+        &self,
+        text: &str,
+    ) -> Option<(String, Vec<attachment::Placeholder>)> {
+        if self.spans(AttributeKind::Attachment).spans.is_empty() {
+            return None;
+        }
+        let placeholders = attachment::placeholders(text, &self.attachments());
+        let buffer = attachment::replace_placeholders(text, &placeholders);
+        Some((buffer, placeholders))
+    }
+
+    /// Returns `true` if there are any attributes that apply to whole paragraphs.
+    pub(crate) fn has_paragraph_attributes(&self) -> bool {
+        [
+            AttributeKind::Alignment,
+            AttributeKind::Indent,
+            AttributeKind::HangingIndent,
+            AttributeKind::SpaceBefore,
+            AttributeKind::SpaceAfter,
+            AttributeKind::Marker,
+        ]
+        .iter()
+        .any(|kind| !self.spans(*kind).spans.is_empty())
+    }
+
+    /// Returns the style of the paragraph starting at `offset`.
+    pub(crate) fn paragraph_style_at(&self, offset: usize) -> ParagraphStyle {
+        let points = |kind| match self.attribute_at(kind, offset) {
+            Some(Attribute::Indent(points))
+            | Some(Attribute::HangingIndent(points))
+            | Some(Attribute::SpaceBefore(points))
+            | Some(Attribute::SpaceAfter(points)) => *points,
+            _ => 0.0,
+        };
+
+        ParagraphStyle {
+            alignment: self.alignment_at(offset),
+            indent: points(AttributeKind::Indent),
+            hanging_indent: points(AttributeKind::HangingIndent),
+            space_before: points(AttributeKind::SpaceBefore),
+            space_after: points(AttributeKind::SpaceAfter),
+            marker_len: self
+                .spans(AttributeKind::Marker)
+                .iter()
+                .find(|span| span.range.contains(&offset))
+                .map(|span| span.range.end - offset)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Divide `range` into consecutive pieces, each paired with its resolved
+    /// font size; `default_size` is the size of text that has no explicit size.
+    fn font_sizes(
+        &self,
+        range: &Range<usize>,
+        env: &Env,
+        default_size: f64,
+    ) -> Vec<(Range<usize>, f64)> {
+        self.spans(AttributeKind::FontSize)
+            .pieces(range)
+            .into_iter()
+            .map(|(range, size)| match size {
+                Some(Attribute::FontSize(size)) => (range, size.resolve(env)),
+                _ => (range, default_size),
+            })
+            .collect()
+    }
+
+    /// Returns a copy of these spans with the font size of each
+    /// [`Attribute::ScaleToFit`] span multiplied by `scale`, but not reduced
+    /// below that span's minimum.
+    ///
+    /// `default_size` is the size of text that has no explicit size.
+    ///
+    /// Returns `None` if there are no spans that can be scaled.
+    pub(crate) fn scaled_to_fit(
+        &self,
+        env: &Env,
+        default_size: f64,
+        scale: f64,
+    ) -> Option<AttributeSpans> {
+        if self.spans(AttributeKind::ScaleToFit).spans.is_empty() {
+            return None;
+        }
+        let mut attrs = self.clone();
+        for span in self.spans(AttributeKind::ScaleToFit).iter() {
+            let min = match span.attr {
+                Attribute::ScaleToFit { min } => min,
+                _ => continue,
+            };
+            for (range, size) in self.font_sizes(&span.range, env, default_size) {
+                // never grow text that is already smaller than the minimum
+                let scaled = (size * scale).max(min.min(size));
+                attrs.add(range, Attribute::size(scaled));
+            }
+        }
+        Some(attrs)
+    }
+
+    /// Synthesize small capitals for `text`, which these spans belong to.
+    ///
+    /// Returns the text with the lowercase letters in [`FontVariant::SmallCaps`]
+    /// spans replaced by their uppercase forms, and a copy of these spans with
+    /// the font size of those letters reduced. `default_size` is the size of
+    /// text that has no explicit size.
+    ///
+    /// Returns `None` if there are no small caps spans.
+    pub(crate) fn small_caps(
+        &self,
+        text: &str,
+        env: &Env,
+        default_size: f64,
+    ) -> Option<(String, AttributeSpans)> {
+        let mut spans = self
+            .spans(AttributeKind::FontVariant)
+            .iter()
+            .filter(|span| span.attr == Attribute::FontVariant(FontVariant::SmallCaps))
+            .peekable();
+        spans.peek()?;
+
+        let mut buffer = String::with_capacity(text.len());
+        let mut converted: Vec<Range<usize>> = Vec::new();
+        let mut pos = 0;
+        for span in spans {
+            buffer.push_str(&text[pos..span.range.start]);
+            for (idx, c) in text[span.range.clone()].char_indices() {
+                let idx = span.range.start + idx;
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    // only convert letters whose length doesn't change, so
+                    // that offsets into the text remain valid.
+                    (Some(u), None) if c.is_lowercase() && u.len_utf8() == c.len_utf8() => {
+                        buffer.push(u);
+                        match converted.last_mut() {
+                            Some(range) if range.end == idx => range.end += c.len_utf8(),
+                            _ => converted.push(idx..idx + c.len_utf8()),
+                        }
+                    }
+                    _ => buffer.push(c),
+                }
+            }
+            pos = span.range.end;
+        }
+        buffer.push_str(&text[pos..]);
+
+        let mut attrs = self.clone();
+        for range in converted {
+            for (range, size) in self.font_sizes(&range, env, default_size) {
+                attrs.add(range, Attribute::size(size * SMALL_CAPS_SCALE));
+            }
+        }
+        Some((buffer, attrs))
+    }
+
+    /// Shift the baseline of the text in `range` by `shift`, and scale its
+    /// font size by `size_scale`.
+    ///
+    /// The shift is added to any existing baseline shift, and the scale
+    /// multiplies any existing [`Attribute::FontScale`], so that nested
+    /// superscripts stack.
+    pub(crate) fn shift_baseline(&mut self, range: Range<usize>, shift: f64, size_scale: f64) {
+        for (range, existing) in self.spans(AttributeKind::BaselineShift).pieces(&range) {
+            let existing = match existing {
+                Some(Attribute::BaselineShift(existing)) => existing,
+                _ => 0.0,
+            };
+            self.add(range, Attribute::baseline_shift(existing + shift));
+        }
+        for (range, existing) in self.spans(AttributeKind::FontScale).pieces(&range) {
+            let existing = match existing {
+                Some(Attribute::FontScale(existing)) => existing,
+                _ => 1.0,
+            };
+            self.add(range, Attribute::font_scale(existing * size_scale));
+        }
+    }
+
+    /// Returns the ranges of text with an [`Attribute::BaselineShift`], and
+    /// the distance in points by which each is raised.
+    ///
+    /// `default_size` is the size of text that has no explicit size.
+    pub(crate) fn baseline_shifts(&self, env: &Env, default_size: f64) -> Vec<(Range<usize>, f64)> {
+        let mut shifts = Vec::new();
+        for span in self.spans(AttributeKind::BaselineShift).iter() {
+            let shift = match span.attr {
+                Attribute::BaselineShift(shift) => shift,
+                _ => continue,
+            };
+            for (range, size) in self.font_sizes(&span.range, env, default_size) {
+                shifts.push((range, shift * size));
+            }
+        }
+        shifts
+    }
+
+    /// Returns a copy of these spans in which the text with an
+    /// [`Attribute::BaselineShift`] is transparent, or `None` if there is no
+    /// such text.
+    pub(crate) fn hide_shifted_text(&self) -> Option<AttributeSpans> {
+        let shifted = self.spans(AttributeKind::BaselineShift);
+        if shifted.spans.is_empty() {
+            return None;
+        }
+        let mut attrs = self.clone();
+        for span in shifted.iter() {
+            attrs.add(
+                span.range.clone(),
+                Attribute::text_color(Color::TRANSPARENT),
+            );
+        }
+        Some(attrs)
+    }
+
+    /// Returns a copy of these spans with each [`Attribute::FontScale`]
+    /// applied to the font size of the text it covers, and removed.
+    ///
+    /// `default_size` is the size of text that has no explicit size.
+    ///
+    /// Returns `None` if there are no font scale spans.
+    pub(crate) fn resolve_font_scales(
+        &self,
+        env: &Env,
+        default_size: f64,
+    ) -> Option<AttributeSpans> {
+        if self.spans(AttributeKind::FontScale).spans.is_empty() {
+            return None;
+        }
+        let mut attrs = self.clone();
+        attrs.retain(|kind| kind != AttributeKind::FontScale);
+        for span in self.spans(AttributeKind::FontScale).iter() {
+            let scale = match span.attr {
+                Attribute::FontScale(scale) => scale,
+                _ => continue,
+            };
+            for (range, size) in self.font_sizes(&span.range, env, default_size) {
+                attrs.add(range, Attribute::size(size * scale));
+            }
+        }
+        Some(attrs)
+    }
+
+    /// Resolve the background colors of these spans.
+    ///
+    /// Background colors are not supported by [`piet`], and are painted
+    /// separately.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    pub(crate) fn background_colors(&self, env: &Env) -> Vec<(Range<usize>, Color)> {
+        self.spans(AttributeKind::BackgroundColor)
+            .iter()
+            .filter_map(|span| match &span.attr {
+                Attribute::BackgroundColor(color) => Some((span.range.clone(), color.resolve(env))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
+        self.sets
+            .iter()
+            .flat_map(|set| set.iter())
+            .any(|span| match &span.attr {
+                Attribute::FontSize(value)
+                | Attribute::LetterSpacing(value)
+                | Attribute::LineHeight(value) => ctx.env_key_changed(value),
+                Attribute::TextColor(color) | Attribute::BackgroundColor(color) => {
+                    ctx.env_key_changed(color)
+                }
+                Attribute::Descriptor(font) => ctx.env_key_changed(font),
+                _ => false,
+            })
+    }
+}
+
+impl<T: Clone> SpanSet<T> {
+    fn iter(&self) -> impl Iterator<Item = &Span<T>> {
+        self.spans.iter()
+    }
+
+    /// Add a `Span` to this `SpanSet`.
+    ///
+    /// Spans can be added in any order. existing spans will be updated
+    /// as required.
+    fn add(&mut self, span: Span<T>) {
+        let span_start = span.range.start;
+        let span_end = span.range.end;
+        let insert_idx = self
+            .spans
+            .iter()
+            .position(|x| x.range.start >= span.range.start)
+            .unwrap_or_else(|| self.spans.len());
+
+        // if we are inserting into the middle of an existing span we need
+        // to add the trailing portion back afterwards.
+        let mut prev_remainder = None;
+
+        if insert_idx > 0 {
+            // truncate the preceding item, if necessary
+            let before = self.spans.get_mut(insert_idx - 1).unwrap();
+            if before.range.end > span_end {
+                let mut remainder = before.clone();
+                remainder.range.start = span_end;
+                prev_remainder = Some(remainder);
+            }
+            before.range.end = before.range.end.min(span_start);
+        }
+
+        self.spans.insert(insert_idx, span);
+        if let Some(remainder) = prev_remainder.take() {
+            self.spans.insert(insert_idx + 1, remainder);
+        }
+
+        // clip any existing spans as needed
+        for after in self.spans.iter_mut().skip(insert_idx + 1) {
+            after.range.start = after.range.start.max(span_end);
+            after.range.end = after.range.end.max(span_end);
+        }
+
+        // remove any spans that have been overwritten
+        self.spans.retain(|span| !span.is_empty());
+    }
+
+    /// Append the spans of `other` to this `SpanSet`, shifting them by `offset`.
+    fn append(&mut self, other: &SpanSet<T>, offset: usize) {
+        debug_assert!(self
+            .spans
+            .last()
+            .map(|span| span.range.end <= offset)
+            .unwrap_or(true));
+        self.spans.extend(other.spans.iter().map(|span| {
+            Span::new(
+                span.range.start + offset..span.range.end + offset,
+                span.attr.clone(),
+            )
+        }));
+    }
+
+    /// Returns a new `SpanSet` containing the spans that intersect `range`,
+    /// clipped to that range and shifted to be relative to its start.
+    fn slice(&self, range: &Range<usize>) -> SpanSet<T> {
+        let spans = self
+            .spans
+            .iter()
+            .filter_map(|span| {
+                let start = span.range.start.max(range.start);
+                let end = span.range.end.min(range.end);
+                if start < end {
+                    Some(Span::new(
+                        start - range.start..end - range.start,
+                        span.attr.clone(),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        SpanSet { spans }
+    }
+
+    /// Add `attr` to the parts of `range` that are not covered by any span.
+    fn fill(&mut self, range: &Range<usize>, attr: T) {
+        for (range, existing) in self.pieces(range) {
+            if existing.is_none() {
+                self.add(Span::new(range, attr.clone()));
+            }
+        }
+    }
+
+    /// Update the spans to account for `len` bytes inserted at `offset`.
+    fn insert(&mut self, offset: usize, len: usize, behavior: InsertBehavior) {
+        match behavior {
+            InsertBehavior::Split => self.edit(offset..offset, len),
+            InsertBehavior::Extend | InsertBehavior::ExtendPreceding => {
+                let extend_preceding = behavior == InsertBehavior::ExtendPreceding;
+                for span in self.spans.iter_mut() {
+                    if span.range.start >= offset {
+                        span.range.start += len;
+                        span.range.end += len;
+                    } else if span.range.end > offset
+                        || (extend_preceding && span.range.end == offset)
+                    {
+                        span.range.end += len;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove the text in `range`, shrinking the spans that overlap it.
+    ///
+    /// Spans that lie entirely within `range` are removed.
+    fn delete(&mut self, range: &Range<usize>) {
+        let map = |offset: usize| {
+            if offset <= range.start {
+                offset
+            } else if offset <= range.end {
+                range.start
+            } else {
+                offset - range.len()
+            }
+        };
+        for span in self.spans.iter_mut() {
+            span.range = map(span.range.start)..map(span.range.end);
+        }
+        self.spans.retain(|span| !span.is_empty());
+    }
+
+    /// Move the ends of every span with `f`, removing spans that become empty.
+    fn map_ranges(&mut self, f: &impl Fn(usize) -> usize) {
+        let mut prev_end = 0;
+        for span in self.spans.iter_mut() {
+            let range = f(span.range.start)..f(span.range.end);
+            assert!(
+                range.start >= prev_end && range.start <= range.end,
+                "span range {:?} was mapped to {:?}, out of order",
+                span.range,
+                range
+            );
+            prev_end = range.end;
+            span.range = range;
+        }
+        self.spans.retain(|span| !span.is_empty());
+    }
+
+    /// Remove the spans that lie entirely within `range`.
+    fn remove_within(&mut self, range: &Range<usize>) {
+        self.spans
+            .retain(|span| span.range.start < range.start || span.range.end > range.end);
+    }
+
+    /// Divide `range` into consecutive pieces, each paired with the value of
+    /// the span covering it, or `None` if it is not covered by any span.
+    fn pieces(&self, range: &Range<usize>) -> Vec<(Range<usize>, Option<T>)> {
+        let mut pieces = Vec::new();
+        if range.start >= range.end {
+            return pieces;
+        }
+        let mut pos = range.start;
+        for span in self.spans.iter() {
+            if span.range.end <= pos || span.range.start >= span.range.end {
+                continue;
+            }
+            if span.range.start >= range.end {
+                break;
+            }
+            if span.range.start > pos {
+                pieces.push((pos..span.range.start, None));
+            }
+            let end = span.range.end.min(range.end);
+            pieces.push((span.range.start.max(pos)..end, Some(span.attr.clone())));
+            pos = end;
+        }
+        if pos < range.end {
+            pieces.push((pos..range.end, None));
+        }
+        pieces
+    }
+
+    /// Remove the provided range from all spans in this `SpanSet`.
+    ///
+    /// Spans that start before or end after the range keep the portion
+    /// that lies outside of it.
+    fn remove(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let mut spans = Vec::with_capacity(self.spans.len() + 1);
+        for span in self.spans.drain(..) {
+            if span.range.end <= range.start || span.range.start >= range.end {
+                spans.push(span);
+                continue;
+            }
+            if span.range.start < range.start {
+                spans.push(Span::new(span.range.start..range.start, span.attr.clone()));
+            }
+            if span.range.end > range.end {
+                spans.push(Span::new(range.end..span.range.end, span.attr));
+            }
+        }
+        self.spans = spans;
+    }
+
+    /// Edit the spans, inserting empty space into the changed region if needed.
+    ///
+    /// This is used to keep the spans up to date as edits occur in the buffer.
+    ///
+    /// `changed` is the range of the string that has been replaced; this can
+    /// be an empty range (eg, 10..10) for the insertion case.
+    ///
+    /// `new_len` is the length of the inserted text.
+    // the branches are much more readable without sharing code
+    #[allow(clippy::branches_sharing_code)]
+    fn edit(&mut self, changed: Range<usize>, new_len: usize) {
+        let old_len = changed.len();
+        let mut to_insert = None;
+
+        for (idx, Span { range, attr }) in self.spans.iter_mut().enumerate() {
+            if range.end <= changed.start {
+                continue;
+            } else if range.start < changed.start {
+                // we start before but end inside; truncate end
+                if range.end <= changed.end {
+                    range.end = changed.start;
+                // we start before and end after; this is a special case,
+                // we'll need to add a new span
+                } else {
+                    let new_start = changed.start + new_len;
+                    let new_end = range.end - old_len + new_len;
+                    let new_span = Span::new(new_start..new_end, attr.clone());
+                    to_insert = Some((idx + 1, new_span));
+                    range.end = changed.start;
+                }
+            // start inside
+            } else if range.start < changed.end {
+                range.start = changed.start + new_len;
+                // end inside; collapse
+                if range.end <= changed.end {
+                    range.end = changed.start + new_len;
+                // end outside: adjust by length delta
+                } else {
+                    range.end -= old_len;
+                    range.end += new_len;
+                }
+            // whole range is after:
+            } else {
+                range.start -= old_len;
+                range.start += new_len;
+                range.end -= old_len;
+                range.end += new_len;
+            }
+        }
+        if let Some((idx, span)) = to_insert.take() {
+            self.spans.insert(idx, span);
+        }
+
+        self.spans.retain(|span| !span.is_empty());
+    }
+}
+
+impl<T> Span<T> {
+    fn new(range: Range<usize>, attr: T) -> Self {
+        Span { range, attr }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.range.end <= self.range.start
+    }
+}
+
+impl Attribute {
+    /// Create a new font size attribute.
+    pub fn size(size: impl Into<KeyOrValue<f64>>) -> Self {
+        Attribute::FontSize(size.into())
+    }
+
+    /// Create a new forground color attribute.
+    pub fn text_color(color: impl Into<KeyOrValue<Color>>) -> Self {
+        Attribute::TextColor(color.into())
+    }
+
+    /// Create a new background color attribute.
+    pub fn background_color(color: impl Into<KeyOrValue<Color>>) -> Self {
+        Attribute::BackgroundColor(color.into())
+    }
+
+    /// Create a new background color attribute, used to highlight text.
+    ///
+    /// This is equivalent to [`Attribute::background_color`].
+    pub fn background(color: impl Into<KeyOrValue<Color>>) -> Self {
+        Attribute::background_color(color)
+    }
+
+    /// Create a new font family attribute.
+    pub fn font_family(family: FontFamily) -> Self {
+        Attribute::FontFamily(family)
+    }
+
+    /// Create a new `FontWeight` attribute.
+    pub fn weight(weight: FontWeight) -> Self {
+        Attribute::Weight(weight)
+    }
+
+    /// Create a new `FontStyle` attribute.
+    pub fn style(style: FontStyle) -> Self {
+        Attribute::Style(style)
+    }
+
+    /// Create a new underline attribute.
     pub fn underline(underline: bool) -> Self {
         Attribute::Underline(underline)
     }
 
-    /// Create a new `FontDescriptor` attribute.
-    pub fn font_descriptor(font: impl Into<KeyOrValue<FontDescriptor>>) -> Self {
-        Attribute::Descriptor(font.into())
+    /// Create a new strikethrough attribute.
+    pub fn strikethrough(strikethrough: bool) -> Self {
+        Attribute::Strikethrough(strikethrough)
+    }
+
+    /// Create a new letter spacing attribute.
+    pub fn letter_spacing(spacing: impl Into<KeyOrValue<f64>>) -> Self {
+        Attribute::LetterSpacing(spacing.into())
+    }
+
+    /// Create a new line height attribute, as a multiple of the font size.
+    pub fn line_height(height: impl Into<KeyOrValue<f64>>) -> Self {
+        Attribute::LineHeight(height.into())
+    }
+
+    /// Create a new baseline shift attribute, as a multiple of the font size.
+    pub fn baseline_shift(shift: f64) -> Self {
+        Attribute::BaselineShift(shift)
+    }
+
+    /// Create a new font scale attribute.
+    pub fn font_scale(scale: f64) -> Self {
+        Attribute::FontScale(scale)
+    }
+
+    /// Create a new `TextAlignment` attribute.
+    pub fn alignment(alignment: TextAlignment) -> Self {
+        Attribute::Alignment(alignment)
+    }
+
+    /// Create a new first line indent attribute, in points.
+    pub fn indent(indent: f64) -> Self {
+        Attribute::Indent(indent)
+    }
+
+    /// Create a new hanging indent attribute, in points.
+    pub fn hanging_indent(indent: f64) -> Self {
+        Attribute::HangingIndent(indent)
+    }
+
+    /// Create a new attribute for the space above a paragraph, in points.
+    pub fn space_before(space: f64) -> Self {
+        Attribute::SpaceBefore(space)
+    }
+
+    /// Create a new attribute for the space below a paragraph, in points.
+    pub fn space_after(space: f64) -> Self {
+        Attribute::SpaceAfter(space)
+    }
+
+    /// Create a new list marker attribute.
+    pub fn marker() -> Self {
+        Attribute::Marker
+    }
+
+    /// Create a new scale-to-fit attribute, with the given minimum font size.
+    pub fn scale_to_fit(min: f64) -> Self {
+        Attribute::ScaleToFit { min }
+    }
+
+    /// Create a new `FontVariant` attribute.
+    pub fn font_variant(variant: FontVariant) -> Self {
+        Attribute::FontVariant(variant)
+    }
+
+    /// Create a new `FontFeatures` attribute.
+    pub fn font_features(features: impl IntoIterator<Item = (FeatureTag, u32)>) -> Self {
+        Attribute::FontFeatures(features.into_iter().collect())
+    }
+
+    /// Create a new `InlineAttachment` attribute.
+    pub fn attachment(attachment: InlineAttachment) -> Self {
+        Attribute::Attachment(attachment)
+    }
+
+    /// Create a new `FontDescriptor` attribute.
+    pub fn font_descriptor(font: impl Into<KeyOrValue<FontDescriptor>>) -> Self {
+        Attribute::Descriptor(font.into())
+    }
+
+    /// Returns the [`AttributeKind`] of this attribute.
+    pub fn kind(&self) -> AttributeKind {
+        match self {
+            Attribute::FontFamily(_) => AttributeKind::FontFamily,
+            Attribute::FontSize(_) => AttributeKind::FontSize,
+            Attribute::Weight(_) => AttributeKind::Weight,
+            Attribute::TextColor(_) => AttributeKind::TextColor,
+            Attribute::BackgroundColor(_) => AttributeKind::BackgroundColor,
+            Attribute::Style(_) => AttributeKind::Style,
+            Attribute::Underline(_) => AttributeKind::Underline,
+            Attribute::Strikethrough(_) => AttributeKind::Strikethrough,
+            Attribute::LetterSpacing(_) => AttributeKind::LetterSpacing,
+            Attribute::LineHeight(_) => AttributeKind::LineHeight,
+            Attribute::BaselineShift(_) => AttributeKind::BaselineShift,
+            Attribute::FontScale(_) => AttributeKind::FontScale,
+            Attribute::Alignment(_) => AttributeKind::Alignment,
+            Attribute::Indent(_) => AttributeKind::Indent,
+            Attribute::HangingIndent(_) => AttributeKind::HangingIndent,
+            Attribute::SpaceBefore(_) => AttributeKind::SpaceBefore,
+            Attribute::SpaceAfter(_) => AttributeKind::SpaceAfter,
+            Attribute::Marker => AttributeKind::Marker,
+            Attribute::ScaleToFit { .. } => AttributeKind::ScaleToFit,
+            Attribute::FontVariant(_) => AttributeKind::FontVariant,
+            Attribute::FontFeatures(_) => AttributeKind::FontFeatures,
+            Attribute::Attachment(_) => AttributeKind::Attachment,
+            Attribute::Descriptor(_) => AttributeKind::Descriptor,
+        }
+    }
+}
+
+impl<T: PartialEq> SpanSet<T> {
+    /// Merge adjacent spans with equal attributes.
+    fn coalesce(&mut self) {
+        self.spans.dedup_by(|next, prev| {
+            let merge = prev.range.end == next.range.start && prev.attr == next.attr;
+            if merge {
+                prev.range.end = next.range.end;
+            }
+            merge
+        });
+    }
+}
+
+impl<T> Default for SpanSet<T> {
+    fn default() -> Self {
+        SpanSet { spans: Vec::new() }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::ops::Range;
+
+    use serde_crate::ser::Error as _;
+    use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use std::convert::TryFrom;
+
+    use super::{Attribute, AttributeKind, AttributeSpans, FeatureTag, FontVariant, Link};
+    use crate::piet::{Color, FontFamily, FontStyle, FontWeight, TextAlignment};
+    use crate::{Command, FontDescriptor, Key, KeyOrValue, Target};
+
+    /// A [`KeyOrValue`], with the key represented by its name.
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum ValueRepr<T> {
+        Key(String),
+        Value(T),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum StyleRepr {
+        Regular,
+        Italic,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum AlignmentRepr {
+        Start,
+        End,
+        Center,
+        Justified,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum VariantRepr {
+        Normal,
+        SmallCaps,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct DescriptorRepr {
+        family: String,
+        size: f64,
+        weight: u16,
+        style: StyleRepr,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum AttributeRepr {
+        FontFamily(String),
+        FontSize(ValueRepr<f64>),
+        Weight(u16),
+        TextColor(ValueRepr<u32>),
+        BackgroundColor(ValueRepr<u32>),
+        Style(StyleRepr),
+        Underline(bool),
+        Strikethrough(bool),
+        LetterSpacing(ValueRepr<f64>),
+        LineHeight(ValueRepr<f64>),
+        BaselineShift(f64),
+        FontScale(f64),
+        Alignment(AlignmentRepr),
+        Indent(f64),
+        HangingIndent(f64),
+        SpaceBefore(f64),
+        SpaceAfter(f64),
+        Marker,
+        ScaleToFit { min: f64 },
+        FontVariant(VariantRepr),
+        FontFeatures(Vec<(FeatureTag, u32)>),
+        Descriptor(ValueRepr<DescriptorRepr>),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct SpanRepr<A> {
+        range: Range<usize>,
+        attribute: A,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct LinkRepr {
+        range: Range<usize>,
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tooltip: Option<String>,
+    }
+
+    impl<T> ValueRepr<T> {
+        fn from_key_or_value<U>(item: &KeyOrValue<U>, f: impl FnOnce(&U) -> T) -> Self {
+            match item {
+                KeyOrValue::Concrete(value) => ValueRepr::Value(f(value)),
+                KeyOrValue::Key(key) => ValueRepr::Key(key.name().to_owned()),
+            }
+        }
+
+        fn into_key_or_value<U>(self, f: impl FnOnce(T) -> U) -> KeyOrValue<U> {
+            match self {
+                ValueRepr::Value(value) => KeyOrValue::Concrete(f(value)),
+                ValueRepr::Key(name) => KeyOrValue::Key(Key::from_name(name)),
+            }
+        }
+    }
+
+    impl From<FontStyle> for StyleRepr {
+        fn from(style: FontStyle) -> StyleRepr {
+            match style {
+                FontStyle::Regular => StyleRepr::Regular,
+                FontStyle::Italic => StyleRepr::Italic,
+            }
+        }
+    }
+
+    impl From<StyleRepr> for FontStyle {
+        fn from(style: StyleRepr) -> FontStyle {
+            match style {
+                StyleRepr::Regular => FontStyle::Regular,
+                StyleRepr::Italic => FontStyle::Italic,
+            }
+        }
+    }
+
+    impl From<TextAlignment> for AlignmentRepr {
+        fn from(alignment: TextAlignment) -> AlignmentRepr {
+            match alignment {
+                TextAlignment::Start => AlignmentRepr::Start,
+                TextAlignment::End => AlignmentRepr::End,
+                TextAlignment::Center => AlignmentRepr::Center,
+                TextAlignment::Justified => AlignmentRepr::Justified,
+            }
+        }
+    }
+
+    impl From<AlignmentRepr> for TextAlignment {
+        fn from(alignment: AlignmentRepr) -> TextAlignment {
+            match alignment {
+                AlignmentRepr::Start => TextAlignment::Start,
+                AlignmentRepr::End => TextAlignment::End,
+                AlignmentRepr::Center => TextAlignment::Center,
+                AlignmentRepr::Justified => TextAlignment::Justified,
+            }
+        }
+    }
+
+    impl From<FontVariant> for VariantRepr {
+        fn from(variant: FontVariant) -> VariantRepr {
+            match variant {
+                FontVariant::Normal => VariantRepr::Normal,
+                FontVariant::SmallCaps => VariantRepr::SmallCaps,
+            }
+        }
+    }
+
+    impl From<VariantRepr> for FontVariant {
+        fn from(variant: VariantRepr) -> FontVariant {
+            match variant {
+                VariantRepr::Normal => FontVariant::Normal,
+                VariantRepr::SmallCaps => FontVariant::SmallCaps,
+            }
+        }
+    }
+
+    fn family_from_name(name: String) -> FontFamily {
+        match name.as_str() {
+            "serif" => FontFamily::SERIF,
+            "sans-serif" => FontFamily::SANS_SERIF,
+            "monospace" => FontFamily::MONOSPACE,
+            "system-ui" => FontFamily::SYSTEM_UI,
+            _ => FontFamily::new_unchecked(name),
+        }
+    }
+
+    impl From<&FontDescriptor> for DescriptorRepr {
+        fn from(font: &FontDescriptor) -> DescriptorRepr {
+            DescriptorRepr {
+                family: font.family.name().to_owned(),
+                size: font.size,
+                weight: font.weight.to_raw(),
+                style: font.style.into(),
+            }
+        }
     }
-}
 
-impl<T> Default for SpanSet<T> {
-    fn default() -> Self {
-        SpanSet { spans: Vec::new() }
+    impl From<DescriptorRepr> for FontDescriptor {
+        fn from(font: DescriptorRepr) -> FontDescriptor {
+            FontDescriptor::new(family_from_name(font.family))
+                .with_size(font.size)
+                .with_weight(FontWeight::new(font.weight))
+                .with_style(font.style.into())
+        }
+    }
+
+    impl TryFrom<Attribute> for AttributeRepr {
+        type Error = &'static str;
+
+        fn try_from(attr: Attribute) -> Result<AttributeRepr, Self::Error> {
+            Ok(match attr {
+                Attribute::FontFamily(family) => AttributeRepr::FontFamily(family.name().into()),
+                Attribute::FontSize(size) => {
+                    AttributeRepr::FontSize(ValueRepr::from_key_or_value(&size, |size| *size))
+                }
+                Attribute::Weight(weight) => AttributeRepr::Weight(weight.to_raw()),
+                Attribute::TextColor(color) => AttributeRepr::TextColor(
+                    ValueRepr::from_key_or_value(&color, Color::as_rgba_u32),
+                ),
+                Attribute::BackgroundColor(color) => AttributeRepr::BackgroundColor(
+                    ValueRepr::from_key_or_value(&color, Color::as_rgba_u32),
+                ),
+                Attribute::Style(style) => AttributeRepr::Style(style.into()),
+                Attribute::Underline(underline) => AttributeRepr::Underline(underline),
+                Attribute::Strikethrough(strikethrough) => {
+                    AttributeRepr::Strikethrough(strikethrough)
+                }
+                Attribute::LetterSpacing(spacing) => AttributeRepr::LetterSpacing(
+                    ValueRepr::from_key_or_value(&spacing, |spacing| *spacing),
+                ),
+                Attribute::LineHeight(height) => {
+                    AttributeRepr::LineHeight(ValueRepr::from_key_or_value(&height, |height| {
+                        *height
+                    }))
+                }
+                Attribute::BaselineShift(shift) => AttributeRepr::BaselineShift(shift),
+                Attribute::FontScale(scale) => AttributeRepr::FontScale(scale),
+                Attribute::Alignment(alignment) => AttributeRepr::Alignment(alignment.into()),
+                Attribute::Indent(indent) => AttributeRepr::Indent(indent),
+                Attribute::HangingIndent(indent) => AttributeRepr::HangingIndent(indent),
+                Attribute::SpaceBefore(space) => AttributeRepr::SpaceBefore(space),
+                Attribute::SpaceAfter(space) => AttributeRepr::SpaceAfter(space),
+                Attribute::Marker => AttributeRepr::Marker,
+                Attribute::ScaleToFit { min } => AttributeRepr::ScaleToFit { min },
+                Attribute::FontVariant(variant) => AttributeRepr::FontVariant(variant.into()),
+                Attribute::FontFeatures(features) => AttributeRepr::FontFeatures(features),
+                Attribute::Descriptor(font) => {
+                    AttributeRepr::Descriptor(ValueRepr::from_key_or_value(&font, |font| {
+                        font.into()
+                    }))
+                }
+                Attribute::Attachment(_) => return Err("inline attachments cannot be serialized"),
+            })
+        }
+    }
+
+    impl From<AttributeRepr> for Attribute {
+        fn from(attr: AttributeRepr) -> Attribute {
+            match attr {
+                AttributeRepr::FontFamily(name) => Attribute::FontFamily(family_from_name(name)),
+                AttributeRepr::FontSize(size) => {
+                    Attribute::FontSize(size.into_key_or_value(|size| size))
+                }
+                AttributeRepr::Weight(weight) => Attribute::Weight(FontWeight::new(weight)),
+                AttributeRepr::TextColor(color) => {
+                    Attribute::TextColor(color.into_key_or_value(Color::from_rgba32_u32))
+                }
+                AttributeRepr::BackgroundColor(color) => {
+                    Attribute::BackgroundColor(color.into_key_or_value(Color::from_rgba32_u32))
+                }
+                AttributeRepr::Style(style) => Attribute::Style(style.into()),
+                AttributeRepr::Underline(underline) => Attribute::Underline(underline),
+                AttributeRepr::Strikethrough(strikethrough) => {
+                    Attribute::Strikethrough(strikethrough)
+                }
+                AttributeRepr::LetterSpacing(spacing) => {
+                    Attribute::LetterSpacing(spacing.into_key_or_value(|spacing| spacing))
+                }
+                AttributeRepr::LineHeight(height) => {
+                    Attribute::LineHeight(height.into_key_or_value(|height| height))
+                }
+                AttributeRepr::BaselineShift(shift) => Attribute::BaselineShift(shift),
+                AttributeRepr::FontScale(scale) => Attribute::FontScale(scale),
+                AttributeRepr::Alignment(alignment) => Attribute::Alignment(alignment.into()),
+                AttributeRepr::Indent(indent) => Attribute::Indent(indent),
+                AttributeRepr::HangingIndent(indent) => Attribute::HangingIndent(indent),
+                AttributeRepr::SpaceBefore(space) => Attribute::SpaceBefore(space),
+                AttributeRepr::SpaceAfter(space) => Attribute::SpaceAfter(space),
+                AttributeRepr::Marker => Attribute::Marker,
+                AttributeRepr::ScaleToFit { min } => Attribute::ScaleToFit { min },
+                AttributeRepr::FontVariant(variant) => Attribute::FontVariant(variant.into()),
+                AttributeRepr::FontFeatures(features) => Attribute::FontFeatures(features),
+                AttributeRepr::Descriptor(font) => {
+                    Attribute::Descriptor(font.into_key_or_value(Into::into))
+                }
+            }
+        }
+    }
+
+    impl Serialize for FeatureTag {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for FeatureTag {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let tag = String::deserialize(deserializer)?;
+            FeatureTag::new(&tag).ok_or_else(|| {
+                serde_crate::de::Error::custom(format!("invalid feature tag '{}'", tag))
+            })
+        }
+    }
+
+    impl Serialize for Attribute {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            AttributeRepr::try_from(self.clone())
+                .map_err(S::Error::custom)?
+                .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Attribute {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            AttributeRepr::deserialize(deserializer).map(Attribute::from)
+        }
+    }
+
+    /// `AttributeSpans` are serialized as a sequence of attributes and ranges,
+    /// in the order returned by [`AttributeSpans::iter`]. Inline attachments
+    /// cannot be serialized, and are skipped.
+    impl Serialize for AttributeSpans {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(
+                self.iter()
+                    .filter(|(_, attribute)| attribute.kind() != AttributeKind::Attachment)
+                    .map(|(range, attribute)| SpanRepr { range, attribute }),
+            )
+        }
+    }
+
+    impl<'de> Deserialize<'de> for AttributeSpans {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let spans = Vec::<SpanRepr<Attribute>>::deserialize(deserializer)?;
+            let mut attrs = AttributeSpans::new();
+            for SpanRepr { range, attribute } in spans {
+                attrs.add(range, attribute);
+            }
+            Ok(attrs)
+        }
+    }
+
+    /// A [`Command`] cannot be serialized; instead we serialize the name of
+    /// its selector, and deserialized links carry a [`DESERIALIZED_LINK`]
+    /// command with that name as the payload. Only the main command is
+    /// serialized; additional commands and callbacks are not.
+    ///
+    /// [`DESERIALIZED_LINK`]: super::DESERIALIZED_LINK
+    impl Serialize for Link {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            LinkRepr {
+                range: self.range(),
+                command: self.command.selector_name().to_owned(),
+                tooltip: self.tooltip.as_deref().map(str::to_owned),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Link {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let LinkRepr {
+                range,
+                command,
+                tooltip,
+            } = LinkRepr::deserialize(deserializer)?;
+            let command = Command::new(super::DESERIALIZED_LINK, command, Target::Auto);
+            let link = Link::new(range, command);
+            Ok(match tooltip {
+                Some(tooltip) => link.with_tooltip(tooltip),
+                None => link,
+            })
+        }
     }
 }
 
@@ -380,6 +2388,491 @@ mod tests {
         assert_eq!(&spans.spans, &vec![Span::new(0..5, 3), Span::new(5..20, 4)]);
     }
 
+    #[test]
+    fn remove_spans() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(0..10, 1));
+        spans.remove(3..6);
+        assert_eq!(&spans.spans, &vec![Span::new(0..3, 1), Span::new(6..10, 1)]);
+
+        spans.add(Span::new(12..16, 2));
+        spans.add(Span::new(18..20, 3));
+        spans.remove(8..19);
+        assert_eq!(
+            &spans.spans,
+            &vec![Span::new(0..3, 1), Span::new(6..8, 1), Span::new(19..20, 3)]
+        );
+
+        spans.remove(5..5);
+        assert_eq!(spans.spans.len(), 3);
+        spans.remove(0..30);
+        assert!(spans.spans.is_empty());
+    }
+
+    #[test]
+    fn strikethrough_and_underline_overlap() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::underline(true));
+        attrs.add(5..15, Attribute::strikethrough(true));
+
+        let piet_attrs = attrs.to_piet_attrs(&Env::empty());
+        assert_eq!(piet_attrs.len(), 2);
+        assert!(matches!(piet_attrs[0], (ref r, PietAttr::Underline(true)) if *r == (0..10)));
+        assert!(matches!(piet_attrs[1], (ref r, PietAttr::Strikethrough(true)) if *r == (5..15)));
+    }
+
+    #[test]
+    fn overlapping_background_colors() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::background_color(Color::RED));
+        attrs.add(5..15, Attribute::background_color(Color::BLACK));
+
+        assert!(attrs.to_piet_attrs(&Env::empty()).is_empty());
+        let backgrounds = attrs.background_colors(&Env::empty());
+        assert_eq!(backgrounds, vec![(0..5, Color::RED), (5..15, Color::BLACK)]);
+    }
+
+    #[test]
+    fn letter_spacing_is_ignored_by_piet() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::letter_spacing(2.0));
+        attrs.add(0..10, Attribute::underline(true));
+
+        let piet_attrs = attrs.to_piet_attrs(&Env::empty());
+        assert_eq!(piet_attrs.len(), 1);
+        assert!(matches!(piet_attrs[0], (_, PietAttr::Underline(true))));
+        assert!(attrs
+            .iter()
+            .any(|(_, attr)| attr.kind() == AttributeKind::LetterSpacing));
+    }
+
+    #[test]
+    fn letter_spacing_and_font_descriptor_compose() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::font_descriptor(FontDescriptor::default()));
+        attrs.add(2..6, Attribute::letter_spacing(1.5));
+        attrs.add(4..8, Attribute::font_descriptor(FontDescriptor::default()));
+
+        let spacing: Vec<_> = attrs
+            .iter()
+            .filter(|(_, attr)| attr.kind() == AttributeKind::LetterSpacing)
+            .map(|(range, _)| range)
+            .collect();
+        assert_eq!(spacing, vec![2..6]);
+        // the second descriptor splits the first into 0..4, 4..8 and 8..10,
+        // and each span is expanded into four piet attributes
+        assert_eq!(attrs.to_piet_attrs(&Env::empty()).len(), 12);
+    }
+
+    #[test]
+    fn span_pieces() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(2..4, 1));
+        spans.add(Span::new(6..10, 2));
+
+        assert_eq!(
+            spans.pieces(&(0..8)),
+            vec![(0..2, None), (2..4, Some(1)), (4..6, None), (6..8, Some(2))]
+        );
+        assert_eq!(spans.pieces(&(3..3)), vec![]);
+        assert_eq!(
+            spans.pieces(&(7..12)),
+            vec![(7..10, Some(2)), (10..12, None)]
+        );
+    }
+
+    #[test]
+    fn nested_baseline_shifts_stack() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..6, Attribute::size(10.0));
+        attrs.shift_baseline(2..8, 0.5, 0.5);
+        attrs.shift_baseline(4..6, 0.5, 0.5);
+
+        let shifts: Vec<_> = attrs
+            .spans(AttributeKind::BaselineShift)
+            .iter()
+            .map(|span| (span.range.clone(), span.attr.clone()))
+            .collect();
+        assert_eq!(
+            shifts,
+            vec![
+                (2..4, Attribute::baseline_shift(0.5)),
+                (4..6, Attribute::baseline_shift(1.0)),
+                (6..8, Attribute::baseline_shift(0.5)),
+            ]
+        );
+        let scales: Vec<_> = attrs
+            .spans(AttributeKind::FontScale)
+            .iter()
+            .map(|span| (span.range.clone(), span.attr.clone()))
+            .collect();
+        assert_eq!(
+            scales,
+            vec![
+                (2..4, Attribute::font_scale(0.5)),
+                (4..6, Attribute::font_scale(0.25)),
+                (6..8, Attribute::font_scale(0.5)),
+            ]
+        );
+
+        // text without an explicit size is scaled from the default size
+        let resolved = attrs.resolve_font_scales(&Env::empty(), 20.0).unwrap();
+        assert!(resolved.spans(AttributeKind::FontScale).spans.is_empty());
+        let sizes: Vec<_> = resolved
+            .spans(AttributeKind::FontSize)
+            .iter()
+            .map(|span| match span.attr {
+                Attribute::FontSize(KeyOrValue::Concrete(size)) => (span.range.clone(), size),
+                _ => panic!("unexpected attribute {:?}", span.attr),
+            })
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![(0..2, 10.0), (2..4, 5.0), (4..6, 2.5), (6..8, 10.0)]
+        );
+    }
+
+    #[test]
+    fn alignment_at_line_start() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..6, Attribute::alignment(TextAlignment::Center));
+        attrs.add(6..20, Attribute::alignment(TextAlignment::End));
+
+        assert_eq!(attrs.alignment_at(0), Some(TextAlignment::Center));
+        assert_eq!(attrs.alignment_at(6), Some(TextAlignment::End));
+        assert_eq!(attrs.alignment_at(20), None);
+        assert!(attrs.to_piet_attrs(&Env::empty()).is_empty());
+    }
+
+    #[test]
+    fn attachment_placeholders() {
+        let text = "a\u{FFFC}b\u{FFFC}";
+        let mut attrs = AttributeSpans::new();
+        assert!(attrs.attachment_placeholders(text).is_none());
+
+        let attachment = InlineAttachment::new((12.0, 10.0), |_, _| ());
+        attrs.add(1..4, Attribute::attachment(attachment.clone()));
+        attrs.add(0..8, Attribute::size(20.0));
+
+        let (buffer, placeholders) = attrs.attachment_placeholders(text).unwrap();
+        assert_eq!(buffer, "a\u{2003}\u{2003}b\u{FFFC}");
+        assert_eq!(placeholders.len(), 1);
+        assert_eq!(attrs.attachments(), vec![(1..4, attachment)]);
+
+        // the placeholder is two em spaces, each half the attachment's width
+        let sizes: Vec<_> = attrs
+            .to_piet_attrs(&Env::empty())
+            .into_iter()
+            .filter_map(|(range, attr)| match attr {
+                PietAttr::FontSize(size) => Some((range, size)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sizes, vec![(0..8, 20.0), (1..4, 6.0)]);
+    }
+
+    #[test]
+    fn coalesce_keeps_attachments_apart() {
+        let attachment = InlineAttachment::new((12.0, 10.0), |_, _| ());
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..3, Attribute::attachment(attachment.clone()));
+        attrs.add(3..6, Attribute::attachment(attachment.clone()));
+        attrs.coalesce();
+        assert_eq!(
+            attrs.attachments(),
+            vec![(0..3, attachment.clone()), (3..6, attachment)]
+        );
+    }
+
+    #[test]
+    fn paragraph_styles() {
+        let mut attrs = AttributeSpans::new();
+        assert!(!attrs.has_paragraph_attributes());
+        attrs.add(0..6, Attribute::size(20.0));
+        assert!(!attrs.has_paragraph_attributes());
+
+        attrs.add(0..6, Attribute::alignment(TextAlignment::Center));
+        attrs.add(0..6, Attribute::space_after(8.0));
+        attrs.add(6..20, Attribute::indent(12.0));
+        attrs.add(6..20, Attribute::hanging_indent(24.0));
+        attrs.add(6..8, Attribute::marker());
+        assert!(attrs.has_paragraph_attributes());
+
+        assert_eq!(
+            attrs.paragraph_style_at(0),
+            ParagraphStyle {
+                alignment: Some(TextAlignment::Center),
+                space_after: 8.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            attrs.paragraph_style_at(6),
+            ParagraphStyle {
+                indent: 12.0,
+                hanging_indent: 24.0,
+                marker_len: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(attrs.paragraph_style_at(7).marker_len, 1);
+        assert_eq!(attrs.paragraph_style_at(20), ParagraphStyle::default());
+        assert_eq!(attrs.len(), 6);
+    }
+
+    #[test]
+    fn insert_at_span_boundaries() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(0..4, 1));
+        spans.add(Span::new(4..8, 2));
+
+        let mut split = spans.clone();
+        split.insert(4, 3, InsertBehavior::Split);
+        assert_eq!(split.spans, vec![Span::new(0..4, 1), Span::new(7..11, 2)]);
+
+        let mut extend = spans.clone();
+        extend.insert(4, 3, InsertBehavior::Extend);
+        assert_eq!(extend.spans, vec![Span::new(0..4, 1), Span::new(7..11, 2)]);
+
+        let mut extend = spans.clone();
+        extend.insert(8, 3, InsertBehavior::Extend);
+        assert_eq!(extend.spans, spans.spans);
+
+        let mut extend = spans.clone();
+        extend.insert(0, 3, InsertBehavior::Extend);
+        assert_eq!(extend.spans, vec![Span::new(3..7, 1), Span::new(7..11, 2)]);
+    }
+
+    #[test]
+    fn insert_inside_span() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(2..6, 1));
+
+        let mut split = spans.clone();
+        split.insert(4, 2, InsertBehavior::Split);
+        assert_eq!(split.spans, vec![Span::new(2..4, 1), Span::new(6..8, 1)]);
+
+        let mut extend = spans;
+        extend.insert(4, 2, InsertBehavior::Extend);
+        assert_eq!(extend.spans, vec![Span::new(2..8, 1)]);
+    }
+
+    #[test]
+    fn resolved_style() {
+        let env = Env::empty()
+            .adding(
+                crate::theme::UI_FONT,
+                FontDescriptor::default().with_size(12.0),
+            )
+            .adding(crate::theme::TEXT_COLOR, Color::BLACK);
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::weight(FontWeight::BOLD));
+        attrs.add(0..10, Attribute::size(20.0));
+        attrs.add(5..10, Attribute::font_descriptor(FontDescriptor::default()));
+        attrs.add(6..8, Attribute::text_color(Color::WHITE));
+        attrs.add(6..8, Attribute::background_color(Color::RED));
+
+        let style = attrs.resolved_style_at(2, &env);
+        assert_eq!(style.weight, FontWeight::BOLD);
+        assert_eq!(style.size, 20.0);
+        assert_eq!(style.text_color, Color::BLACK);
+        assert!(!style.underline);
+
+        // the descriptor starts later, and takes precedence
+        let style = attrs.resolved_style_at(6, &env);
+        assert_eq!(style.weight, FontWeight::REGULAR);
+        assert_eq!(style.text_color, Color::WHITE);
+        assert_eq!(style.background_color, Some(Color::RED));
+
+        let style = attrs.resolved_style_at(12, &env);
+        assert_eq!(style.size, 12.0);
+        assert_eq!(style.background_color, None);
+
+        // a font scale applies to the default size
+        attrs.add(12..14, Attribute::font_scale(0.5));
+        assert_eq!(attrs.resolved_style_at(12, &env).size, 6.0);
+        assert_eq!(attrs.resolved_style_at(4, &env).size, 20.0);
+    }
+
+    #[test]
+    fn scale_to_fit_sizes() {
+        let mut attrs = AttributeSpans::new();
+        assert!(attrs.scaled_to_fit(&Env::empty(), 10.0, 0.5).is_none());
+
+        attrs.add(0..4, Attribute::size(20.0));
+        attrs.add(2..8, Attribute::scale_to_fit(6.0));
+        attrs.add(8..10, Attribute::size(4.0));
+        attrs.add(8..10, Attribute::scale_to_fit(6.0));
+
+        let scaled = attrs.scaled_to_fit(&Env::empty(), 10.0, 0.5).unwrap();
+        let sizes: Vec<_> = scaled
+            .spans(AttributeKind::FontSize)
+            .iter()
+            .map(|span| match span.attr {
+                Attribute::FontSize(KeyOrValue::Concrete(size)) => (span.range.clone(), size),
+                _ => panic!("unexpected attribute {:?}", span.attr),
+            })
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![(0..2, 20.0), (2..4, 10.0), (4..8, 6.0), (8..10, 4.0)]
+        );
+    }
+
+    #[test]
+    fn line_height_is_kept_but_not_laid_out() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::line_height(1.5));
+        assert!(attrs.to_piet_attrs(&Env::empty()).is_empty());
+
+        attrs.insert(10, 5, InsertBehavior::ExtendPreceding);
+        assert_eq!(
+            attrs.attribute_in_range(0..15, AttributeKind::LineHeight),
+            Some(Attribute::line_height(1.5))
+        );
+    }
+
+    #[test]
+    fn link_style_preserves_explicit_styles() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(2..4, Attribute::text_color(Color::RED));
+        attrs.add_link_style(0..6);
+
+        let colors: Vec<_> = attrs
+            .spans(AttributeKind::TextColor)
+            .iter()
+            .map(|span| {
+                let is_key = matches!(span.attr, Attribute::TextColor(KeyOrValue::Key(_)));
+                (span.range.clone(), is_key)
+            })
+            .collect();
+        assert_eq!(colors, vec![(0..2, true), (2..4, false), (4..6, true)]);
+        assert_eq!(
+            attrs.spans(AttributeKind::Underline).spans,
+            vec![Span::new(0..6, Attribute::Underline(true))]
+        );
+    }
+
+    #[test]
+    fn attributes_at_offset() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..10, Attribute::weight(FontWeight::BOLD));
+        attrs.add(0..10, Attribute::font_descriptor(FontDescriptor::default()));
+        attrs.add(4..6, Attribute::underline(true));
+
+        let at = attrs.attributes_at(5);
+        assert_eq!(at.len(), 3);
+        assert_eq!(at[0].kind(), AttributeKind::Descriptor);
+        assert_eq!(at[1], Attribute::weight(FontWeight::BOLD));
+        assert_eq!(at[2], Attribute::underline(true));
+        assert!(attrs.attributes_at(10).is_empty());
+    }
+
+    #[test]
+    fn attribute_in_range() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..4, Attribute::weight(FontWeight::BOLD));
+        attrs.add(4..8, Attribute::weight(FontWeight::BOLD));
+        attrs.add(8..10, Attribute::weight(FontWeight::LIGHT));
+        attrs.add(2..5, Attribute::underline(true));
+
+        let bold = Some(Attribute::weight(FontWeight::BOLD));
+        assert_eq!(attrs.attribute_in_range(1..7, AttributeKind::Weight), bold);
+        assert_eq!(attrs.attribute_in_range(6..9, AttributeKind::Weight), None);
+        assert_eq!(attrs.attribute_in_range(9..12, AttributeKind::Weight), None);
+        assert_eq!(attrs.attribute_in_range(3..3, AttributeKind::Weight), bold);
+        assert_eq!(
+            attrs.attribute_in_range(1..4, AttributeKind::Underline),
+            None
+        );
+        assert_eq!(
+            attrs.attribute_in_range(2..5, AttributeKind::Underline),
+            Some(Attribute::underline(true))
+        );
+    }
+
+    #[test]
+    fn attribute_runs() {
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..4, Attribute::weight(FontWeight::BOLD));
+        attrs.add(4..8, Attribute::weight(FontWeight::BOLD));
+        attrs.add(6..12, Attribute::underline(true));
+
+        let runs: Vec<_> = attrs
+            .runs(10)
+            .into_iter()
+            .map(|(range, attrs)| (range, attrs.len()))
+            .collect();
+        assert_eq!(runs, vec![(0..6, 1), (6..8, 2), (8..10, 1)]);
+        assert!(AttributeSpans::new().runs(0).is_empty());
+        assert_eq!(AttributeSpans::new().runs(3), vec![(0..3, vec![])]);
+    }
+
+    #[test]
+    fn runs_match_attributes_at() {
+        let mut attrs = AttributeSpans::new();
+        for i in 0..50 {
+            let start = (i * 7) % 60;
+            let range = start..start + 1 + (i * 13) % 11;
+            match i % 4 {
+                0 => attrs.add(range, Attribute::underline(i % 3 == 0)),
+                1 => attrs.add(range, Attribute::size(i as f64)),
+                2 => attrs.add(range, Attribute::font_descriptor(FontDescriptor::default())),
+                _ => attrs.add(range, Attribute::weight(FontWeight::new(i as u16 * 10))),
+            }
+        }
+
+        let runs = attrs.runs(80);
+        assert_eq!(runs.first().unwrap().0.start, 0);
+        assert_eq!(runs.last().unwrap().0.end, 80);
+        for pair in runs.windows(2) {
+            assert_eq!(pair[0].0.end, pair[1].0.start);
+            assert_ne!(pair[0].1, pair[1].1);
+        }
+        for (range, run_attrs) in runs {
+            for offset in range {
+                assert_eq!(attrs.attributes_at(offset), run_attrs, "offset {}", offset);
+            }
+        }
+    }
+
+    #[test]
+    fn insert_extending_preceding() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(0..4, 1));
+        spans.add(Span::new(4..8, 2));
+
+        spans.insert(4, 2, InsertBehavior::ExtendPreceding);
+        assert_eq!(spans.spans, vec![Span::new(0..6, 1), Span::new(6..10, 2)]);
+        spans.insert(10, 1, InsertBehavior::ExtendPreceding);
+        assert_eq!(spans.spans, vec![Span::new(0..6, 1), Span::new(6..11, 2)]);
+    }
+
+    #[test]
+    fn delete_shrinks_spans() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(0..4, 1));
+        spans.add(Span::new(4..6, 2));
+        spans.add(Span::new(6..12, 3));
+
+        spans.delete(&(2..8));
+        assert_eq!(spans.spans, vec![Span::new(0..2, 1), Span::new(2..6, 3)]);
+        spans.delete(&(3..4));
+        assert_eq!(spans.spans, vec![Span::new(0..2, 1), Span::new(2..5, 3)]);
+    }
+
+    #[test]
+    fn slice_spans() {
+        let mut spans = SpanSet::<u32>::default();
+        spans.add(Span::new(0..4, 1));
+        spans.add(Span::new(4..8, 2));
+        spans.add(Span::new(10..12, 3));
+
+        let sliced = spans.slice(&(2..10));
+        assert_eq!(&sliced.spans, &vec![Span::new(0..2, 1), Span::new(2..6, 2)]);
+        assert!(spans.slice(&(8..10)).spans.is_empty());
+    }
+
     #[test]
     fn edit_spans() {
         let mut spans = SpanSet::<u32>::default();
@@ -407,4 +2900,140 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn link_properties_survive_edits() {
+        let link = Link::new(2..6, Selector::NOOP.into())
+            .with_hover_color(Color::BLACK)
+            .with_active_color(Color::WHITE)
+            .with_tooltip("destination");
+        let edited = link.edited(&(0..1), 3);
+        assert_eq!(edited.len(), 1);
+        assert_eq!(edited[0].range(), 4..8);
+        assert!(matches!(
+            edited[0].hover_color(),
+            Some(KeyOrValue::Concrete(c)) if *c == Color::BLACK
+        ));
+        assert!(matches!(
+            edited[0].active_color(),
+            Some(KeyOrValue::Concrete(c)) if *c == Color::WHITE
+        ));
+        assert_eq!(edited[0].tooltip().map(|s| &**s), Some("destination"));
+    }
+
+    #[test]
+    fn link_commands() {
+        const FIRST: Selector<u32> = Selector::new("druid-test.link.first");
+        const SECOND: Selector = Selector::new("druid-test.link.second");
+
+        let link = Link::new(0..2, FIRST.with(1)).with_command(SECOND);
+        let commands: Vec<_> = link.commands().collect();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].get(FIRST), Some(&1));
+        assert!(commands[1].is(SECOND));
+
+        let callback = Link::new_with_callback(0..2, |_, _| ()).with_command(SECOND);
+        let commands: Vec<_> = callback.commands().collect();
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].is(SECOND));
+    }
+
+    #[test]
+    fn coalesce() {
+        let mut attrs = AttributeSpans::new();
+        for i in 0..10_000 {
+            let weight = if i < 5_000 {
+                FontWeight::BOLD
+            } else {
+                FontWeight::NORMAL
+            };
+            attrs.add(i * 4..(i + 1) * 4, Attribute::Weight(weight));
+            attrs.add(i * 4..(i + 1) * 4, Attribute::Underline(true));
+        }
+        attrs.add(40_004..40_008, Attribute::Underline(true));
+        assert_eq!(attrs.to_piet_attrs(&Env::empty()).len(), 20_001);
+
+        attrs.coalesce();
+        let spans: Vec<_> = attrs.iter().map(|(range, _)| range).collect();
+        assert_eq!(
+            spans,
+            vec![0..20_000, 0..40_000, 20_000..40_000, 40_004..40_008]
+        );
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let mut attrs = AttributeSpans::new();
+        assert!(attrs.is_empty());
+        assert_eq!(attrs.len(), 0);
+
+        attrs.add(0..10, Attribute::Weight(FontWeight::BOLD));
+        attrs.add(0..5, Attribute::Underline(true));
+        attrs.add(3..6, Attribute::Weight(FontWeight::LIGHT));
+        assert!(!attrs.is_empty());
+        assert_eq!(attrs.len(), 4);
+
+        attrs.clear_range(0..10);
+        assert!(attrs.is_empty());
+    }
+
+    #[test]
+    fn font_features() {
+        assert_eq!(FeatureTag::new("ss01").unwrap().as_str(), "ss01");
+        assert_eq!(FeatureTag::new("tnum"), Some(FeatureTag::TABULAR_FIGURES));
+        assert_eq!(FeatureTag::new("ss1"), None);
+        assert_eq!(FeatureTag::new("ss\u{e9}"), None);
+
+        let tnum = Attribute::font_features(vec![(FeatureTag::TABULAR_FIGURES, 1)]);
+        let no_liga = Attribute::font_features(vec![(FeatureTag::STANDARD_LIGATURES, 0)]);
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..4, tnum.clone());
+        attrs.add(4..8, tnum.clone());
+        attrs.add(8..12, no_liga.clone());
+        attrs.coalesce();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(
+            attrs.attribute_in_range(2..6, AttributeKind::FontFeatures),
+            Some(tnum)
+        );
+        assert_eq!(attrs.attributes_at(10), vec![no_liga]);
+
+        // features are not passed on to piet
+        attrs.edit(0..2, 0);
+        assert!(attrs.to_piet_attrs(&Env::empty()).is_empty());
+        attrs.remove(0..10, AttributeKind::FontFeatures);
+        assert_eq!(attrs.len(), 0);
+    }
+
+    #[test]
+    fn small_caps() {
+        let text = "Hi straße ok";
+        let mut attrs = AttributeSpans::new();
+        attrs.add(0..12, Attribute::font_variant(FontVariant::SmallCaps));
+        attrs.add(3..5, Attribute::size(20.0));
+
+        let (buffer, attrs) = attrs.small_caps(text, &Env::empty(), 10.0).unwrap();
+        assert_eq!(buffer, "HI STRAßE Ok");
+        assert_eq!(buffer.len(), text.len());
+
+        let sizes: Vec<_> = attrs
+            .spans(AttributeKind::FontSize)
+            .iter()
+            .map(|span| (span.range.clone(), span.attr.clone()))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                (1..2, Attribute::size(8.0)),
+                (3..5, Attribute::size(16.0)),
+                (5..7, Attribute::size(8.0)),
+                (9..10, Attribute::size(8.0)),
+                (11..12, Attribute::size(8.0)),
+            ]
+        );
+
+        assert!(AttributeSpans::new()
+            .small_caps(text, &Env::empty(), 10.0)
+            .is_none());
+    }
 }
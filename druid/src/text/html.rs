@@ -0,0 +1,874 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Converting between a subset of HTML and [`RichText`].
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::{Attribute, ResolvedStyle, RichText, RichTextBuilder, TextStorage};
+use crate::piet::{Color, FontFamily, FontStyle, FontWeight, TextStorage as _};
+use crate::{Application, ClipboardFormat, Command, Env, Selector};
+
+/// The [`Selector`] of the [`Command`] attached to links created from `<a>` tags
+/// by [`rich_text_from_html`].
+///
+/// The payload is the value of the tag's `href` attribute.
+///
+/// [`Command`]: crate::Command
+pub const HTML_LINK: Selector<String> = Selector::new("druid-builtin.html-link");
+
+/// An error produced when HTML cannot be converted to [`RichText`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlError {
+    /// A tag or comment starting at the given byte offset was never terminated.
+    UnterminatedTag {
+        /// The byte offset of the tag in the input.
+        offset: usize,
+    },
+    /// A closing tag did not match the most recently opened element.
+    MismatchedTag {
+        /// The byte offset of the closing tag in the input.
+        offset: usize,
+        /// The name of the element that was expected to be closed, if any.
+        expected: Option<String>,
+        /// The name of the closing tag.
+        found: String,
+    },
+    /// An element was still open at the end of the input.
+    UnclosedElement {
+        /// The name of the element.
+        name: String,
+    },
+}
+
+/// An element that has been opened but not yet closed.
+struct OpenElement {
+    name: String,
+    start: usize,
+    attrs: Vec<Attribute>,
+    href: Option<String>,
+}
+
+/// Convert a subset of HTML to [`RichText`].
+///
+/// The following tags are supported:
+///
+/// - `<b>` and `<strong>` produce bold text
+/// - `<i>` and `<em>` produce italic text
+/// - `<u>` and `<ins>` produce underlined text
+/// - `<s>`, `<strike>` and `<del>` produce strikethrough text
+/// - `<code>`, `<kbd>` and `<tt>` produce monospaced text
+/// - `<span style="...">` supports the `color`, `background-color`, `font-weight`,
+///   `font-style` and `text-decoration` properties
+/// - `<font color="...">` sets the text color
+/// - `<a href="...">` produces a [`Link`], whose [`Command`] has the
+///   [`HTML_LINK`] selector and the value of `href` as its payload; it is
+///   styled as described in [`AttributesAdder::link`]
+/// - `<br>` produces a line break
+///
+/// Colors can be specified as `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)`,
+/// or as one of the sixteen basic named CSS colors. Invalid or unsupported
+/// style properties are ignored.
+///
+/// Nested elements accumulate their attributes; where two elements set the
+/// same attribute, the innermost element wins. Unknown tags are stripped,
+/// but their text is kept. Comments are removed, and character entities such
+/// as `&amp;` and `&#x2014;` are decoded. Whitespace is preserved as-is.
+///
+/// Malformed input, such as mismatched or unterminated tags, produces an
+/// [`HtmlError`].
+///
+/// [`Link`]: super::Link
+/// [`Command`]: crate::Command
+/// [`AttributesAdder::link`]: super::AttributesAdder::link
+pub fn rich_text_from_html(html: &str) -> Result<RichText, HtmlError> {
+    let mut builder = RichTextBuilder::new();
+    let mut current_pos = 0;
+    let mut open_elements: Vec<OpenElement> = Vec::new();
+    let mut closed_elements: Vec<(usize, Range<usize>, OpenElement)> = Vec::new();
+    let mut offset = 0;
+
+    while offset < html.len() {
+        let rest = &html[offset..];
+        if !rest.starts_with('<') {
+            let text_len = rest.find('<').unwrap_or(rest.len());
+            let text = decode_entities(&rest[..text_len]);
+            builder.push(&text);
+            current_pos += text.len();
+            offset += text_len;
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or(HtmlError::UnterminatedTag { offset })?;
+            offset += end + 3;
+            continue;
+        }
+
+        let tag_len = tag_len(rest).ok_or(HtmlError::UnterminatedTag { offset })?;
+        let tag = &rest[1..tag_len - 1];
+        let tag_offset = offset;
+        offset += tag_len;
+
+        if tag.starts_with('!') || tag.starts_with('?') {
+            // doctype or processing instruction
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_ascii_lowercase();
+            match open_elements.pop() {
+                Some(element) if element.name == name => {
+                    let range = element.start..current_pos;
+                    closed_elements.push((open_elements.len(), range, element));
+                }
+                other => {
+                    return Err(HtmlError::MismatchedTag {
+                        offset: tag_offset,
+                        expected: other.map(|element| element.name),
+                        found: name,
+                    })
+                }
+            }
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_len = tag
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(tag.len());
+        let name = tag[..name_len].to_ascii_lowercase();
+        let html_attrs = parse_html_attributes(&tag[name_len..]);
+
+        if name == "br" {
+            builder.push("\n");
+            current_pos += 1;
+        }
+        if self_closing || is_void_element(&name) {
+            continue;
+        }
+
+        let mut element = OpenElement {
+            name,
+            start: current_pos,
+            attrs: Vec::new(),
+            href: None,
+        };
+        element.add_attributes(&html_attrs);
+        open_elements.push(element);
+    }
+
+    if let Some(element) = open_elements.pop() {
+        return Err(HtmlError::UnclosedElement { name: element.name });
+    }
+
+    // Attributes added later take precedence, so we add attributes for
+    // the outermost elements first.
+    closed_elements.sort_by_key(|(depth, _, _)| *depth);
+    for (_, range, element) in closed_elements {
+        let mut adder = builder.add_attributes_for_range(range);
+        for attr in element.attrs {
+            adder.add_attr(attr);
+        }
+        if let Some(href) = element.href {
+            adder.link(HTML_LINK.with(href));
+        }
+    }
+    Ok(builder.build())
+}
+
+impl RichText {
+    /// Create a new `RichText` by parsing the provided HTML.
+    ///
+    /// This is equivalent to [`rich_text_from_html`]; see its documentation
+    /// for the supported tags.
+    pub fn from_html(html: &str) -> Result<RichText, HtmlError> {
+        rich_text_from_html(html)
+    }
+
+    /// Convert this `RichText` to HTML.
+    ///
+    /// Each run of text with a uniform style is written with the properties
+    /// that differ from the default style, as returned by
+    /// [`resolved_style_at`]; colors and other values are resolved through
+    /// the provided [`Env`]. Line breaks are written as `<br>`.
+    ///
+    /// Links created by [`rich_text_from_html`] are written as `<a href="...">`
+    /// elements with their original `href`. So are links whose command has a
+    /// `String` payload that starts with a URL scheme, such as `https:` or
+    /// `mailto:`, as created by `MarkdownStyle` for absolute URLs; the
+    /// payload is used as the URL. Other links are written as plain text.
+    /// The underline and [`LINK_COLOR`] that links are drawn with by default
+    /// are left out, so that they are styled by the application the HTML is
+    /// pasted into.
+    ///
+    /// The output can be parsed with [`rich_text_from_html`], which gives
+    /// back the same bold, italic, underline and strikethrough text, colors
+    /// and links, although font families and sizes are not preserved.
+    ///
+    /// To convert only part of the text, use [`slice`] first.
+    ///
+    /// [`resolved_style_at`]: RichText::resolved_style_at
+    /// [`LINK_COLOR`]: crate::theme::LINK_COLOR
+    /// [`slice`]: RichText::slice
+    pub fn to_html(&self, env: &Env) -> String {
+        let default_style = RichText::new("".into()).resolved_style_at(0, env);
+        let links = self.links();
+        let href_at = |offset: usize| {
+            links
+                .iter()
+                .find(|link| link.range().contains(&offset))
+                .and_then(|link| link.commands().find_map(link_href))
+        };
+
+        let mut html = String::new();
+        let mut current_href = None;
+        for (range, style) in self.iter_resolved_runs(env) {
+            // Split the run wherever a link starts or ends.
+            let mut boundaries: Vec<usize> = links
+                .iter()
+                .flat_map(|link| vec![link.range().start, link.range().end])
+                .filter(|offset| range.start < *offset && *offset < range.end)
+                .collect();
+            boundaries.sort_unstable();
+            boundaries.dedup();
+            boundaries.push(range.end);
+
+            let mut start = range.start;
+            for end in boundaries {
+                let href = href_at(start);
+                if href != current_href {
+                    if current_href.is_some() {
+                        html.push_str("</a>");
+                    }
+                    if let Some(href) = href {
+                        let _ = write!(html, "<a href=\"{}\">", escape_html(href));
+                    }
+                    current_href = href;
+                }
+                let link_style;
+                let base_style = if href.is_some() {
+                    link_style = ResolvedStyle {
+                        text_color: env.get(crate::theme::LINK_COLOR),
+                        underline: true,
+                        ..default_style.clone()
+                    };
+                    &link_style
+                } else {
+                    &default_style
+                };
+                write_styled_html(&mut html, &self.as_str()[start..end], &style, base_style);
+                start = end;
+            }
+        }
+        if current_href.is_some() {
+            html.push_str("</a>");
+        }
+        html
+    }
+
+    /// Returns this text as a set of [`ClipboardFormat`]s, containing both
+    /// HTML, as produced by [`to_html`], and plain text.
+    ///
+    /// The formats are ordered from richest to plainest, so that they can be
+    /// passed to [`Clipboard::put_formats`] and the application receiving
+    /// them can pick the best one it understands. To copy a selection, call
+    /// this on a [`slice`] of the text.
+    ///
+    /// [`to_html`]: RichText::to_html
+    /// [`Clipboard::put_formats`]: crate::Clipboard::put_formats
+    /// [`slice`]: RichText::slice
+    pub fn to_clipboard_formats(&self, env: &Env) -> Vec<ClipboardFormat> {
+        vec![
+            ClipboardFormat::new(ClipboardFormat::HTML, self.to_html(env)),
+            ClipboardFormat::from(self.as_str()),
+        ]
+    }
+
+    /// Put this text on the system clipboard, as both HTML and plain text.
+    ///
+    /// This allows the formatting to be kept when pasting into applications
+    /// that support it; see [`to_clipboard_formats`].
+    ///
+    /// [`to_clipboard_formats`]: RichText::to_clipboard_formats
+    pub fn copy_to_clipboard(&self, env: &Env) {
+        Application::global()
+            .clipboard()
+            .put_formats(&self.to_clipboard_formats(env));
+    }
+}
+
+/// Returns the URL that a link's command opens, if it has one.
+fn link_href(cmd: &Command) -> Option<&String> {
+    cmd.get(HTML_LINK)
+        .or_else(|| cmd.payload::<String>().filter(|s| has_url_scheme(s)))
+}
+
+/// Returns `true` if `s` starts with a URL scheme followed by a colon.
+fn has_url_scheme(s: &str) -> bool {
+    match s.find(':') {
+        Some(end) if end > 0 => {
+            let scheme = &s[..end];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        _ => false,
+    }
+}
+
+/// Write `text` with the parts of `style` that differ from `default`.
+fn write_styled_html(
+    html: &mut String,
+    text: &str,
+    style: &ResolvedStyle,
+    default: &ResolvedStyle,
+) {
+    let mut css = Vec::new();
+    if style.family != default.family {
+        css.push(format!(
+            "font-family: '{}'",
+            escape_html(style.family.name())
+        ));
+    }
+    if (style.size - default.size).abs() > f64::EPSILON {
+        css.push(format!("font-size: {}px", style.size));
+    }
+    if style.weight != default.weight {
+        css.push(format!("font-weight: {}", style.weight.to_raw()));
+    }
+    if style.style != default.style {
+        let font_style = match style.style {
+            FontStyle::Regular => "normal",
+            FontStyle::Italic => "italic",
+        };
+        css.push(format!("font-style: {}", font_style));
+    }
+    if style.text_color != default.text_color {
+        css.push(format!("color: {}", css_color(&style.text_color)));
+    }
+    if let Some(color) = &style.background_color {
+        css.push(format!("background-color: {}", css_color(color)));
+    }
+
+    if !css.is_empty() {
+        let _ = write!(html, "<span style=\"{}\">", css.join("; "));
+    }
+    let underline = style.underline && !default.underline;
+    let strikethrough = style.strikethrough && !default.strikethrough;
+    if underline {
+        html.push_str("<u>");
+    }
+    if strikethrough {
+        html.push_str("<s>");
+    }
+    html.push_str(&escape_html(text).replace('\n', "<br>"));
+    if strikethrough {
+        html.push_str("</s>");
+    }
+    if underline {
+        html.push_str("</u>");
+    }
+    if !css.is_empty() {
+        html.push_str("</span>");
+    }
+}
+
+fn css_color(color: &Color) -> String {
+    let rgba = color.as_rgba_u32();
+    if rgba & 0xff == 0xff {
+        format!("#{:06x}", rgba >> 8)
+    } else {
+        format!("#{:08x}", rgba)
+    }
+}
+
+/// Replace the characters that are special in HTML with character entities.
+fn escape_html(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+impl std::fmt::Display for HtmlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HtmlError::UnterminatedTag { offset } => {
+                write!(f, "unterminated tag at offset {}", offset)
+            }
+            HtmlError::MismatchedTag {
+                offset,
+                expected: Some(expected),
+                found,
+            } => write!(
+                f,
+                "expected </{}> but found </{}> at offset {}",
+                expected, found, offset
+            ),
+            HtmlError::MismatchedTag {
+                offset,
+                expected: None,
+                found,
+            } => write!(f, "unexpected </{}> at offset {}", found, offset),
+            HtmlError::UnclosedElement { name } => write!(f, "unclosed <{}> element", name),
+        }
+    }
+}
+
+impl std::error::Error for HtmlError {}
+
+impl OpenElement {
+    fn add_attributes(&mut self, html_attrs: &[(String, String)]) {
+        let attr_value = |name: &str| {
+            html_attrs
+                .iter()
+                .find(|(attr_name, _)| attr_name == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        match self.name.as_str() {
+            "b" | "strong" => self.attrs.push(Attribute::weight(FontWeight::BOLD)),
+            "i" | "em" => self.attrs.push(Attribute::style(FontStyle::Italic)),
+            "u" | "ins" => self.attrs.push(Attribute::underline(true)),
+            "s" | "strike" | "del" => self.attrs.push(Attribute::strikethrough(true)),
+            "code" | "kbd" | "tt" => self
+                .attrs
+                .push(Attribute::font_family(FontFamily::MONOSPACE)),
+            "font" => {
+                if let Some(color) = attr_value("color").and_then(parse_color) {
+                    self.attrs.push(Attribute::text_color(color));
+                }
+            }
+            "a" => {
+                if let Some(href) = attr_value("href") {
+                    self.href = Some(href.to_owned());
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(style) = attr_value("style") {
+            self.add_style_attributes(style);
+        }
+    }
+
+    fn add_style_attributes(&mut self, style: &str) {
+        for declaration in style.split(';') {
+            let mut parts = declaration.splitn(2, ':');
+            let property = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let value = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+
+            let attr = match property.as_str() {
+                "color" => parse_color(&value).map(Attribute::text_color),
+                "background-color" => parse_color(&value).map(Attribute::background_color),
+                "font-weight" => match value.as_str() {
+                    "bold" | "bolder" => Some(FontWeight::BOLD),
+                    "normal" => Some(FontWeight::REGULAR),
+                    other => other.parse().ok().map(FontWeight::new),
+                }
+                .map(Attribute::weight),
+                "font-style" => match value.as_str() {
+                    "italic" | "oblique" => Some(Attribute::style(FontStyle::Italic)),
+                    "normal" => Some(Attribute::style(FontStyle::Regular)),
+                    _ => None,
+                },
+                "text-decoration" | "text-decoration-line" => match value.as_str() {
+                    "underline" => Some(Attribute::underline(true)),
+                    "line-through" => Some(Attribute::strikethrough(true)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            self.attrs.extend(attr);
+        }
+    }
+}
+
+/// Returns the length of the tag at the start of `s`, including the
+/// angle brackets, skipping over any quoted attribute values.
+fn tag_len(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (idx, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(idx + 1),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Parse the attributes of a tag into (lowercase name, decoded value) pairs.
+fn parse_html_attributes(mut s: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            break;
+        }
+        let name_len = s
+            .find(|c: char| c.is_ascii_whitespace() || c == '=')
+            .unwrap_or(s.len());
+        let name = s[..name_len].to_ascii_lowercase();
+        s = s[name_len..].trim_start();
+
+        let value = if let Some(rest) = s.strip_prefix('=') {
+            let rest = rest.trim_start();
+            let (value, value_len) = match rest.chars().next() {
+                Some(quote @ '"') | Some(quote @ '\'') => {
+                    let end = rest[1..].find(quote).map(|i| i + 1).unwrap_or(rest.len());
+                    (&rest[1..end], (end + 1).min(rest.len()))
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(rest.len());
+                    (&rest[..end], end)
+                }
+            };
+            s = &rest[value_len..];
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+        attrs.push((name, value));
+    }
+    attrs
+}
+
+fn is_void_element(name: &str) -> bool {
+    matches!(
+        name,
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+/// Replace character entities with the characters they represent.
+///
+/// Unrecognized entities are left as-is.
+fn decode_entities(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest
+            .find(';')
+            .filter(|end| *end <= 32)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(num) = entity.strip_prefix('#') {
+        let code = match num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return std::char::from_u32(code);
+    }
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "hellip" => '\u{2026}',
+        "copy" => '\u{a9}',
+        "reg" => '\u{ae}',
+        _ => return None,
+    };
+    Some(c)
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim().to_ascii_lowercase();
+    if let Some(hex) = s.strip_prefix('#') {
+        let digit = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 1)?, 16).ok();
+        let byte = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+        return match hex.len() {
+            3 => Some(Color::rgb8(digit(0)? * 17, digit(1)? * 17, digit(2)? * 17)),
+            6 => Some(Color::rgb8(byte(0)?, byte(2)?, byte(4)?)),
+            8 => Some(Color::rgba8(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => None,
+        };
+    }
+    if let Some(args) = s.strip_prefix("rgb(").and_then(|s| s.strip_suffix(')')) {
+        let mut components = args.split(',').map(|c| c.trim().parse::<u8>().ok());
+        let r = components.next()??;
+        let g = components.next()??;
+        let b = components.next()??;
+        return match components.next() {
+            None => Some(Color::rgb8(r, g, b)),
+            Some(_) => None,
+        };
+    }
+    let color = match s.as_str() {
+        "black" => Color::BLACK,
+        "silver" => Color::SILVER,
+        "gray" | "grey" => Color::GRAY,
+        "white" => Color::WHITE,
+        "maroon" => Color::MAROON,
+        "red" => Color::RED,
+        "purple" => Color::PURPLE,
+        "fuchsia" => Color::FUCHSIA,
+        "green" => Color::GREEN,
+        "lime" => Color::LIME,
+        "olive" => Color::OLIVE,
+        "yellow" => Color::YELLOW,
+        "navy" => Color::NAVY,
+        "blue" => Color::BLUE,
+        "teal" => Color::TEAL,
+        "aqua" => Color::AQUA,
+        "transparent" => Color::TRANSPARENT,
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextStorage;
+    use crate::KeyOrValue;
+
+    fn attrs_at(text: &RichText, idx: usize) -> Vec<Attribute> {
+        text.spans()
+            .filter(|(range, _)| range.contains(&idx))
+            .map(|(_, attr)| attr.clone())
+            .collect()
+    }
+
+    #[test]
+    fn simple_tags() {
+        let text = rich_text_from_html("<b>bold</b> <i>italic</i> <u>under</u>").unwrap();
+        assert_eq!(text.as_str(), "bold italic under");
+        assert!(matches!(
+            attrs_at(&text, 0)[..],
+            [Attribute::Weight(FontWeight::BOLD)]
+        ));
+        assert!(matches!(
+            attrs_at(&text, 5)[..],
+            [Attribute::Style(FontStyle::Italic)]
+        ));
+        assert!(matches!(
+            attrs_at(&text, 12)[..],
+            [Attribute::Underline(true)]
+        ));
+        assert!(attrs_at(&text, 4).is_empty());
+    }
+
+    #[test]
+    fn nested_tags_accumulate() {
+        let text = rich_text_from_html(
+            r#"<span style="color: #ff0000">red <b>bold <font color="blue">blue</font></b></span>"#,
+        )
+        .unwrap();
+        assert_eq!(text.as_str(), "red bold blue");
+
+        let colors: Vec<_> = text
+            .spans()
+            .filter_map(|(range, attr)| match attr {
+                Attribute::TextColor(KeyOrValue::Concrete(color)) => Some((range, color.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            colors,
+            vec![(0..9, Color::rgb8(0xff, 0, 0)), (9..13, Color::BLUE)]
+        );
+        assert_eq!(attrs_at(&text, 10).len(), 2);
+    }
+
+    #[test]
+    fn links() {
+        let text =
+            RichText::from_html(r#"see <a href="https://linebender.org?a=1&amp;b=2">here</a>"#)
+                .unwrap();
+        assert_eq!(text.as_str(), "see here");
+        let links = text.links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].range(), 4..8);
+        assert_eq!(
+            links[0].command.get(HTML_LINK).map(String::as_str),
+            Some("https://linebender.org?a=1&b=2")
+        );
+    }
+
+    #[test]
+    fn unknown_tags_and_entities() {
+        let text = rich_text_from_html(
+            "<p>a &lt;b&gt; &amp; <blink>c</blink>&#x2014;&#33; &bogus;<br/>d</p><!-- gone -->",
+        )
+        .unwrap();
+        assert_eq!(text.as_str(), "a <b> & c\u{2014}! &bogus;\nd");
+        assert_eq!(text.spans().count(), 0);
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            rich_text_from_html("<b>bold</i>").unwrap_err(),
+            HtmlError::MismatchedTag {
+                offset: 7,
+                expected: Some("b".into()),
+                found: "i".into()
+            }
+        );
+        assert_eq!(
+            rich_text_from_html("text<b").unwrap_err(),
+            HtmlError::UnterminatedTag { offset: 4 }
+        );
+        assert_eq!(
+            rich_text_from_html("<i>text").unwrap_err(),
+            HtmlError::UnclosedElement { name: "i".into() }
+        );
+        assert!(rich_text_from_html("</b>").is_err());
+        assert!(rich_text_from_html("<!-- comment").is_err());
+    }
+
+    #[test]
+    fn to_html() {
+        let env = Env::empty()
+            .adding(crate::theme::UI_FONT, crate::FontDescriptor::default())
+            .adding(crate::theme::TEXT_COLOR, Color::BLACK)
+            .adding(crate::theme::LINK_COLOR, Color::BLUE);
+        let text = rich_text_from_html(
+            r#"<b>bold</b> &amp; <a href="https://x.org?a&amp;b">link</a><br><font color="red">red</font>"#,
+        )
+        .unwrap();
+        let html = text.to_html(&env);
+        assert_eq!(
+            html,
+            "<span style=\"font-weight: 700\">bold</span> &amp; \
+             <a href=\"https://x.org?a&amp;b\">link</a>\
+             <br><span style=\"color: #ff0000\">red</span>"
+        );
+
+        let round_trip = rich_text_from_html(&html).unwrap();
+        assert_eq!(round_trip.as_str(), text.as_str());
+        assert_eq!(round_trip.links()[0].range(), 7..11);
+        assert_eq!(
+            round_trip.links()[0]
+                .command
+                .get(HTML_LINK)
+                .map(String::as_str),
+            Some("https://x.org?a&b")
+        );
+        assert!(matches!(
+            attrs_at(&round_trip, 0)[..],
+            [Attribute::Weight(FontWeight::BOLD)]
+        ));
+    }
+
+    #[test]
+    fn html_round_trip() {
+        let env = Env::empty()
+            .adding(crate::theme::UI_FONT, crate::FontDescriptor::default())
+            .adding(crate::theme::TEXT_COLOR, Color::BLACK)
+            .adding(crate::theme::LINK_COLOR, Color::BLUE);
+        let text = rich_text_from_html(
+            "<b>bold <i>both</i></b> <u>under</u> <font color=\"#00ff00\">green</font> \
+             <a href=\"https://druid.rs\">link <i>it</i></a>",
+        )
+        .unwrap();
+        let round_trip = rich_text_from_html(&text.to_html(&env)).unwrap();
+
+        assert_eq!(round_trip.as_str(), text.as_str());
+        assert_eq!(
+            round_trip.iter_resolved_runs(&env).collect::<Vec<_>>(),
+            text.iter_resolved_runs(&env).collect::<Vec<_>>()
+        );
+        let link_start = text.as_str().find("link").unwrap();
+        assert_eq!(round_trip.links()[0].range(), text.links()[0].range());
+        // the link keeps its themed styling
+        assert_eq!(
+            attrs_at(&round_trip, link_start),
+            attrs_at(&text, link_start)
+        );
+
+        let slice = text.slice(link_start..);
+        assert_eq!(
+            slice.to_html(&env),
+            "<a href=\"https://druid.rs\">link <span style=\"font-style: italic\">it</span></a>"
+        );
+    }
+
+    #[test]
+    fn to_html_only_writes_urls() {
+        const OPEN_LINK: Selector<String> = Selector::new("druid-test.html.open-link");
+
+        let env = Env::empty()
+            .adding(crate::theme::UI_FONT, crate::FontDescriptor::default())
+            .adding(crate::theme::TEXT_COLOR, Color::BLACK)
+            .adding(crate::theme::LINK_COLOR, Color::BLUE);
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("url")
+            .link(OPEN_LINK.with("https://druid.rs".into()));
+        builder.push(" ");
+        builder
+            .push("name")
+            .link(OPEN_LINK.with("not-a.url".into()));
+        let html = builder.build().to_html(&env);
+        assert!(html.starts_with("<a href=\"https://druid.rs\">url</a> "));
+        assert!(!html.contains("not-a.url"));
+
+        assert!(has_url_scheme("mailto:someone@example.com"));
+        assert!(!has_url_scheme("druid-builtin.html-link"));
+        assert!(!has_url_scheme(":x"));
+    }
+}
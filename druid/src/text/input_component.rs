@@ -14,18 +14,22 @@
 
 //! A widget component that integrates with the platform text system.
 
+use std::borrow::Cow;
 use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::fmt;
 use std::ops::Range;
+use std::rc::Rc;
 use std::sync::{Arc, Weak};
 
 use tracing::instrument;
+use unicode_segmentation::UnicodeSegmentation;
 
 use super::{
     EditableText, ImeHandlerRef, ImeInvalidation, InputHandler, Movement, Selection, TextAction,
-    TextLayout, TextStorage,
+    TextLayout, TextStorage, UndoStack,
 };
 use crate::kurbo::{Line, Point, Rect, Vec2};
-use crate::piet::TextLayout as _;
+use crate::piet::{StrokeStyle, TextLayout as _};
 use crate::widget::prelude::*;
 use crate::{text, theme, Cursor, Env, Modifiers, Selector, TextAlignment, UpdateCtx};
 
@@ -96,17 +100,68 @@ pub struct EditSession<T> {
     pub send_notification_on_cancel: bool,
     selection: Selection,
     accepts_newlines: bool,
-    accepts_tabs: bool,
+    tab_behavior: TabBehavior,
     alignment: TextAlignment,
     /// The y-position of the text when it does not fill our width.
     alignment_offset: f64,
     /// The portion of the text that is currently marked by the IME.
     composition_range: Option<Range<usize>>,
     drag_granularity: DragGranularity,
+    undo: UndoStack<T>,
+    input_filter: Option<InputFilter>,
+    /// The text from before the current composition, if it has not yet been
+    /// checked by the input filter.
+    filter_base: Option<T>,
+    max_length: Option<usize>,
     /// The origin of the textbox, relative to the origin of the window.
     pub origin: Point,
 }
 
+/// An underline under text that is being composed by an input method.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CompositionUnderline {
+    line: Line,
+    /// `true` if this is under the clause that the input method is editing,
+    /// which is drawn with a heavier, solid line.
+    selected: bool,
+}
+
+/// What a text component does when the user presses the tab key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabBehavior {
+    /// Send the [`TextComponent::TAB`] notification, or, with shift, the
+    /// [`TextComponent::BACKTAB`] notification, which a [`TextBox`] uses to
+    /// move the focus to the next or previous widget.
+    ///
+    /// This is the default.
+    ///
+    /// [`TextBox`]: crate::widget::TextBox
+    FocusNext,
+    /// Insert a tab into the text.
+    Insert {
+        /// If set, this many spaces are inserted instead of a tab character.
+        spaces: Option<usize>,
+        /// If `true`, shift-tab sends the [`TextComponent::BACKTAB`]
+        /// notification when the cursor is at the very start of the text, so
+        /// that the user can still move the focus back; otherwise, shift-tab
+        /// does nothing.
+        focus_prev_at_start: bool,
+    },
+}
+
+/// Why text entered by the user was not inserted.
+#[derive(Debug, Clone, Copy, Default)]
+struct Rejected {
+    /// Some text was rejected by the input filter.
+    filtered: bool,
+    /// Some text was cut short by the maximum length.
+    truncated: bool,
+}
+
+/// A function that decides whether a grapheme entered by the user is accepted.
+#[derive(Clone)]
+struct InputFilter(Rc<dyn Fn(&str) -> bool>);
+
 /// An object that can be used to acquire an `ImeHandler`.
 ///
 /// This does not own the session; when the widget that owns the session
@@ -196,7 +251,7 @@ impl TextComponent<()> {
 
     /// A notification sent by the component when the user presses the tab key.
     ///
-    /// This is not sent if `accepts_tabs` is true.
+    /// This is only sent with [`TabBehavior::FocusNext`].
     ///
     /// An ancestor can handle this event in order to do things like request
     /// a focus change.
@@ -204,11 +259,25 @@ impl TextComponent<()> {
 
     /// A notification sent by the component when the user inserts a backtab.
     ///
-    /// This is not sent if `accepts_tabs` is true.
+    /// This is only sent with [`TabBehavior::FocusNext`], or at the start of
+    /// the text with [`TabBehavior::Insert`] if `focus_prev_at_start` is set.
     ///
     /// An ancestor can handle this event in order to do things like request
     /// a focus change.
     pub const BACKTAB: Selector = Selector::new("druid-builtin.textbox-backtab");
+
+    /// A notification sent by the component when text entered by the user
+    /// was rejected by its input filter.
+    ///
+    /// See [`EditSession::set_input_filter`].
+    pub const INPUT_REJECTED: Selector = Selector::new("druid-builtin.textbox-input-rejected");
+
+    /// A notification sent by the component when text entered by the user
+    /// was cut short because the text reached its maximum length.
+    ///
+    /// See [`EditSession::set_max_length`].
+    pub const TEXT_LENGTH_LIMIT: Selector =
+        Selector::new("druid-builtin.textbox-text-length-limit");
 }
 
 impl<T> TextComponent<T> {
@@ -342,14 +411,35 @@ impl<T: TextStorage + EditableText> Widget<T> for TextComponent<T> {
                 }
                 let text = self.borrow_mut().take_external_text_change();
                 let selection = self.borrow_mut().take_external_selection_change();
+                let (text, selection, rejected) = self
+                    .borrow_mut()
+                    .filter_external_change(data, text, selection);
+                if rejected.filtered || rejected.truncated {
+                    ctx.invalidate_text_input(ImeInvalidation::Reset);
+                }
+                if rejected.filtered {
+                    ctx.submit_notification(TextComponent::INPUT_REJECTED);
+                }
+                if rejected.truncated {
+                    ctx.submit_notification(TextComponent::TEXT_LENGTH_LIMIT);
+                }
                 if let Some(text) = text {
+                    let mut session = self.borrow_mut();
+                    let before_sel = session.selection;
+                    let after_sel = selection.unwrap_or(before_sel);
+                    let composing = session.composition_range.is_some();
+                    session
+                        .undo
+                        .record_input(data, before_sel, &text, after_sel, composing);
+                    drop(session);
                     self.borrow_mut().layout.set_text(text.clone());
                     *data = text;
                 }
                 if let Some(selection) = selection {
                     self.borrow_mut().selection = selection;
-                    ctx.request_paint();
                 }
+                // the composition may have started, changed, or ended.
+                ctx.request_paint();
                 ctx.request_update();
             }
             _ => (),
@@ -454,27 +544,28 @@ impl<T: TextStorage + EditableText> Widget<T> for TextComponent<T> {
             env.get(theme::SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR)
         };
 
-        let cursor_color = env.get(theme::CURSOR_COLOR);
         let text_offset = Vec2::new(self.borrow().alignment_offset, 0.0);
 
         let selection = self.borrow().selection();
         let composition = self.borrow().composition_range();
-        let sel_rects = self.borrow().layout.rects_for_range(selection.range());
         if let Some(composition) = composition {
-            // I believe selection should always be contained in composition range while composing?
-            assert!(composition.start <= selection.anchor && composition.end >= selection.active);
-            let comp_rects = self.borrow().layout.rects_for_range(composition);
-            for region in comp_rects {
-                let y = region.max_y().floor();
-                let line = Line::new((region.min_x(), y), (region.max_x(), y)) + text_offset;
-                ctx.stroke(line, &cursor_color, 1.0);
+            // the input method uses the selection to mark the clause it is
+            // editing, which is shown by its underline instead.
+            let background = env.get(theme::COMPOSITION_BACKGROUND_COLOR);
+            for region in self.borrow().layout.rects_for_range(composition) {
+                ctx.fill(region + text_offset, &background);
             }
-            for region in sel_rects {
-                let y = region.max_y().floor();
-                let line = Line::new((region.min_x(), y), (region.max_x(), y)) + text_offset;
-                ctx.stroke(line, &cursor_color, 2.0);
+            let color = env.get(theme::COMPOSITION_UNDERLINE_COLOR);
+            let dashed = StrokeStyle::new().dash_pattern(&[2.0, 2.0]);
+            for underline in self.borrow().composition_underlines() {
+                if underline.selected {
+                    ctx.stroke(underline.line, &color, 2.0);
+                } else {
+                    ctx.stroke_styled(underline.line, &color, 1.0, &dashed);
+                }
             }
         } else {
+            let sel_rects = self.borrow().layout.rects_for_range(selection.range());
             for region in sel_rects {
                 let rounded = (region + text_offset).to_rounded_rect(1.0);
                 ctx.fill(rounded, &selection_color);
@@ -517,6 +608,16 @@ impl<T> EditSession<T> {
         self.accepts_newlines = accepts_newlines;
     }
 
+    /// Set what happens when the user presses the tab key.
+    pub fn set_tab_behavior(&mut self, behavior: TabBehavior) {
+        self.tab_behavior = behavior;
+    }
+
+    /// What happens when the user presses the tab key.
+    pub fn tab_behavior(&self) -> TabBehavior {
+        self.tab_behavior
+    }
+
     /// Set the text alignment.
     ///
     /// This is only meaningful for single-line text that does not fill
@@ -530,6 +631,78 @@ impl<T> EditSession<T> {
         self.alignment
     }
 
+    /// Returns `true` if there is an edit that can be undone.
+    ///
+    /// This can be used to enable or disable an 'Undo' menu item.
+    pub fn can_undo(&self) -> bool {
+        self.undo.can_undo()
+    }
+
+    /// Returns `true` if there is an undone edit that can be redone.
+    pub fn can_redo(&self) -> bool {
+        self.undo.can_redo()
+    }
+
+    /// Set the maximum number of edits that can be undone.
+    ///
+    /// Consecutive typing is grouped into one edit per word. The default
+    /// limit is 100.
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.undo.set_limit(limit);
+    }
+
+    /// Set a function that decides whether each grapheme of the text entered
+    /// by the user is accepted.
+    ///
+    /// The filter is applied to text typed by the user or committed by an
+    /// input method; rejected graphemes are removed, and the component sends
+    /// the [`TextComponent::INPUT_REJECTED`] notification. Text inserted with
+    /// [`insert_text`] is not filtered; use [`filter_input`] first.
+    ///
+    /// [`insert_text`]: EditSession::insert_text
+    /// [`filter_input`]: EditSession::filter_input
+    pub fn set_input_filter(&mut self, filter: impl Fn(&str) -> bool + 'static) {
+        self.input_filter = Some(InputFilter(Rc::new(filter)));
+    }
+
+    /// Remove the input filter set with [`set_input_filter`].
+    ///
+    /// [`set_input_filter`]: EditSession::set_input_filter
+    pub fn clear_input_filter(&mut self) {
+        self.input_filter = None;
+        self.filter_base = None;
+    }
+
+    /// Set the maximum length of the text, in graphemes, or `None` for no
+    /// limit.
+    ///
+    /// Text typed by the user or committed by an input method is cut short
+    /// when it would make the text longer than this, and the component sends
+    /// the [`TextComponent::TEXT_LENGTH_LIMIT`] notification. Text that is
+    /// already longer is left as it is. Text inserted with [`insert_text`]
+    /// is not limited; use [`truncate_input`] first.
+    ///
+    /// [`insert_text`]: EditSession::insert_text
+    /// [`truncate_input`]: EditSession::truncate_input
+    pub fn set_max_length(&mut self, max_length: Option<usize>) {
+        self.max_length = max_length;
+    }
+
+    /// The maximum length of the text, in graphemes.
+    pub fn max_length(&self) -> Option<usize> {
+        self.max_length
+    }
+
+    /// Returns `text` without the graphemes rejected by the input filter.
+    pub fn filter_input<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.input_filter {
+            Some(InputFilter(filter)) if !text.graphemes(true).all(|g| filter(g)) => {
+                text.graphemes(true).filter(|g| filter(g)).collect()
+            }
+            _ => text.into(),
+        }
+    }
+
     /// Returns any invalidation action that should be passed to the platform.
     ///
     /// The user of this component *must* check this after calling `update`.
@@ -586,18 +759,166 @@ impl<T: TextStorage + EditableText> EditSession<T> {
     /// text state, by calling [`EventCtx::invalidate_text_input`].
     #[must_use]
     pub fn insert_text(&mut self, data: &mut T, new_text: &str) -> ImeInvalidation {
+        let before = data.clone();
+        let before_sel = self.selection;
         let new_cursor_pos = self.selection.min() + new_text.len();
         data.edit(self.selection.range(), new_text);
         self.selection = Selection::caret(new_cursor_pos);
+        self.undo
+            .record_unit(&before, before_sel, data, self.selection);
         self.scroll_to_selection_end(true);
         ImeInvalidation::Reset
     }
 
+    /// Set the selection to `selection`, moved to the nearest valid
+    /// selection in `data`.
+    ///
+    /// Offsets past the end of `data` are moved to its end, and offsets
+    /// inside a grapheme are moved to the start of that grapheme. Like
+    /// [`set_selection`], this returns an invalidation for the platform
+    /// if the selection changed.
+    ///
+    /// [`set_selection`]: EditSession::set_selection
+    #[must_use]
+    pub fn set_selection_in(&mut self, data: &T, selection: Selection) -> Option<ImeInvalidation> {
+        let text = data.as_str();
+        let to_grapheme = |offset: usize| {
+            let mut offset = offset.min(text.len());
+            while !text.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            data.next_grapheme_offset(offset)
+                .and_then(|next| data.prev_grapheme_offset(next))
+                .unwrap_or(offset)
+        };
+        let selection =
+            Selection::new(to_grapheme(selection.anchor), to_grapheme(selection.active))
+                .with_h_pos(selection.h_pos);
+        self.set_selection(selection)
+    }
+
+    /// Undo the most recent edit, restoring the text and selection from
+    /// before it.
+    ///
+    /// Returns `None` if there is nothing to undo. Otherwise, the caller is
+    /// responsible for notifying the platform of the change in text state,
+    /// by calling [`EventCtx::invalidate_text_input`].
+    #[must_use]
+    pub fn undo(&mut self, data: &mut T) -> Option<ImeInvalidation> {
+        let (text, selection) = self.undo.undo(data)?;
+        *data = text;
+        self.selection = selection;
+        self.scroll_to_selection_end(true);
+        Some(ImeInvalidation::Reset)
+    }
+
+    /// Redo the most recently undone edit.
+    ///
+    /// This is the counterpart to [`undo`](EditSession::undo).
+    #[must_use]
+    pub fn redo(&mut self, data: &mut T) -> Option<ImeInvalidation> {
+        let (text, selection) = self.undo.redo(data)?;
+        *data = text;
+        self.selection = selection;
+        self.scroll_to_selection_end(true);
+        Some(ImeInvalidation::Reset)
+    }
+
+    /// Returns the start of `text` that can replace the current selection
+    /// without the text becoming longer than its maximum length.
+    ///
+    /// The result always ends on a grapheme boundary.
+    pub fn truncate_input<'a>(&self, data: &T, text: &'a str) -> &'a str {
+        let max_length = match self.max_length {
+            Some(max_length) => max_length,
+            None => return text,
+        };
+        let range = self.selection.range();
+        let data = data.as_str();
+        let kept =
+            data[..range.start].graphemes(true).count() + data[range.end..].graphemes(true).count();
+        grapheme_prefix(text, max_length.saturating_sub(kept))
+    }
+
+    /// Apply the input filter and maximum length to a change made by the
+    /// platform.
+    ///
+    /// Text being composed by an input method is kept as it is, and is
+    /// checked when the composition ends. Returns the new text and
+    /// selection, and why any text was rejected.
+    fn filter_external_change(
+        &mut self,
+        data: &T,
+        text: Option<T>,
+        selection: Option<Selection>,
+    ) -> (Option<T>, Option<Selection>, Rejected) {
+        let rejected = Rejected::default();
+        if self.input_filter.is_none() && self.max_length.is_none() {
+            return (text, selection, rejected);
+        }
+        if self.composition_range.is_some() {
+            if text.is_some() && self.filter_base.is_none() {
+                self.filter_base = Some(data.clone());
+            }
+            return (text, selection, rejected);
+        }
+        let base = self.filter_base.take();
+        if text.is_none() && base.is_none() {
+            return (text, selection, rejected);
+        }
+
+        let changed = text.is_some();
+        let before = base.unwrap_or_else(|| data.clone());
+        let mut after = text.unwrap_or_else(|| data.clone());
+        let (prefix, _, inserted) = super::undo::diff(before.as_str(), after.as_str());
+        let end = prefix + inserted.len();
+        let filtered = self.filter_input(inserted);
+        let mut accepted: &str = &filtered;
+        if let Some(max_length) = self.max_length {
+            let kept = after.as_str()[..prefix].graphemes(true).count()
+                + after.as_str()[end..].graphemes(true).count();
+            accepted = grapheme_prefix(accepted, max_length.saturating_sub(kept));
+        }
+        let rejected = Rejected {
+            filtered: filtered.len() != inserted.len(),
+            truncated: accepted.len() != filtered.len(),
+        };
+        if accepted.len() == inserted.len() {
+            return (Some(after).filter(|_| changed), selection, rejected);
+        }
+
+        // remove the rejected text, and move the selection to match.
+        let accepted = accepted.to_owned();
+        let removed = inserted.len() - accepted.len();
+        after.edit(prefix..end, &accepted);
+        let adjust = |pos: usize| {
+            if pos >= end {
+                pos - removed
+            } else {
+                pos.min(prefix + accepted.len())
+            }
+        };
+        let sel = selection.unwrap_or(self.selection);
+        let sel = Selection::new(adjust(sel.anchor), adjust(sel.active));
+        (Some(after), Some(sel), rejected)
+    }
+
+    /// Returns `true` if the selection is not empty, and the text is not
+    /// masked, so that [`set_clipboard`] would copy it.
+    ///
+    /// [`set_clipboard`]: EditSession::set_clipboard
+    pub fn can_copy(&self) -> bool {
+        self.layout.mask().is_none() && !self.selection.is_caret()
+    }
+
     /// Sets the clipboard to the contents of the current selection.
     ///
     /// Returns `true` if the clipboard was set, and `false` if not (indicating)
-    /// that the selection was empty.)
+    /// that the selection was empty, or that the text is masked.)
     pub fn set_clipboard(&self) -> bool {
+        if self.layout.mask().is_some() {
+            return false;
+        }
         if let Some(text) = self
             .layout
             .text()
@@ -631,6 +952,9 @@ impl<T: TextStorage + EditableText> EditSession<T> {
                 let len = buffer.len();
                 self.external_selection_change = Some(Selection::new(0, len));
             }
+            TextAction::SelectWord if self.layout.mask().is_some() => {
+                self.external_selection_change = Some(Selection::new(0, buffer.len()));
+            }
             TextAction::SelectWord => {
                 if self.selection.is_caret() {
                     let range =
@@ -681,18 +1005,22 @@ impl<T: TextStorage + EditableText> EditSession<T> {
                     self.ime_insert_text(buffer, &newline_type.to_string());
                 }
             }
-            TextAction::InsertTab { ignore_hotkey } => {
-                if ignore_hotkey || self.accepts_tabs {
-                    self.ime_insert_text(buffer, "\t");
-                } else if !ignore_hotkey {
-                    self.external_action = Some(action);
-                }
-            }
-            TextAction::InsertBacktab => {
-                if !self.accepts_tabs {
-                    self.external_action = Some(action);
-                }
-            }
+            TextAction::InsertTab { ignore_hotkey } => match self.tab_behavior {
+                TabBehavior::Insert {
+                    spaces: Some(spaces),
+                    ..
+                } if !ignore_hotkey => self.ime_insert_text(buffer, &" ".repeat(spaces)),
+                TabBehavior::FocusNext if !ignore_hotkey => self.external_action = Some(action),
+                _ => self.ime_insert_text(buffer, "\t"),
+            },
+            TextAction::InsertBacktab => match self.tab_behavior {
+                TabBehavior::FocusNext => self.external_action = Some(action),
+                TabBehavior::Insert {
+                    focus_prev_at_start: true,
+                    ..
+                } if self.selection == Selection::caret(0) => self.external_action = Some(action),
+                TabBehavior::Insert { .. } => (),
+            },
             TextAction::InsertSingleQuoteIgnoringSmartQuotes => self.ime_insert_text(buffer, "'"),
             TextAction::InsertDoubleQuoteIgnoringSmartQuotes => self.ime_insert_text(buffer, "\""),
             TextAction::Cancel if self.send_notification_on_cancel => {
@@ -724,7 +1052,44 @@ impl<T: TextStorage + EditableText> EditSession<T> {
         self.scroll_to_selection_end(true);
     }
 
+    /// The underlines drawn under the text that is being composed, if any.
+    ///
+    /// The whole composition is underlined, and the part of it that is
+    /// selected, which the input method uses for the clause that is being
+    /// edited, gets an additional, heavier underline.
+    fn composition_underlines(&self) -> Vec<CompositionUnderline> {
+        let composition = match &self.composition_range {
+            Some(range) => range.clone(),
+            None => return Vec::new(),
+        };
+        let selection = self.selection.range();
+        let clause = selection.start.max(composition.start)..selection.end.min(composition.end);
+        let text_offset = Vec2::new(self.alignment_offset, 0.0);
+        let underline = |region: Rect, selected| {
+            let y = region.max_y().floor();
+            let line = Line::new((region.min_x(), y), (region.max_x(), y)) + text_offset;
+            CompositionUnderline { line, selected }
+        };
+
+        let mut underlines: Vec<_> = self
+            .layout
+            .rects_for_range(composition)
+            .into_iter()
+            .map(|region| underline(region, false))
+            .collect();
+        if !clause.is_empty() {
+            underlines.extend(
+                self.layout
+                    .rects_for_range(clause)
+                    .into_iter()
+                    .map(|region| underline(region, true)),
+            );
+        }
+        underlines
+    }
+
     fn do_mouse_down(&mut self, point: Point, mods: Modifiers, count: u8) {
+        self.undo.close_group();
         let point = point - Vec2::new(self.alignment_offset, 0.0);
         let pos = self.layout.text_position_for_point(point);
         if mods.shift() {
@@ -742,7 +1107,6 @@ impl<T: TextStorage + EditableText> EditSession<T> {
 
     fn do_drag(&mut self, point: Point) {
         let point = point - Vec2::new(self.alignment_offset, 0.0);
-        //FIXME: this should behave differently if we were double or triple clicked
         let pos = self.layout.text_position_for_point(point);
         let text = match self.layout.text() {
             Some(text) => text,
@@ -797,6 +1161,10 @@ impl<T: TextStorage + EditableText> EditSession<T> {
     }
 
     fn word_for_pos(&self, pos: usize) -> Range<usize> {
+        // the words in masked text are not revealed.
+        if self.layout.mask().is_some() {
+            return 0..self.layout.text().map_or(pos, |text| text.as_str().len());
+        }
         let layout = match self.layout.layout() {
             Some(layout) => layout,
             None => return pos..pos,
@@ -820,6 +1188,10 @@ impl<T: TextStorage + EditableText> EditSession<T> {
             .map(|t| !t.same(new_data))
             .unwrap_or(true)
         {
+            // the history no longer applies if the text was changed elsewhere
+            if !self.undo.is_current(new_data) {
+                self.undo.clear();
+            }
             self.update_pending_invalidation(ImeInvalidation::Reset);
             self.layout.set_text(new_data.clone());
         }
@@ -869,6 +1241,10 @@ impl<T: TextStorage + EditableText> InputHandler for EditSessionHandle<T> {
     }
 
     fn slice(&self, range: Range<usize>) -> std::borrow::Cow<str> {
+        // the platform is never given the contents of masked text.
+        if self.inner.borrow().layout.mask().is_some() {
+            return "*".repeat(range.len()).into();
+        }
         self.text.slice(range).unwrap()
     }
 
@@ -878,20 +1254,14 @@ impl<T: TextStorage + EditableText> InputHandler for EditSessionHandle<T> {
     }
 
     fn hit_test_point(&self, point: Point) -> crate::piet::HitTestPoint {
-        self.inner
-            .borrow()
-            .layout
-            .layout()
-            .map(|layout| layout.hit_test_point(point))
-            .unwrap_or_default()
+        self.inner.borrow().layout.hit_test_point(point)
     }
 
     fn line_range(&self, index: usize, _affinity: druid_shell::text::Affinity) -> Range<usize> {
-        let inner = self.inner.borrow();
-        let layout = inner.layout.layout().unwrap();
-        let hit = layout.hit_test_text_position(index);
-        let metric = layout.line_metric(hit.line).unwrap();
-        metric.range()
+        self.inner
+            .borrow()
+            .layout
+            .line_range_for_text_position(index)
     }
 
     fn bounding_box(&self) -> Option<Rect> {
@@ -903,9 +1273,8 @@ impl<T: TextStorage + EditableText> InputHandler for EditSessionHandle<T> {
         let origin = self.inner.borrow().origin;
         let layout = &self.inner.borrow().layout;
         if range.is_empty() {
-            let hit = layout
-                .layout()
-                .map(|l| l.hit_test_text_position(range.start))?;
+            let offset = layout.layout_offset(range.start);
+            let hit = layout.layout().map(|l| l.hit_test_text_position(offset))?;
             let line = layout.layout().and_then(|l| l.line_metric(hit.line))?;
             let x = hit.point.x;
             Some(Rect::new(x, line.y_offset, x, line.y_offset + line.height))
@@ -930,10 +1299,27 @@ impl<T: TextStorage + EditableText> InputHandler for EditSessionHandle<T> {
     }
 }
 
+impl fmt::Debug for InputFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("InputFilter")
+    }
+}
+
+/// Returns the first `count` graphemes of `text`.
+fn grapheme_prefix(text: &str, count: usize) -> &str {
+    match text.grapheme_indices(true).nth(count) {
+        Some((idx, _)) => &text[..idx],
+        None => text,
+    }
+}
+
 impl<T> Default for TextComponent<T> {
     fn default() -> Self {
+        // editing needs a single layout for the whole text
+        let mut layout = TextLayout::new();
+        layout.set_split_paragraphs(false);
         let inner = EditSession {
-            layout: TextLayout::new(),
+            layout,
             external_scroll_to: None,
             external_text_change: None,
             external_selection_change: None,
@@ -944,10 +1330,14 @@ impl<T> Default for TextComponent<T> {
             send_notification_on_return: false,
             send_notification_on_cancel: false,
             accepts_newlines: false,
-            accepts_tabs: false,
+            tab_behavior: TabBehavior::FocusNext,
             alignment: TextAlignment::Start,
             alignment_offset: 0.0,
             drag_granularity: DragGranularity::Grapheme,
+            undo: UndoStack::default(),
+            input_filter: None,
+            filter_base: None,
+            max_length: None,
             origin: Point::ZERO,
         };
 
@@ -958,3 +1348,172 @@ impl<T> Default for TextComponent<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piet::Device;
+
+    #[test]
+    fn input_filter() {
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        session.set_input_filter(|g| g.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(session.filter_input("1a2\u{301}3"), "13");
+        assert!(matches!(session.filter_input("123"), Cow::Borrowed(_)));
+
+        // typing 'x4' after '12', with the caret after the insertion
+        let data = String::from("12|");
+        session.selection = Selection::caret(2);
+        let text = Some(String::from("12x4|"));
+        let (text, sel, rejected) =
+            session.filter_external_change(&data, text, Some(Selection::caret(4)));
+        assert!(rejected.filtered);
+        assert_eq!(text.as_deref(), Some("124|"));
+        assert_eq!(sel, Some(Selection::caret(3)));
+
+        // composed text is only filtered when the composition ends
+        session.composition_range = Some(2..3);
+        let text = Some(String::from("12a|"));
+        let (text, _, rejected) = session.filter_external_change(&data, text, None);
+        assert!(!rejected.filtered);
+        assert_eq!(text.as_deref(), Some("12a|"));
+        session.composition_range = None;
+        let (text, _, rejected) = session.filter_external_change(&"12a|".into(), None, None);
+        assert!(rejected.filtered);
+        assert_eq!(text.as_deref(), Some("12|"));
+    }
+
+    #[test]
+    fn set_selection_in() {
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        let data = String::from("ae\u{301}b");
+
+        // inside 'e\u{301}', and past the end of the text
+        let inval = session.set_selection_in(&data, Selection::new(3, 10));
+        assert!(inval.is_some());
+        assert_eq!(session.selection(), Selection::new(1, data.len()));
+        let inval = session.set_selection_in(&data, Selection::new(2, 5));
+        assert!(inval.is_none());
+    }
+
+    #[test]
+    fn max_length() {
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        session.set_max_length(Some(4));
+
+        // a paste is cut short without splitting 'e\u{301}'
+        let data = String::from("ab");
+        session.selection = Selection::caret(2);
+        assert_eq!(session.truncate_input(&data, "cd"), "cd");
+        assert_eq!(session.truncate_input(&data, "ce\u{301}f"), "ce\u{301}");
+        session.selection = Selection::new(0, 2);
+        assert_eq!(session.truncate_input(&data, "ghijk"), "ghij");
+
+        // typing in the middle keeps the caret after the accepted text
+        let data = String::from("abc");
+        session.selection = Selection::caret(1);
+        let text = Some(String::from("axyzbc"));
+        let (text, sel, rejected) =
+            session.filter_external_change(&data, text, Some(Selection::caret(4)));
+        assert!(rejected.truncated && !rejected.filtered);
+        assert_eq!(text.as_deref(), Some("axbc"));
+        assert_eq!(sel, Some(Selection::caret(2)));
+
+        // deleting is always allowed
+        let (text, _, rejected) =
+            session.filter_external_change(&data, Some("ac".into()), Some(Selection::caret(1)));
+        assert!(!rejected.truncated);
+        assert_eq!(text.as_deref(), Some("ac"));
+    }
+
+    #[test]
+    fn composition_underlines() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        session.layout.set_text("abcdef".to_string());
+        session
+            .layout
+            .rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+        drop(session);
+        let mut handle = EditSessionHandle::new(component.edit_session.clone());
+
+        // the input method is composing 'bcde', with the caret at its end
+        handle.set_composition_range(Some(1..5));
+        component.borrow_mut().selection = Selection::caret(5);
+        let underlines = component.borrow().composition_underlines();
+        assert_eq!(underlines.len(), 1);
+        let composition = underlines[0];
+        assert!(!composition.selected);
+        assert!(composition.line.p0.x > 0.0);
+        assert!(composition.line.p1.x > composition.line.p0.x);
+
+        // it then selects the clause 'cd'
+        component.borrow_mut().selection = Selection::new(2, 4);
+        let underlines = component.borrow().composition_underlines();
+        assert_eq!(underlines.len(), 2);
+        assert_eq!(underlines[0], composition);
+        let clause = underlines[1];
+        assert!(clause.selected);
+        assert!(clause.line.p0.x > composition.line.p0.x);
+        assert!(clause.line.p1.x < composition.line.p1.x);
+        assert_eq!(clause.line.p0.y, composition.line.p0.y);
+
+        // and commits it
+        handle.set_composition_range(None);
+        assert!(component.borrow().composition_underlines().is_empty());
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn can_copy() {
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        assert!(!session.can_copy());
+        session.selection = Selection::new(3, 1);
+        assert!(session.can_copy());
+        session.layout.set_mask(Some('*'));
+        assert!(!session.can_copy());
+    }
+
+    #[test]
+    fn tab_behavior() {
+        let component = TextComponent::<String>::default();
+        let mut session = component.borrow_mut();
+        let mut buffer = String::from("ab");
+        let tab = TextAction::InsertTab {
+            ignore_hotkey: false,
+        };
+        session.selection = Selection::caret(1);
+        session.do_action(&mut buffer, tab);
+        assert_eq!(buffer, "ab");
+        assert!(matches!(
+            session.take_external_action(),
+            Some(TextAction::InsertTab { .. })
+        ));
+
+        session.set_tab_behavior(TabBehavior::Insert {
+            spaces: Some(2),
+            focus_prev_at_start: true,
+        });
+        session.do_action(&mut buffer, tab);
+        assert_eq!(buffer, "a  b");
+        assert!(session.take_external_action().is_none());
+
+        // shift-tab only moves the focus back from the start of the text
+        session.do_action(&mut buffer, TextAction::InsertBacktab);
+        assert!(session.take_external_action().is_none());
+        session.selection = Selection::caret(0);
+        session.do_action(&mut buffer, TextAction::InsertBacktab);
+        assert!(matches!(
+            session.take_external_action(),
+            Some(TextAction::InsertBacktab)
+        ));
+    }
+}
@@ -17,14 +17,23 @@
 use std::ops::Range;
 use std::rc::Rc;
 
-use super::{EnvUpdateCtx, Link, TextStorage};
-use crate::kurbo::{Line, Point, Rect, Size};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::attachment::{self, Placeholder};
+use super::{EnvUpdateCtx, InlineAttachment, Link, LinkState, TextStorage, WritingDirection};
+use crate::kurbo::{Line, Point, Rect, Size, Vec2};
 use crate::piet::{
-    Color, PietText, PietTextLayout, Text as _, TextAlignment, TextAttribute, TextLayout as _,
-    TextLayoutBuilder as _,
+    Color, HitTestPoint, HitTestPosition, LineMetric, PietText, PietTextLayout, Text as _,
+    TextAlignment, TextAttribute, TextLayout as _, TextLayoutBuilder as _,
 };
 use crate::{Env, FontDescriptor, KeyOrValue, PaintCtx, RenderContext, UpdateCtx};
 
+/// The number of layouts tried when searching for the scale at which text
+/// with [`Attribute::ScaleToFit`] spans fits its width.
+///
+/// [`Attribute::ScaleToFit`]: super::Attribute::ScaleToFit
+const SCALE_TO_FIT_STEPS: usize = 6;
+
 /// A component for displaying text on screen.
 ///
 /// This is a type intended to be used by other widgets that display text.
@@ -39,6 +48,12 @@ use crate::{Env, FontDescriptor, KeyOrValue, PaintCtx, RenderContext, UpdateCtx}
 /// to call [`rebuild_if_needed`] again, generally by scheduling another [`layout`]
 /// pass.
 ///
+/// The distance between lines is determined by the fonts of the text;
+/// [`piet`] does not currently support changing it, so an
+/// [`Attribute::LineHeight`] has no effect on the layout.
+///
+/// [`piet`]: https://docs.rs/piet
+/// [`Attribute::LineHeight`]: super::Attribute::LineHeight
 /// [`layout`]: trait.Widget.html#tymethod.layout
 /// [`update`]: trait.Widget.html#tymethod.update
 /// [`needs_rebuild_after_update`]: #method.needs_rebuild_after_update
@@ -53,11 +68,39 @@ pub struct TextLayout<T> {
     // using a `FontDescriptor` in the `Env`.
     text_size_override: Option<KeyOrValue<f64>>,
     text_color: KeyOrValue<Color>,
-    layout: Option<PietTextLayout>,
+    paragraphs: Option<Rc<[Paragraph]>>,
+    /// Runs of text with a shifted baseline, each laid out on its own and
+    /// drawn over the space it takes in the `paragraphs`.
+    shifted: Rc<[Paragraph]>,
+    size: Size,
     wrap_width: f64,
     alignment: TextAlignment,
+    split_paragraphs: bool,
     links: Rc<[(Rect, usize)]>,
+    /// The link being hovered or pressed, and its state.
+    link_state: Option<(usize, LinkState)>,
+    backgrounds: Rc<[(Rect, Color)]>,
+    attachments: Rc<[(Rect, InlineAttachment)]>,
     text_is_rtl: bool,
+    mask: Option<char>,
+    /// When the text is masked, the offsets of its grapheme boundaries.
+    mask_offsets: Rc<[usize]>,
+    /// Otherwise, the positions of the placeholders of its attachments.
+    placeholders: Rc<[Placeholder]>,
+}
+
+/// A separately laid-out part of the text of a [`TextLayout`].
+///
+/// Text with paragraph attributes is laid out one paragraph at a time, and
+/// the first line of an indented paragraph, or the marker of a list item, is
+/// laid out on its own; other text is laid out as a single `Paragraph`.
+#[derive(Clone)]
+struct Paragraph {
+    /// The range of the text covered by this layout.
+    range: Range<usize>,
+    /// The position of this layout, relative to the origin of the `TextLayout`.
+    origin: Vec2,
+    layout: PietTextLayout,
 }
 
 /// Metrics describing the layout text.
@@ -84,11 +127,20 @@ impl<T> TextLayout<T> {
             font: crate::theme::UI_FONT.into(),
             text_color: crate::theme::TEXT_COLOR.into(),
             text_size_override: None,
-            layout: None,
+            paragraphs: None,
+            shifted: Rc::new([]),
+            size: Size::ZERO,
             wrap_width: f64::INFINITY,
             alignment: Default::default(),
+            split_paragraphs: true,
             links: Rc::new([]),
+            link_state: None,
+            backgrounds: Rc::new([]),
+            attachments: Rc::new([]),
             text_is_rtl: false,
+            mask: None,
+            mask_offsets: Rc::new([]),
+            placeholders: Rc::new([]),
         }
     }
 
@@ -97,7 +149,7 @@ impl<T> TextLayout<T> {
         let color = color.into();
         if color != self.text_color {
             self.text_color = color;
-            self.layout = None;
+            self.paragraphs = None;
         }
     }
 
@@ -113,7 +165,7 @@ impl<T> TextLayout<T> {
         let font = font.into();
         if font != self.font {
             self.font = font;
-            self.layout = None;
+            self.paragraphs = None;
             self.text_size_override = None;
         }
     }
@@ -128,7 +180,7 @@ impl<T> TextLayout<T> {
         let size = size.into();
         if Some(&size) != self.text_size_override.as_ref() {
             self.text_size_override = Some(size);
-            self.layout = None;
+            self.paragraphs = None;
         }
     }
 
@@ -141,7 +193,7 @@ impl<T> TextLayout<T> {
         // 1e-4 is an arbitrary small-enough value that we don't care to rewrap
         if (width - self.wrap_width).abs() > 1e-4 {
             self.wrap_width = width;
-            self.layout = None;
+            self.paragraphs = None;
         }
     }
 
@@ -151,17 +203,101 @@ impl<T> TextLayout<T> {
     pub fn set_text_alignment(&mut self, alignment: TextAlignment) {
         if self.alignment != alignment {
             self.alignment = alignment;
-            self.layout = None;
+            self.paragraphs = None;
         }
     }
 
-    /// Returns `true` if this layout's text appears to be right-to-left.
+    /// Set a character to display in place of each grapheme of the text, as
+    /// is done for passwords, or `None` to display the text itself.
+    ///
+    /// Masked text is laid out without any of its attributes. Positions
+    /// passed to and returned by the methods of this type are still offsets
+    /// into the unmasked text; to convert between those and offsets into the
+    /// [`layout`], use [`layout_offset`] and [`text_offset`].
     ///
-    /// See [`piet::util::first_strong_rtl`] for more information.
+    /// [`layout`]: TextLayout::layout
+    /// [`layout_offset`]: TextLayout::layout_offset
+    /// [`text_offset`]: TextLayout::text_offset
+    pub fn set_mask(&mut self, mask: Option<char>) {
+        if self.mask != mask {
+            self.mask = mask;
+            self.paragraphs = None;
+        }
+    }
+
+    /// The character displayed in place of each grapheme, if the text is masked.
+    pub fn mask(&self) -> Option<char> {
+        self.mask
+    }
+
+    /// Convert a position in the text to the equivalent position in the
+    /// inner Piet [`layout`].
+    ///
+    /// These are the same unless the text is masked (see [`set_mask`]), or
+    /// has [`InlineAttachment`]s, which are laid out as placeholders that
+    /// may be longer than the character they replace.
+    ///
+    /// [`layout`]: TextLayout::layout
+    /// [`set_mask`]: TextLayout::set_mask
+    pub fn layout_offset(&self, text_pos: usize) -> usize {
+        match self.mask {
+            Some(mask) => {
+                let idx = match self.mask_offsets.binary_search(&text_pos) {
+                    Ok(idx) | Err(idx) => idx,
+                };
+                idx.min(self.mask_offsets.len().saturating_sub(1)) * mask.len_utf8()
+            }
+            None => attachment::to_layout_offset(&self.placeholders, text_pos),
+        }
+    }
+
+    /// Convert a position in the inner Piet [`layout`] to the equivalent
+    /// position in the text.
+    ///
+    /// This is the inverse of [`layout_offset`].
+    ///
+    /// [`layout`]: TextLayout::layout
+    /// [`layout_offset`]: TextLayout::layout_offset
+    pub fn text_offset(&self, layout_pos: usize) -> usize {
+        match self.mask {
+            Some(mask) => {
+                let idx = layout_pos / mask.len_utf8();
+                let last = self.mask_offsets.last().copied().unwrap_or_default();
+                self.mask_offsets.get(idx).copied().unwrap_or(last)
+            }
+            None => attachment::to_text_offset(&self.placeholders, layout_pos),
+        }
+    }
+
+    /// Set whether text with paragraph attributes is laid out one paragraph
+    /// at a time.
+    ///
+    /// This is `true` by default, which allows paragraphs to have their own
+    /// alignment, indents and spacing. Code that needs a single [`layout`]
+    /// for the whole text, such as a text component that moves the cursor
+    /// with [`movement`], can turn this off; the paragraph attributes are
+    /// then ignored.
+    ///
+    /// [`layout`]: TextLayout::layout
+    /// [`movement`]: super::movement
+    pub fn set_split_paragraphs(&mut self, split: bool) {
+        if self.split_paragraphs != split {
+            self.split_paragraphs = split;
+            self.paragraphs = None;
+        }
+    }
+
+    /// Returns `true` if this layout's text is right-to-left.
+    ///
+    /// This is the [`writing_direction`] of the text, or if that is
+    /// [`WritingDirection::Natural`], the direction of its first strong
+    /// character; see [`piet::util::first_strong_rtl`] for more information.
+    ///
+    /// [`writing_direction`]: TextStorage::writing_direction
     ///
     /// [`piet::util::first_strong_rtl`]: crate::piet::util::first_strong_rtl
     pub fn text_is_rtl(&self) -> bool {
-        self.text_is_rtl
+        self.text_is_rtl && self.mask.is_none()
     }
 }
 
@@ -182,15 +318,32 @@ impl<T: TextStorage> TextLayout<T> {
     /// This does not account for things like the text changing, handling that
     /// is the responsibility of the user.
     pub fn needs_rebuild(&self) -> bool {
-        self.layout.is_none()
+        self.paragraphs.is_none()
     }
 
     /// Set the text to display.
     pub fn set_text(&mut self, text: T) {
         if self.text.is_none() || !self.text.as_ref().unwrap().same(&text) {
-            self.text_is_rtl = crate::piet::util::first_strong_rtl(text.as_str());
+            self.text_is_rtl = match text.writing_direction() {
+                WritingDirection::LeftToRight => false,
+                WritingDirection::RightToLeft => true,
+                WritingDirection::Natural => crate::piet::util::first_strong_rtl(text.as_str()),
+            };
             self.text = Some(text);
-            self.layout = None;
+            self.link_state = None;
+            self.paragraphs = None;
+        }
+    }
+
+    /// Set the state of the link the mouse is interacting with, if any.
+    ///
+    /// The link is restyled by [`TextStorage::with_link_state`] when the
+    /// layout is rebuilt. The index refers to the slice returned by
+    /// [`TextStorage::links`]; the state is cleared when the text changes.
+    pub fn set_link_state(&mut self, state: Option<(usize, LinkState)>) {
+        if state != self.link_state {
+            self.link_state = state;
+            self.paragraphs = None;
         }
     }
 
@@ -203,9 +356,45 @@ impl<T: TextStorage> TextLayout<T> {
 
     /// Returns the inner Piet [`TextLayout`] type.
     ///
+    /// **Note:** this returns `None` if the text is laid out in several
+    /// parts, as is the case for text with paragraph attributes (see
+    /// [`TextStorage::paragraphs`]) unless [`set_split_paragraphs`] has been
+    /// used to turn this off. Use [`paragraph_layouts`] to get the layout of
+    /// each part, or the methods on this type, which work in either case.
+    ///
     /// [`TextLayout`]: ./piet/trait.TextLayout.html
+    /// [`TextStorage::paragraphs`]: super::TextStorage::paragraphs
+    /// [`set_split_paragraphs`]: TextLayout::set_split_paragraphs
+    /// [`paragraph_layouts`]: TextLayout::paragraph_layouts
     pub fn layout(&self) -> Option<&PietTextLayout> {
-        self.layout.as_ref()
+        match self.paragraphs.as_deref()? {
+            [paragraph] if paragraph.range.start == 0 && paragraph.origin == Vec2::ZERO => {
+                Some(&paragraph.layout)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the separately laid-out parts of the text.
+    ///
+    /// Each item is the range of the text that a part covers, its position
+    /// relative to the origin of this layout, and its Piet [`TextLayout`],
+    /// whose offsets are relative to the start of the range. Text without
+    /// paragraph attributes is laid out as a single part; otherwise each
+    /// paragraph is a part, except that the first line of an indented
+    /// paragraph and the marker of a list item are laid out on their own.
+    ///
+    /// This is empty until [`rebuild_if_needed`] has been called.
+    ///
+    /// [`TextLayout`]: ./piet/trait.TextLayout.html
+    /// [`rebuild_if_needed`]: TextLayout::rebuild_if_needed
+    pub fn paragraph_layouts(
+        &self,
+    ) -> impl Iterator<Item = (Range<usize>, Vec2, &PietTextLayout)> + '_ {
+        self.paragraphs
+            .iter()
+            .flat_map(|paragraphs| paragraphs.iter())
+            .map(|p| (p.range.clone(), p.origin, &p.layout))
     }
 
     /// The size of the laid-out text.
@@ -214,9 +403,9 @@ impl<T: TextStorage> TextLayout<T> {
     ///
     /// [`rebuild_if_needed`]: #method.rebuild_if_needed
     pub fn size(&self) -> Size {
-        self.layout
+        self.paragraphs
             .as_ref()
-            .map(|layout| layout.size())
+            .map(|_| self.size)
             .unwrap_or_default()
     }
 
@@ -228,47 +417,152 @@ impl<T: TextStorage> TextLayout<T> {
     /// [`LayoutMetrics`]: struct.LayoutMetrics.html
     pub fn layout_metrics(&self) -> LayoutMetrics {
         debug_assert!(
-            self.layout.is_some(),
+            self.paragraphs.is_some(),
             "TextLayout::layout_metrics called without rebuilding layout object. Text was '{}'",
             self.text().as_ref().map(|s| s.as_str()).unwrap_or_default()
         );
 
-        if let Some(layout) = self.layout.as_ref() {
-            let first_baseline = layout.line_metric(0).unwrap().baseline;
-            let size = layout.size();
-            LayoutMetrics {
-                size,
-                first_baseline,
-                trailing_whitespace_width: layout.trailing_whitespace_width(),
+        match self.paragraphs.as_deref() {
+            Some(paragraphs) if !paragraphs.is_empty() => {
+                let first = &paragraphs[0];
+                let first_baseline = first.origin.y + first.layout.line_metric(0).unwrap().baseline;
+                let trailing_whitespace_width = paragraphs
+                    .iter()
+                    .map(|p| p.origin.x + p.layout.trailing_whitespace_width())
+                    .fold(0.0, f64::max);
+                LayoutMetrics {
+                    size: self.size,
+                    first_baseline,
+                    trailing_whitespace_width,
+                }
             }
-        } else {
-            LayoutMetrics::default()
+            _ => LayoutMetrics::default(),
         }
     }
 
+    /// Returns the paragraph containing the text position `text_pos`.
+    ///
+    /// A position in the line break between two paragraphs belongs to the
+    /// first of them.
+    fn paragraph_for_text_position(&self, text_pos: usize) -> Option<&Paragraph> {
+        let paragraphs = self.paragraphs.as_ref()?;
+        paragraphs
+            .iter()
+            .rev()
+            .find(|p| p.range.start <= text_pos)
+            .or_else(|| paragraphs.first())
+    }
+
+    /// Returns the paragraph that is nearest to `point` in the vertical direction.
+    fn paragraph_for_point(&self, point: Point) -> Option<&Paragraph> {
+        let paragraphs = self.paragraphs.as_ref()?;
+        paragraphs
+            .iter()
+            .rev()
+            .find(|p| p.origin.y <= point.y)
+            .or_else(|| paragraphs.first())
+    }
+
     /// For a given `Point` (relative to this object's origin), returns index
     /// into the underlying text of the nearest grapheme boundary.
     pub fn text_position_for_point(&self, point: Point) -> usize {
-        self.layout
-            .as_ref()
-            .map(|layout| layout.hit_test_point(point).idx)
+        self.hit_test_point(point).idx
+    }
+
+    /// For a given `Point` (relative to this object's origin), returns a
+    /// [`HitTestPoint`] with the index into the underlying text of the
+    /// nearest grapheme boundary, and whether the point is inside the text.
+    ///
+    /// This is the inverse of [`point_for_text_position`].
+    ///
+    /// [`point_for_text_position`]: TextLayout::point_for_text_position
+    pub fn hit_test_point(&self, point: Point) -> HitTestPoint {
+        self.paragraph_for_point(point)
+            .map(|p| {
+                let mut hit = p.layout.hit_test_point(point - p.origin);
+                hit.idx = self.text_offset(p.range.start + hit.idx);
+                hit
+            })
             .unwrap_or_default()
     }
 
     /// Given the utf-8 position of a character boundary in the underlying text,
     /// return the `Point` (relative to this object's origin) representing the
     /// boundary of the containing grapheme.
+    pub fn point_for_text_position(&self, text_pos: usize) -> Point {
+        self.hit_test_text_position(text_pos).point
+    }
+
+    /// Given a utf-8 position in the underlying text, return a
+    /// [`HitTestPosition`] with the point on the baseline (relative to this
+    /// object's origin) at the boundary of the containing grapheme, and the
+    /// index of its line, which can be passed to [`line_metric`] to find the
+    /// height of the line.
     ///
-    /// # Panics
+    /// Positions past the end of the text are treated as the end of the
+    /// text, and positions that are not character boundaries as the start
+    /// of their character.
     ///
-    /// Panics if `text_pos` is not a character boundary.
-    pub fn point_for_text_position(&self, text_pos: usize) -> Point {
-        self.layout
-            .as_ref()
-            .map(|layout| layout.hit_test_text_position(text_pos).point)
+    /// This is the inverse of [`hit_test_point`].
+    ///
+    /// [`line_metric`]: TextLayout::line_metric
+    /// [`hit_test_point`]: TextLayout::hit_test_point
+    pub fn hit_test_text_position(&self, text_pos: usize) -> HitTestPosition {
+        let text_pos = self.layout_offset(text_pos);
+        self.paragraph_for_text_position(text_pos)
+            .map(|p| {
+                let mut pos = p.hit_test_text_position(text_pos);
+                pos.line += self.first_line(p);
+                pos
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the [`LineMetric`] for the line at `line`, counting the lines
+    /// of every paragraph, or `None` if there is no such line.
+    ///
+    /// The offsets are positions in the underlying text, and the `y_offset`
+    /// is relative to this object's origin.
+    pub fn line_metric(&self, line: usize) -> Option<LineMetric> {
+        let mut line = line;
+        for p in self.paragraphs.as_ref()?.iter() {
+            let count = p.layout.line_count();
+            if line < count {
+                let mut metric = p.layout.line_metric(line)?;
+                metric.start_offset = self.text_offset(p.range.start + metric.start_offset);
+                metric.end_offset = self.text_offset(p.range.start + metric.end_offset);
+                metric.y_offset += p.origin.y;
+                return Some(metric);
+            }
+            line -= count;
+        }
+        None
+    }
+
+    /// Returns the range of the underlying text in the visual line that
+    /// contains `text_pos`, including any trailing whitespace and line break.
+    ///
+    /// This is useful for moving to the start or end of a line. To find the
+    /// boundaries of words or graphemes, use the methods of [`EditableText`].
+    ///
+    /// [`EditableText`]: super::EditableText
+    pub fn line_range_for_text_position(&self, text_pos: usize) -> Range<usize> {
+        let line = self.hit_test_text_position(text_pos).line;
+        self.line_metric(line)
+            .map(|metric| metric.start_offset..metric.end_offset)
             .unwrap_or_default()
     }
 
+    /// Returns the number of lines in all the paragraphs before `paragraph`.
+    fn first_line(&self, paragraph: &Paragraph) -> usize {
+        self.paragraphs
+            .iter()
+            .flat_map(|paragraphs| paragraphs.iter())
+            .take_while(|p| !std::ptr::eq(*p, paragraph))
+            .map(|p| p.layout.line_count())
+            .sum()
+    }
+
     /// Given a utf-8 range in the underlying text, return a `Vec` of `Rect`s
     /// representing the nominal bounding boxes of the text in that range.
     ///
@@ -276,9 +570,12 @@ impl<T: TextStorage> TextLayout<T> {
     ///
     /// Panics if the range start or end is not a character boundary.
     pub fn rects_for_range(&self, range: Range<usize>) -> Vec<Rect> {
-        self.layout
+        self.paragraphs
             .as_ref()
-            .map(|layout| layout.rects_for_range(range))
+            .map(|paragraphs| {
+                let range = self.layout_offset(range.start)..self.layout_offset(range.end);
+                rects_for_range(paragraphs, range)
+            })
             .unwrap_or_default()
     }
 
@@ -289,15 +586,15 @@ impl<T: TextStorage> TextLayout<T> {
     ///
     /// range is expected to be on a single visual line.
     pub fn underline_for_range(&self, range: Range<usize>) -> Line {
-        self.layout
-            .as_ref()
-            .map(|layout| {
-                let p1 = layout.hit_test_text_position(range.start);
-                let p2 = layout.hit_test_text_position(range.end);
-                let line_metric = layout.line_metric(p1.line).unwrap();
+        let range = self.layout_offset(range.start)..self.layout_offset(range.end);
+        self.paragraph_for_text_position(range.start)
+            .map(|p| {
+                let p1 = p.hit_test_text_position(range.start);
+                let p2 = p.hit_test_text_position(range.end);
+                let line_metric = p.layout.line_metric(p1.line).unwrap();
                 // heuristic; 1/5 of height is a rough guess at the descender pos?
-                let y_pos = line_metric.baseline + (line_metric.height / 5.0);
-                Line::new((p1.point.x, y_pos), (p2.point.x, y_pos))
+                let y_pos = line_metric.baseline + (line_metric.height / 5.0) + p.origin.y;
+                Line::new(Point::new(p1.point.x, y_pos), Point::new(p2.point.x, y_pos))
             })
             .unwrap_or_else(|| Line::new(Point::ZERO, Point::ZERO))
     }
@@ -305,13 +602,13 @@ impl<T: TextStorage> TextLayout<T> {
     /// Given the utf-8 position of a character boundary in the underlying text,
     /// return a `Line` suitable for drawing a vertical cursor at that boundary.
     pub fn cursor_line_for_text_position(&self, text_pos: usize) -> Line {
-        self.layout
-            .as_ref()
-            .map(|layout| {
-                let pos = layout.hit_test_text_position(text_pos);
-                let line_metrics = layout.line_metric(pos.line).unwrap();
-                let p1 = (pos.point.x, line_metrics.y_offset);
-                let p2 = (pos.point.x, (line_metrics.y_offset + line_metrics.height));
+        let text_pos = self.layout_offset(text_pos);
+        self.paragraph_for_text_position(text_pos)
+            .map(|p| {
+                let pos = p.hit_test_text_position(text_pos);
+                let line_metrics = p.layout.line_metric(pos.line).unwrap();
+                let p1 = Point::new(pos.point.x, line_metrics.y_offset + p.origin.y);
+                let p2 = p1 + Vec2::new(0.0, line_metrics.height);
                 Line::new(p1, p2)
             })
             .unwrap_or_else(|| Line::new(Point::ZERO, Point::ZERO))
@@ -325,13 +622,35 @@ impl<T: TextStorage> TextLayout<T> {
     ///
     /// [`Link`]: super::attribute::Link
     pub fn link_for_pos(&self, pos: Point) -> Option<&Link> {
-        let (_, i) = self
-            .links
+        let i = self.link_index_for_pos(pos)?;
+        let text = self.text()?;
+        text.links().get(i)
+    }
+
+    /// For a given `Point` (relative to this object's origin), returns the index
+    /// of the [`Link`] at that point, if any.
+    ///
+    /// The index refers to the slice returned by [`TextStorage::links`].
+    ///
+    /// [`Link`]: super::attribute::Link
+    pub fn link_index_for_pos(&self, pos: Point) -> Option<usize> {
+        self.links
             .iter()
-            .rfind(|(hit_box, _)| hit_box.contains(pos))?;
+            .rfind(|(hit_box, _)| hit_box.contains(pos))
+            .map(|(_, i)| *i)
+    }
 
-        let text = self.text()?;
-        text.links().get(*i)
+    /// Returns the rects, relative to this object's origin, covered by the
+    /// [`Link`] with the given index.
+    ///
+    /// A link that spans multiple lines has one rect per line.
+    ///
+    /// [`Link`]: super::attribute::Link
+    pub fn link_rects(&self, index: usize) -> impl Iterator<Item = Rect> + '_ {
+        self.links
+            .iter()
+            .filter(move |(_, i)| *i == index)
+            .map(|(rect, _)| *rect)
     }
 
     /// Called during the containing widgets `update` method; this text object
@@ -340,7 +659,7 @@ impl<T: TextStorage> TextLayout<T> {
     ///
     /// Returns `true` if the text item needs to be rebuilt.
     pub fn needs_rebuild_after_update(&mut self, ctx: &mut UpdateCtx) -> bool {
-        if ctx.env_changed() && self.layout.is_some() {
+        if ctx.env_changed() && self.paragraphs.is_some() {
             let rebuild = ctx.env_key_changed(&self.font)
                 || ctx.env_key_changed(&self.text_color)
                 || self
@@ -355,10 +674,10 @@ impl<T: TextStorage> TextLayout<T> {
                     .unwrap_or(false);
 
             if rebuild {
-                self.layout = None;
+                self.paragraphs = None;
             }
         }
-        self.layout.is_none()
+        self.paragraphs.is_none()
     }
 
     /// Rebuild the inner layout as needed.
@@ -374,7 +693,7 @@ impl<T: TextStorage> TextLayout<T> {
     /// [`layout`]: trait.Widget.html#method.layout
     pub fn rebuild_if_needed(&mut self, factory: &mut PietText, env: &Env) {
         if let Some(text) = &self.text {
-            if self.layout.is_none() {
+            if self.paragraphs.is_none() {
                 let font = self.font.resolve(env);
                 let color = self.text_color.resolve(env);
                 let size_override = self.text_size_override.as_ref().map(|key| key.resolve(env));
@@ -385,29 +704,340 @@ impl<T: TextStorage> TextLayout<T> {
                     font
                 };
 
-                let builder = factory
-                    .new_text_layout(text.clone())
-                    .max_width(self.wrap_width)
-                    .alignment(self.alignment)
-                    .font(descriptor.family.clone(), descriptor.size)
-                    .default_attribute(descriptor.weight)
-                    .default_attribute(descriptor.style)
-                    .default_attribute(TextAttribute::TextColor(color));
-                let layout = text.add_attributes(builder, env).build().unwrap();
+                let alignment = self.alignment;
+
+                // Masked text is laid out as a string of mask characters,
+                // one for each grapheme, without any attributes.
+                if let Some(mask) = self.mask {
+                    let graphemes = text.as_str().grapheme_indices(true);
+                    self.mask_offsets = graphemes
+                        .map(|(offset, _)| offset)
+                        .chain(Some(text.as_str().len()))
+                        .collect();
+                    let masked: String = std::iter::repeat(mask)
+                        .take(self.mask_offsets.len() - 1)
+                        .collect();
+                    let range = 0..masked.len();
+                    let layout = factory
+                        .new_text_layout(masked)
+                        .max_width(self.wrap_width)
+                        .alignment(alignment)
+                        .font(descriptor.family.clone(), descriptor.size)
+                        .default_attribute(descriptor.weight)
+                        .default_attribute(descriptor.style)
+                        .default_attribute(TextAttribute::TextColor(color))
+                        .build()
+                        .unwrap();
+                    self.size = layout.size();
+                    let paragraph = Paragraph {
+                        range,
+                        origin: Vec2::ZERO,
+                        layout,
+                    };
+                    self.paragraphs = Some(vec![paragraph].into());
+                    self.shifted = Rc::new([]);
+                    self.links = Rc::new([]);
+                    self.backgrounds = Rc::new([]);
+                    self.attachments = Rc::new([]);
+                    self.placeholders = Rc::new([]);
+                    return;
+                }
 
+                let build = |factory: &mut PietText, text: &T, wrap_width: f64| {
+                    let builder = factory
+                        .new_text_layout(text.clone())
+                        .max_width(wrap_width)
+                        .alignment(alignment)
+                        .font(descriptor.family.clone(), descriptor.size)
+                        .default_attribute(descriptor.weight)
+                        .default_attribute(descriptor.style)
+                        .default_attribute(TextAttribute::TextColor(color.clone()));
+                    text.add_attributes(builder, env).build().unwrap()
+                };
+                let styled = self
+                    .link_state
+                    .and_then(|(index, state)| text.with_link_state(index, state));
+                let text = styled.as_ref().unwrap_or(text);
+                // Baseline shifts are relative to the font size before it is
+                // scaled.
+                let shifts = text.baseline_shifts(env, descriptor.size);
+                // Font scales, such as those of superscripts, are applied to
+                // the default size here, where it is known.
+                let scaled = text.resolve_font_scales(env, descriptor.size);
+                let text = scaled.as_ref().unwrap_or(text);
+                // Small capitals are synthesized by laying out a modified copy
+                // of the text, which has the same offsets as the original.
+                let small_caps = text.small_caps(env, descriptor.size);
+                let text = small_caps.as_ref().unwrap_or(text);
+                // Attachments are replaced by placeholders, which may be longer
+                // than the characters they replace; from here on, offsets are
+                // into the placeholder text.
+                self.placeholders =
+                    attachment::placeholders(text.as_str(), &text.attachments()).into();
+                let placeholders = text.attachment_placeholders();
+                let text = placeholders.as_ref().unwrap_or(text);
+                // Text with a shifted baseline takes up space in the layout,
+                // but is not drawn there; see `shifted` below.
+                let shifts: Vec<_> = shifts
+                    .into_iter()
+                    .map(|(range, shift)| {
+                        let range = self.layout_offset(range.start)..self.layout_offset(range.end);
+                        (range, shift)
+                    })
+                    .collect();
+                let visible = text;
+                let hidden = text.hide_shifted_text();
+                let text = hidden.as_ref().unwrap_or(text);
+
+                // If the text has spans that may shrink, and it needs more
+                // lines than it would with unlimited width, we search for the
+                // largest scale at which it fits.
+                let fit = |factory: &mut PietText, text: &T, wrap_width: f64| {
+                    let layout = build(factory, text, wrap_width);
+                    if !wrap_width.is_finite() {
+                        return layout;
+                    }
+                    let smallest = match text.scaled_to_fit(env, descriptor.size, 0.0) {
+                        Some(smallest) => smallest,
+                        None => return layout,
+                    };
+                    let line_count = build(factory, text, f64::INFINITY).line_count();
+                    if layout.line_count() <= line_count {
+                        return layout;
+                    }
+                    let mut best = build(factory, &smallest, wrap_width);
+                    if best.line_count() <= line_count {
+                        let (mut lo, mut hi) = (0.0, 1.0);
+                        for _ in 0..SCALE_TO_FIT_STEPS {
+                            let scale = (lo + hi) / 2.0;
+                            let scaled = text.scaled_to_fit(env, descriptor.size, scale).unwrap();
+                            let candidate = build(factory, &scaled, wrap_width);
+                            if candidate.line_count() <= line_count {
+                                lo = scale;
+                                best = candidate;
+                            } else {
+                                hi = scale;
+                            }
+                        }
+                    }
+                    best
+                };
+
+                // Text with paragraph attributes is laid out one paragraph at
+                // a time, and the paragraphs are stacked vertically.
+                let styled_paragraphs = text
+                    .paragraphs()
+                    .filter(|_| self.split_paragraphs)
+                    .and_then(|paragraphs| {
+                        paragraphs
+                            .into_iter()
+                            .map(|(range, style)| {
+                                Some((text.paragraph_text(range.clone())?, range, style))
+                            })
+                            .collect::<Option<Vec<_>>>()
+                    });
+
+                let (paragraphs, size) = match styled_paragraphs {
+                    Some(styled_paragraphs) => {
+                        let mut paragraphs = Vec::new();
+                        let mut alignments = Vec::new();
+                        let mut y = 0.0;
+                        // the indent is at the start of the line, which is on
+                        // the right for right-to-left text; piet leaves it
+                        // there when the wrap width is reduced by the indent,
+                        // and otherwise it is added when aligning below.
+                        let (rtl, wrap_width) = (self.text_is_rtl, self.wrap_width);
+                        let line_x = |indent: f64| if rtl { 0.0 } else { indent };
+                        for (text, range, style) in styled_paragraphs {
+                            y += style.space_before;
+                            let alignment = style.alignment.unwrap_or(self.alignment);
+                            let indent = style.indent.max(0.0);
+                            let hanging = style.hanging_indent.max(0.0);
+                            let first_width = (wrap_width - indent).max(0.0);
+                            let rest_width = (wrap_width - hanging).max(0.0);
+
+                            // A list marker is laid out on its own at the indent,
+                            // and the text after it at the hanging indent, with
+                            // their first baselines lined up.
+                            let marker_len = style.marker_len;
+                            if marker_len > 0 && marker_len < range.len() {
+                                if let (Some(marker), Some(body)) = (
+                                    text.paragraph_text(0..marker_len),
+                                    text.paragraph_text(marker_len..range.len()),
+                                ) {
+                                    let marker = fit(factory, &marker, first_width);
+                                    let body = fit(factory, &body, rest_width);
+                                    let baseline = |layout: &PietTextLayout| {
+                                        layout.line_metric(0).map_or(0.0, |l| l.baseline)
+                                    };
+                                    let top = baseline(&marker).max(baseline(&body));
+                                    let mut bottom = y;
+                                    let marker_end = range.start + marker_len;
+                                    for (range, line_indent, layout) in vec![
+                                        (range.start..marker_end, indent, marker),
+                                        (marker_end..range.end, hanging, body),
+                                    ] {
+                                        let x = line_x(line_indent);
+                                        let origin = Vec2::new(x, y + top - baseline(&layout));
+                                        bottom = f64::max(bottom, origin.y + layout.size().height);
+                                        paragraphs.push(Paragraph {
+                                            range,
+                                            origin,
+                                            layout,
+                                        });
+                                        alignments.push((alignment, line_indent));
+                                    }
+                                    y = bottom + style.space_after;
+                                    continue;
+                                }
+                            }
+
+                            let mut layout = fit(factory, &text, first_width);
+
+                            // piet cannot indent a single line, so when the first
+                            // line is indented differently from the others it is
+                            // laid out on its own, followed by the rest of the
+                            // paragraph.
+                            let mut rest = None;
+                            if indent != hanging && layout.line_count() > 1 {
+                                let end = layout.line_metric(0).unwrap().end_offset;
+                                if let (Some(first), Some(remainder)) = (
+                                    text.paragraph_text(0..end),
+                                    text.paragraph_text(end..range.len()),
+                                ) {
+                                    layout = fit(factory, &first, first_width);
+                                    rest = Some((end, fit(factory, &remainder, rest_width)));
+                                }
+                            }
+
+                            let first_end = rest
+                                .as_ref()
+                                .map_or(range.end, |(end, _)| range.start + end);
+                            let height = layout.size().height;
+                            paragraphs.push(Paragraph {
+                                range: range.start..first_end,
+                                origin: Vec2::new(line_x(indent), y),
+                                layout,
+                            });
+                            alignments.push((alignment, indent));
+                            y += height;
+                            if let Some((_, layout)) = rest {
+                                let height = layout.size().height;
+                                paragraphs.push(Paragraph {
+                                    range: first_end..range.end,
+                                    origin: Vec2::new(line_x(hanging), y),
+                                    layout,
+                                });
+                                alignments.push((alignment, hanging));
+                                y += height;
+                            }
+                            y += style.space_after;
+                        }
+
+                        let width = paragraphs
+                            .iter()
+                            .zip(&alignments)
+                            .map(|(p, (_, indent))| indent + p.layout.size().width)
+                            .fold(0.0, f64::max);
+                        // With an unlimited wrap width, piet aligns each layout
+                        // within its own width; we align them with each other.
+                        if !self.wrap_width.is_finite() {
+                            for (paragraph, (alignment, indent)) in
+                                paragraphs.iter_mut().zip(alignments)
+                            {
+                                let extra = width - indent - paragraph.layout.size().width;
+                                paragraph.origin.x += extra * alignment_factor(alignment, rtl);
+                            }
+                        }
+                        (paragraphs, Size::new(width, y))
+                    }
+                    None => {
+                        let layout = fit(factory, text, self.wrap_width);
+                        let size = layout.size();
+                        let paragraph = Paragraph {
+                            range: 0..text.as_str().len(),
+                            origin: Vec2::ZERO,
+                            layout,
+                        };
+                        (vec![paragraph], size)
+                    }
+                };
+
+                // Each line of a run of shifted text is laid out on its own,
+                // and placed so that its baseline is shifted from the baseline
+                // of the line; the height of the line is unchanged.
+                let mut shifted = Vec::new();
+                for (range, shift) in &shifts {
+                    for p in paragraphs.iter() {
+                        for line in 0..p.layout.line_count() {
+                            let metric = p.layout.line_metric(line).unwrap();
+                            let start = range.start.max(p.range.start + metric.start_offset);
+                            let end = range.end.min(p.range.start + metric.end_offset);
+                            if start >= end {
+                                continue;
+                            }
+                            let run = match visible.paragraph_text(start..end) {
+                                Some(run) => build(factory, &run, f64::INFINITY),
+                                None => continue,
+                            };
+                            let local = p.local_offset(start)..p.local_offset(end);
+                            let x = match p.layout.rects_for_range(local).first() {
+                                Some(rect) => p.origin.x + rect.x0,
+                                None => continue,
+                            };
+                            let run_baseline = run.line_metric(0).map_or(0.0, |l| l.baseline);
+                            let baseline = p.origin.y + metric.y_offset + metric.baseline;
+                            shifted.push(Paragraph {
+                                range: start..end,
+                                origin: Vec2::new(x, baseline - shift - run_baseline),
+                                layout: run,
+                            });
+                        }
+                    }
+                }
+
+                // links on shifted text can be clicked where it is drawn, as
+                // well as where it is laid out.
                 self.links = text
                     .links()
                     .iter()
                     .enumerate()
                     .flat_map(|(i, link)| {
-                        layout
-                            .rects_for_range(link.range())
+                        rects_for_range(&paragraphs, link.range())
                             .into_iter()
+                            .chain(rects_for_range(&shifted, link.range()))
                             .map(move |rect| (rect, i))
                     })
                     .collect();
 
-                self.layout = Some(layout);
+                self.backgrounds = text
+                    .background_colors(env)
+                    .into_iter()
+                    .flat_map(|(range, color)| {
+                        rects_for_range(&paragraphs, range)
+                            .into_iter()
+                            .map(move |rect| (rect, color.clone()))
+                    })
+                    .collect();
+
+                self.size = size;
+                self.paragraphs = Some(paragraphs.into());
+                self.shifted = shifted.into();
+
+                self.attachments = text
+                    .attachments()
+                    .into_iter()
+                    .filter_map(|(range, attachment)| {
+                        let paragraphs = self.paragraphs.as_deref()?;
+                        let x = rects_for_range(paragraphs, range.clone()).first()?.x0;
+                        let p = self.paragraph_for_text_position(range.start)?;
+                        let pos = p.layout.hit_test_text_position(p.local_offset(range.start));
+                        let line = p.layout.line_metric(pos.line)?;
+                        let top = p.origin.y + line.y_offset;
+                        let rect = attachment.rect(x, top, top + line.baseline, top + line.height);
+                        Some((rect, attachment))
+                    })
+                    .collect();
             }
         }
     }
@@ -422,28 +1052,86 @@ impl<T: TextStorage> TextLayout<T> {
     ///  [`rebuild_if_needed`]: #method.rebuild_if_needed
     pub fn draw(&self, ctx: &mut PaintCtx, point: impl Into<Point>) {
         debug_assert!(
-            self.layout.is_some(),
+            self.paragraphs.is_some(),
             "TextLayout::draw called without rebuilding layout object. Text was '{}'",
             self.text
                 .as_ref()
                 .map(|t| t.as_str())
                 .unwrap_or("layout is missing text")
         );
-        if let Some(layout) = self.layout.as_ref() {
-            ctx.draw_text(layout, point);
+        if let Some(paragraphs) = self.paragraphs.as_ref() {
+            let point = point.into();
+            for (rect, color) in self.backgrounds.iter() {
+                ctx.fill(*rect + point.to_vec2(), color);
+            }
+            for paragraph in paragraphs.iter().chain(self.shifted.iter()) {
+                ctx.draw_text(&paragraph.layout, point + paragraph.origin);
+            }
+            for (rect, attachment) in self.attachments.iter() {
+                attachment.paint(ctx, *rect + point.to_vec2());
+            }
         }
     }
 }
 
+impl Paragraph {
+    /// Convert a position in the whole text to a position in this paragraph,
+    /// clamping it to the paragraph's range.
+    fn local_offset(&self, text_pos: usize) -> usize {
+        text_pos.max(self.range.start).min(self.range.end) - self.range.start
+    }
+
+    /// Hit-test the position `text_pos`, clamped to this paragraph and moved
+    /// to the start of its character. The point is relative to the origin of
+    /// the `TextLayout`, and the line is the line in this paragraph.
+    fn hit_test_text_position(&self, text_pos: usize) -> HitTestPosition {
+        let text = self.layout.text();
+        let mut offset = self.local_offset(text_pos).min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let mut pos = self.layout.hit_test_text_position(offset);
+        pos.point += self.origin;
+        pos
+    }
+}
+
+/// Returns the rects covered by `range` in the text laid out in `paragraphs`.
+fn rects_for_range(paragraphs: &[Paragraph], range: Range<usize>) -> Vec<Rect> {
+    paragraphs
+        .iter()
+        .filter(|p| p.range.start < range.end && range.start < p.range.end)
+        .flat_map(|p| {
+            let local = p.local_offset(range.start)..p.local_offset(range.end);
+            p.layout
+                .rects_for_range(local)
+                .into_iter()
+                .map(move |rect| rect + p.origin)
+        })
+        .collect()
+}
+
+/// Returns the fraction of the extra horizontal space that goes before a
+/// line with the given alignment.
+fn alignment_factor(alignment: TextAlignment, rtl: bool) -> f64 {
+    match alignment {
+        TextAlignment::Center => 0.5,
+        TextAlignment::Start | TextAlignment::Justified if rtl => 1.0,
+        TextAlignment::End if !rtl => 1.0,
+        _ => 0.0,
+    }
+}
+
 impl<T> std::fmt::Debug for TextLayout<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_struct("TextLayout")
             .field("font", &self.font)
             .field("text_size_override", &self.text_size_override)
             .field("text_color", &self.text_color)
+            .field("mask", &self.mask)
             .field(
                 "layout",
-                if self.layout.is_some() {
+                if self.paragraphs.is_some() {
                     &"Some"
                 } else {
                     &"None"
@@ -458,3 +1146,399 @@ impl<T: TextStorage> Default for TextLayout<T> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piet::Device;
+    use crate::text::{ArcStr, Attribute, RichText, RichTextBuilder};
+    use crate::Selector;
+
+    const LINK: Selector = Selector::new("druid-test.text-layout.link");
+
+    #[test]
+    fn wrapped_link_hit_testing() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("x ");
+        builder.push("a link that wraps").link(LINK);
+        builder.push(" after");
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.set_wrap_width(60.0);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        let rects: Vec<_> = layout.link_rects(0).collect();
+        assert!(rects.len() > 1, "link does not wrap: {:?}", rects);
+        for rect in &rects {
+            assert_eq!(layout.link_index_for_pos(rect.center()), Some(0));
+        }
+
+        // the text before the link on its first line, and after it on its
+        // last line, is not part of the link.
+        let (first, last) = (rects[0], rects[rects.len() - 1]);
+        let before = Point::new(first.x0 / 2.0, first.center().y);
+        assert_eq!(layout.link_index_for_pos(before), None);
+        let after = Point::new(last.x1 + 1.0, last.center().y);
+        assert_eq!(layout.link_index_for_pos(after), None);
+
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn link_state_rebuilds_layout() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+        let env = Env::with_default_i10n();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("link").link(LINK).link_hover_color(Color::RED);
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.rebuild_if_needed(piet.text(), &env);
+        assert!(!layout.needs_rebuild());
+
+        layout.set_link_state(Some((0, LinkState::Hovered)));
+        assert!(layout.needs_rebuild());
+        layout.rebuild_if_needed(piet.text(), &env);
+        layout.set_link_state(Some((0, LinkState::Hovered)));
+        assert!(!layout.needs_rebuild());
+        layout.set_link_state(Some((0, LinkState::Pressed)));
+        assert!(layout.needs_rebuild());
+        layout.rebuild_if_needed(piet.text(), &env);
+
+        // changing the text clears the state
+        layout.set_text(RichText::new("plain".into()));
+        layout.rebuild_if_needed(piet.text(), &env);
+        layout.set_link_state(None);
+        assert!(!layout.needs_rebuild());
+
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn hit_test_round_trip() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("small ");
+        builder.push("BIG").size(30.0);
+        builder.push(" again");
+        let text = builder.build();
+        let len = text.len();
+        let mut layout = TextLayout::<RichText>::from_text(text);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        for offset in 0..len {
+            // probe just inside the leading edge of each character, halfway
+            // down its line
+            let pos = layout.hit_test_text_position(offset);
+            let line = layout.line_metric(pos.line).unwrap();
+            let point = Point::new(pos.point.x + 0.5, line.y_offset + line.height / 2.0);
+            let hit = layout.hit_test_point(point);
+            assert_eq!(hit.idx, offset);
+            assert!(hit.is_inside, "offset {} is not inside", offset);
+        }
+
+        let end = Point::new(layout.size().width + 10.0, 1.0);
+        let hit = layout.hit_test_point(end);
+        assert_eq!(hit.idx, len);
+        assert!(!hit.is_inside);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn text_position_edge_cases() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let text = "one\n\nth\u{e9}";
+        let mut layout = TextLayout::<ArcStr>::from_text(text);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        // the empty line, and the end of the text, are lines of their own.
+        let empty = layout.hit_test_text_position(4);
+        assert_eq!(empty.line, 1);
+        let end = layout.hit_test_text_position(text.len());
+        assert_eq!(end.line, 2);
+        let metric = layout.line_metric(end.line).unwrap();
+        assert!(metric.height > 0.0);
+        assert!(end.point.y > metric.y_offset && end.point.y <= metric.y_offset + metric.height);
+        assert!(layout.line_metric(3).is_none());
+
+        // positions past the end, and inside a character, are moved back.
+        assert_eq!(layout.hit_test_text_position(100).point, end.point);
+        let inside = layout.hit_test_text_position(text.len() - 1);
+        assert_eq!(inside.point, layout.point_for_text_position(text.len() - 2));
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn line_ranges() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let text = "wrapping words\nnext";
+        let mut layout = TextLayout::<ArcStr>::from_text(text);
+        layout.set_wrap_width(60.0);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        // the first line wraps, and the next one starts where it ends.
+        let first = layout.line_range_for_text_position(0);
+        assert_eq!(first.start, 0);
+        assert!(first.end > 0 && first.end < 15, "no wrap: {:?}", first);
+        let second = layout.line_range_for_text_position(first.end);
+        assert_eq!(second.start, first.end);
+        assert_eq!(layout.line_range_for_text_position(14).end, 15);
+        assert_eq!(
+            layout.line_range_for_text_position(text.len()),
+            15..text.len()
+        );
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn writing_direction() {
+        let hebrew = RichText::new("\u{5E9}\u{5DC}\u{5D5}\u{5DD} abc".into());
+        let layout = TextLayout::<RichText>::from_text(hebrew.clone());
+        assert!(layout.text_is_rtl());
+
+        let latin = RichText::new("abc \u{5E9}\u{5DC}\u{5D5}\u{5DD}".into());
+        let mut layout = TextLayout::<RichText>::from_text(latin.clone());
+        assert!(!layout.text_is_rtl());
+        let latin = latin.with_direction(WritingDirection::RightToLeft);
+        assert_eq!(latin.slice(0..3).direction(), WritingDirection::RightToLeft);
+        layout.set_text(latin);
+        assert!(layout.text_is_rtl());
+
+        layout.set_text(hebrew.with_direction(WritingDirection::LeftToRight));
+        assert!(!layout.text_is_rtl());
+    }
+
+    #[test]
+    fn masked_offsets() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        // 'e' with a combining accent, and a family emoji, are one grapheme each.
+        let text = "ae\u{301}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}b";
+        let mut layout = TextLayout::<ArcStr>::from_text(text);
+        layout.set_mask(Some('*'));
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        let boundaries = [0, 1, 4, 22, 23];
+        for (i, offset) in boundaries.iter().enumerate() {
+            assert_eq!(layout.layout_offset(*offset), i);
+            assert_eq!(layout.text_offset(i), *offset);
+        }
+        assert_eq!(layout.layout().unwrap().line_text(0), Some("****"));
+
+        // each grapheme is as wide as the mask character.
+        let x = |pos| layout.point_for_text_position(pos).x;
+        assert!((x(22) - x(4) - (x(1) - x(0))).abs() < 1e-6);
+        let end = Point::new(layout.size().width + 10.0, 1.0);
+        assert_eq!(layout.text_position_for_point(end), text.len());
+
+        layout.set_mask(None);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+        assert_eq!(layout.layout_offset(22), 22);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn baseline_shift() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+        let env = Env::with_default_i10n();
+        let font_size = env.get(crate::theme::UI_FONT).size;
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("x");
+        builder.push("2").font_scale(0.7);
+        let mut scaled = TextLayout::<RichText>::from_text(builder.build());
+        scaled.rebuild_if_needed(piet.text(), &env);
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("x");
+        builder.push("2").superscript().link(LINK);
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.rebuild_if_needed(piet.text(), &env);
+
+        // the shifted text keeps its space, and does not change the line height
+        assert_eq!(layout.size(), scaled.size());
+        assert_eq!(layout.shifted.len(), 1);
+        let run = &layout.shifted[0];
+        assert_eq!(run.range, 1..2);
+        let run_baseline = run.origin.y + run.layout.line_metric(0).unwrap().baseline;
+        let baseline = layout.layout_metrics().first_baseline;
+        assert!((baseline - run_baseline - 0.33 * font_size).abs() < 1e-6);
+
+        // the link can be clicked where the text is drawn
+        let drawn = run.layout.size().to_rect() + run.origin;
+        assert_eq!(layout.link_rects(0).count(), 2);
+        assert_eq!(layout.link_index_for_pos(drawn.center()), Some(0));
+
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn wide_attachment() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("a");
+        builder.push_attachment(InlineAttachment::new((60.0, 10.0), |_, _| ()));
+        builder.push("b");
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        // the placeholder is six em spaces, each ten points wide
+        assert_eq!(layout.layout_offset(1), 1);
+        assert_eq!(layout.layout_offset(4), 19);
+        assert_eq!(layout.text_offset(19), 4);
+        let x = |pos| layout.point_for_text_position(pos).x;
+        assert!((x(4) - x(1) - 60.0).abs() < 1e-6);
+
+        // the line is not as tall as the attachment is wide
+        assert!(layout.size().height < 30.0, "{:?}", layout.size());
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn paragraph_stacking() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("first\n").space_after(5.0);
+        builder.push("second ").space_before(10.0).indent(20.0);
+        builder.push("link").link(LINK);
+        let text = builder.build();
+        let len = text.len();
+        let mut layout = TextLayout::<RichText>::from_text(text);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+        assert!(layout.layout().is_none());
+
+        let parts: Vec<_> = layout.paragraph_layouts().collect();
+        assert_eq!(parts.len(), 2);
+        let (first_range, first_origin, first) = &parts[0];
+        let (second_range, second_origin, _) = &parts[1];
+        // paragraph ranges do not include the line break
+        assert_eq!((first_range.clone(), second_range.clone()), (0..5, 6..len));
+        assert_eq!(*first_origin, Vec2::ZERO);
+        assert_eq!(second_origin.x, 20.0);
+        assert_eq!(second_origin.y, first.size().height + 15.0);
+
+        // hit-testing accounts for the position of each paragraph.
+        let start = layout.point_for_text_position(6);
+        assert!(start.x >= 20.0 && start.y > second_origin.y);
+        for offset in 0..len {
+            let hit = layout.hit_test_point(layout.point_for_text_position(offset));
+            assert_eq!(hit.idx, offset);
+        }
+
+        // so do the rects of links.
+        let rects: Vec<_> = layout.link_rects(0).collect();
+        assert_eq!(rects.len(), 1);
+        assert!(rects[0].x0 > 20.0 && rects[0].y0 >= second_origin.y);
+        assert_eq!(layout.link_index_for_pos(rects[0].center()), Some(0));
+        let above = Point::new(rects[0].center().x, first.size().height / 2.0);
+        assert_eq!(layout.link_index_for_pos(above), None);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn paragraph_alignment() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("a\n").alignment(TextAlignment::Center);
+        builder.push("b\n").alignment(TextAlignment::End);
+        builder.push("a wider line");
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        // each paragraph is aligned on its own.
+        let width = layout.size().width;
+        let parts: Vec<_> = layout.paragraph_layouts().collect();
+        assert_eq!(parts.len(), 3);
+        let extra = |i: usize| width - parts[i].2.size().width;
+        assert!((parts[0].1.x - extra(0) / 2.0).abs() < 1e-9);
+        assert!((parts[1].1.x - extra(1)).abs() < 1e-9);
+        assert_eq!(parts[2].1.x, 0.0);
+
+        // without splitting, the whole text is a single layout, and the
+        // alignment of the first paragraph is the only one passed to piet.
+        layout.set_split_paragraphs(false);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+        assert!(layout.layout().is_some());
+        assert_eq!(layout.paragraph_layouts().count(), 1);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn hanging_indent() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("a paragraph of words that wraps")
+            .add_attr(Attribute::hanging_indent(30.0));
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.set_wrap_width(80.0);
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+
+        // the first line is laid out on its own, and the rest below it at the
+        // hanging indent.
+        let parts: Vec<_> = layout.paragraph_layouts().collect();
+        assert_eq!(parts.len(), 2);
+        let (_, first_origin, first) = &parts[0];
+        let (rest_range, rest_origin, rest) = &parts[1];
+        assert_eq!(first.line_count(), 1);
+        assert_eq!(*first_origin, Vec2::ZERO);
+        assert_eq!(*rest_origin, Vec2::new(30.0, first.size().height));
+        assert!(rest.size().width <= 50.0);
+        let rest_start = layout.point_for_text_position(rest_range.start);
+        assert!(rest_start.x >= 30.0 && rest_start.y > first.size().height);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn rtl_indent_unlimited_width() {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(100, 100, 1.0).unwrap();
+        let mut piet = target.render_context();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("\u{5E9}\u{5DC}\u{5D5}\u{5DD}\n").indent(20.0);
+        builder.push("\u{5E9}\u{5DC}\u{5D5}\u{5DD} \u{5E9}\u{5DC}\u{5D5}\u{5DD}");
+        let mut layout = TextLayout::<RichText>::from_text(builder.build());
+        layout.rebuild_if_needed(piet.text(), &Env::with_default_i10n());
+        assert!(layout.text_is_rtl());
+
+        // the indent is on the right, and both paragraphs start on the right.
+        let width = layout.size().width;
+        let right_edges: Vec<_> = layout
+            .paragraph_layouts()
+            .map(|(_, origin, layout)| origin.x + layout.size().width)
+            .collect();
+        assert!((right_edges[0] - (width - 20.0)).abs() < 1e-6);
+        assert!((right_edges[1] - width).abs() < 1e-6);
+        piet.finish().unwrap();
+    }
+}
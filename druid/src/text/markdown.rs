@@ -0,0 +1,309 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Converting Markdown to [`RichText`].
+
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use super::{AttributesAdder, RichText, RichTextBuilder};
+use crate::piet::{Color, FontFamily, FontStyle, FontWeight};
+use crate::{KeyOrValue, Selector};
+
+const BLOCKQUOTE_COLOR: Color = Color::grey8(0x88);
+const LINK_COLOR: Color = Color::rgb8(0, 0, 0xEE);
+
+/// Styling used when converting Markdown to [`RichText`].
+///
+/// See [`RichText::from_markdown`] for more information.
+#[derive(Debug, Clone)]
+pub struct MarkdownStyle {
+    heading_sizes: [KeyOrValue<f64>; 6],
+    code_font: FontFamily,
+    code_color: Option<KeyOrValue<Color>>,
+    blockquote_color: KeyOrValue<Color>,
+    link_color: KeyOrValue<Color>,
+    link_selector: Selector<String>,
+}
+
+impl MarkdownStyle {
+    /// Create a new `MarkdownStyle`.
+    ///
+    /// Links in the Markdown source will produce a [`Command`] with the
+    /// provided [`Selector`], carrying the link's destination as a payload.
+    ///
+    /// [`Command`]: crate::Command
+    pub fn new(link_selector: Selector<String>) -> Self {
+        MarkdownStyle {
+            heading_sizes: [
+                KeyOrValue::Concrete(38.0),
+                KeyOrValue::Concrete(32.0),
+                KeyOrValue::Concrete(26.0),
+                KeyOrValue::Concrete(20.0),
+                KeyOrValue::Concrete(16.0),
+                KeyOrValue::Concrete(12.0),
+            ],
+            code_font: FontFamily::MONOSPACE,
+            code_color: None,
+            blockquote_color: BLOCKQUOTE_COLOR.into(),
+            link_color: LINK_COLOR.into(),
+            link_selector,
+        }
+    }
+
+    /// Builder-style method to set the font size of headings of the given level.
+    ///
+    /// `level` is in the range `1..=6`; other values are ignored.
+    pub fn with_heading_size(mut self, level: usize, size: impl Into<KeyOrValue<f64>>) -> Self {
+        if let Some(item) = level
+            .checked_sub(1)
+            .and_then(|idx| self.heading_sizes.get_mut(idx))
+        {
+            *item = size.into();
+        }
+        self
+    }
+
+    /// Builder-style method to set the font family used for inline code and code blocks.
+    pub fn with_code_font(mut self, family: FontFamily) -> Self {
+        self.code_font = family;
+        self
+    }
+
+    /// Builder-style method to set the text color used for inline code and code blocks.
+    pub fn with_code_color(mut self, color: impl Into<KeyOrValue<Color>>) -> Self {
+        self.code_color = Some(color.into());
+        self
+    }
+
+    /// Builder-style method to set the text color used for block quotes.
+    pub fn with_blockquote_color(mut self, color: impl Into<KeyOrValue<Color>>) -> Self {
+        self.blockquote_color = color.into();
+        self
+    }
+
+    /// Builder-style method to set the text color used for links.
+    pub fn with_link_color(mut self, color: impl Into<KeyOrValue<Color>>) -> Self {
+        self.link_color = color.into();
+        self
+    }
+
+    fn add_code_attributes(&self, attrs: &mut AttributesAdder) {
+        attrs.font_family(self.code_font.clone());
+        if let Some(color) = &self.code_color {
+            attrs.text_color(color.clone());
+        }
+    }
+
+    fn add_attributes_for_tag(&self, tag: &Tag, mut attrs: AttributesAdder) {
+        match tag {
+            Tag::Heading(level) => {
+                let idx = (*level as usize).clamp(1, 6) - 1;
+                attrs
+                    .size(self.heading_sizes[idx].clone())
+                    .weight(FontWeight::BOLD);
+            }
+            Tag::BlockQuote => {
+                attrs
+                    .style(FontStyle::Italic)
+                    .text_color(self.blockquote_color.clone());
+            }
+            Tag::CodeBlock(_) => self.add_code_attributes(&mut attrs),
+            Tag::Emphasis => {
+                attrs.style(FontStyle::Italic);
+            }
+            Tag::Strong => {
+                attrs.weight(FontWeight::BOLD);
+            }
+            Tag::Strikethrough => {
+                attrs.strikethrough(true);
+            }
+            Tag::Link(_, dest, _) => {
+                attrs
+                    .underline(true)
+                    .text_color(self.link_color.clone())
+                    .link(self.link_selector.with(dest.to_string()));
+            }
+            _ => (),
+        }
+    }
+}
+
+impl RichText {
+    /// Create a new `RichText` by parsing the provided Markdown text.
+    ///
+    /// The following elements are supported, and are styled according to
+    /// the provided [`MarkdownStyle`]:
+    ///
+    /// - emphasis, strong emphasis and strikethrough
+    /// - inline code and code blocks
+    /// - headings
+    /// - block quotes
+    /// - links, which produce a [`Link`] carrying the link's destination.
+    ///
+    /// Other elements, such as lists, tables and images, are not styled; any
+    /// text they contain is included as-is. Raw HTML is included verbatim.
+    ///
+    /// When elements are nested, the attributes of the inner element take
+    /// precedence; for instance a link inside a block quote uses the link color.
+    ///
+    /// This method is only available with the `markdown` feature.
+    ///
+    /// [`Link`]: super::Link
+    pub fn from_markdown(text: &str, style: &MarkdownStyle) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        let mut current_pos = 0;
+        let mut tag_stack = Vec::new();
+        let mut tag_ranges: Vec<(usize, Range<usize>, Tag)> = Vec::new();
+        let mut needs_separator = false;
+
+        for event in Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH) {
+            match event {
+                Event::Start(tag) => {
+                    if needs_separator && is_block(&tag) {
+                        builder.push("\n\n");
+                        current_pos += 2;
+                        needs_separator = false;
+                    }
+                    tag_stack.push((current_pos, tag));
+                }
+                Event::End(_) => {
+                    let (start, tag) = tag_stack
+                        .pop()
+                        .expect("parser does not return unbalanced tags");
+                    needs_separator |= is_block(&tag);
+                    tag_ranges.push((tag_stack.len(), start..current_pos, tag));
+                }
+                Event::Text(txt) | Event::Html(txt) => {
+                    builder.push(&txt);
+                    current_pos += txt.len();
+                }
+                Event::Code(txt) => {
+                    style.add_code_attributes(&mut builder.push(&txt));
+                    current_pos += txt.len();
+                }
+                Event::SoftBreak => {
+                    builder.push(" ");
+                    current_pos += 1;
+                }
+                Event::HardBreak => {
+                    builder.push("\n");
+                    current_pos += 1;
+                }
+                _ => (),
+            }
+        }
+
+        // Attributes added later take precedence, so we add attributes for
+        // the outermost tags first.
+        tag_ranges.sort_by_key(|(depth, _, _)| *depth);
+        for (_, range, tag) in tag_ranges {
+            style.add_attributes_for_tag(&tag, builder.add_attributes_for_range(range));
+        }
+        builder.build()
+    }
+}
+
+fn is_block(tag: &Tag) -> bool {
+    !matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piet::TextStorage as _;
+    use crate::text::{Attribute, TextStorage};
+
+    const OPEN_LINK: Selector<String> = Selector::new("druid-test.markdown.open-link");
+
+    fn attrs_at(text: &RichText, idx: usize) -> Vec<Attribute> {
+        text.spans()
+            .filter(|(range, _)| range.contains(&idx))
+            .map(|(_, attr)| attr.clone())
+            .collect()
+    }
+
+    #[test]
+    fn nested_emphasis() {
+        let text = RichText::from_markdown("*a **b** c*", &MarkdownStyle::new(OPEN_LINK));
+        assert_eq!(text.as_str(), "a b c");
+
+        let outer = attrs_at(&text, 0);
+        assert_eq!(outer.len(), 1);
+        assert!(matches!(outer[0], Attribute::Style(FontStyle::Italic)));
+
+        let inner = attrs_at(&text, 2);
+        assert_eq!(inner.len(), 2);
+        assert!(inner
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Style(FontStyle::Italic))));
+        assert!(inner
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Weight(w) if *w == FontWeight::BOLD)));
+    }
+
+    #[test]
+    fn link_inside_bold() {
+        let text = RichText::from_markdown(
+            "**bold [link](https://linebender.org) text**",
+            &MarkdownStyle::new(OPEN_LINK),
+        );
+        assert_eq!(text.as_str(), "bold link text");
+
+        let links = text.links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].range(), 5..9);
+        assert_eq!(
+            links[0].command.get(OPEN_LINK).map(String::as_str),
+            Some("https://linebender.org")
+        );
+
+        let link_attrs = attrs_at(&text, 5);
+        assert!(link_attrs
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Weight(w) if *w == FontWeight::BOLD)));
+        assert!(link_attrs
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Underline(true))));
+    }
+
+    #[test]
+    fn link_color_inside_blockquote() {
+        let style = MarkdownStyle::new(OPEN_LINK)
+            .with_blockquote_color(Color::BLACK)
+            .with_link_color(Color::WHITE);
+        let text = RichText::from_markdown("> quote [link](dest)", &style);
+        assert_eq!(text.as_str(), "quote link");
+
+        let colors: Vec<_> = text
+            .spans()
+            .filter_map(|(range, attr)| match attr {
+                Attribute::TextColor(KeyOrValue::Concrete(color)) => Some((range, color.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(colors, vec![(0..6, Color::BLACK), (6..10, Color::WHITE)]);
+    }
+
+    #[test]
+    fn blocks_are_separated() {
+        let text = RichText::from_markdown("# Title\n\nBody", &MarkdownStyle::new(OPEN_LINK));
+        assert_eq!(text.as_str(), "Title\n\nBody");
+        assert!(attrs_at(&text, 7).is_empty());
+    }
+}
@@ -14,10 +14,15 @@
 
 //! Editing and displaying text.
 
+#[cfg(feature = "ansi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ansi")))]
+mod ansi;
+mod attachment;
 mod attribute;
 mod backspace;
 mod editable_text;
 mod font_descriptor;
+mod html;
 
 #[deprecated(since = "0.8.0", note = "use types from druid::text module instead")]
 #[doc(hidden)]
@@ -29,9 +34,17 @@ mod format_priv;
 mod input_component;
 mod input_methods;
 mod layout;
+#[cfg(feature = "markdown")]
+#[cfg_attr(docsrs, doc(cfg(feature = "markdown")))]
+mod markdown;
 mod movement;
+mod number_format;
 mod rich_text;
 mod storage;
+#[cfg(feature = "syntect")]
+#[cfg_attr(docsrs, doc(cfg(feature = "syntect")))]
+mod syntax;
+mod undo;
 
 pub use crate::piet::{FontFamily, FontStyle, FontWeight, TextAlignment};
 pub use druid_shell::text::{
@@ -39,16 +52,32 @@ pub use druid_shell::text::{
     Selection, VerticalMovement, WritingDirection,
 };
 
-pub use self::attribute::{Attribute, AttributeSpans, Link};
+pub use self::attachment::{AttachmentAlignment, InlineAttachment, OBJECT_REPLACEMENT_CHARACTER};
+#[cfg(feature = "serde")]
+pub use self::attribute::DESERIALIZED_LINK;
+pub use self::attribute::{
+    Attribute, AttributeKind, AttributeSpans, FeatureTag, FontVariant, InsertBehavior, Link,
+    LinkState, ParagraphStyle, ResolvedStyle,
+};
 pub use self::backspace::offset_for_delete_backwards;
 pub use self::editable_text::{EditableText, EditableTextCursor, StringCursor};
 pub use self::font_descriptor::FontDescriptor;
 pub use self::format_priv::{Formatter, ParseFormatter, Validation, ValidationError};
+pub use self::html::{rich_text_from_html, HtmlError, HTML_LINK};
 pub use self::layout::{LayoutMetrics, TextLayout};
+#[cfg(feature = "markdown")]
+pub use self::markdown::MarkdownStyle;
 pub use self::movement::movement;
-pub use input_component::{EditSession, TextComponent};
+pub use self::number_format::{NumberError, NumberFormatter};
+pub use input_component::{EditSession, TabBehavior, TextComponent};
 pub use input_methods::ImeHandlerRef;
-pub use rich_text::{AttributesAdder, RichText, RichTextBuilder};
+pub use rich_text::{
+    AttributesAdder, CharBoundaryError, ListMarker, RichText, RichTextBuilder, RichTextDelta,
+};
 pub use storage::{ArcStr, EnvUpdateCtx, TextStorage};
+#[cfg(feature = "syntect")]
+pub use syntax::SyntaxHighlightedText;
 
 pub(crate) use input_methods::TextFieldRegistration;
+pub(crate) use movement::word_range_for_pos;
+pub(crate) use undo::UndoStack;
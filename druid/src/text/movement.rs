@@ -34,10 +34,10 @@ use crate::text::{
 pub fn movement<T: EditableText + TextStorage>(
     m: Movement,
     s: Selection,
-    layout: &TextLayout<T>,
+    text_layout: &TextLayout<T>,
     modify: bool,
 ) -> Selection {
-    let (text, layout) = match (layout.text(), layout.layout()) {
+    let (text, layout) = match (text_layout.text(), text_layout.layout()) {
         (Some(text), Some(layout)) => (text, layout),
         _ => {
             debug_assert!(false, "movement() called before layout rebuild");
@@ -45,7 +45,13 @@ pub fn movement<T: EditableText + TextStorage>(
         }
     };
 
-    let writing_direction = if crate::piet::util::first_strong_rtl(text.as_str()) {
+    // offsets into the piet layout differ from offsets into the text if the
+    // text is masked.
+    let to_layout = |pos| text_layout.layout_offset(pos);
+    let to_text = |pos| text_layout.text_offset(pos);
+    let masked = text_layout.mask().is_some();
+
+    let writing_direction = if text_layout.text_is_rtl() {
         WritingDirection::RightToLeft
     } else {
         WritingDirection::LeftToRight
@@ -71,7 +77,7 @@ pub fn movement<T: EditableText + TextStorage>(
             }
         }
         Movement::Vertical(VerticalMovement::LineUp) => {
-            let cur_pos = layout.hit_test_text_position(s.active);
+            let cur_pos = layout.hit_test_text_position(to_layout(s.active));
             let h_pos = s.h_pos.unwrap_or(cur_pos.point.x);
             if cur_pos.line == 0 {
                 (0, Some(h_pos))
@@ -80,7 +86,7 @@ pub fn movement<T: EditableText + TextStorage>(
                 let point_above = Point::new(h_pos, cur_pos.point.y - lm.height);
                 let up_pos = layout.hit_test_point(point_above);
                 if up_pos.is_inside {
-                    (up_pos.idx, Some(h_pos))
+                    (to_text(up_pos.idx), Some(h_pos))
                 } else {
                     // because we can't specify affinity, moving up when h_pos
                     // is wider than both the current line and the previous line
@@ -88,12 +94,12 @@ pub fn movement<T: EditableText + TextStorage>(
                     // current line; so we handle this as a special-case.
                     let lm_prev = layout.line_metric(cur_pos.line.saturating_sub(1)).unwrap();
                     let up_pos = lm_prev.end_offset - lm_prev.trailing_whitespace;
-                    (up_pos, Some(h_pos))
+                    (to_text(up_pos), Some(h_pos))
                 }
             }
         }
         Movement::Vertical(VerticalMovement::LineDown) => {
-            let cur_pos = layout.hit_test_text_position(s.active);
+            let cur_pos = layout.hit_test_text_position(to_layout(s.active));
             let h_pos = s.h_pos.unwrap_or(cur_pos.point.x);
             if cur_pos.line == layout.line_count() - 1 {
                 (text.len(), Some(h_pos))
@@ -103,7 +109,7 @@ pub fn movement<T: EditableText + TextStorage>(
                 let y_below = lm.y_offset + lm.height + 1.0;
                 let point_below = Point::new(h_pos, y_below);
                 let up_pos = layout.hit_test_point(point_below);
-                (up_pos.idx, Some(point_below.x))
+                (to_text(up_pos.idx), Some(point_below.x))
             }
         }
         Movement::Vertical(VerticalMovement::DocumentStart) => (0, None),
@@ -113,15 +119,19 @@ pub fn movement<T: EditableText + TextStorage>(
         Movement::ParagraphEnd => (text.next_line_break(s.active), None),
 
         Movement::Line(d) => {
-            let hit = layout.hit_test_text_position(s.active);
+            let hit = layout.hit_test_text_position(to_layout(s.active));
             let lm = layout.line_metric(hit.line).unwrap();
             let offset = if d.is_upstream_for_direction(writing_direction) {
                 lm.start_offset
             } else {
                 lm.end_offset - lm.trailing_whitespace
             };
-            (offset, None)
+            (to_text(offset), None)
         }
+        // Word boundaries in masked text are not revealed; moving by word
+        // moves to the start or end of the text.
+        Movement::Word(d) if masked && d.is_upstream_for_direction(writing_direction) => (0, None),
+        Movement::Word(_) if masked => (text.len(), None),
         Movement::Word(d) if d.is_upstream_for_direction(writing_direction) => {
             let offset = if s.is_caret() || modify {
                 text.prev_word_offset(s.active).unwrap_or(0)
@@ -161,14 +171,37 @@ pub fn movement<T: EditableText + TextStorage>(
 /// If the position is on a word boundary, that will be considered the start
 /// of the range.
 ///
-/// This uses Unicode word boundaries, as defined in [UAX#29].
+/// This uses Unicode word boundaries, as defined in [UAX#29], except that a
+/// run of punctuation is treated as a single word. A position at the end of
+/// the text is considered to be in the last word.
 ///
 /// [UAX#29]: http://www.unicode.org/reports/tr29/
 pub(crate) fn word_range_for_pos(text: &str, pos: usize) -> Range<usize> {
-    text.split_word_bound_indices()
+    let words: Vec<_> = text
+        .split_word_bound_indices()
         .map(|(ix, word)| ix..(ix + word.len()))
-        .find(|range| range.contains(&pos))
-        .unwrap_or(pos..pos)
+        .collect();
+    let idx = match words.iter().position(|range| range.contains(&pos)) {
+        Some(idx) => idx,
+        None if pos == text.len() && !words.is_empty() => words.len() - 1,
+        None => return pos..pos,
+    };
+
+    let is_punctuation = |range: &Range<usize>| {
+        text[range.clone()]
+            .chars()
+            .all(|c| !c.is_alphanumeric() && !c.is_whitespace())
+    };
+    let mut range = words[idx].clone();
+    if is_punctuation(&range) {
+        for word in words[..idx].iter().rev().take_while(|r| is_punctuation(r)) {
+            range.start = word.start;
+        }
+        for word in words[idx + 1..].iter().take_while(|r| is_punctuation(r)) {
+            range.end = word.end;
+        }
+    }
+    range
 }
 
 #[cfg(test)]
@@ -186,6 +219,16 @@ mod tests {
         assert_eq!(word_range_for_pos("hello world", 5), 5..6);
     }
 
+    #[test]
+    fn word_range_punctuation() {
+        let text = "wait... what?!";
+        assert_eq!(word_range_for_pos(text, 5), 4..7);
+        assert_eq!(word_range_for_pos(text, 12), 12..14);
+        assert_eq!(word_range_for_pos(text, 9), 8..12);
+        assert_eq!(word_range_for_pos(text, text.len()), 12..14);
+        assert_eq!(word_range_for_pos("", 0), 0..0);
+    }
+
     #[test]
     fn word_range_rtl() {
         let rtl = "مرحبا بالعالم";
@@ -0,0 +1,402 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A [`Formatter`] for numbers, with grouping and decimal separators.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use unic_langid::LanguageIdentifier;
+
+use super::{Formatter, Selection, Validation, ValidationError};
+
+const NO_BREAK_SPACE: char = '\u{A0}';
+const NARROW_NO_BREAK_SPACE: char = '\u{202F}';
+const RIGHT_SINGLE_QUOTE: char = '\u{2019}';
+
+/// A [`Formatter`] for `f64` and `i64` values, written with the grouping
+/// ("thousands") and decimal separators of a locale.
+///
+/// While the user is typing, input is validated permissively: separators
+/// may appear anywhere in the integer part, so `1,` and `12,34` are
+/// accepted, and the text is written with the separators in the right
+/// places once editing finishes. Characters that cannot be part of a
+/// number, a second decimal separator, more decimal places than the
+/// [`precision`], and numbers that can only move further outside the
+/// [`range`] as more digits are typed are rejected.
+///
+/// Errors are reported as a [`NumberError`], whose message is suitable for
+/// showing to the user.
+///
+/// # Examples
+///
+/// ```
+/// use druid::text::{Formatter, NumberFormatter};
+///
+/// let formatter = NumberFormatter::for_locale("de-DE").with_precision(2);
+/// assert_eq!(formatter.format(&1234.5), "1.234,50");
+/// let value: f64 = formatter.value("1.234,5").unwrap();
+/// assert_eq!(value, 1234.5);
+/// ```
+///
+/// [`precision`]: NumberFormatter::with_precision
+/// [`range`]: NumberFormatter::with_range
+#[derive(Debug, Clone)]
+pub struct NumberFormatter {
+    group_separator: Option<char>,
+    decimal_separator: char,
+    precision: Option<usize>,
+    min: f64,
+    max: f64,
+}
+
+/// The reason a [`NumberFormatter`] rejected some input.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum NumberError {
+    /// There is no number in the input.
+    Empty,
+    /// The input contains a character that is not part of a number.
+    InvalidCharacter(char),
+    /// The number has more decimal places than the formatter allows.
+    TooManyDecimals(usize),
+    /// The number is outside of the formatter's range.
+    OutOfRange {
+        /// The smallest allowed value.
+        min: f64,
+        /// The largest allowed value.
+        max: f64,
+    },
+}
+
+impl NumberFormatter {
+    /// Create a new `NumberFormatter`, with `,` grouping digits and `.` as
+    /// the decimal separator.
+    pub fn new() -> Self {
+        NumberFormatter {
+            group_separator: Some(','),
+            decimal_separator: '.',
+            precision: None,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+
+    /// Create a new `NumberFormatter` using the separators of `locale`, a
+    /// language identifier such as `"fr-FR"`.
+    ///
+    /// Unknown and invalid locales use the same separators as [`new`].
+    ///
+    /// [`new`]: NumberFormatter::new
+    pub fn for_locale(locale: &str) -> Self {
+        let (group, decimal) = locale
+            .parse::<LanguageIdentifier>()
+            .map(|locale| separators_for_locale(&locale))
+            .unwrap_or((',', '.'));
+        NumberFormatter::new().with_separators(Some(group), decimal)
+    }
+
+    /// Builder-style method to set the separator between groups of three
+    /// digits, or `None` for no grouping, and the decimal separator.
+    ///
+    /// If the grouping separator is a space, any kind of space is accepted
+    /// as a separator in the input.
+    pub fn with_separators(mut self, group: Option<char>, decimal: char) -> Self {
+        self.group_separator = group;
+        self.decimal_separator = decimal;
+        self
+    }
+
+    /// Builder-style method to set the number of decimal places.
+    ///
+    /// Values are formatted with exactly this many decimal places, and input
+    /// with more is rejected. By default, values are formatted with as many
+    /// decimal places as they need.
+    pub fn with_precision(mut self, decimal_places: usize) -> Self {
+        self.precision = Some(decimal_places);
+        self
+    }
+
+    /// Builder-style method to set the range of allowed values.
+    pub fn with_range(mut self, range: RangeInclusive<f64>) -> Self {
+        self.min = *range.start();
+        self.max = *range.end();
+        self
+    }
+
+    fn is_group_separator(&self, c: char) -> bool {
+        match self.group_separator {
+            Some(sep) if sep.is_whitespace() => c.is_whitespace(),
+            Some(RIGHT_SINGLE_QUOTE) => c == RIGHT_SINGLE_QUOTE || c == '\'',
+            Some(sep) => c == sep,
+            None => false,
+        }
+    }
+
+    /// Write the digits of an integer with grouping separators, and the
+    /// decimal part, if any, after the decimal separator.
+    fn write_number(&self, plain: &str) -> String {
+        let (sign, plain) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain),
+        };
+        let (int, frac) = match plain.find('.') {
+            Some(idx) => (&plain[..idx], Some(&plain[idx + 1..])),
+            None => (plain, None),
+        };
+
+        let mut result = String::from(sign);
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                if let Some(sep) = self.group_separator {
+                    result.push(sep);
+                }
+            }
+            result.push(c);
+        }
+        if let Some(frac) = frac {
+            result.push(self.decimal_separator);
+            result.push_str(frac);
+        }
+        result
+    }
+
+    /// Check that `input` could be the start of a number, returning it in
+    /// the format understood by [`str::parse`].
+    fn check_partial(&self, input: &str, integer: bool) -> Result<String, NumberError> {
+        let input = input.trim();
+        let (negative, digits) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        if negative && self.min >= 0.0 {
+            return Err(self.out_of_range());
+        }
+
+        let mut plain = String::from(if negative { "-" } else { "" });
+        let mut decimals = None;
+        for c in digits.chars() {
+            if c.is_ascii_digit() {
+                plain.push(c);
+                decimals = decimals.map(|n| n + 1);
+            } else if c == self.decimal_separator && decimals.is_none() {
+                if integer || self.precision == Some(0) {
+                    return Err(NumberError::TooManyDecimals(0));
+                }
+                plain.push('.');
+                decimals = Some(0);
+            } else if self.is_group_separator(c) && decimals.is_none() {
+                continue;
+            } else {
+                return Err(NumberError::InvalidCharacter(c));
+            }
+        }
+
+        match (decimals, self.precision) {
+            (Some(n), Some(max)) if n > max => Err(NumberError::TooManyDecimals(max)),
+            _ => Ok(plain),
+        }
+    }
+
+    /// Parse and check a complete number.
+    fn parse(&self, input: &str, integer: bool) -> Result<f64, NumberError> {
+        let plain = self.check_partial(input, integer)?;
+        if !plain.chars().any(|c| c.is_ascii_digit()) {
+            return Err(NumberError::Empty);
+        }
+        let value = plain.parse::<f64>().map_err(|_| NumberError::Empty)?;
+        if value < self.min || value > self.max {
+            return Err(self.out_of_range());
+        }
+        Ok(value)
+    }
+
+    fn validate(&self, input: &str, integer: bool) -> Validation {
+        let plain = match self.check_partial(input, integer) {
+            Ok(plain) => plain,
+            Err(err) => return Validation::failure(err),
+        };
+        // typing more digits only moves a number further from zero.
+        match plain.parse::<f64>() {
+            Ok(value) if (value > 0.0 && value > self.max) || (value < 0.0 && value < self.min) => {
+                Validation::failure(self.out_of_range())
+            }
+            _ => Validation::success(),
+        }
+    }
+
+    fn out_of_range(&self) -> NumberError {
+        NumberError::OutOfRange {
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+impl Formatter<f64> for NumberFormatter {
+    fn format(&self, value: &f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        match self.precision {
+            Some(precision) => self.write_number(&format!("{:.*}", precision, value)),
+            None => self.write_number(&value.to_string()),
+        }
+    }
+
+    fn validate_partial_input(&self, input: &str, _sel: &Selection) -> Validation {
+        self.validate(input, false)
+    }
+
+    fn value(&self, input: &str) -> Result<f64, ValidationError> {
+        self.parse(input, false).map_err(ValidationError::new)
+    }
+}
+
+impl Formatter<i64> for NumberFormatter {
+    fn format(&self, value: &i64) -> String {
+        self.write_number(&value.to_string())
+    }
+
+    fn validate_partial_input(&self, input: &str, _sel: &Selection) -> Validation {
+        self.validate(input, true)
+    }
+
+    fn value(&self, input: &str) -> Result<i64, ValidationError> {
+        self.parse(input, true).map_err(ValidationError::new)?;
+        // parse again as an integer, as an f64 cannot represent every i64
+        self.check_partial(input, true)
+            .ok()
+            .and_then(|plain| plain.parse().ok())
+            .ok_or_else(|| ValidationError::new(self.out_of_range()))
+    }
+}
+
+impl Default for NumberFormatter {
+    fn default() -> Self {
+        NumberFormatter::new()
+    }
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NumberError::Empty => write!(f, "Enter a number"),
+            NumberError::InvalidCharacter(c) => write!(f, "'{}' is not part of a number", c),
+            NumberError::TooManyDecimals(0) => write!(f, "Enter a whole number"),
+            NumberError::TooManyDecimals(1) => write!(f, "Enter at most 1 decimal place"),
+            NumberError::TooManyDecimals(n) => write!(f, "Enter at most {} decimal places", n),
+            NumberError::OutOfRange { min, max } if min.is_finite() && max.is_finite() => {
+                write!(f, "Enter a number from {} to {}", min, max)
+            }
+            NumberError::OutOfRange { min, .. } if min.is_finite() => {
+                write!(f, "Enter a number of at least {}", min)
+            }
+            NumberError::OutOfRange { max, .. } => write!(f, "Enter a number of at most {}", max),
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+/// Languages that group digits with `.` and use `,` as the decimal separator.
+const DOT_GROUPING: &[&str] = &[
+    "da", "de", "el", "es", "hr", "id", "is", "it", "nl", "pt", "ro", "sl", "sr", "tr", "vi",
+];
+
+/// Languages that group digits with a space and use `,` as the decimal separator.
+const SPACE_GROUPING: &[&str] = &[
+    "bg", "cs", "et", "fi", "hu", "lt", "lv", "nb", "nn", "no", "pl", "ru", "sk", "sv", "uk",
+];
+
+/// Returns the grouping and decimal separators used in `locale`.
+fn separators_for_locale(locale: &LanguageIdentifier) -> (char, char) {
+    let language = locale.language.as_str();
+    let region = locale.region.as_ref().map(|region| region.as_str());
+    match (language, region) {
+        ("de", Some("CH")) | ("de", Some("LI")) | ("it", Some("CH")) => (RIGHT_SINGLE_QUOTE, '.'),
+        ("fr", _) => (NARROW_NO_BREAK_SPACE, ','),
+        _ if DOT_GROUPING.contains(&language) => ('.', ','),
+        _ if SPACE_GROUPING.contains(&language) => (NO_BREAK_SPACE, ','),
+        _ => (',', '.'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepts(formatter: &NumberFormatter, input: &str) -> bool {
+        let validation = Formatter::<f64>::validate_partial_input(
+            formatter,
+            input,
+            &Selection::caret(input.len()),
+        );
+        !validation.is_err()
+    }
+
+    #[test]
+    fn format_locales() {
+        let en = NumberFormatter::for_locale("en-US");
+        assert_eq!(en.format(&1234567.25), "1,234,567.25");
+        assert_eq!(en.format(&-123.0), "-123");
+        assert_eq!(en.format(&-123456i64), "-123,456");
+
+        let de = NumberFormatter::for_locale("de").with_precision(2);
+        assert_eq!(de.format(&1234.5), "1.234,50");
+        let ch = NumberFormatter::for_locale("de-CH");
+        assert_eq!(ch.format(&1234.5), "1\u{2019}234.5");
+        let fr = NumberFormatter::for_locale("fr-FR");
+        assert_eq!(fr.format(&1234.5), "1\u{202F}234,5");
+        let unknown = NumberFormatter::for_locale("not a locale");
+        assert_eq!(unknown.format(&1234.5), "1,234.5");
+    }
+
+    #[test]
+    fn partial_input() {
+        let en = NumberFormatter::new().with_precision(2);
+        for input in &["", "-", "1,", "12,34", "1,234.", "1234.5", "-0.05"] {
+            assert!(accepts(&en, input), "rejected {:?}", input);
+        }
+        for input in &["1.2.3", "1.234", "12a", "1e5", "1.2,3"] {
+            assert!(!accepts(&en, input), "accepted {:?}", input);
+        }
+
+        // spaces are accepted for any space separator.
+        let fr = NumberFormatter::for_locale("fr");
+        assert_eq!(Formatter::<f64>::value(&fr, "1 234,5").ok(), Some(1234.5));
+        assert_eq!(
+            Formatter::<f64>::value(&fr, "1\u{A0}234").ok(),
+            Some(1234.0)
+        );
+    }
+
+    #[test]
+    fn range_and_integers() {
+        let formatter = NumberFormatter::new().with_range(0.0..=100.0);
+        assert!(accepts(&formatter, "10"));
+        assert!(!accepts(&formatter, "101"));
+        assert!(!accepts(&formatter, "-1"));
+
+        let err = Formatter::<f64>::value(&formatter, "150").unwrap_err();
+        assert_eq!(err.to_string(), "Enter a number from 0 to 100");
+        assert!(Formatter::<f64>::value(&formatter, "").is_err());
+
+        assert_eq!(Formatter::<i64>::value(&formatter, "1,0").ok(), Some(10));
+        assert!(Formatter::<i64>::value(&formatter, "1.5").is_err());
+        let big = NumberFormatter::new();
+        let value = Formatter::<i64>::value(&big, "9,007,199,254,740,993").ok();
+        assert_eq!(value, Some(9_007_199_254_740_993));
+    }
+}
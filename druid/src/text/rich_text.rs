@@ -14,16 +14,37 @@
 
 //! Rich text with style spans.
 
-use std::ops::{Range, RangeBounds};
+use std::borrow::Cow;
+use std::ops::{Add, Range, RangeBounds};
 use std::sync::Arc;
 
-use super::attribute::Link;
-use super::{Attribute, AttributeSpans, EnvUpdateCtx, TextStorage};
+use caseless::Caseless;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::attachment;
+use super::attribute::{Link, LinkState};
+use super::editable_text;
+use super::{
+    Attribute, AttributeKind, AttributeSpans, EditableText, EnvUpdateCtx, FeatureTag, FontVariant,
+    InlineAttachment, InsertBehavior, ParagraphStyle, ResolvedStyle, StringCursor, TextStorage,
+    WritingDirection, OBJECT_REPLACEMENT_CHARACTER,
+};
 use crate::piet::{
-    util, Color, FontFamily, FontStyle, FontWeight, PietTextLayoutBuilder, TextLayoutBuilder,
-    TextStorage as PietTextStorage,
+    util, Color, FontFamily, FontStyle, FontWeight, PietTextLayoutBuilder, TextAlignment,
+    TextLayoutBuilder, TextStorage as PietTextStorage,
 };
-use crate::{ArcStr, Command, Data, Env, FontDescriptor, KeyOrValue};
+use crate::{ArcStr, Command, Data, Env, EventCtx, FontDescriptor, KeyOrValue};
+
+/// The baseline shift of superscript text, as a multiple of the font size.
+const SUPERSCRIPT_SHIFT: f64 = 0.33;
+/// The baseline shift of subscript text, as a multiple of the font size.
+const SUBSCRIPT_SHIFT: f64 = -0.2;
+/// The factor by which the font size of superscripts and subscripts is scaled.
+const SCRIPT_SIZE_SCALE: f64 = 0.7;
+/// The indent of each level of a list, in points.
+const LIST_INDENT: f64 = 24.0;
+/// The bullets used by each level of a bulleted list.
+const BULLETS: [&str; 3] = ["\u{2022}", "\u{25E6}", "\u{25AA}"];
 
 /// Text with optional style spans.
 #[derive(Clone, Debug, Data)]
@@ -31,6 +52,38 @@ pub struct RichText {
     buffer: ArcStr,
     attrs: Arc<AttributeSpans>,
     links: Arc<[Link]>,
+    highlights: Option<Arc<Highlights>>,
+    #[data(eq)]
+    direction: WritingDirection,
+}
+
+/// The changes that turn one [`RichText`] into another.
+///
+/// A delta is computed with [`RichText::diff`], and applied with
+/// [`RichText::apply`]. It holds only the text that was replaced and the
+/// attributes that changed, which makes it suitable for an undo stack.
+#[derive(Debug, Clone, Default)]
+pub struct RichTextDelta {
+    /// The byte range of the old text that is replaced.
+    range: Range<usize>,
+    /// The text that replaces `range`.
+    text: String,
+    /// The range of the new text in which attributes changed, and the
+    /// attributes that lie entirely within it.
+    attrs: Option<(Range<usize>, Vec<(Range<usize>, Attribute)>)>,
+    /// The new links, if they may have changed.
+    links: Option<Arc<[Link]>>,
+    /// The new base direction, if it changed.
+    direction: Option<WritingDirection>,
+}
+
+/// The matches highlighted by [`RichText::highlight_matches`].
+#[derive(Debug)]
+struct Highlights {
+    /// The attributes added to highlight the matches, and their ranges.
+    spans: Vec<(Range<usize>, Attribute)>,
+    /// The attributes that the highlights were added over.
+    covered: AttributeSpans,
 }
 
 impl RichText {
@@ -45,6 +98,8 @@ impl RichText {
             buffer,
             attrs: Arc::new(attributes),
             links: Arc::new([]),
+            highlights: None,
+            direction: WritingDirection::Natural,
         }
     }
 
@@ -68,183 +123,2642 @@ impl RichText {
 
     /// Add an [`Attribute`] to the provided range of text.
     ///
+    /// # Panics
+    ///
+    /// Panics if the start or end of the range does not lie on a `char`
+    /// boundary. Use [`try_add_attribute`] if the range comes from an
+    /// untrusted source.
+    ///
     /// [`Attribute`]: enum.Attribute.html
+    /// [`try_add_attribute`]: RichText::try_add_attribute
     pub fn add_attribute(&mut self, range: impl RangeBounds<usize>, attr: Attribute) {
+        if let Err(err) = self.try_add_attribute(range, attr) {
+            panic!("invalid attribute range: {}", err);
+        }
+    }
+
+    /// Add an [`Attribute`] to the provided range of text, or return an error
+    /// if the start or end of the range does not lie on a `char` boundary.
+    ///
+    /// This is the non-panicking version of [`add_attribute`].
+    ///
+    /// [`Attribute`]: enum.Attribute.html
+    /// [`add_attribute`]: RichText::add_attribute
+    pub fn try_add_attribute(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        attr: Attribute,
+    ) -> Result<(), CharBoundaryError> {
         let range = util::resolve_range(range, self.buffer.len());
+        check_char_boundaries(&self.buffer, &range)?;
         Arc::make_mut(&mut self.attrs).add(range, attr);
+        Ok(())
     }
-}
 
-impl PietTextStorage for RichText {
-    fn as_str(&self) -> &str {
-        self.buffer.as_str()
+    /// Append another `RichText` to the end of this one.
+    ///
+    /// The attributes and links of `other` are preserved, and their ranges
+    /// are shifted to refer to the appended text.
+    pub fn append(&mut self, other: &RichText) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other.clone();
+            return;
+        }
+
+        let offset = self.buffer.len();
+        let mut buffer = String::with_capacity(offset + other.buffer.len());
+        buffer.push_str(&self.buffer);
+        buffer.push_str(&other.buffer);
+        self.buffer = buffer.into();
+
+        Arc::make_mut(&mut self.attrs).append(&other.attrs, offset);
+        if !other.links.is_empty() {
+            self.links = self
+                .links
+                .iter()
+                .cloned()
+                .chain(other.links.iter().map(|link| link.shifted(offset)))
+                .collect::<Vec<_>>()
+                .into();
+        }
     }
-}
 
-impl TextStorage for RichText {
-    fn add_attributes(
-        &self,
-        mut builder: PietTextLayoutBuilder,
-        env: &Env,
-    ) -> PietTextLayoutBuilder {
-        for (range, attr) in self.attrs.to_piet_attrs(env) {
-            builder = builder.range_attribute(range, attr);
+    /// Compute the changes that turn this text into `other`.
+    ///
+    /// The delta replaces the shortest range of this text that differs from
+    /// `other`, in the same way as [`EditableText::edit`], and records the
+    /// attributes of `other` in the smallest range in which they differ from
+    /// those of this text after that replacement.
+    /// If the two texts share their storage, as when one is an unmodified
+    /// clone of the other, this is very cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use druid::piet::TextStorage as _;
+    /// # use druid::text::RichText;
+    /// let old = RichText::new("Hello world".into());
+    /// let new = RichText::new("Hello, world".into());
+    /// let delta = old.diff(&new);
+    /// assert_eq!(delta.range(), 5..5);
+    /// assert_eq!(delta.text(), ",");
+    ///
+    /// let mut text = old.clone();
+    /// text.apply(&delta);
+    /// assert_eq!(text.as_str(), "Hello, world");
+    /// ```
+    pub fn diff(&self, other: &RichText) -> RichTextDelta {
+        if self.same(other) {
+            return RichTextDelta::default();
+        }
+
+        let (range, text) = if self.buffer.same(&other.buffer) {
+            (0..0, String::new())
+        } else {
+            let (old, new) = (self.buffer.as_bytes(), other.buffer.as_bytes());
+            let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+            while !self.buffer.is_char_boundary(prefix) {
+                prefix -= 1;
+            }
+            let mut suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            while !self.buffer.is_char_boundary(old.len() - suffix) {
+                suffix -= 1;
+            }
+            let text = other.buffer[prefix..new.len() - suffix].to_string();
+            (prefix..old.len() - suffix, text)
+        };
+        let unchanged = range.is_empty() && text.is_empty();
+
+        let attrs = if unchanged && Arc::ptr_eq(&self.attrs, &other.attrs) {
+            None
+        } else {
+            let mut edited = (*self.attrs).clone();
+            edited.delete(range.clone());
+            edited.insert(range.start, text.len(), InsertBehavior::ExtendPreceding);
+            let old: Vec<_> = edited.iter().collect();
+            let new: Vec<_> = other.attrs.iter().collect();
+            let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let old = &old[prefix..old.len() - suffix];
+            let new = &new[prefix..new.len() - suffix];
+
+            // every span that is in only one of the two lies in `changed`.
+            let changed = old
+                .iter()
+                .filter(|span| !new.contains(span))
+                .chain(new.iter().filter(|span| !old.contains(span)))
+                .map(|(range, _)| range.clone())
+                .fold(None, |changed: Option<Range<usize>>, range| {
+                    Some(match changed {
+                        Some(changed) => changed.start.min(range.start)..changed.end.max(range.end),
+                        None => range,
+                    })
+                });
+            changed.map(|changed| {
+                let spans = other
+                    .attrs
+                    .iter()
+                    .filter(|(range, _)| changed.start <= range.start && range.end <= changed.end)
+                    .map(|(range, attr)| (range, attr.clone()))
+                    .collect();
+                (changed, spans)
+            })
+        };
+
+        let links = if (unchanged && Arc::ptr_eq(&self.links, &other.links))
+            || (self.links.is_empty() && other.links.is_empty())
+        {
+            None
+        } else {
+            Some(other.links.clone())
+        };
+
+        let direction = Some(other.direction).filter(|dir| *dir != self.direction);
+
+        RichTextDelta {
+            range,
+            text,
+            attrs,
+            links,
+            direction,
         }
-        builder
     }
 
-    fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
-        self.attrs.env_update(ctx)
+    /// Apply a [`RichTextDelta`] computed by [`diff`].
+    ///
+    /// If this text is the one the delta was computed from, it becomes equal
+    /// to the other text.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range replaced by the delta is not valid for this text.
+    ///
+    /// [`diff`]: RichText::diff
+    pub fn apply(&mut self, delta: &RichTextDelta) {
+        EditableText::edit(self, delta.range.clone(), delta.text.as_str());
+        if let Some((changed, spans)) = &delta.attrs {
+            let attrs = Arc::make_mut(&mut self.attrs);
+            attrs.remove_within(changed.clone());
+            for (range, attr) in spans {
+                attrs.add(range.clone(), attr.clone());
+            }
+        }
+        if let Some(links) = &delta.links {
+            self.links = links.clone();
+        }
+        if let Some(direction) = delta.direction {
+            self.direction = direction;
+        }
     }
 
-    fn links(&self) -> &[Link] {
-        &self.links
+    /// Builder-style method to set the base [`WritingDirection`] of the text.
+    ///
+    /// The base direction decides the side that text with
+    /// [`TextAlignment::Start`] is aligned to, the side that paragraph
+    /// indents are on, and which way the cursor moves. The default is
+    /// [`WritingDirection::Natural`], which uses the direction of the first
+    /// character with a strong direction, such as a Latin, Hebrew or Arabic
+    /// letter.
+    ///
+    /// Attribute ranges are always byte ranges of the text; the order of the
+    /// runs of left-to-right and right-to-left text within each line is
+    /// decided by the platform's text layout.
+    pub fn with_direction(mut self, direction: WritingDirection) -> Self {
+        self.direction = direction;
+        self
     }
-}
 
-/// A builder for creating [`RichText`] objects.
-///
-/// This builder allows you to construct a [`RichText`] object by building up a sequence
-/// of styled sub-strings; first you [`push`](RichTextBuilder::push) a `&str` onto the string,
-/// and then you can optionally add styles to that text via the returned [`AttributesAdder`]
-/// object.
-///
-/// # Example
-/// ```
-/// # use druid::text::{Attribute, RichTextBuilder};
-/// # use druid::{FontWeight, Color};
-/// let mut builder = RichTextBuilder::new();
-/// builder.push("Hello ");
-/// builder.push("World!").weight(FontWeight::BOLD);
-///
-/// // Can also use write!
-/// write!(builder, "Here is your number: {}", 1).underline(true).text_color(Color::RED);
-///
-/// let rich_text = builder.build();
-/// ```
-///
-/// [`RichText`]: RichText
-#[derive(Default)]
-pub struct RichTextBuilder {
-    buffer: String,
-    attrs: AttributeSpans,
-    links: Vec<Link>,
-}
+    /// The base [`WritingDirection`] of the text.
+    ///
+    /// See [`with_direction`] for more information.
+    ///
+    /// [`with_direction`]: RichText::with_direction
+    pub fn direction(&self) -> WritingDirection {
+        self.direction
+    }
 
-impl RichTextBuilder {
-    /// Create a new `RichTextBuilder`.
-    pub fn new() -> Self {
-        Self::default()
+    /// Returns a new `RichText` containing this text followed by `other`.
+    ///
+    /// This is equivalent to cloning this text and calling [`append`].
+    /// Concatenation is also available via the `+` operator.
+    ///
+    /// [`append`]: RichText::append
+    pub fn concat(&self, other: &RichText) -> RichText {
+        let mut text = self.clone();
+        text.append(other);
+        text
     }
 
-    /// Append a `&str` to the end of the text.
+    /// Returns an iterator over the [`Attribute`]s of this text, along with
+    /// the ranges they apply to.
     ///
-    /// This method returns a [`AttributesAdder`] that can be used to style the newly
-    /// added string slice.
-    pub fn push(&mut self, string: &str) -> AttributesAdder {
-        let range = self.buffer.len()..(self.buffer.len() + string.len());
-        self.buffer.push_str(string);
-        self.add_attributes_for_range(range)
+    /// Items are yielded in order of ascending range start. Links are not
+    /// included; they are available via [`TextStorage::links`].
+    pub fn spans(&self) -> impl Iterator<Item = (Range<usize>, &Attribute)> {
+        self.attrs.iter()
     }
 
-    /// Glue for usage of the write! macro.
+    /// Returns a new `RichText` containing the text in the provided range.
     ///
-    /// This method should generally not be invoked manually, but rather through the write! macro itself.
-    #[doc(hidden)]
-    pub fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> AttributesAdder {
-        use std::fmt::Write;
-        let start = self.buffer.len();
-        self.buffer
-            .write_fmt(fmt)
-            .expect("a formatting trait implementation returned an error");
-        self.add_attributes_for_range(start..self.buffer.len())
+    /// Attributes and links are clipped to the range, and are shifted so that
+    /// they are relative to its start. Links that do not intersect the range
+    /// are dropped.
+    ///
+    /// See [`get`] for a non-panicking version of this method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start or end of the range do not lie on a `char` boundary.
+    ///
+    /// [`get`]: RichText::get
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> RichText {
+        let range = util::resolve_range(range, self.buffer.len());
+        self.get(range.clone())
+            .unwrap_or_else(|| panic!("range {:?} does not lie on char boundaries", range))
     }
 
-    /// Get an [`AttributesAdder`] for the given range.
+    /// Returns a new `RichText` containing the text in the provided range,
+    /// or `None` if the range does not lie on `char` boundaries.
     ///
-    /// This can be used to modify styles for a given range after it has been added.
-    pub fn add_attributes_for_range(&mut self, range: impl RangeBounds<usize>) -> AttributesAdder {
+    /// This is the non-panicking version of [`slice`].
+    ///
+    /// [`slice`]: RichText::slice
+    pub fn get(&self, range: impl RangeBounds<usize>) -> Option<RichText> {
         let range = util::resolve_range(range, self.buffer.len());
-        AttributesAdder {
-            rich_text_builder: self,
-            range,
-        }
+        let buffer = self.buffer.get(range.clone())?;
+
+        Some(RichText {
+            buffer: buffer.into(),
+            attrs: Arc::new(self.attrs.slice(range.clone())),
+            links: self
+                .links
+                .iter()
+                .filter_map(|link| link.clipped(&range))
+                .collect::<Vec<_>>()
+                .into(),
+            highlights: None,
+            direction: self.direction,
+        })
     }
 
-    /// Build the `RichText`.
-    pub fn build(self) -> RichText {
+    /// Replace the text in `range` with `replacement`, preserving the styles
+    /// of the surrounding text.
+    ///
+    /// Attributes and links that lie entirely within `range` are removed,
+    /// and those that start or end within it are clipped; those after it
+    /// are moved to account for the change in length. The replacement text
+    /// has no attributes of its own, except where it is inside an attribute
+    /// that contains all of `range`, which is split around it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start or end of `range` does not lie on a `char` boundary.
+    pub fn replace_range(&mut self, range: impl RangeBounds<usize>, replacement: &str) {
+        let range = util::resolve_range(range, self.buffer.len());
+        self.clear_highlights();
+        let mut buffer = self.buffer.to_string();
+        buffer.replace_range(range.clone(), replacement);
+        self.buffer = buffer.into();
+
+        Arc::make_mut(&mut self.attrs).edit(range.clone(), replacement.len());
+        self.links = self
+            .links
+            .iter()
+            .flat_map(|link| link.edited(&range, replacement.len()))
+            .collect();
+    }
+
+    /// Replace the text with `text`, moving every attribute and link with
+    /// `f`, which maps offsets in the current text to offsets in `text`.
+    ///
+    /// This is useful for changes to the whole text, such as expanding tabs,
+    /// that would otherwise take many calls to [`replace_range`]. `f` is
+    /// called with the start and end of each attribute and link, and must
+    /// not decrease; attributes and links that become empty are removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` changes the order of the ends of the attributes or
+    /// links, or maps any of them past the end of `text`, or to an offset
+    /// that is not on a `char` boundary.
+    ///
+    /// [`replace_range`]: RichText::replace_range
+    pub fn map_ranges(&mut self, text: impl Into<ArcStr>, f: impl Fn(usize) -> usize) {
+        let text = text.into();
+        let f = |offset: usize| {
+            let mapped = f(offset);
+            assert!(
+                text.is_char_boundary(mapped),
+                "offset {} was mapped to {}, which is not a char boundary",
+                offset,
+                mapped
+            );
+            mapped
+        };
+        self.clear_highlights();
+        Arc::make_mut(&mut self.attrs).map_ranges(f);
+        self.links = self
+            .links
+            .iter()
+            .filter_map(|link| link.mapped(f))
+            .collect();
+        self.buffer = text;
+    }
+
+    /// Insert `string` at the byte offset `offset`.
+    ///
+    /// Attributes and links that contain `offset` grow to include the
+    /// inserted text, and those that start at or after `offset` move to
+    /// follow it. Text inserted exactly at the boundary between spans, or at
+    /// the start or end of a span, does not take on that span's attributes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is greater than the length of the text, or if it
+    /// does not lie on a `char` boundary.
+    pub fn insert_str(&mut self, offset: usize, string: &str) {
+        self.clear_highlights();
+        let mut buffer = self.buffer.to_string();
+        buffer.insert_str(offset, string);
+        self.buffer = buffer.into();
+
+        Arc::make_mut(&mut self.attrs).insert(offset, string.len(), InsertBehavior::Extend);
+        self.links = self
+            .links
+            .iter()
+            .flat_map(|link| link.inserted(offset, string.len(), InsertBehavior::Extend))
+            .collect();
+    }
+
+    /// Returns a new `RichText` containing at most the first `len` grapheme
+    /// clusters (user-perceived characters) of this text, followed by
+    /// `ellipsis` if any text was removed.
+    ///
+    /// Attributes and links are clipped to the retained text, as with
+    /// [`slice`]. The ellipsis takes on the attributes of the last retained
+    /// character, other than inline attachments, and is not part of any link.
+    /// To style the ellipsis differently, use [`slice`] and
+    /// [`RichTextBuilder::push_rich_text`] instead.
+    ///
+    /// [`slice`]: RichText::slice
+    pub fn truncate_to(&self, len: usize, ellipsis: &str) -> RichText {
+        let end = match self.buffer.grapheme_indices(true).nth(len) {
+            Some((end, _)) => end,
+            None => return self.clone(),
+        };
+        let mut text = self.slice(..end);
+        let mut buffer = text.buffer.to_string();
+        buffer.push_str(ellipsis);
+        text.buffer = buffer.into();
+        let attrs = Arc::make_mut(&mut text.attrs);
+        attrs.insert(end, ellipsis.len(), InsertBehavior::ExtendPreceding);
+        attrs.remove(end..end + ellipsis.len(), AttributeKind::Attachment);
+        text
+    }
+
+    /// Returns a new `RichText` with leading and trailing whitespace removed.
+    ///
+    /// Attributes and links are clipped to the remaining text, as with [`slice`].
+    ///
+    /// [`slice`]: RichText::slice
+    pub fn trim(&self) -> RichText {
+        let start = self.buffer.len() - self.buffer.trim_start().len();
+        let end = self.buffer.trim_end().len().max(start);
+        self.slice(start..end)
+    }
+
+    /// Returns a new `RichText` in which each run of whitespace is replaced
+    /// by a single space.
+    ///
+    /// The space takes on the attributes of the first character of the run
+    /// it replaces. Attributes and links that start or end within a run
+    /// are shrunk accordingly, and those that lie entirely within the
+    /// collapsed part of a run are removed.
+    ///
+    /// Leading and trailing whitespace is collapsed but not removed; use
+    /// [`trim`] for that.
+    ///
+    /// [`trim`]: RichText::trim
+    pub fn normalize_whitespace(&self) -> RichText {
+        let mut buffer = String::with_capacity(self.buffer.len());
+        let mut collapsed = Vec::new();
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if !c.is_whitespace() {
+                buffer.push(c);
+                continue;
+            }
+            let mut end = start + c.len_utf8();
+            while let Some(&(idx, next)) = chars.peek() {
+                if !next.is_whitespace() {
+                    break;
+                }
+                end = idx + next.len_utf8();
+                chars.next();
+            }
+            buffer.push(' ');
+            if end > start + 1 {
+                collapsed.push(start + 1..end);
+            }
+        }
+
+        // Deleting from the back keeps the earlier ranges valid.
+        let mut attrs = (*self.attrs).clone();
+        let mut links = self.links.to_vec();
+        for range in collapsed.into_iter().rev() {
+            attrs.delete(range.clone());
+            links = links
+                .iter()
+                .filter_map(|link| link.deleted(&range))
+                .collect();
+        }
         RichText {
-            buffer: self.buffer.into(),
-            attrs: self.attrs.into(),
-            links: self.links.into(),
+            buffer: buffer.into(),
+            attrs: Arc::new(attrs),
+            links: links.into(),
+            highlights: None,
+            direction: self.direction,
         }
     }
-}
 
-/// Adds Attributes to the text.
-///
-/// See also: [`RichTextBuilder`](RichTextBuilder)
-pub struct AttributesAdder<'a> {
-    rich_text_builder: &'a mut RichTextBuilder,
-    range: Range<usize>,
-}
+    /// Find every case-insensitive occurrence of `query` in the text, and
+    /// add `attrs` to each of them.
+    ///
+    /// Case is ignored by comparing the full Unicode case folding of the text
+    /// and the query, so that "STRASSE" matches "Straße". The ranges of the matches are returned in order, for
+    /// instance to scroll to them. Matches do not overlap.
+    ///
+    /// The attributes are layered over any existing attributes of the same
+    /// kinds, which are restored by [`clear_highlights`]. Highlights from a
+    /// previous call are cleared first; editing the text also clears them.
+    ///
+    /// [`clear_highlights`]: RichText::clear_highlights
+    pub fn highlight_matches(&mut self, query: &str, attrs: &[Attribute]) -> Vec<Range<usize>> {
+        self.clear_highlights();
+        let ranges = case_insensitive_matches(&self.buffer, query);
+        if ranges.is_empty() || attrs.is_empty() {
+            return ranges;
+        }
 
-impl AttributesAdder<'_> {
-    /// Add the given attribute.
-    pub fn add_attr(&mut self, attr: Attribute) -> &mut Self {
-        self.rich_text_builder.attrs.add(self.range.clone(), attr);
-        self
+        let kinds: Vec<_> = attrs.iter().map(Attribute::kind).collect();
+        let mut covered = AttributeSpans::new();
+        for (span_range, attr) in self.attrs.iter() {
+            if !kinds.contains(&attr.kind()) {
+                continue;
+            }
+            for range in &ranges {
+                let start = range.start.max(span_range.start);
+                let end = range.end.min(span_range.end);
+                if start < end {
+                    covered.add(start..end, attr.clone());
+                }
+            }
+        }
+
+        let spans: Vec<_> = ranges
+            .iter()
+            .flat_map(|range| attrs.iter().map(move |attr| (range.clone(), attr.clone())))
+            .collect();
+        let attr_spans = Arc::make_mut(&mut self.attrs);
+        for (range, attr) in &spans {
+            attr_spans.add(range.clone(), attr.clone());
+        }
+        self.highlights = Some(Arc::new(Highlights { spans, covered }));
+        ranges
     }
 
-    /// Add a font size attribute.
-    pub fn size(&mut self, size: impl Into<KeyOrValue<f64>>) -> &mut Self {
-        self.add_attr(Attribute::size(size));
-        self
+    /// Remove the attributes added by [`highlight_matches`], restoring any
+    /// attributes they were layered over.
+    ///
+    /// Attributes added over the highlights since they were added are kept.
+    ///
+    /// [`highlight_matches`]: RichText::highlight_matches
+    pub fn clear_highlights(&mut self) {
+        let highlights = match self.highlights.take() {
+            Some(highlights) => highlights,
+            None => return,
+        };
+        // The parts of the highlights that have not been replaced since.
+        let mut cleared = Vec::new();
+        for (range, attr) in self.attrs.iter() {
+            for (highlight_range, highlight) in &highlights.spans {
+                let start = range.start.max(highlight_range.start);
+                let end = range.end.min(highlight_range.end);
+                if start < end && attr == highlight {
+                    cleared.push((start..end, attr.kind()));
+                }
+            }
+        }
+
+        let spans = Arc::make_mut(&mut self.attrs);
+        for (range, kind) in &cleared {
+            spans.remove(range.clone(), *kind);
+        }
+        for (range, attr) in highlights.covered.iter() {
+            for (cleared_range, kind) in &cleared {
+                let start = range.start.max(cleared_range.start);
+                let end = range.end.min(cleared_range.end);
+                if start < end && attr.kind() == *kind {
+                    spans.add(start..end, attr.clone());
+                }
+            }
+        }
     }
 
-    /// Add a forground color attribute.
-    pub fn text_color(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
-        self.add_attr(Attribute::text_color(color));
-        self
+    /// Remove all attributes of the given [`AttributeKind`] from the provided range of text.
+    ///
+    /// Any portion of an existing attribute that lies outside of the range is kept.
+    pub fn remove_attribute(&mut self, range: impl RangeBounds<usize>, kind: AttributeKind) {
+        let range = util::resolve_range(range, self.buffer.len());
+        Arc::make_mut(&mut self.attrs).remove(range, kind);
     }
 
-    /// Add a font family attribute.
-    pub fn font_family(&mut self, family: FontFamily) -> &mut Self {
-        self.add_attr(Attribute::font_family(family));
-        self
+    /// Remove all attributes from the provided range of text.
+    ///
+    /// Any portion of an existing attribute that lies outside of the range is kept.
+    /// Links are not affected.
+    pub fn remove_attributes(&mut self, range: impl RangeBounds<usize>) {
+        let range = util::resolve_range(range, self.buffer.len());
+        Arc::make_mut(&mut self.attrs).clear_range(range);
     }
 
-    /// Add a `FontWeight` attribute.
-    pub fn weight(&mut self, weight: FontWeight) -> &mut Self {
-        self.add_attr(Attribute::weight(weight));
-        self
+    /// Merge adjacent spans that have equal attributes.
+    ///
+    /// This is worth doing for text that was built up from many small pieces;
+    /// see [`AttributeSpans::coalesce`] for details.
+    pub fn coalesce_attributes(&mut self) {
+        Arc::make_mut(&mut self.attrs).coalesce();
     }
 
-    /// Add a `FontStyle` attribute.
-    pub fn style(&mut self, style: FontStyle) -> &mut Self {
-        self.add_attr(Attribute::style(style));
-        self
+    /// Retain only the kinds of attributes for which the predicate returns `true`.
+    ///
+    /// See [`AttributeSpans::retain`] for details. Links are not affected.
+    pub fn retain_attributes(&mut self, f: impl FnMut(AttributeKind) -> bool) {
+        Arc::make_mut(&mut self.attrs).retain(f);
     }
 
-    /// Add a underline attribute.
-    pub fn underline(&mut self, underline: bool) -> &mut Self {
-        self.add_attr(Attribute::underline(underline));
-        self
+    /// Returns the attributes that apply at the given byte offset.
+    ///
+    /// See [`AttributeSpans::attributes_at`] for the order in which they are returned.
+    pub fn attributes_at(&self, offset: usize) -> Vec<Attribute> {
+        self.attrs.attributes_at(offset)
     }
 
-    /// Add a `FontDescriptor` attribute.
-    pub fn font_descriptor(&mut self, font: impl Into<KeyOrValue<FontDescriptor>>) -> &mut Self {
-        self.add_attr(Attribute::font_descriptor(font));
-        self
+    /// Returns the attribute of the given [`AttributeKind`] if it applies,
+    /// with the same value, to the whole of the given range.
+    ///
+    /// This can be used to show whether a style applies to a selection, and
+    /// to detect a mixed state; see [`AttributeSpans::attribute_in_range`].
+    pub fn attribute_in_range(
+        &self,
+        range: impl RangeBounds<usize>,
+        kind: AttributeKind,
+    ) -> Option<Attribute> {
+        let range = util::resolve_range(range, self.buffer.len());
+        self.attrs.attribute_in_range(range, kind)
     }
 
-    /// Add a [`Link`] attribute.
+    /// Returns an iterator over the maximal runs of text with uniform attributes.
     ///
-    /// [`Link`]: super::attribute::Link
-    pub fn link(&mut self, command: impl Into<Command>) -> &mut Self {
-        self.rich_text_builder
-            .links
-            .push(Link::new(self.range.clone(), command.into()));
-        self
+    /// The runs are non-overlapping and cover the whole text; each is paired
+    /// with the attributes that apply to it, in the order described in
+    /// [`AttributeSpans::attributes_at`]. This is useful when converting a
+    /// `RichText` to another format.
+    pub fn iter_runs(&self) -> impl Iterator<Item = (Range<usize>, Vec<Attribute>)> {
+        self.attrs.runs(self.buffer.len()).into_iter()
+    }
+
+    /// Returns an iterator over the maximal runs of text with a uniform
+    /// [`ResolvedStyle`].
+    ///
+    /// This is like [`iter_runs`], except that the style of each run is
+    /// resolved against the provided [`Env`], as in [`resolved_style_at`].
+    ///
+    /// [`iter_runs`]: RichText::iter_runs
+    /// [`resolved_style_at`]: RichText::resolved_style_at
+    pub fn iter_resolved_runs(
+        &self,
+        env: &Env,
+    ) -> impl Iterator<Item = (Range<usize>, ResolvedStyle)> {
+        let mut runs: Vec<(Range<usize>, ResolvedStyle)> = Vec::new();
+        for (range, attrs) in self.attrs.runs(self.buffer.len()) {
+            let style = AttributeSpans::resolve_style(&attrs, env);
+            match runs.last_mut() {
+                Some((prev_range, prev)) if *prev == style => prev_range.end = range.end,
+                _ => runs.push((range, style)),
+            }
+        }
+        runs.into_iter()
+    }
+
+    /// Returns the effective style of the text at the given byte offset.
+    ///
+    /// This layers all of the attributes that apply at `offset` on top of the
+    /// default font and text color in the [`theme`]; it can be used, for
+    /// instance, to show the formatting at the cursor in a toolbar.
+    ///
+    /// A widget may draw the text with a different default font or color,
+    /// which is not reflected here.
+    ///
+    /// [`theme`]: crate::theme
+    pub fn resolved_style_at(&self, offset: usize, env: &Env) -> ResolvedStyle {
+        self.attrs.resolved_style_at(offset, env)
+    }
+
+    /// Returns `true` if any attributes have been applied to this text.
+    ///
+    /// This includes the styles added for links.
+    pub fn has_attributes(&self) -> bool {
+        !self.attrs.is_empty()
+    }
+}
+
+impl Add<&RichText> for RichText {
+    type Output = RichText;
+
+    fn add(mut self, other: &RichText) -> RichText {
+        self.append(other);
+        self
+    }
+}
+
+impl Add<&RichText> for &RichText {
+    type Output = RichText;
+
+    fn add(self, other: &RichText) -> RichText {
+        self.concat(other)
+    }
+}
+
+impl PietTextStorage for RichText {
+    fn as_str(&self) -> &str {
+        self.buffer.as_str()
+    }
+}
+
+impl TextStorage for RichText {
+    fn add_attributes(
+        &self,
+        mut builder: PietTextLayoutBuilder,
+        env: &Env,
+    ) -> PietTextLayoutBuilder {
+        if !self.has_attributes() {
+            return builder;
+        }
+        // piet only supports a single alignment per layout, so the alignment
+        // of the first paragraph is used for all of them; `TextLayout` passes
+        // each paragraph separately unless paragraph splitting is disabled.
+        if let Some(alignment) = self.attrs.alignment_at(0) {
+            builder = builder.alignment(alignment);
+        }
+        for (range, attr) in self.attrs.to_piet_attrs(env) {
+            builder = builder.range_attribute(range, attr);
+        }
+        builder
+    }
+
+    fn background_colors(&self, env: &Env) -> Vec<(Range<usize>, Color)> {
+        self.attrs.background_colors(env)
+    }
+
+    fn scaled_to_fit(&self, env: &Env, default_size: f64, scale: f64) -> Option<Self> {
+        let attrs = self.attrs.scaled_to_fit(env, default_size, scale)?;
+        Some(RichText {
+            buffer: self.buffer.clone(),
+            attrs: Arc::new(attrs),
+            links: self.links.clone(),
+            highlights: None,
+            direction: self.direction,
+        })
+    }
+
+    fn with_link_state(&self, index: usize, state: LinkState) -> Option<Self> {
+        let link = self.links.get(index)?;
+        let color = link.color_for_state(state)?.clone();
+        let mut attrs = (*self.attrs).clone();
+        attrs.add(link.range(), Attribute::TextColor(color));
+        Some(RichText {
+            buffer: self.buffer.clone(),
+            attrs: Arc::new(attrs),
+            links: self.links.clone(),
+            highlights: None,
+            direction: self.direction,
+        })
+    }
+
+    fn resolve_font_scales(&self, env: &Env, default_size: f64) -> Option<Self> {
+        let attrs = self.attrs.resolve_font_scales(env, default_size)?;
+        Some(RichText {
+            buffer: self.buffer.clone(),
+            attrs: Arc::new(attrs),
+            links: self.links.clone(),
+            highlights: None,
+            direction: self.direction,
+        })
+    }
+
+    fn small_caps(&self, env: &Env, default_size: f64) -> Option<Self> {
+        let (buffer, attrs) = self.attrs.small_caps(&self.buffer, env, default_size)?;
+        Some(RichText {
+            buffer: buffer.into(),
+            attrs: Arc::new(attrs),
+            links: self.links.clone(),
+            highlights: None,
+            direction: self.direction,
+        })
+    }
+
+    fn baseline_shifts(&self, env: &Env, default_size: f64) -> Vec<(Range<usize>, f64)> {
+        self.attrs.baseline_shifts(env, default_size)
+    }
+
+    fn hide_shifted_text(&self) -> Option<Self> {
+        let attrs = self.attrs.hide_shifted_text()?;
+        Some(RichText {
+            buffer: self.buffer.clone(),
+            attrs: Arc::new(attrs),
+            links: self.links.clone(),
+            highlights: None,
+            direction: self.direction,
+        })
+    }
+
+    fn attachments(&self) -> Vec<(Range<usize>, InlineAttachment)> {
+        self.attrs.attachments()
+    }
+
+    fn attachment_placeholders(&self) -> Option<Self> {
+        let (buffer, placeholders) = self.attrs.attachment_placeholders(&self.buffer)?;
+        let mut text = self.clone();
+        text.map_ranges(buffer, |offset| {
+            attachment::to_layout_offset(&placeholders, offset)
+        });
+        Some(text)
+    }
+
+    fn paragraphs(&self) -> Option<Vec<(Range<usize>, ParagraphStyle)>> {
+        if !self.attrs.has_paragraph_attributes() {
+            return None;
+        }
+        let mut paragraphs = Vec::new();
+        let mut start = 0;
+        for line in self.buffer.split('\n') {
+            let end = start + line.strip_suffix('\r').unwrap_or(line).len();
+            paragraphs.push((start..end, self.attrs.paragraph_style_at(start)));
+            start += line.len() + 1;
+        }
+        Some(paragraphs)
+    }
+
+    fn paragraph_text(&self, range: Range<usize>) -> Option<Self> {
+        self.get(range)
+    }
+
+    fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
+        self.attrs.env_update(ctx) || self.links.iter().any(|link| link.env_update(ctx))
+    }
+
+    fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    fn writing_direction(&self) -> WritingDirection {
+        self.direction
+    }
+}
+
+/// Edits keep the attributes and links consistent with the text: the
+/// attributes and links that overlap a removed range shrink, and are removed
+/// if they lie entirely within it. Inserted text takes on the attributes of
+/// the text before it, but does not extend a link that ends where it is
+/// inserted.
+impl EditableText for RichText {
+    fn cursor(&self, position: usize) -> Option<StringCursor> {
+        StringCursor::new(&self.buffer, position)
+    }
+
+    fn edit(&mut self, range: Range<usize>, new: impl Into<String>) {
+        let new = new.into();
+        if range.is_empty() && new.is_empty() {
+            return;
+        }
+        self.clear_highlights();
+        let mut buffer = self.buffer.to_string();
+        buffer.replace_range(range.clone(), &new);
+        self.buffer = buffer.into();
+
+        let attrs = Arc::make_mut(&mut self.attrs);
+        attrs.delete(range.clone());
+        attrs.insert(range.start, new.len(), InsertBehavior::ExtendPreceding);
+        self.links = self
+            .links
+            .iter()
+            .filter_map(|link| link.deleted(&range))
+            .flat_map(|link| link.inserted(range.start, new.len(), InsertBehavior::Extend))
+            .collect();
+    }
+
+    fn slice(&self, range: Range<usize>) -> Option<Cow<str>> {
+        self.buffer.get(range).map(Cow::from)
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn prev_word_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_word_offset(&self.buffer, offset)
+    }
+
+    fn next_word_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_word_offset(&self.buffer, offset)
+    }
+
+    fn prev_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_grapheme_offset(&self.buffer, offset)
+    }
+
+    fn next_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_grapheme_offset(&self.buffer, offset)
+    }
+
+    fn prev_codepoint_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_codepoint_offset(&self.buffer, offset)
+    }
+
+    fn next_codepoint_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_codepoint_offset(&self.buffer, offset)
+    }
+
+    fn preceding_line_break(&self, offset: usize) -> usize {
+        editable_text::preceding_line_break(&self.buffer, offset)
+    }
+
+    fn next_line_break(&self, offset: usize) -> usize {
+        editable_text::next_line_break(&self.buffer, offset)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    fn from_str(s: &str) -> Self {
+        RichText::new(s.into())
+    }
+}
+
+/// An error returned when a range of text does not lie on `char` boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharBoundaryError {
+    offset: usize,
+}
+
+impl CharBoundaryError {
+    /// The first offset in the range that is not on a `char` boundary.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl std::fmt::Display for CharBoundaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "offset {} is not on a char boundary", self.offset)
+    }
+}
+
+impl std::error::Error for CharBoundaryError {}
+
+fn check_char_boundaries(text: &str, range: &Range<usize>) -> Result<(), CharBoundaryError> {
+    match [range.start, range.end]
+        .iter()
+        .find(|offset| !text.is_char_boundary(**offset))
+    {
+        Some(&offset) => Err(CharBoundaryError { offset }),
+        None => Ok(()),
+    }
+}
+
+impl RichTextDelta {
+    /// Returns `true` if applying this delta has no effect.
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+            && self.text.is_empty()
+            && self.attrs.is_none()
+            && self.links.is_none()
+            && self.direction.is_none()
+    }
+
+    /// The byte range of the old text that is replaced.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// The text that replaces [`range`].
+    ///
+    /// [`range`]: RichTextDelta::range
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Returns the ranges of the non-overlapping occurrences of `query` in
+/// `text`, comparing their lowercase forms.
+fn case_insensitive_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: String = query.chars().default_case_fold().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    // For each offset in `folded`, the offset in `text` of the character
+    // whose case folding starts there; matches must start and end at these.
+    // Full case folding maps each character independently of its context.
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for (offset, c) in text.char_indices() {
+        offsets.push(Some(offset));
+        folded.extend(std::iter::once(c).default_case_fold());
+        offsets.resize(folded.len(), None);
+    }
+    offsets.push(Some(text.len()));
+
+    folded
+        .match_indices(&query)
+        .filter_map(|(start, m)| Some(offsets[start]?..offsets[start + m.len()]?))
+        .collect()
+}
+
+/// A builder for creating [`RichText`] objects.
+///
+/// This builder allows you to construct a [`RichText`] object by building up a sequence
+/// of styled sub-strings; first you [`push`](RichTextBuilder::push) a `&str` onto the string,
+/// and then you can optionally add styles to that text via the returned [`AttributesAdder`]
+/// object.
+///
+/// # Example
+/// ```
+/// # use druid::text::{Attribute, RichTextBuilder};
+/// # use druid::{FontWeight, Color};
+/// let mut builder = RichTextBuilder::new();
+/// builder.push("Hello ");
+/// builder.push("World!").weight(FontWeight::BOLD);
+///
+/// // Can also use write!
+/// write!(builder, "Here is your number: {}", 1).underline(true).text_color(Color::RED);
+///
+/// let rich_text = builder.build();
+/// ```
+///
+/// [`RichText`]: RichText
+#[derive(Default)]
+pub struct RichTextBuilder {
+    buffer: String,
+    attrs: AttributeSpans,
+    links: Vec<Link>,
+    defaults: Vec<Attribute>,
+    /// The marker and number of the last list item at each level.
+    list_counters: Vec<(ListMarker, usize)>,
+    /// The start of the paragraph of the last list item.
+    list_item_start: Option<usize>,
+}
+
+/// The kind of marker at the start of a list item.
+///
+/// See [`RichTextBuilder::push_list_item`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListMarker {
+    /// A bullet, which varies with the nesting level.
+    Bullet,
+    /// A number, counting the items of the list at the same level.
+    Number,
+}
+
+impl RichTextBuilder {
+    /// Create a new `RichTextBuilder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `RichTextBuilder` with room for at least `capacity` bytes
+    /// of text.
+    pub fn with_capacity(capacity: usize) -> Self {
+        RichTextBuilder {
+            buffer: String::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Reserve room for at least `additional` more bytes of text.
+    ///
+    /// This can avoid repeated reallocation when building a large document.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buffer.reserve(additional);
+    }
+
+    /// Builder-style method to set a default [`Attribute`].
+    ///
+    /// See [`set_default_attribute`] for more information.
+    ///
+    /// [`set_default_attribute`]: RichTextBuilder::set_default_attribute
+    pub fn with_default_attribute(mut self, attr: Attribute) -> Self {
+        self.set_default_attribute(attr);
+        self
+    }
+
+    /// Set a default [`Attribute`].
+    ///
+    /// Default attributes are added to all text that is subsequently added
+    /// with [`push`] or the `write!` macro; text that has already been added
+    /// is not affected. Attributes added with the returned [`AttributesAdder`]
+    /// take precedence over the defaults.
+    ///
+    /// Setting a default replaces any existing default of the same [`AttributeKind`].
+    ///
+    /// [`push`]: RichTextBuilder::push
+    pub fn set_default_attribute(&mut self, attr: Attribute) {
+        let kind = attr.kind();
+        self.defaults.retain(|existing| existing.kind() != kind);
+        self.defaults.push(attr);
+    }
+
+    /// Remove all default attributes.
+    ///
+    /// Text added afterwards will only receive the attributes that are
+    /// explicitly added to it.
+    pub fn clear_default_attributes(&mut self) {
+        self.defaults.clear();
+    }
+
+    /// Append a `&str` to the end of the text.
+    ///
+    /// This method returns a [`AttributesAdder`] that can be used to style the newly
+    /// added string slice.
+    pub fn push(&mut self, string: &str) -> AttributesAdder {
+        let range = self.buffer.len()..(self.buffer.len() + string.len());
+        self.buffer.push_str(string);
+        self.add_default_attributes(range.clone());
+        self.add_attributes_for_range(range)
+    }
+
+    /// Append a `&str` to the end of the text, and style it with the provided closure.
+    ///
+    /// This is equivalent to [`push`], but is more convenient when the styling
+    /// depends on runtime conditions, such as in a loop.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use druid::text::RichTextBuilder;
+    /// # use druid::FontWeight;
+    /// let mut builder = RichTextBuilder::new();
+    /// for (i, word) in ["one ", "two ", "three"].iter().enumerate() {
+    ///     builder.push_with(word, |attrs| {
+    ///         if i % 2 == 1 {
+    ///             attrs.weight(FontWeight::BOLD);
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// [`push`]: RichTextBuilder::push
+    pub fn push_with(&mut self, string: &str, f: impl FnOnce(&mut AttributesAdder)) -> &mut Self {
+        f(&mut self.push(string));
+        self
+    }
+
+    /// Insert a `&str` at the given byte offset.
+    ///
+    /// Existing attributes and links that start at or after `offset` are
+    /// moved to follow the inserted text. Spans that contain `offset` are
+    /// either extended or split, according to `behavior`. Default attributes
+    /// are added to the inserted text, as with [`push`].
+    ///
+    /// This method returns an [`AttributesAdder`] that can be used to style
+    /// the inserted text.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is greater than the length of the text, or if it
+    /// does not lie on a `char` boundary.
+    ///
+    /// [`push`]: RichTextBuilder::push
+    pub fn insert(
+        &mut self,
+        offset: usize,
+        string: &str,
+        behavior: InsertBehavior,
+    ) -> AttributesAdder {
+        let len = string.len();
+        self.buffer.insert_str(offset, string);
+        self.attrs.insert(offset, len, behavior);
+
+        self.links = self
+            .links
+            .iter()
+            .flat_map(|link| link.inserted(offset, len, behavior))
+            .collect();
+
+        let range = offset..offset + len;
+        self.add_default_attributes(range.clone());
+        self.add_attributes_for_range(range)
+    }
+
+    /// Append an [`InlineAttachment`] to the end of the text.
+    ///
+    /// This appends an [`OBJECT_REPLACEMENT_CHARACTER`] with an
+    /// [`Attribute::Attachment`], and applies the default attributes to it,
+    /// as with [`push`]. The returned [`AttributesAdder`] can be used to add
+    /// further attributes, such as a [`link`].
+    ///
+    /// [`push`]: RichTextBuilder::push
+    /// [`link`]: AttributesAdder::link
+    pub fn push_attachment(&mut self, attachment: InlineAttachment) -> AttributesAdder {
+        let mut buf = [0; 4];
+        let mut attrs = self.push(OBJECT_REPLACEMENT_CHARACTER.encode_utf8(&mut buf));
+        attrs.add_attr(Attribute::attachment(attachment));
+        attrs
+    }
+
+    /// Append an item of a bulleted or numbered list to the end of the text.
+    ///
+    /// The item starts a new paragraph, so a newline is added first unless the
+    /// text is empty or already ends with one. The marker is followed by
+    /// `text`, and the whole paragraph gets an [`Attribute::Indent`] and an
+    /// [`Attribute::HangingIndent`] so that the marker sits at the indent of
+    /// `level`, and the text, including any wrapped lines, is aligned one
+    /// level further in.
+    ///
+    /// Numbered items are counted automatically: an item continues the
+    /// numbering of the list it follows, and nested levels restart from one.
+    /// A paragraph that is not a list item ends the list.
+    ///
+    /// The returned [`AttributesAdder`] styles `text`; the marker only gets
+    /// the default attributes. Text pushed afterwards is part of the same item
+    /// until the next newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use druid::piet::TextStorage as _;
+    /// # use druid::text::{ListMarker, RichTextBuilder};
+    /// let mut builder = RichTextBuilder::new();
+    /// builder.push_list_item(ListMarker::Number, 0, "First");
+    /// builder.push_list_item(ListMarker::Bullet, 1, "Nested");
+    /// builder.push_list_item(ListMarker::Number, 0, "Second");
+    /// assert_eq!(builder.build().as_str(), "1. First\n\u{25E6} Nested\n2. Second");
+    /// ```
+    pub fn push_list_item(
+        &mut self,
+        marker: ListMarker,
+        level: usize,
+        text: &str,
+    ) -> AttributesAdder {
+        let in_list = self
+            .list_item_start
+            .and_then(|start| self.buffer.get(start..))
+            .map_or(false, |item| !item.trim_end_matches('\n').contains('\n'));
+        if !in_list {
+            self.list_counters.clear();
+        }
+        if !self.buffer.is_empty() && !self.buffer.ends_with('\n') {
+            self.push("\n");
+        }
+
+        self.list_counters.truncate(level + 1);
+        self.list_counters.resize(level + 1, (marker, 0));
+        let counter = &mut self.list_counters[level];
+        if counter.0 != marker {
+            *counter = (marker, 0);
+        }
+        counter.1 += 1;
+        let marker_text = match marker {
+            ListMarker::Bullet => format!("{} ", BULLETS[level % BULLETS.len()]),
+            ListMarker::Number => format!("{}. ", counter.1),
+        };
+
+        let start = self.buffer.len();
+        self.list_item_start = Some(start);
+        self.push(&marker_text).add_attr(Attribute::Marker);
+        let text_start = self.buffer.len();
+        self.push(text);
+        let mut paragraph = self.add_attributes_for_range(start..);
+        paragraph.add_attr(Attribute::Indent(level as f64 * LIST_INDENT));
+        paragraph.add_attr(Attribute::HangingIndent((level + 1) as f64 * LIST_INDENT));
+        self.add_attributes_for_range(text_start..)
+    }
+
+    /// Append an already styled [`RichText`] to the end of the text.
+    ///
+    /// The attributes and links of `text` are preserved; default attributes
+    /// are not applied. This method returns
+    /// an [`AttributesAdder`] that can be used to add further styles to the
+    /// newly added text; these take precedence over its existing attributes.
+    pub fn push_rich_text(&mut self, text: &RichText) -> AttributesAdder {
+        let offset = self.buffer.len();
+        self.buffer.push_str(&text.buffer);
+        self.attrs.append(&text.attrs, offset);
+        self.links
+            .extend(text.links.iter().map(|link| link.shifted(offset)));
+        self.add_attributes_for_range(offset..)
+    }
+
+    /// Glue for usage of the write! macro.
+    ///
+    /// This method should generally not be invoked manually, but rather through the write! macro itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a formatting trait implementation returns an error; use
+    /// [`try_write_fmt`] to handle the error instead.
+    ///
+    /// [`try_write_fmt`]: RichTextBuilder::try_write_fmt
+    #[doc(hidden)]
+    pub fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> AttributesAdder {
+        self.try_write_fmt(fmt)
+            .expect("a formatting trait implementation returned an error")
+    }
+
+    /// Append formatted text to the end of the text, returning an error if a
+    /// formatting trait implementation fails.
+    ///
+    /// If formatting fails, any partially written text is removed. On success,
+    /// this returns an [`AttributesAdder`] for the new text, as with [`push`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use druid::piet::TextStorage as _;
+    /// # use druid::text::RichTextBuilder;
+    /// # fn main() -> Result<(), std::fmt::Error> {
+    /// let mut builder = RichTextBuilder::new();
+    /// builder
+    ///     .try_write_fmt(format_args!("{} apples", 3))?
+    ///     .underline(true);
+    /// assert_eq!(builder.build().as_str(), "3 apples");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`push`]: RichTextBuilder::push
+    pub fn try_write_fmt(
+        &mut self,
+        fmt: std::fmt::Arguments<'_>,
+    ) -> Result<AttributesAdder, std::fmt::Error> {
+        use std::fmt::Write;
+        let start = self.buffer.len();
+        if let Err(err) = self.buffer.write_fmt(fmt) {
+            self.buffer.truncate(start);
+            return Err(err);
+        }
+        self.add_default_attributes(start..self.buffer.len());
+        Ok(self.add_attributes_for_range(start..self.buffer.len()))
+    }
+
+    fn add_default_attributes(&mut self, range: Range<usize>) {
+        for attr in &self.defaults {
+            self.attrs.add(range.clone(), attr.clone());
+        }
+    }
+
+    /// Get an [`AttributesAdder`] for the given range.
+    ///
+    /// This can be used to modify styles for a given range after it has been added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start or end of the range does not lie on a `char` boundary.
+    pub fn add_attributes_for_range(&mut self, range: impl RangeBounds<usize>) -> AttributesAdder {
+        let range = util::resolve_range(range, self.buffer.len());
+        if let Err(err) = check_char_boundaries(&self.buffer, &range) {
+            panic!("invalid attribute range: {}", err);
+        }
+        AttributesAdder {
+            rich_text_builder: self,
+            range,
+        }
+    }
+
+    /// Build the `RichText`.
+    pub fn build(self) -> RichText {
+        RichText {
+            buffer: self.buffer.into(),
+            attrs: self.attrs.into(),
+            links: self.links.into(),
+            highlights: None,
+            direction: WritingDirection::Natural,
+        }
+    }
+}
+
+impl<'a> Extend<&'a RichText> for RichTextBuilder {
+    fn extend<I: IntoIterator<Item = &'a RichText>>(&mut self, iter: I) {
+        for text in iter {
+            self.push_rich_text(text);
+        }
+    }
+}
+
+/// Adds Attributes to the text.
+///
+/// See also: [`RichTextBuilder`](RichTextBuilder)
+pub struct AttributesAdder<'a> {
+    rich_text_builder: &'a mut RichTextBuilder,
+    range: Range<usize>,
+}
+
+impl AttributesAdder<'_> {
+    /// Add the given attribute.
+    pub fn add_attr(&mut self, attr: Attribute) -> &mut Self {
+        self.rich_text_builder.attrs.add(self.range.clone(), attr);
+        self
+    }
+
+    /// Add a font size attribute.
+    pub fn size(&mut self, size: impl Into<KeyOrValue<f64>>) -> &mut Self {
+        self.add_attr(Attribute::size(size));
+        self
+    }
+
+    /// Add a forground color attribute.
+    pub fn text_color(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
+        self.add_attr(Attribute::text_color(color));
+        self
+    }
+
+    /// Add a background color attribute.
+    pub fn background_color(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
+        self.add_attr(Attribute::background_color(color));
+        self
+    }
+
+    /// Add a background color attribute, used to highlight text.
+    ///
+    /// This is equivalent to [`AttributesAdder::background_color`].
+    pub fn background(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
+        self.background_color(color)
+    }
+
+    /// Add a font family attribute.
+    pub fn font_family(&mut self, family: FontFamily) -> &mut Self {
+        self.add_attr(Attribute::font_family(family));
+        self
+    }
+
+    /// Add a `FontWeight` attribute.
+    pub fn weight(&mut self, weight: FontWeight) -> &mut Self {
+        self.add_attr(Attribute::weight(weight));
+        self
+    }
+
+    /// Add a `FontStyle` attribute.
+    pub fn style(&mut self, style: FontStyle) -> &mut Self {
+        self.add_attr(Attribute::style(style));
+        self
+    }
+
+    /// Add a underline attribute.
+    pub fn underline(&mut self, underline: bool) -> &mut Self {
+        self.add_attr(Attribute::underline(underline));
+        self
+    }
+
+    /// Add a strikethrough attribute.
+    ///
+    /// The line is drawn in the color of the text; see
+    /// [`Attribute::Strikethrough`].
+    pub fn strikethrough(&mut self, strikethrough: bool) -> &mut Self {
+        self.add_attr(Attribute::strikethrough(strikethrough));
+        self
+    }
+
+    /// Add a letter spacing attribute.
+    ///
+    /// See [`Attribute::LetterSpacing`] for the current limitations.
+    pub fn letter_spacing(&mut self, spacing: impl Into<KeyOrValue<f64>>) -> &mut Self {
+        self.add_attr(Attribute::letter_spacing(spacing));
+        self
+    }
+
+    /// Add a line height attribute, as a multiple of the font size.
+    ///
+    /// See [`Attribute::LineHeight`] for how conflicting spans are resolved,
+    /// and for the current limitations.
+    pub fn line_height(&mut self, height: impl Into<KeyOrValue<f64>>) -> &mut Self {
+        self.add_attr(Attribute::line_height(height));
+        self
+    }
+
+    /// Add a baseline shift attribute, as a multiple of the font size.
+    pub fn baseline_shift(&mut self, shift: f64) -> &mut Self {
+        self.add_attr(Attribute::baseline_shift(shift));
+        self
+    }
+
+    /// Add a font scale attribute.
+    ///
+    /// See [`Attribute::FontScale`] for details.
+    pub fn font_scale(&mut self, scale: f64) -> &mut Self {
+        self.add_attr(Attribute::font_scale(scale));
+        self
+    }
+
+    /// Style this range as a superscript.
+    ///
+    /// This raises the baseline and reduces the font size. The shift is added
+    /// to any existing baseline shift, and the size is reduced with an
+    /// [`Attribute::FontScale`] that multiplies any existing scale, so a
+    /// superscript within a superscript is raised further and made smaller.
+    /// The scale applies to the resolved font size of the text, including
+    /// the default size of the layout.
+    ///
+    /// See [`Attribute::BaselineShift`] for how the shifted text is laid out.
+    pub fn superscript(&mut self) -> &mut Self {
+        self.rich_text_builder.attrs.shift_baseline(
+            self.range.clone(),
+            SUPERSCRIPT_SHIFT,
+            SCRIPT_SIZE_SCALE,
+        );
+        self
+    }
+
+    /// Style this range as a subscript.
+    ///
+    /// This lowers the baseline and reduces the font size, in the same
+    /// manner as [`superscript`].
+    ///
+    /// [`superscript`]: AttributesAdder::superscript
+    pub fn subscript(&mut self) -> &mut Self {
+        self.rich_text_builder.attrs.shift_baseline(
+            self.range.clone(),
+            SUBSCRIPT_SHIFT,
+            SCRIPT_SIZE_SCALE,
+        );
+        self
+    }
+
+    /// Add a `TextAlignment` attribute.
+    ///
+    /// See [`Attribute::Alignment`] for how conflicting spans are resolved.
+    pub fn alignment(&mut self, alignment: TextAlignment) -> &mut Self {
+        self.add_attr(Attribute::alignment(alignment));
+        self
+    }
+
+    /// Indent the first line of the paragraphs in this range by `indent` points.
+    ///
+    /// See [`Attribute::Indent`] for more information.
+    pub fn indent(&mut self, indent: f64) -> &mut Self {
+        self.add_attr(Attribute::indent(indent));
+        self
+    }
+
+    /// Add `space` points above the paragraphs in this range.
+    pub fn space_before(&mut self, space: f64) -> &mut Self {
+        self.add_attr(Attribute::space_before(space));
+        self
+    }
+
+    /// Add `space` points below the paragraphs in this range.
+    pub fn space_after(&mut self, space: f64) -> &mut Self {
+        self.add_attr(Attribute::space_after(space));
+        self
+    }
+
+    /// Allow the font size of this range to be reduced, down to `min` points,
+    /// so that the text fits the available width.
+    ///
+    /// See [`Attribute::ScaleToFit`] for more information.
+    pub fn scale_to_fit(&mut self, min: f64) -> &mut Self {
+        self.add_attr(Attribute::scale_to_fit(min));
+        self
+    }
+
+    /// Set whether this range is drawn in small capitals.
+    ///
+    /// Small capitals are currently always synthesized; see
+    /// [`Attribute::FontVariant`] for details.
+    pub fn small_caps(&mut self, small_caps: bool) -> &mut Self {
+        let variant = if small_caps {
+            FontVariant::SmallCaps
+        } else {
+            FontVariant::Normal
+        };
+        self.add_attr(Attribute::font_variant(variant));
+        self
+    }
+
+    /// Add OpenType font features, such as tabular figures for numbers that
+    /// line up in a table:
+    ///
+    /// ```
+    /// # use druid::text::{FeatureTag, RichTextBuilder};
+    /// let mut builder = RichTextBuilder::new();
+    /// builder
+    ///     .push("1,234.50")
+    ///     .font_features(vec![(FeatureTag::TABULAR_FIGURES, 1)]);
+    /// ```
+    ///
+    /// Font features do not yet affect drawing; see
+    /// [`Attribute::FontFeatures`] for details.
+    pub fn font_features(
+        &mut self,
+        features: impl IntoIterator<Item = (FeatureTag, u32)>,
+    ) -> &mut Self {
+        self.add_attr(Attribute::font_features(features));
+        self
+    }
+
+    /// Add a `FontDescriptor` attribute.
+    pub fn font_descriptor(&mut self, font: impl Into<KeyOrValue<FontDescriptor>>) -> &mut Self {
+        self.add_attr(Attribute::font_descriptor(font));
+        self
+    }
+
+    /// Add a [`Link`] attribute.
+    ///
+    /// The link is underlined and drawn in the theme's [`LINK_COLOR`], except
+    /// where an underline or text color has already been set for this range.
+    /// Use [`link_unstyled`] to add a link without any styling.
+    ///
+    /// [`Link`]: super::attribute::Link
+    /// [`LINK_COLOR`]: crate::theme::LINK_COLOR
+    /// [`link_unstyled`]: AttributesAdder::link_unstyled
+    pub fn link(&mut self, command: impl Into<Command>) -> &mut Self {
+        self.rich_text_builder
+            .attrs
+            .add_link_style(self.range.clone());
+        self.link_unstyled(command)
+    }
+
+    /// Add a [`Link`] attribute, without adding any styling.
+    ///
+    /// [`Link`]: super::attribute::Link
+    pub fn link_unstyled(&mut self, command: impl Into<Command>) -> &mut Self {
+        self.rich_text_builder
+            .links
+            .push(Link::new(self.range.clone(), command.into()));
+        self
+    }
+
+    /// Add a [`Link`] attribute that calls the provided closure when clicked.
+    ///
+    /// The link is styled in the same way as by [`link`]. Links with callbacks
+    /// and links with commands can be freely mixed in the same text.
+    ///
+    /// [`Link`]: super::attribute::Link
+    /// [`link`]: AttributesAdder::link
+    pub fn on_click(&mut self, f: impl Fn(&mut EventCtx, &Env) + 'static) -> &mut Self {
+        self.rich_text_builder
+            .attrs
+            .add_link_style(self.range.clone());
+        self.rich_text_builder
+            .links
+            .push(Link::new_with_callback(self.range.clone(), f));
+        self
+    }
+
+    /// Set the color of the text of the links added to this range while the
+    /// mouse is over them.
+    ///
+    /// This applies to links that have already been added with [`link`],
+    /// [`link_unstyled`] or [`on_click`]; see [`Link::with_hover_color`].
+    ///
+    /// [`link`]: AttributesAdder::link
+    /// [`link_unstyled`]: AttributesAdder::link_unstyled
+    /// [`on_click`]: AttributesAdder::on_click
+    /// [`Link::with_hover_color`]: super::attribute::Link::with_hover_color
+    pub fn link_hover_color(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
+        let color = color.into();
+        self.edit_links(|link| link.with_hover_color(color.clone()));
+        self
+    }
+
+    /// Set the color of the text of the links added to this range while they
+    /// are being pressed.
+    ///
+    /// Like [`link_hover_color`], this applies to links that have already
+    /// been added; see [`Link::with_active_color`].
+    ///
+    /// [`link_hover_color`]: AttributesAdder::link_hover_color
+    /// [`Link::with_active_color`]: super::attribute::Link::with_active_color
+    pub fn link_active_color(&mut self, color: impl Into<KeyOrValue<Color>>) -> &mut Self {
+        let color = color.into();
+        self.edit_links(|link| link.with_active_color(color.clone()));
+        self
+    }
+
+    fn edit_links(&mut self, f: impl Fn(Link) -> Link) {
+        for link in self.rich_text_builder.links.iter_mut() {
+            if link.range() == self.range {
+                *link = f(link.clone());
+            }
+        }
+    }
+}
+
+/// `RichText` is serialized as a struct with its text, its attributes, its
+/// links, and its base direction, which may be omitted when deserializing.
+///
+/// Links are serialized by the name of their command's selector; see
+/// [`DESERIALIZED_LINK`] for details.
+///
+/// [`DESERIALIZED_LINK`]: super::DESERIALIZED_LINK
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde_crate::de::Error as _;
+    use serde_crate::ser::SerializeStruct;
+    use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct RichTextRepr {
+        text: String,
+        attributes: AttributeSpans,
+        links: Vec<Link>,
+        #[serde(default)]
+        direction: Option<DirectionRepr>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "snake_case")]
+    enum DirectionRepr {
+        Natural,
+        LeftToRight,
+        RightToLeft,
+    }
+
+    impl From<WritingDirection> for DirectionRepr {
+        fn from(direction: WritingDirection) -> Self {
+            match direction {
+                WritingDirection::Natural => DirectionRepr::Natural,
+                WritingDirection::LeftToRight => DirectionRepr::LeftToRight,
+                WritingDirection::RightToLeft => DirectionRepr::RightToLeft,
+            }
+        }
+    }
+
+    impl From<DirectionRepr> for WritingDirection {
+        fn from(direction: DirectionRepr) -> Self {
+            match direction {
+                DirectionRepr::Natural => WritingDirection::Natural,
+                DirectionRepr::LeftToRight => WritingDirection::LeftToRight,
+                DirectionRepr::RightToLeft => WritingDirection::RightToLeft,
+            }
+        }
+    }
+
+    impl Serialize for RichText {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("RichText", 4)?;
+            state.serialize_field("text", &*self.buffer)?;
+            state.serialize_field("attributes", &*self.attrs)?;
+            state.serialize_field("links", &*self.links)?;
+            state.serialize_field("direction", &DirectionRepr::from(self.direction))?;
+            state.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for RichText {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let RichTextRepr {
+                text,
+                attributes,
+                links,
+                direction,
+            } = RichTextRepr::deserialize(deserializer)?;
+
+            let is_valid = |range: &Range<usize>| {
+                range.start <= range.end
+                    && text.is_char_boundary(range.start)
+                    && text.is_char_boundary(range.end)
+            };
+            if let Some((range, _)) = attributes.iter().find(|(range, _)| !is_valid(range)) {
+                return Err(D::Error::custom(format!(
+                    "attribute range {:?} is not valid for text of length {}",
+                    range,
+                    text.len()
+                )));
+            }
+            if let Some(link) = links.iter().find(|link| !is_valid(&link.range())) {
+                return Err(D::Error::custom(format!(
+                    "link range {:?} is not valid for text of length {}",
+                    link.range(),
+                    text.len()
+                )));
+            }
+
+            Ok(RichText {
+                buffer: text.into(),
+                attrs: Arc::new(attributes),
+                links: links.into(),
+                highlights: None,
+                direction: direction.map_or(WritingDirection::Natural, Into::into),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Selector;
+
+    const LINK: Selector<usize> = Selector::new("druid-test.rich-text.link");
+
+    fn link_payload(text: &RichText, idx: usize) -> usize {
+        *text.links()[idx].command.get_unchecked(LINK)
+    }
+
+    #[test]
+    fn append() {
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("hello ")
+            .weight(FontWeight::BOLD)
+            .link_unstyled(LINK.with(1));
+        let mut text = builder.build();
+
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("world")
+            .underline(true)
+            .link_unstyled(LINK.with(2));
+        text.append(&builder.build());
+
+        assert_eq!(text.as_str(), "hello world");
+        assert_eq!(text.links().len(), 2);
+        assert_eq!(text.links()[0].range(), 0..6);
+        assert_eq!(text.links()[1].range(), 6..11);
+        assert_eq!(link_payload(&text, 1), 2);
+
+        let attrs = text.attrs.to_piet_attrs(&Env::empty());
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].0, 0..6);
+        assert_eq!(attrs[1].0, 6..11);
+    }
+
+    #[test]
+    fn remove_attributes() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello ").weight(FontWeight::BOLD);
+        builder.push("world").text_color(Color::RED).underline(true);
+        let mut text = builder.build();
+
+        text.remove_attributes(3..8);
+        let attrs = text.attrs.to_piet_attrs(&Env::empty());
+        let ranges: Vec<_> = attrs.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, vec![0..3, 8..11, 8..11]);
+
+        text.remove_attribute(.., AttributeKind::Underline);
+        assert_eq!(text.attrs.to_piet_attrs(&Env::empty()).len(), 2);
+    }
+
+    #[test]
+    fn trim() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("  hello").weight(FontWeight::BOLD);
+        builder.push(" world \n").link_unstyled(LINK.with(1));
+        let text = builder.build().trim();
+
+        assert_eq!(text.as_str(), "hello world");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..5]);
+        assert_eq!(text.links()[0].range(), 5..11);
+
+        assert!(RichText::new(" \t ".into()).trim().is_empty());
+    }
+
+    #[test]
+    fn normalize_whitespace() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("a \t ").weight(FontWeight::BOLD);
+        builder.push("\n b").underline(true);
+        builder.push("  ").link_unstyled(LINK.with(1));
+        builder.push("\u{3000}c");
+        let text = builder.build().normalize_whitespace();
+
+        assert_eq!(text.as_str(), "a b c");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        // the space after "a" takes on the attributes of the first span,
+        // so the underline shrinks to just "b".
+        assert_eq!(ranges, vec![0..2, 2..3]);
+        assert_eq!(text.links()[0].range(), 3..4);
+    }
+
+    #[test]
+    fn coalesce_attributes() {
+        let mut builder = RichTextBuilder::new();
+        for token in &["let", " ", "x", " = ", "1", ";"] {
+            builder.push(token).text_color(Color::WHITE);
+        }
+        builder.push("// done").text_color(Color::grey8(0x88));
+        let mut text = builder.build();
+        assert_eq!(text.spans().count(), 7);
+
+        text.coalesce_attributes();
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..10, 10..17]);
+    }
+
+    #[test]
+    fn push_with() {
+        let mut builder = RichTextBuilder::new();
+        for (i, word) in ["a", "b", "c"].iter().enumerate() {
+            builder.push_with(word, |attrs| {
+                if i != 1 {
+                    attrs.underline(true);
+                }
+            });
+        }
+        let text = builder.build();
+
+        assert_eq!(text.as_str(), "abc");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..1, 2..3]);
+    }
+
+    #[test]
+    fn insert_str() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello").weight(FontWeight::BOLD);
+        builder.push("world").link_unstyled(LINK.with(1));
+        let mut text = builder.build();
+
+        // inside a span: the span grows
+        text.insert_str(2, "--");
+        // at a boundary: neither span grows
+        text.insert_str(7, " ");
+        assert_eq!(text.as_str(), "he--llo world");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..7]);
+        assert_eq!(text.links()[0].range(), 8..13);
+
+        // at the end of the text
+        text.insert_str(13, "!");
+        assert_eq!(text.as_str(), "he--llo world!");
+        assert_eq!(text.links()[0].range(), 8..13);
+    }
+
+    #[test]
+    fn map_ranges() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("a").link_unstyled(LINK.with(1));
+        builder.push("\tb").weight(FontWeight::BOLD);
+        builder.push("\t");
+        let mut text = builder.build();
+
+        // expand the tabs to the next multiple of four columns
+        let expanded = "a   b   ";
+        text.map_ranges(expanded, |offset| match offset {
+            0 | 1 => offset,
+            2 => 4,
+            3 => 5,
+            _ => 8,
+        });
+        assert_eq!(text.as_str(), expanded);
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![1..5]);
+        assert_eq!(text.links()[0].range(), 0..1);
+
+        // a span that becomes empty is removed
+        text.map_ranges("ab", |offset| offset.min(1));
+        assert_eq!(text.spans().count(), 0);
+        assert_eq!(text.links().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn map_ranges_not_char_boundary() {
+        let mut text = RichText::new("ab".into()).with_attribute(0..1, Attribute::underline(true));
+        text.map_ranges("\u{e9}b", |offset| offset);
+    }
+
+    #[test]
+    fn truncate_to() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("naïve ").weight(FontWeight::BOLD);
+        builder.push("café").link_unstyled(LINK.with(1));
+        let text = builder.build();
+
+        // the length is counted in grapheme clusters, not bytes.
+        assert!(text.truncate_to(10, "…").same(&text));
+
+        let truncated = text.truncate_to(3, "…");
+        assert_eq!(truncated.as_str(), "naï…");
+        // the ellipsis takes on the attributes of the text before it.
+        let spans: Vec<_> = truncated.spans().map(|(range, _)| range).collect();
+        assert_eq!(spans, vec![0..7]);
+        assert!(truncated.links().is_empty());
+
+        let truncated = text.truncate_to(8, "...");
+        assert_eq!(truncated.as_str(), "naïve ca...");
+        let spans: Vec<_> = truncated.spans().map(|(range, _)| range).collect();
+        assert_eq!(spans, vec![0..7]);
+        let links: Vec<_> = truncated.links().iter().map(Link::range).collect();
+        assert_eq!(links, vec![7..9]);
+
+        // a combining mark is kept with the character it modifies.
+        let text =
+            RichText::new("e\u{301}e\u{301}".into()).with_attribute(.., Attribute::underline(true));
+        let truncated = text.truncate_to(1, "…");
+        assert_eq!(truncated.as_str(), "e\u{301}…");
+        assert_eq!(
+            truncated.attribute_in_range(.., AttributeKind::Underline),
+            Some(Attribute::underline(true))
+        );
+    }
+
+    #[test]
+    fn retain_attributes() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello ").weight(FontWeight::BOLD);
+        builder.push("world").text_color(Color::RED).underline(true);
+        let mut text = builder.build();
+
+        text.retain_attributes(|kind| kind != AttributeKind::TextColor);
+        let kinds: Vec<_> = text.spans().map(|(_, attr)| attr.kind()).collect();
+        assert_eq!(kinds, vec![AttributeKind::Weight, AttributeKind::Underline]);
+    }
+
+    #[test]
+    fn slice() {
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("héllo ")
+            .weight(FontWeight::BOLD)
+            .link_unstyled(LINK.with(1));
+        builder
+            .push("wörld")
+            .underline(true)
+            .link_unstyled(LINK.with(2));
+        builder.push("!").link_unstyled(LINK.with(3));
+        let text = builder.build();
+
+        let sliced = text.slice(3..10);
+        assert_eq!(sliced.as_str(), "llo wö");
+        assert_eq!(sliced.links().len(), 2);
+        assert_eq!(sliced.links()[0].range(), 0..4);
+        assert_eq!(sliced.links()[1].range(), 4..7);
+        assert_eq!(link_payload(&sliced, 1), 2);
+
+        let attrs = sliced.attrs.to_piet_attrs(&Env::empty());
+        let ranges: Vec<_> = attrs.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, vec![0..4, 4..7]);
+
+        assert_eq!(text.slice(..).as_str(), text.as_str());
+        assert!(text.slice(5..5).is_empty());
+    }
+
+    #[test]
+    fn try_add_attribute() {
+        let mut text = RichText::new("héllo".into());
+        assert_eq!(
+            text.try_add_attribute(2..4, Attribute::underline(true)),
+            Err(CharBoundaryError { offset: 2 })
+        );
+        assert_eq!(text.spans().count(), 0);
+        assert!(text
+            .try_add_attribute(1..3, Attribute::underline(true))
+            .is_ok());
+        assert_eq!(text.spans().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "offset 2 is not on a char boundary")]
+    fn add_attributes_for_range_not_char_boundary() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("héllo");
+        builder.add_attributes_for_range(0..2).underline(true);
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn get_not_char_boundary() {
+        let text = RichText::new("héllo".into());
+        assert!(text.get(2..4).is_none());
+        assert!(text.get(1..3).is_some());
+        assert!(text.get(4..2).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_not_char_boundary() {
+        RichText::new("héllo".into()).slice(2..4);
+    }
+
+    #[test]
+    fn spans() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello ").underline(true);
+        builder
+            .push("world")
+            .weight(FontWeight::BOLD)
+            .underline(true);
+        let text = builder.build();
+
+        let spans: Vec<_> = text.spans().collect();
+        assert_eq!(spans.len(), 3);
+        assert!(matches!(spans[0], (ref r, Attribute::Underline(true)) if *r == (0..6)));
+        assert!(
+            matches!(spans[1], (ref r, Attribute::Weight(w)) if *r == (6..11) && *w == FontWeight::BOLD)
+        );
+        assert!(matches!(spans[2], (ref r, Attribute::Underline(true)) if *r == (6..11)));
+    }
+
+    #[test]
+    fn concat() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("naïve ").style(FontStyle::Italic);
+        let first = builder.build();
+
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("café")
+            .link_unstyled(LINK.with(1))
+            .underline(true);
+        let second = builder.build();
+
+        let text = &first + &second;
+        assert_eq!(text.as_str(), "naïve café");
+        assert_eq!(text.links()[0].range(), 7..12);
+        assert_eq!(&text.as_str()[text.links()[0].range()], "café");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..7, 7..12]);
+
+        let empty = RichText::new("".into());
+        assert!(first.concat(&empty).same(&first));
+        assert!(empty.concat(&first).same(&first));
+        assert_eq!((first + &second).as_str(), "naïve café");
+    }
+
+    #[test]
+    fn append_empty() {
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("hello")
+            .weight(FontWeight::BOLD)
+            .link_unstyled(LINK.with(1));
+        let original = builder.build();
+
+        let mut text = original.clone();
+        text.append(&RichText::new("".into()));
+        assert_eq!(text.as_str(), "hello");
+        assert_eq!(text.links().len(), 1);
+        assert_eq!(text.attrs.to_piet_attrs(&Env::empty()).len(), 1);
+
+        let mut text = RichText::new("".into());
+        text.append(&original);
+        assert_eq!(text.as_str(), "hello");
+        assert_eq!(text.links()[0].range(), 0..5);
+        assert_eq!(link_payload(&text, 0), 1);
+        assert_eq!(text.attrs.to_piet_attrs(&Env::empty()).len(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use crate::text::DESERIALIZED_LINK;
+
+        const TEXT_COLOR: crate::Key<Color> = crate::Key::new("druid-test.rich-text.color");
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello ");
+        builder
+            .push("world")
+            .size(20.0)
+            .weight(FontWeight::BOLD)
+            .text_color(TEXT_COLOR)
+            .font_features(vec![(FeatureTag::TABULAR_FIGURES, 1)])
+            .link_unstyled(LINK.with(7));
+        let text = builder
+            .build()
+            .with_direction(WritingDirection::RightToLeft);
+
+        let json = serde_json::to_string(&text).unwrap();
+        let decoded: RichText = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.as_str(), "hello world");
+        assert_eq!(decoded.direction(), WritingDirection::RightToLeft);
+        let original: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        let round_tripped: Vec<_> = decoded.spans().map(|(range, _)| range).collect();
+        assert_eq!(original, round_tripped);
+        assert!(decoded.spans().any(|(_, attr)| matches!(
+            attr,
+            Attribute::TextColor(KeyOrValue::Key(key))
+                if key.name() == "druid-test.rich-text.color"
+        )));
+        assert!(decoded
+            .spans()
+            .any(|(_, attr)| *attr
+                == Attribute::font_features(vec![(FeatureTag::TABULAR_FIGURES, 1)])));
+
+        let links = decoded.links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].range(), 6..11);
+        assert_eq!(
+            links[0].command.get(DESERIALIZED_LINK).map(String::as_str),
+            Some(LINK.symbol())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_invalid_ranges() {
+        let json = r#"{"text":"hi","attributes":[{"range":{"start":0,"end":5},"attribute":{"underline":true}}],"links":[]}"#;
+        assert!(serde_json::from_str::<RichText>(json).is_err());
+        // the direction is optional
+        let json = r#"{"text":"hi","attributes":[],"links":[]}"#;
+        let text = serde_json::from_str::<RichText>(json).unwrap();
+        assert_eq!(text.direction(), WritingDirection::Natural);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_key_names() {
+        let attribute = |i: usize| format!(r#"{{"text_color":{{"key":"druid-test.key-{}"}}}}"#, i);
+        for i in 0..1000 {
+            let json = attribute(i);
+            let attr = serde_json::from_str::<Attribute>(&json).unwrap();
+            assert_eq!(serde_json::to_string(&attr).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn push_rich_text() {
+        let mut inner = RichTextBuilder::new();
+        inner
+            .push("world")
+            .underline(true)
+            .link_unstyled(LINK.with(1));
+        let inner = inner.build();
+
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello ");
+        builder.push_rich_text(&inner).weight(FontWeight::BOLD);
+        builder.push("!").underline(true);
+        let text = builder.build();
+
+        assert_eq!(text.as_str(), "hello world!");
+        let spans: Vec<_> = text
+            .spans()
+            .map(|(range, attr)| (range, attr.kind()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (6..11, AttributeKind::Weight),
+                (6..11, AttributeKind::Underline),
+                (11..12, AttributeKind::Underline),
+            ]
+        );
+        assert_eq!(text.links()[0].range(), 6..11);
+        assert_eq!(link_payload(&text, 0), 1);
+    }
+
+    #[test]
+    fn default_attributes() {
+        let mut builder =
+            RichTextBuilder::new().with_default_attribute(Attribute::weight(FontWeight::BOLD));
+        builder.push("bold ");
+        builder.push("light").weight(FontWeight::LIGHT);
+        builder.set_default_attribute(Attribute::weight(FontWeight::THIN));
+        builder.push(" thin");
+        builder.clear_default_attributes();
+        builder.push(" plain");
+        let text = builder.build();
+
+        let weights: Vec<_> = text
+            .spans()
+            .filter_map(|(range, attr)| match attr {
+                Attribute::Weight(weight) => Some((range, *weight)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            weights,
+            vec![
+                (0..5, FontWeight::BOLD),
+                (5..10, FontWeight::LIGHT),
+                (10..15, FontWeight::THIN),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("Hello, ");
+        builder
+            .push("name")
+            .underline(true)
+            .link_unstyled(LINK.with(0));
+        builder.push("!").weight(FontWeight::BOLD);
+
+        builder
+            .insert(7, "dear ", InsertBehavior::Split)
+            .style(FontStyle::Italic);
+        builder.insert(16, "!!", InsertBehavior::Extend);
+        builder.insert(14, "-", InsertBehavior::Split);
+        let text = builder.build();
+
+        assert_eq!(text.as_str(), "Hello, dear na-me!!!");
+        let spans: Vec<_> = text
+            .spans()
+            .map(|(range, attr)| (range, attr.kind()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (7..12, AttributeKind::Style),
+                (12..14, AttributeKind::Underline),
+                (15..17, AttributeKind::Underline),
+                (19..20, AttributeKind::Weight),
+            ]
+        );
+        let links: Vec<_> = text.links().iter().map(Link::range).collect();
+        assert_eq!(links, vec![12..14, 15..17]);
+    }
+
+    #[test]
+    fn replace_range() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("one ").weight(FontWeight::BOLD);
+        builder
+            .push("two")
+            .underline(true)
+            .link_unstyled(LINK.with(2));
+        builder
+            .push(" three")
+            .style(FontStyle::Italic)
+            .link_unstyled(LINK.with(3));
+        let mut text = builder.build();
+
+        text.replace_range(2..9, "ly 2 t");
+        assert_eq!(text.as_str(), "only 2 three");
+        let spans: Vec<_> = text
+            .spans()
+            .map(|(range, attr)| (range, attr.kind()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(0..2, AttributeKind::Weight), (8..12, AttributeKind::Style)]
+        );
+        assert_eq!(text.links().len(), 1);
+        assert_eq!(text.links()[0].range(), 8..12);
+        assert_eq!(link_payload(&text, 0), 3);
+
+        text.replace_range(10..11, "");
+        assert_eq!(text.as_str(), "only 2 thre");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..2, 8..10, 10..11]);
+        let links: Vec<_> = text.links().iter().map(Link::range).collect();
+        assert_eq!(links, vec![8..10, 10..11]);
+    }
+
+    #[test]
+    fn editable_text() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("hello").weight(FontWeight::BOLD);
+        builder
+            .push(" world")
+            .underline(true)
+            .link_unstyled(LINK.with(1));
+        let mut text = builder.build();
+
+        // typing at the end of a span continues it
+        EditableText::edit(&mut text, 5..5, "!");
+        assert_eq!(text.as_str(), "hello! world");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..6, 6..12]);
+        assert_eq!(text.links()[0].range(), 6..12);
+
+        // deleting across a boundary shrinks both spans
+        EditableText::edit(&mut text, 4..8, "");
+        assert_eq!(text.as_str(), "hellorld");
+        let ranges: Vec<_> = text.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..4, 4..8]);
+        assert_eq!(text.links()[0].range(), 4..8);
+
+        // deleting the whole link removes it
+        EditableText::edit(&mut text, 4..8, "");
+        assert_eq!(text.as_str(), "hell");
+        assert!(text.links().is_empty());
+        assert_eq!(text.spans().count(), 1);
+
+        assert_eq!(text.next_word_offset(0), Some(4));
+        assert_eq!(EditableText::slice(&text, 1..3).as_deref(), Some("el"));
+    }
+
+    #[test]
+    fn push_attachment() {
+        let attachment = InlineAttachment::new((16.0, 16.0), |_, _| ());
+        let mut builder = RichTextBuilder::new().with_default_attribute(Attribute::size(20.0));
+        builder.push("a ");
+        builder
+            .push_attachment(attachment.clone())
+            .link(LINK.with(2));
+        let text = builder.build();
+
+        assert_eq!(text.as_str(), "a \u{FFFC}");
+        assert_eq!(text.attachments(), vec![(2..5, attachment)]);
+        assert_eq!(text.links()[0].range(), 2..5);
+        let placeholders = text.attachment_placeholders().unwrap();
+        assert_eq!(placeholders.as_str(), "a \u{2003}");
+        assert_eq!(placeholders.links()[0].range(), 2..5);
+
+        // a wide attachment is laid out as several placeholders, and the
+        // attributes and links are moved to match
+        let wide = InlineAttachment::new((40.0, 10.0), |_, _| ());
+        let mut builder = RichTextBuilder::new();
+        builder.push_attachment(wide).link(LINK.with(2));
+        builder.push("b").weight(FontWeight::BOLD);
+        let placeholders = builder.build().attachment_placeholders().unwrap();
+        assert_eq!(placeholders.as_str(), format!("{}b", "\u{2003}".repeat(4)));
+        assert_eq!(placeholders.links()[0].range(), 0..12);
+        let ranges: Vec<_> = placeholders.spans().map(|(range, _)| range).collect();
+        assert_eq!(ranges, vec![0..12, 0..12, 0..12, 12..13]);
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("The ");
+        builder.push("quick").weight(FontWeight::BOLD);
+        builder.push(" brown fox");
+        let old = builder.build();
+        assert!(old.diff(&old.clone()).is_empty());
+
+        // typing, as an editor would
+        let mut new = old.clone();
+        EditableText::edit(&mut new, 9..9, "ish");
+        let delta = old.diff(&new);
+        assert_eq!((delta.range(), delta.text()), (9..9, "ish"));
+        assert!(delta.attrs.is_none());
+
+        // restyling without changing the text
+        let mut restyled = new.clone();
+        restyled.add_attribute(0..3, Attribute::underline(true));
+        restyled.remove_attribute(4..9, AttributeKind::Weight);
+        let delta = new.diff(&restyled);
+        assert_eq!(delta.range(), 0..0);
+        assert_eq!(delta.attrs.as_ref().unwrap().0, 0..12);
+
+        let mut replaced = restyled.clone();
+        replaced.replace_range(13..18, "grün");
+        replaced.add_attribute(13..18, Attribute::size(20.0));
+        let delta = restyled.diff(&replaced);
+        assert_eq!((delta.range(), delta.text()), (13..17, "grü"));
+
+        // the changed range does not split the common first byte of 'ü' and 'ö'
+        let mut umlaut = replaced.clone();
+        umlaut.replace_range(15..17, "ö");
+        let delta = replaced.diff(&umlaut);
+        assert_eq!((delta.range(), delta.text()), (15..17, "ö"));
+
+        let mut text = old.clone();
+        let versions = [&old, &new, &restyled, &replaced, &umlaut];
+        for (from, to) in versions.iter().zip(&versions[1..]) {
+            text.apply(&from.diff(to));
+            assert_eq!(text.as_str(), to.as_str());
+            assert_eq!(
+                text.spans().collect::<Vec<_>>(),
+                to.spans().collect::<Vec<_>>()
+            );
+        }
+
+        // and back again
+        text.apply(&umlaut.diff(&old));
+        assert_eq!(text.as_str(), old.as_str());
+        assert_eq!(
+            text.spans().collect::<Vec<_>>(),
+            old.spans().collect::<Vec<_>>()
+        );
+
+        // changing only the direction
+        let rtl = old.clone().with_direction(WritingDirection::RightToLeft);
+        let delta = old.diff(&rtl);
+        assert!(!delta.is_empty());
+        text.apply(&delta);
+        assert_eq!(text.direction(), WritingDirection::RightToLeft);
+        assert!(rtl.diff(&text).is_empty());
+    }
+
+    #[test]
+    fn highlight_matches() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("Find the ");
+        builder
+            .push("FIND")
+            .background_color(Color::BLACK)
+            .weight(FontWeight::BOLD);
+        builder.push(" in Straße, finder!");
+        let mut text = builder.build();
+        let original = text.clone();
+
+        let highlight = Attribute::background_color(Color::WHITE);
+        let ranges = text.highlight_matches("fInD", &[highlight.clone()]);
+        assert_eq!(ranges, vec![0..4, 9..13, 26..30]);
+        let highlighted: Vec<_> = text
+            .spans()
+            .filter(|(_, attr)| attr.kind() == AttributeKind::BackgroundColor)
+            .map(|(range, _)| range)
+            .collect();
+        assert_eq!(highlighted, vec![0..4, 9..13, 26..30]);
+
+        // 'ß' case folds to "ss"
+        assert_eq!(text.highlight_matches("STRASSE", &[]), vec![17..24]);
+        assert_eq!(text.highlight_matches("straße", &[]), vec![17..24]);
+        assert_eq!(case_insensitive_matches("ﬁnd", "FIND"), vec![0..5]);
+
+        text.highlight_matches("find", &[highlight.clone()]);
+        text.clear_highlights();
+        assert_eq!(
+            text.spans().collect::<Vec<_>>(),
+            original.spans().collect::<Vec<_>>()
+        );
+
+        // attributes added after highlighting are kept when clearing
+        text.highlight_matches("find", &[highlight]);
+        let added = Attribute::background_color(Color::RED);
+        text.add_attribute(0..2, added.clone());
+        text.add_attribute(26..30, Attribute::underline(true));
+        text.clear_highlights();
+        let backgrounds: Vec<_> = text
+            .spans()
+            .filter(|(_, attr)| attr.kind() == AttributeKind::BackgroundColor)
+            .collect();
+        assert_eq!(
+            backgrounds,
+            vec![
+                (0..2, &added),
+                (9..13, &Attribute::background_color(Color::BLACK)),
+            ]
+        );
+        assert!(text
+            .spans()
+            .any(|(range, attr)| range == (26..30) && *attr == Attribute::underline(true)));
+
+        // the case folding of 'İ' is two characters, and a match cannot
+        // start or end between them
+        assert_eq!(case_insensitive_matches("İx ix", "ix"), vec![4..6]);
+        assert!(case_insensitive_matches("İx", "\u{307}x").is_empty());
+        assert_eq!(case_insensitive_matches("aaa", "aa"), vec![0..2]);
+        assert!(case_insensitive_matches("abc", "").is_empty());
+    }
+
+    #[test]
+    fn try_write_fmt() {
+        struct Failing;
+
+        impl std::fmt::Display for Failing {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("partial")?;
+                Err(std::fmt::Error)
+            }
+        }
+
+        let mut builder = RichTextBuilder::with_capacity(16);
+        builder.push("a");
+        assert!(builder.try_write_fmt(format_args!("b{}", Failing)).is_err());
+        builder
+            .try_write_fmt(format_args!("{}", 2))
+            .unwrap()
+            .underline(true);
+        let text = builder.build();
+        assert_eq!(text.as_str(), "a2");
+        assert_eq!(text.spans().next().unwrap().0, 1..2);
+    }
+
+    #[test]
+    fn list_items() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("Intro");
+        builder.push_list_item(ListMarker::Number, 0, "one");
+        builder.push_list_item(ListMarker::Number, 1, "nested");
+        builder.push_list_item(ListMarker::Number, 1, "nested");
+        builder
+            .push_list_item(ListMarker::Number, 0, "two")
+            .weight(FontWeight::BOLD);
+        builder.push("\n");
+        builder.push_list_item(ListMarker::Number, 1, "restarted");
+        builder.push_list_item(ListMarker::Bullet, 0, "bullet");
+        builder.push("\nOutro\n");
+        builder.push_list_item(ListMarker::Number, 0, "new list");
+        let text = builder.build();
+
+        assert_eq!(
+            text.as_str(),
+            "Intro\n1. one\n1. nested\n2. nested\n2. two\n1. restarted\n\u{2022} bullet\nOutro\n1. new list"
+        );
+
+        let paragraphs = text.paragraphs().unwrap();
+        assert_eq!(paragraphs.len(), 9);
+        assert_eq!(paragraphs[0].1, ParagraphStyle::default());
+        assert_eq!(
+            paragraphs[2],
+            (
+                13..22,
+                ParagraphStyle {
+                    indent: LIST_INDENT,
+                    hanging_indent: 2.0 * LIST_INDENT,
+                    marker_len: 3,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_eq!(paragraphs[6].1.marker_len, "\u{2022} ".len());
+
+        let bold: Vec<_> = text
+            .spans()
+            .filter(|(_, attr)| attr.kind() == AttributeKind::Weight)
+            .map(|(range, _)| range)
+            .collect();
+        assert_eq!(bold, vec![36..39]);
+    }
+
+    #[test]
+    fn paragraphs() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("plain\r\n");
+        assert!(builder.build().paragraphs().is_none());
+
+        let mut builder = RichTextBuilder::new();
+        builder
+            .push("Title\n")
+            .alignment(TextAlignment::Center)
+            .space_after(6.0);
+        builder.push("body").indent(10.0);
+        builder.push("\r\n\nend");
+        let text = builder.build();
+
+        let paragraphs = text.paragraphs().unwrap();
+        let ranges: Vec<_> = paragraphs.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, vec![0..5, 6..10, 12..12, 13..16]);
+        assert_eq!(paragraphs[0].1.alignment, Some(TextAlignment::Center));
+        assert_eq!(paragraphs[0].1.space_after, 6.0);
+        assert_eq!(paragraphs[1].1.indent, 10.0);
+        assert_eq!(paragraphs[2].1, ParagraphStyle::default());
+
+        let body = text.paragraph_text(6..10).unwrap();
+        assert_eq!(body.as_str(), "body");
+        assert_eq!(body.paragraphs().unwrap()[0].1.indent, 10.0);
+    }
+
+    #[test]
+    fn callback_links() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("one").link_unstyled(LINK.with(1));
+        builder.push(" two").on_click(|_, _| ());
+        let text = builder.build();
+
+        let links = text.links();
+        assert_eq!(links.len(), 2);
+        assert_eq!(link_payload(&text, 0), 1);
+        assert!(links[1].command.is(Selector::NOOP));
+        assert_eq!(links[1].range(), 3..7);
+    }
+
+    #[test]
+    fn link_state_restyles_link() {
+        let env = Env::empty()
+            .adding(crate::theme::UI_FONT, FontDescriptor::default())
+            .adding(crate::theme::TEXT_COLOR, Color::BLACK)
+            .adding(crate::theme::LINK_COLOR, Color::BLUE);
+        let mut builder = RichTextBuilder::new();
+        builder.push("a ");
+        builder
+            .push("link")
+            .link(LINK.with(1))
+            .link_hover_color(Color::RED)
+            .link_active_color(Color::WHITE);
+        builder.push(" b").link(LINK.with(2));
+        let text = builder.build();
+        assert_eq!(text.resolved_style_at(3, &env).text_color, Color::BLUE);
+
+        let hovered = text.with_link_state(0, LinkState::Hovered).unwrap();
+        assert_eq!(hovered.resolved_style_at(3, &env).text_color, Color::RED);
+        assert_eq!(hovered.resolved_style_at(0, &env).text_color, Color::BLACK);
+        assert_eq!(hovered.resolved_style_at(7, &env).text_color, Color::BLUE);
+
+        let pressed = text.with_link_state(0, LinkState::Pressed).unwrap();
+        assert_eq!(pressed.resolved_style_at(3, &env).text_color, Color::WHITE);
+
+        // a link without colors, or a missing link, is left alone.
+        assert!(text.with_link_state(1, LinkState::Hovered).is_none());
+        assert!(text.with_link_state(2, LinkState::Hovered).is_none());
     }
 }
@@ -14,13 +14,16 @@
 
 //! Storing text.
 
+use std::ops::Range;
 use std::sync::Arc;
 
 use crate::env::KeyLike;
-use crate::piet::{PietTextLayoutBuilder, TextStorage as PietTextStorage};
+use crate::piet::{Color, PietTextLayoutBuilder, TextStorage as PietTextStorage};
 use crate::{Data, Env};
 
-use super::attribute::Link;
+use super::attachment::InlineAttachment;
+use super::attribute::{Link, LinkState, ParagraphStyle};
+use super::WritingDirection;
 use crate::UpdateCtx;
 
 /// A type that represents text that can be displayed.
@@ -32,6 +35,200 @@ pub trait TextStorage: PietTextStorage + Data {
         builder
     }
 
+    /// Any background colors that should be painted behind this text.
+    ///
+    /// Background colors are not supported by [`piet`]; if this `TextStorage`
+    /// object manages background colors it should implement this method,
+    /// and the colors will be painted behind the glyphs in each range.
+    ///
+    /// Where ranges overlap, later items are painted on top of earlier ones.
+    ///
+    /// [`piet`]: https://docs.rs/piet
+    #[allow(unused_variables)]
+    fn background_colors(&self, env: &Env) -> Vec<(Range<usize>, Color)> {
+        Vec::new()
+    }
+
+    /// Returns a copy of this text with the font size of any spans that may
+    /// shrink to fit the available width multiplied by `scale`.
+    ///
+    /// `default_size` is the font size of text without an explicit size.
+    /// Implementations should not reduce sizes below the minimum of each span.
+    ///
+    /// If this `TextStorage` object has no such spans it should return `None`,
+    /// which is the default.
+    #[allow(unused_variables)]
+    fn scaled_to_fit(&self, env: &Env, default_size: f64, scale: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Returns a copy of this text with any [`Attribute::FontScale`] applied
+    /// to the font size of the text it covers.
+    ///
+    /// `default_size` is the font size of text without an explicit size.
+    ///
+    /// If this `TextStorage` object has no such spans it should return `None`,
+    /// which is the default.
+    ///
+    /// [`Attribute::FontScale`]: super::Attribute::FontScale
+    #[allow(unused_variables)]
+    fn resolve_font_scales(&self, env: &Env, default_size: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Returns a copy of this text with synthesized small capitals, if it
+    /// contains any [`FontVariant::SmallCaps`] text.
+    ///
+    /// The returned text is used for layout in place of this one, and must
+    /// have the same length, with `char` boundaries at the same offsets.
+    /// `default_size` is the font size of text without an explicit size.
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// [`FontVariant::SmallCaps`]: super::FontVariant::SmallCaps
+    #[allow(unused_variables)]
+    fn small_caps(&self, env: &Env, default_size: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Returns a copy of this text with the [`Link`] at `index` restyled to
+    /// show its `state`, such as by giving it the link's [`hover_color`].
+    ///
+    /// The returned text is used for layout in place of this one, and must
+    /// have the same length. The index refers to the slice returned by
+    /// [`links`].
+    ///
+    /// The default implementation returns `None`, and the link is not
+    /// restyled.
+    ///
+    /// [`Link`]: super::attribute::Link
+    /// [`hover_color`]: super::attribute::Link::hover_color
+    /// [`links`]: TextStorage::links
+    #[allow(unused_variables)]
+    fn with_link_state(&self, index: usize, state: LinkState) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Any runs of this text that are raised or lowered from the baseline,
+    /// and the distance in points by which each is raised; the distance is
+    /// negative for text that is lowered.
+    ///
+    /// `default_size` is the font size of text without an explicit size.
+    /// If this `TextStorage` object manages baseline shifts, it should
+    /// implement this method and [`hide_shifted_text`]; the [`TextLayout`]
+    /// lays out the text with the runs hidden, and draws each run over the
+    /// space it takes, at the shifted position.
+    ///
+    /// [`hide_shifted_text`]: TextStorage::hide_shifted_text
+    /// [`TextLayout`]: super::TextLayout
+    #[allow(unused_variables)]
+    fn baseline_shifts(&self, env: &Env, default_size: f64) -> Vec<(Range<usize>, f64)> {
+        Vec::new()
+    }
+
+    /// Returns a copy of this text in which the runs returned by
+    /// [`baseline_shifts`] are laid out as usual, but not drawn, such as by
+    /// making them transparent.
+    ///
+    /// The returned text is used for layout in place of this one, and must
+    /// have the same length. The default implementation returns `None`.
+    ///
+    /// [`baseline_shifts`]: TextStorage::baseline_shifts
+    fn hide_shifted_text(&self) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Any [`InlineAttachment`]s in this text, and the ranges of the
+    /// characters they are displayed in place of.
+    ///
+    /// If this `TextStorage` object manages attachments, it should implement
+    /// this method and [`attachment_placeholders`]; the [`TextLayout`] paints
+    /// each attachment over its placeholder.
+    ///
+    /// [`attachment_placeholders`]: TextStorage::attachment_placeholders
+    /// [`TextLayout`]: super::TextLayout
+    fn attachments(&self) -> Vec<(Range<usize>, InlineAttachment)> {
+        Vec::new()
+    }
+
+    /// Returns a copy of this text in which the characters that have
+    /// [`attachments`] are replaced by placeholders that reserve the space of
+    /// each attachment.
+    ///
+    /// The returned text is used for layout in place of this one. Each
+    /// [`OBJECT_REPLACEMENT_CHARACTER`] in the range of an attachment must be
+    /// replaced by the attachment's [`placeholder`], at its
+    /// [`placeholder_font_size`], with the other text unchanged and any
+    /// attributes moved to match.
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// [`attachments`]: TextStorage::attachments
+    /// [`OBJECT_REPLACEMENT_CHARACTER`]: super::OBJECT_REPLACEMENT_CHARACTER
+    /// [`placeholder`]: InlineAttachment::placeholder
+    /// [`placeholder_font_size`]: InlineAttachment::placeholder_font_size
+    fn attachment_placeholders(&self) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The paragraphs of this text and their styles, if this text has any
+    /// attributes that apply to whole paragraphs.
+    ///
+    /// A paragraph is the text between two line breaks, not including the
+    /// line break itself. If this returns `Some`, the [`TextLayout`] lays out
+    /// each paragraph separately, using the text returned by
+    /// [`paragraph_text`], and stacks them vertically; implementations must
+    /// also implement [`paragraph_text`].
+    ///
+    /// The default implementation returns `None`, and the text is laid out
+    /// as a whole.
+    ///
+    /// [`TextLayout`]: super::TextLayout
+    /// [`paragraph_text`]: TextStorage::paragraph_text
+    fn paragraphs(&self) -> Option<Vec<(Range<usize>, ParagraphStyle)>> {
+        None
+    }
+
+    /// Returns a copy of the text in `range`, with its attributes.
+    ///
+    /// This is used to lay out the [`paragraphs`] of text separately. The
+    /// default implementation returns `None`.
+    ///
+    /// [`paragraphs`]: TextStorage::paragraphs
+    #[allow(unused_variables)]
+    fn paragraph_text(&self, range: Range<usize>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The base direction of this text.
+    ///
+    /// The default implementation returns [`WritingDirection::Natural`], and
+    /// the direction is that of the first character with a strong direction.
+    fn writing_direction(&self) -> WritingDirection {
+        WritingDirection::Natural
+    }
+
     /// This is called whenever the Env changes and should return true
     /// if the layout should be rebuilt.
     #[allow(unused_variables)]
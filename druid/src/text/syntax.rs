@@ -0,0 +1,430 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Syntax highlighting with [`syntect`].
+//!
+//! [`syntect`]: https://docs.rs/syntect
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+
+use super::editable_text;
+use super::{EditableText, EnvUpdateCtx, StringCursor, TextStorage};
+use crate::piet::{
+    Color, FontStyle, FontWeight, PietTextLayoutBuilder, TextAttribute, TextLayoutBuilder,
+    TextStorage as PietTextStorage,
+};
+use crate::{theme, ArcStr, Data, Env, Key};
+
+thread_local! {
+    static SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+}
+
+/// Source code that is highlighted according to its language.
+///
+/// The text is parsed with the syntax definitions bundled with [`syntect`],
+/// and each token is classified as a comment, keyword, string, constant,
+/// type or function name. These are drawn with the colors of the
+/// corresponding `SYNTAX_*` keys in the [`theme`], such as
+/// [`SYNTAX_KEYWORD_COLOR`], so that the highlighting follows the current
+/// [`Env`]; keywords are also drawn in bold, and comments in italics.
+///
+/// Highlighting is done when the text is first laid out, and is cached
+/// until the text changes. When the text is edited through [`EditableText`],
+/// for instance in a [`TextBox`], only the lines from the edit onwards are
+/// highlighted again.
+///
+/// This type is only available with the `syntect` feature.
+///
+/// # Examples
+///
+/// ```
+/// use druid::text::SyntaxHighlightedText;
+/// use druid::widget::RawLabel;
+/// use druid::{FontDescriptor, FontFamily, Widget};
+///
+/// fn code_view() -> impl Widget<SyntaxHighlightedText> {
+///     RawLabel::new().with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+/// }
+///
+/// let source = SyntaxHighlightedText::new("fn main() {}", "rust");
+/// # let _ = (code_view(), source);
+/// ```
+///
+/// [`syntect`]: https://docs.rs/syntect
+/// [`SYNTAX_KEYWORD_COLOR`]: crate::theme::SYNTAX_KEYWORD_COLOR
+/// [`TextBox`]: crate::widget::TextBox
+#[derive(Clone)]
+pub struct SyntaxHighlightedText {
+    source: ArcStr,
+    language: ArcStr,
+    cache: Rc<RefCell<HighlightCache>>,
+}
+
+/// The kinds of token that are highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Comment,
+    Keyword,
+    String,
+    Constant,
+    Type,
+    Function,
+}
+
+/// Scope prefixes and the categories they belong to, in order of priority.
+const SCOPE_CATEGORIES: &[(&str, Category)] = &[
+    ("comment", Category::Comment),
+    ("string", Category::String),
+    ("constant", Category::Constant),
+    ("entity.name.function", Category::Function),
+    ("support.function", Category::Function),
+    ("variable.function", Category::Function),
+    ("entity.name.type", Category::Type),
+    ("entity.name.class", Category::Type),
+    ("entity.name.struct", Category::Type),
+    ("entity.name.enum", Category::Type),
+    ("support.type", Category::Type),
+    ("support.class", Category::Type),
+    ("keyword", Category::Keyword),
+    ("storage", Category::Keyword),
+];
+
+/// The highlighted lines at the start of the text.
+#[derive(Clone, Default)]
+struct HighlightCache {
+    lines: Vec<HighlightedLine>,
+}
+
+#[derive(Clone)]
+struct HighlightedLine {
+    /// The range of the line, including its newline.
+    range: Range<usize>,
+    spans: Vec<(Range<usize>, Category)>,
+    /// The parser state at the end of the line.
+    parse_state: ParseState,
+    scopes: ScopeStack,
+}
+
+impl SyntaxHighlightedText {
+    /// Create a new `SyntaxHighlightedText`.
+    ///
+    /// `language` is the name of the language, such as `"Rust"`, or a file
+    /// extension, such as `"rs"`; case is ignored. If the language is not
+    /// recognized, the text is not highlighted.
+    pub fn new(source: impl Into<ArcStr>, language: impl Into<ArcStr>) -> Self {
+        SyntaxHighlightedText {
+            source: source.into(),
+            language: language.into(),
+            cache: Default::default(),
+        }
+    }
+
+    /// The language this text is highlighted as.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Returns the highlighted ranges of the text, highlighting any lines
+    /// that are not already cached.
+    fn spans(&self) -> Vec<(Range<usize>, Category)> {
+        let mut cache = self.cache.borrow_mut();
+        cache.update(&self.source, &self.language);
+        cache
+            .lines
+            .iter()
+            .flat_map(|line| line.spans.iter().cloned())
+            .collect()
+    }
+}
+
+impl HighlightCache {
+    /// Highlight any lines of `text` after those that are already cached.
+    fn update(&mut self, text: &str, language: &str) {
+        let mut start = self.lines.last().map_or(0, |line| line.range.end);
+        if start >= text.len() {
+            return;
+        }
+
+        SYNTAX_SET.with(|syntax_set| {
+            let (mut parse_state, mut scopes) = match self.lines.last() {
+                Some(line) => (line.parse_state.clone(), line.scopes.clone()),
+                None => {
+                    let syntax = syntax_set
+                        .find_syntax_by_token(language)
+                        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+                    (ParseState::new(syntax), ScopeStack::new())
+                }
+            };
+
+            while start < text.len() {
+                let end = text[start..]
+                    .find('\n')
+                    .map_or(text.len(), |i| start + i + 1);
+                let mut spans = Vec::new();
+                let mut pos = start;
+                for (idx, op) in parse_state.parse_line(&text[start..end], syntax_set) {
+                    push_span(&mut spans, pos..start + idx, &scopes);
+                    scopes.apply(&op);
+                    pos = start + idx;
+                }
+                push_span(&mut spans, pos..end, &scopes);
+
+                self.lines.push(HighlightedLine {
+                    range: start..end,
+                    spans,
+                    parse_state: parse_state.clone(),
+                    scopes: scopes.clone(),
+                });
+                start = end;
+            }
+        });
+    }
+
+    /// The number of cached lines that end before `offset`, and so are not
+    /// affected by an edit there.
+    fn lines_before(&self, offset: usize) -> usize {
+        self.lines
+            .iter()
+            .take_while(|line| line.range.end < offset)
+            .count()
+    }
+}
+
+/// Add a span for `range` if the innermost scope that belongs to a category
+/// does, extending the previous span if it has the same category.
+fn push_span(spans: &mut Vec<(Range<usize>, Category)>, range: Range<usize>, scopes: &ScopeStack) {
+    if range.is_empty() {
+        return;
+    }
+    let category = scopes.as_slice().iter().rev().find_map(|scope| {
+        let name = scope.build_string();
+        SCOPE_CATEGORIES
+            .iter()
+            .find(|(prefix, _)| {
+                name.strip_prefix(*prefix)
+                    .map_or(false, |rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map(|(_, category)| *category)
+    });
+    match (category, spans.last_mut()) {
+        (Some(category), Some((prev, prev_category)))
+            if prev.end == range.start && *prev_category == category =>
+        {
+            prev.end = range.end
+        }
+        (Some(category), _) => spans.push((range, category)),
+        (None, _) => (),
+    }
+}
+
+impl Category {
+    const ALL: [Category; 6] = [
+        Category::Comment,
+        Category::Keyword,
+        Category::String,
+        Category::Constant,
+        Category::Type,
+        Category::Function,
+    ];
+
+    fn color_key(self) -> Key<Color> {
+        match self {
+            Category::Comment => theme::SYNTAX_COMMENT_COLOR,
+            Category::Keyword => theme::SYNTAX_KEYWORD_COLOR,
+            Category::String => theme::SYNTAX_STRING_COLOR,
+            Category::Constant => theme::SYNTAX_CONSTANT_COLOR,
+            Category::Type => theme::SYNTAX_TYPE_COLOR,
+            Category::Function => theme::SYNTAX_FUNCTION_COLOR,
+        }
+    }
+}
+
+impl fmt::Debug for SyntaxHighlightedText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyntaxHighlightedText")
+            .field("source", &self.source)
+            .field("language", &self.language)
+            .finish()
+    }
+}
+
+impl Data for SyntaxHighlightedText {
+    fn same(&self, other: &Self) -> bool {
+        self.source.same(&other.source) && self.language.same(&other.language)
+    }
+}
+
+impl PietTextStorage for SyntaxHighlightedText {
+    fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl TextStorage for SyntaxHighlightedText {
+    fn add_attributes(
+        &self,
+        mut builder: PietTextLayoutBuilder,
+        env: &Env,
+    ) -> PietTextLayoutBuilder {
+        for (range, category) in self.spans() {
+            let color = env.get(category.color_key());
+            builder = builder.range_attribute(range.clone(), TextAttribute::TextColor(color));
+            match category {
+                Category::Keyword => {
+                    builder =
+                        builder.range_attribute(range, TextAttribute::Weight(FontWeight::BOLD))
+                }
+                Category::Comment => {
+                    builder =
+                        builder.range_attribute(range, TextAttribute::Style(FontStyle::Italic))
+                }
+                _ => (),
+            }
+        }
+        builder
+    }
+
+    fn env_update(&self, ctx: &EnvUpdateCtx) -> bool {
+        Category::ALL
+            .iter()
+            .any(|category| ctx.env_key_changed(&category.color_key()))
+    }
+}
+
+impl EditableText for SyntaxHighlightedText {
+    fn cursor(&self, position: usize) -> Option<StringCursor> {
+        StringCursor::new(&self.source, position)
+    }
+
+    fn edit(&mut self, range: Range<usize>, new: impl Into<String>) {
+        let new = new.into();
+        if range.is_empty() && new.is_empty() {
+            return;
+        }
+        let mut source = self.source.to_string();
+        source.replace_range(range.clone(), &new);
+        self.source = source.into();
+
+        // Lines before the edit are unaffected, so we keep their highlighting.
+        let keep = self.cache.borrow().lines_before(range.start);
+        match Rc::get_mut(&mut self.cache) {
+            Some(cache) => cache.get_mut().lines.truncate(keep),
+            None => {
+                let lines = self.cache.borrow().lines[..keep].to_vec();
+                self.cache = Rc::new(RefCell::new(HighlightCache { lines }));
+            }
+        }
+    }
+
+    fn slice(&self, range: Range<usize>) -> Option<Cow<str>> {
+        self.source.get(range).map(Cow::from)
+    }
+
+    fn len(&self) -> usize {
+        self.source.len()
+    }
+
+    fn prev_word_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_word_offset(&self.source, offset)
+    }
+
+    fn next_word_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_word_offset(&self.source, offset)
+    }
+
+    fn prev_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_grapheme_offset(&self.source, offset)
+    }
+
+    fn next_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_grapheme_offset(&self.source, offset)
+    }
+
+    fn prev_codepoint_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::prev_codepoint_offset(&self.source, offset)
+    }
+
+    fn next_codepoint_offset(&self, offset: usize) -> Option<usize> {
+        editable_text::next_codepoint_offset(&self.source, offset)
+    }
+
+    fn preceding_line_break(&self, offset: usize) -> usize {
+        editable_text::preceding_line_break(&self.source, offset)
+    }
+
+    fn next_line_break(&self, offset: usize) -> usize {
+        editable_text::next_line_break(&self.source, offset)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    fn from_str(s: &str) -> Self {
+        SyntaxHighlightedText::new(s, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category_at(text: &SyntaxHighlightedText, offset: usize) -> Option<Category> {
+        text.spans()
+            .into_iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(_, category)| category)
+    }
+
+    #[test]
+    fn highlight_rust() {
+        let text = SyntaxHighlightedText::new("fn main() {\n    // hi\n    \"str\"\n}\n", "rust");
+        assert_eq!(category_at(&text, 0), Some(Category::Keyword));
+        assert_eq!(category_at(&text, 3), Some(Category::Function));
+        assert_eq!(category_at(&text, 20), Some(Category::Comment));
+        assert_eq!(category_at(&text, 28), Some(Category::String));
+        assert_eq!(category_at(&text, 10), None);
+    }
+
+    #[test]
+    fn unknown_language() {
+        let text = SyntaxHighlightedText::new("fn main() {}", "not a language");
+        assert_eq!(category_at(&text, 0), None);
+    }
+
+    #[test]
+    fn edits_rehighlight_from_edited_line() {
+        let mut text = SyntaxHighlightedText::new("fn a() {}\nlet x = 1;\n", "rs");
+        assert_eq!(category_at(&text, 18), Some(Category::Constant));
+        assert_eq!(text.cache.borrow().lines.len(), 2);
+
+        text.edit(18..19, "\"1\"");
+        assert_eq!(text.as_str(), "fn a() {}\nlet x = \"1\";\n");
+        assert_eq!(text.cache.borrow().lines.len(), 1);
+        assert_eq!(category_at(&text, 18), Some(Category::String));
+
+        // clones share the cache until one of them is edited
+        let clone = text.clone();
+        text.edit(0..2, "");
+        assert_eq!(clone.cache.borrow().lines.len(), 2);
+        assert_eq!(text.cache.borrow().lines.len(), 0);
+        assert_eq!(category_at(&text, 0), None);
+    }
+}
@@ -0,0 +1,496 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The undo history of an [`EditSession`].
+//!
+//! [`EditSession`]: super::EditSession
+
+use std::collections::VecDeque;
+
+use super::{EditableText, Selection, TextStorage};
+
+/// The default maximum number of undo units kept by an [`UndoStack`].
+pub(crate) const DEFAULT_UNDO_LIMIT: usize = 100;
+
+/// The kind of an edit, used to decide which edits are grouped together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    /// Text inserted by typing.
+    Typing,
+    /// Text removed with backspace or delete.
+    Deletion,
+    /// Text that is being, or has just been, composed by an input method.
+    Composition,
+    /// Any other edit; these are never grouped.
+    Other,
+}
+
+/// A single undo unit: the text it replaced, and the selection before and
+/// after it.
+#[derive(Debug, Clone)]
+struct UndoEntry {
+    kind: EditKind,
+    /// The offset at which the text was replaced.
+    start: usize,
+    /// The text that was removed.
+    old: String,
+    /// The text that was inserted in its place.
+    new: String,
+    before_sel: Selection,
+    after_sel: Selection,
+}
+
+/// A bounded history of edits that can be undone and redone.
+///
+/// Each entry stores only the text that was replaced and its replacement,
+/// so recording an edit costs time in proportion to the size of the edit,
+/// not of the whole text. Undoing an edit restores the characters it
+/// removed, but not any attributes they had.
+#[derive(Debug, Clone)]
+pub(crate) struct UndoStack<T> {
+    undo: VecDeque<UndoEntry>,
+    redo: Vec<UndoEntry>,
+    limit: usize,
+    /// Whether the next edit may be merged into the most recent entry.
+    open: bool,
+    /// The text as it was after the most recent edit, undo or redo.
+    current: Option<T>,
+}
+
+impl<T> UndoStack<T> {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.undo.len() > limit {
+            self.undo.pop_front();
+        }
+        self.redo.truncate(limit);
+    }
+
+    /// Ensure that the next edit starts a new undo unit.
+    pub fn close_group(&mut self) {
+        self.open = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = false;
+        self.current = None;
+    }
+}
+
+impl<T: TextStorage + EditableText> UndoStack<T> {
+    /// Returns `true` if `text` is the text left by the most recent edit,
+    /// undo, or redo recorded here.
+    pub fn is_current(&self, text: &T) -> bool {
+        self.current
+            .as_ref()
+            .map(|current| current.same(text))
+            .unwrap_or(false)
+    }
+
+    /// Record an edit made by the platform's text input.
+    ///
+    /// Consecutive typing is grouped into one unit per word, consecutive
+    /// deletions are grouped together, and everything typed while an input
+    /// method is `composing` becomes a single unit when the composition ends.
+    pub fn record_input(
+        &mut self,
+        before: &T,
+        before_sel: Selection,
+        after: &T,
+        after_sel: Selection,
+        composing: bool,
+    ) {
+        let (prefix, removed, inserted) = diff(before.as_str(), after.as_str());
+        let last_kind = self.undo.back().map(|entry| entry.kind);
+        let kind = if composing || (self.open && last_kind == Some(EditKind::Composition)) {
+            EditKind::Composition
+        } else if inserted.is_empty() && !removed.is_empty() {
+            EditKind::Deletion
+        } else if !inserted.is_empty() && !inserted.contains('\n') {
+            EditKind::Typing
+        } else {
+            EditKind::Other
+        };
+
+        let merge = self.open
+            && last_kind == Some(kind)
+            && match kind {
+                EditKind::Composition => true,
+                EditKind::Typing => {
+                    let after_space = before.as_str()[..prefix]
+                        .chars()
+                        .next_back()
+                        .map(char::is_whitespace)
+                        .unwrap_or(false);
+                    let starts_word = inserted
+                        .chars()
+                        .next()
+                        .map(|c| !c.is_whitespace())
+                        .unwrap_or(false);
+                    self.undo.back().map(|last| last.after_sel) == Some(before_sel)
+                        && !(after_space && starts_word)
+                }
+                EditKind::Deletion => {
+                    self.undo.back().map(|last| last.after_sel) == Some(before_sel)
+                }
+                EditKind::Other => false,
+            };
+
+        let merged = match self.undo.back_mut() {
+            Some(last) if merge => {
+                last.extend(before.as_str(), prefix, removed, inserted, after_sel)
+            }
+            _ => false,
+        };
+        if merged {
+            self.redo.clear();
+            self.current = Some(after.clone());
+        } else {
+            let entry = UndoEntry {
+                kind,
+                start: prefix,
+                old: removed.to_owned(),
+                new: inserted.to_owned(),
+                before_sel,
+                after_sel,
+            };
+            self.push(entry, after);
+        }
+        self.open = kind != EditKind::Other && (kind != EditKind::Composition || composing);
+    }
+
+    /// Record an edit, such as a paste, that is always its own undo unit.
+    pub fn record_unit(
+        &mut self,
+        before: &T,
+        before_sel: Selection,
+        after: &T,
+        after_sel: Selection,
+    ) {
+        let (prefix, removed, inserted) = diff(before.as_str(), after.as_str());
+        let entry = UndoEntry {
+            kind: EditKind::Other,
+            start: prefix,
+            old: removed.to_owned(),
+            new: inserted.to_owned(),
+            before_sel,
+            after_sel,
+        };
+        self.push(entry, after);
+        self.open = false;
+    }
+
+    /// Undo the most recent unit in `text`, returning the text and selection
+    /// from before it.
+    pub fn undo(&mut self, text: &T) -> Option<(T, Selection)> {
+        let entry = self.undo.pop_back()?;
+        let mut text = text.clone();
+        text.edit(
+            entry.start..entry.start + entry.new.len(),
+            entry.old.as_str(),
+        );
+        let selection = entry.before_sel;
+        self.current = Some(text.clone());
+        self.redo.push(entry);
+        self.open = false;
+        Some((text, selection))
+    }
+
+    /// Redo the most recently undone unit in `text`, returning the text and
+    /// selection from after it.
+    pub fn redo(&mut self, text: &T) -> Option<(T, Selection)> {
+        let entry = self.redo.pop()?;
+        let mut text = text.clone();
+        text.edit(
+            entry.start..entry.start + entry.old.len(),
+            entry.new.as_str(),
+        );
+        let selection = entry.after_sel;
+        self.current = Some(text.clone());
+        self.undo.push_back(entry);
+        self.open = false;
+        Some((text, selection))
+    }
+
+    fn push(&mut self, entry: UndoEntry, after: &T) {
+        self.redo.clear();
+        self.current = Some(after.clone());
+        self.undo.push_back(entry);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+}
+
+impl UndoEntry {
+    /// Extend this entry to include a later edit, which replaced `removed`
+    /// with `inserted` at `offset` in `text`, the text left by this entry.
+    ///
+    /// Returns `false`, and leaves the entry unchanged, if the edit does not
+    /// touch the text inserted by this entry.
+    fn extend(
+        &mut self,
+        text: &str,
+        offset: usize,
+        removed: &str,
+        inserted: &str,
+        after_sel: Selection,
+    ) -> bool {
+        let end = self.start + self.new.len();
+        let edit_end = offset + removed.len();
+        if offset > end || edit_end < self.start {
+            return false;
+        }
+        // the unit now covers both edits; text around this entry's
+        // replacement that the new edit changed is added to both sides.
+        let start = self.start.min(offset);
+        let region_end = end.max(edit_end);
+        self.old.insert_str(0, &text[start..self.start]);
+        self.old.push_str(&text[end..region_end]);
+        let mut new = String::with_capacity(region_end - start + inserted.len());
+        new.push_str(&text[start..offset]);
+        new.push_str(inserted);
+        new.push_str(&text[edit_end..region_end]);
+        self.start = start;
+        self.new = new;
+        self.after_sel = after_sel;
+        true
+    }
+}
+
+impl<T> Default for UndoStack<T> {
+    fn default() -> Self {
+        UndoStack {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit: DEFAULT_UNDO_LIMIT,
+            open: false,
+            current: None,
+        }
+    }
+}
+
+/// Returns the length of the common prefix of `before` and `after`, and the
+/// text that was removed from and inserted into `before` after that prefix.
+pub(crate) fn diff<'a>(before: &'a str, after: &'a str) -> (usize, &'a str, &'a str) {
+    let mut prefix = before
+        .bytes()
+        .zip(after.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !before.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = before.len().min(after.len()) - prefix;
+    let mut suffix = before
+        .bytes()
+        .rev()
+        .zip(after.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !before.is_char_boundary(before.len() - suffix) {
+        suffix -= 1;
+    }
+    (
+        prefix,
+        &before[prefix..before.len() - suffix],
+        &after[prefix..after.len() - suffix],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `text` one character at a time at the end of `buf`.
+    fn type_str(stack: &mut UndoStack<String>, buf: &mut String, text: &str) {
+        for c in text.chars() {
+            let before = buf.clone();
+            let before_sel = Selection::caret(buf.len());
+            buf.push(c);
+            stack.record_input(&before, before_sel, buf, Selection::caret(buf.len()), false);
+        }
+    }
+
+    /// Undo the most recent unit in `buf`, returning the restored text.
+    fn undo(stack: &mut UndoStack<String>, buf: &mut String) -> Option<String> {
+        let (text, _) = stack.undo(buf)?;
+        *buf = text.clone();
+        Some(text)
+    }
+
+    #[test]
+    fn typing_groups_by_word() {
+        let mut stack = UndoStack::default();
+        let mut buf = String::new();
+        type_str(&mut stack, &mut buf, "hello world");
+        assert_eq!(buf, "hello world");
+
+        let (text, sel) = stack.undo(&buf).unwrap();
+        assert_eq!(text, "hello ");
+        assert_eq!(sel, Selection::caret(6));
+        let (text, _) = stack.undo(&text).unwrap();
+        assert_eq!(text, "");
+        assert!(!stack.can_undo());
+        assert!(stack.is_current(&text));
+
+        let (text, _) = stack.redo(&text).unwrap();
+        assert_eq!(text, "hello ");
+        assert!(stack.can_redo());
+        let (text, sel) = stack.redo(&text).unwrap();
+        assert_eq!(text, "hello world");
+        assert_eq!(sel, Selection::caret(11));
+    }
+
+    #[test]
+    fn moving_the_caret_starts_a_new_unit() {
+        let mut stack = UndoStack::default();
+        let mut buf = String::new();
+        type_str(&mut stack, &mut buf, "ac");
+
+        let before = buf.clone();
+        buf.edit(1..1, "b");
+        stack.record_input(
+            &before,
+            Selection::caret(1),
+            &buf,
+            Selection::caret(2),
+            false,
+        );
+
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "ac");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "");
+    }
+
+    #[test]
+    fn deletions_and_paste() {
+        let mut stack = UndoStack::default();
+        let mut buf = String::new();
+        type_str(&mut stack, &mut buf, "abc");
+
+        for _ in 0..2 {
+            let before = buf.clone();
+            let len = buf.len();
+            buf.pop();
+            stack.record_input(
+                &before,
+                Selection::caret(len),
+                &buf,
+                Selection::caret(len - 1),
+                false,
+            );
+        }
+        assert_eq!(buf, "a");
+
+        let before = buf.clone();
+        buf.push_str("pasted");
+        stack.record_unit(&before, Selection::caret(1), &buf, Selection::caret(7));
+
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "a");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "abc");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "");
+    }
+
+    #[test]
+    fn deletions_in_both_directions() {
+        let mut stack = UndoStack::default();
+        let mut buf = String::new();
+        type_str(&mut stack, &mut buf, "abcdef");
+        stack.close_group();
+
+        // backspace twice, then delete forwards twice, from the middle
+        for (range, caret) in &[(2..3, 3), (1..2, 2)] {
+            let before = buf.clone();
+            buf.edit(range.clone(), "");
+            let after_sel = Selection::caret(range.start);
+            stack.record_input(&before, Selection::caret(*caret), &buf, after_sel, false);
+        }
+        for _ in 0..2 {
+            let before = buf.clone();
+            buf.edit(1..2, "");
+            stack.record_input(
+                &before,
+                Selection::caret(1),
+                &buf,
+                Selection::caret(1),
+                false,
+            );
+        }
+        assert_eq!(buf, "af");
+
+        let (text, sel) = stack.undo(&buf).unwrap();
+        assert_eq!(text, "abcdef");
+        assert_eq!(sel, Selection::caret(3));
+        let (text, sel) = stack.redo(&text).unwrap();
+        assert_eq!(text, "af");
+        assert_eq!(sel, Selection::caret(1));
+    }
+
+    #[test]
+    fn composition_is_one_unit() {
+        let mut stack = UndoStack::default();
+        let mut buf = String::from("x");
+        for (text, composing) in &[
+            ("xn", true),
+            ("xに", true),
+            ("xにほ", true),
+            ("x日本", false),
+        ] {
+            let before = buf.clone();
+            let before_sel = Selection::caret(before.len());
+            buf = text.to_string();
+            stack.record_input(
+                &before,
+                before_sel,
+                &buf,
+                Selection::caret(buf.len()),
+                *composing,
+            );
+        }
+        type_str(&mut stack, &mut buf, "a");
+
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "x日本");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "x");
+        assert!(!stack.can_undo());
+    }
+
+    #[test]
+    fn limit() {
+        let mut stack = UndoStack::default();
+        stack.set_limit(2);
+        let mut buf = String::new();
+        type_str(&mut stack, &mut buf, "a b c");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "a b ");
+        assert_eq!(undo(&mut stack, &mut buf).unwrap(), "a ");
+        assert!(undo(&mut stack, &mut buf).is_none());
+    }
+
+    #[test]
+    fn diff_char_boundaries() {
+        assert_eq!(diff("aéb", "aèb"), (1, "é", "è"));
+        assert_eq!(diff("abc", "abbc"), (2, "", "b"));
+        assert_eq!(diff("abc", "ac"), (1, "b", ""));
+    }
+}
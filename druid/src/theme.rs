@@ -62,6 +62,34 @@ pub const SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR: Key<Color> =
 pub const SELECTION_TEXT_COLOR: Key<Color> =
     Key::new("org.linebender.druid.theme.selection_text_color");
 pub const CURSOR_COLOR: Key<Color> = Key::new("org.linebender.druid.theme.cursor_color");
+pub const LINK_COLOR: Key<Color> = Key::new("org.linebender.druid.theme.link_color");
+/// The color painted behind a link while the mouse is over it.
+pub const LINK_HOVER_COLOR: Key<Color> = Key::new("org.linebender.druid.theme.link_hover_color");
+/// The color of the underline drawn under text that is being composed by an
+/// input method.
+pub const COMPOSITION_UNDERLINE_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.composition_underline_color");
+/// The color painted behind text that is being composed by an input method.
+pub const COMPOSITION_BACKGROUND_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.composition_background_color");
+
+/// The color of comments in syntax-highlighted text.
+pub const SYNTAX_COMMENT_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.syntax_comment_color");
+/// The color of keywords in syntax-highlighted text.
+pub const SYNTAX_KEYWORD_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.syntax_keyword_color");
+/// The color of string literals in syntax-highlighted text.
+pub const SYNTAX_STRING_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.syntax_string_color");
+/// The color of numbers and other constants in syntax-highlighted text.
+pub const SYNTAX_CONSTANT_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.syntax_constant_color");
+/// The color of type names in syntax-highlighted text.
+pub const SYNTAX_TYPE_COLOR: Key<Color> = Key::new("org.linebender.druid.theme.syntax_type_color");
+/// The color of function names in syntax-highlighted text.
+pub const SYNTAX_FUNCTION_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.syntax_function_color");
 
 pub const TEXT_SIZE_NORMAL: Key<f64> = Key::new("org.linebender.druid.theme.text_size_normal");
 pub const TEXT_SIZE_LARGE: Key<f64> = Key::new("org.linebender.druid.theme.text_size_large");
@@ -88,6 +116,10 @@ pub const TEXTBOX_BORDER_RADIUS: Key<f64> =
 pub const TEXTBOX_BORDER_WIDTH: Key<f64> =
     Key::new("org.linebender.druid.theme.textbox_border_width");
 pub const TEXTBOX_INSETS: Key<Insets> = Key::new("org.linebender.druid.theme.textbox_insets");
+/// The color of a textbox's border when it briefly flashes to show that
+/// input was rejected by its input filter.
+pub const TEXTBOX_REJECTED_BORDER_COLOR: Key<Color> =
+    Key::new("org.linebender.druid.theme.textbox_rejected_border_color");
 
 /// The default horizontal spacing between widgets.
 pub const WIDGET_PADDING_HORIZONTAL: Key<f64> =
@@ -146,6 +178,19 @@ pub(crate) fn add_to_env(env: Env) -> Env {
         .adding(SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR, Color::grey8(0x74))
         .adding(SELECTION_TEXT_COLOR, Color::rgb8(0x00, 0x00, 0x00))
         .adding(CURSOR_COLOR, Color::WHITE)
+        .adding(LINK_COLOR, Color::rgb8(0x5c, 0xc4, 0xff))
+        .adding(LINK_HOVER_COLOR, Color::rgba8(0x5c, 0xc4, 0xff, 0x40))
+        .adding(COMPOSITION_UNDERLINE_COLOR, Color::WHITE)
+        .adding(
+            COMPOSITION_BACKGROUND_COLOR,
+            Color::rgba8(0xff, 0xff, 0xff, 0x18),
+        )
+        .adding(SYNTAX_COMMENT_COLOR, Color::rgb8(0x7f, 0x84, 0x8e))
+        .adding(SYNTAX_KEYWORD_COLOR, Color::rgb8(0xc6, 0x78, 0xdd))
+        .adding(SYNTAX_STRING_COLOR, Color::rgb8(0x98, 0xc3, 0x79))
+        .adding(SYNTAX_CONSTANT_COLOR, Color::rgb8(0xd1, 0x9a, 0x66))
+        .adding(SYNTAX_TYPE_COLOR, Color::rgb8(0xe5, 0xc0, 0x7b))
+        .adding(SYNTAX_FUNCTION_COLOR, Color::rgb8(0x61, 0xaf, 0xef))
         .adding(TEXT_SIZE_NORMAL, 15.0)
         .adding(TEXT_SIZE_LARGE, 24.0)
         .adding(BASIC_WIDGET_HEIGHT, 18.0)
@@ -154,6 +199,7 @@ pub(crate) fn add_to_env(env: Env) -> Env {
         .adding(TEXTBOX_BORDER_RADIUS, 2.)
         .adding(TEXTBOX_BORDER_WIDTH, 1.)
         .adding(TEXTBOX_INSETS, Insets::new(4.0, 4.0, 4.0, 4.0))
+        .adding(TEXTBOX_REJECTED_BORDER_COLOR, Color::rgb8(0xe0, 0x4f, 0x4f))
         .adding(SCROLLBAR_COLOR, Color::rgb8(0xff, 0xff, 0xff))
         .adding(SCROLLBAR_BORDER_COLOR, Color::rgb8(0x77, 0x77, 0x77))
         .adding(SCROLLBAR_MAX_OPACITY, 0.7)
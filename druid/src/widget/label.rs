@@ -14,17 +14,18 @@
 
 //! A label widget.
 
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
 use druid_shell::Cursor;
 
 use crate::debug_state::DebugState;
 use crate::kurbo::Vec2;
-use crate::text::TextStorage;
+use crate::text::{LinkState, Selection, TextStorage};
 use crate::widget::prelude::*;
 use crate::{
-    ArcStr, Color, Data, FontDescriptor, KeyOrValue, LocalizedString, Point, TextAlignment,
-    TextLayout,
+    commands, ArcStr, Color, Data, FontDescriptor, HotKey, KbKey, KeyOrValue, LocalizedString,
+    Point, SysMods, TextAlignment, TextLayout,
 };
 use tracing::{instrument, trace};
 
@@ -92,12 +93,35 @@ pub struct Label<T> {
 ///
 /// This requires the `Data` to implement [`TextStorage`]; to handle static, dynamic, or
 /// localized text, use [`Label`].
+///
+/// If the text contains any [`Link`]s, the label can receive keyboard focus.
+/// While focused, <kbd>Tab</kbd> and <kbd>Shift</kbd>+<kbd>Tab</kbd> move
+/// between the links in the order they appear in the text, before moving
+/// focus to the next or previous widget, and <kbd>Enter</kbd> or
+/// <kbd>Space</kbd> activates the focused link.
+///
+/// A label can also be made [`selectable`], in which case its text can be
+/// selected with the mouse and copied to the clipboard.
+///
+/// [`Link`]: crate::text::Link
+/// [`selectable`]: #method.set_selectable
 pub struct RawLabel<T> {
     layout: TextLayout<T>,
     line_break_mode: LineBreaking,
 
     disabled: bool,
     default_text_color: KeyOrValue<Color>,
+    /// Whether links are highlighted when hovered.
+    link_hover: bool,
+    /// The index of the link under the mouse, if any.
+    hovered_link: Option<usize>,
+    /// The index of the link being pressed, if any.
+    pressed_link: Option<usize>,
+    /// The index of the link with keyboard focus, if any.
+    focused_link: Option<usize>,
+    /// Whether the text can be selected with the mouse.
+    selectable: bool,
+    selection: Selection,
 }
 
 /// Options for handling lines that are too wide for the label.
@@ -156,6 +180,12 @@ impl<T: TextStorage> RawLabel<T> {
             line_break_mode: LineBreaking::Overflow,
             disabled: false,
             default_text_color: crate::theme::TEXT_COLOR.into(),
+            link_hover: true,
+            hovered_link: None,
+            pressed_link: None,
+            focused_link: None,
+            selectable: false,
+            selection: Selection::caret(0),
         }
     }
 
@@ -208,6 +238,71 @@ impl<T: TextStorage> RawLabel<T> {
         self
     }
 
+    /// Builder-style method to set whether links respond to the mouse hovering over them.
+    ///
+    /// See [`set_links_hover_enabled`] for more information.
+    ///
+    /// [`set_links_hover_enabled`]: #method.set_links_hover_enabled
+    pub fn with_links_hover_enabled(mut self, enabled: bool) -> Self {
+        self.set_links_hover_enabled(enabled);
+        self
+    }
+
+    /// Set whether links respond to the mouse hovering over them.
+    ///
+    /// When enabled, which is the default, the cursor changes to a pointer
+    /// over a link, and the hovered link is highlighted with the
+    /// [`LINK_HOVER_COLOR`] from the [`Env`]. Its text is drawn in its
+    /// [`hover_color`], or its [`active_color`] while it is pressed, if the
+    /// link has one.
+    ///
+    /// Disabling this avoids hit-testing every mouse move, which can help in
+    /// labels that are repeated many times; links can still be clicked.
+    ///
+    /// [`hover_color`]: crate::text::Link::hover_color
+    /// [`active_color`]: crate::text::Link::active_color
+    /// [`LINK_HOVER_COLOR`]: ../theme/constant.LINK_HOVER_COLOR.html
+    /// [`Env`]: ../struct.Env.html
+    pub fn set_links_hover_enabled(&mut self, enabled: bool) {
+        self.link_hover = enabled;
+        if !enabled {
+            self.hovered_link = None;
+            self.pressed_link = None;
+            self.update_link_state();
+        }
+    }
+
+    /// Builder-style method to set whether the text can be selected.
+    ///
+    /// See [`set_selectable`] for more information.
+    ///
+    /// [`set_selectable`]: #method.set_selectable
+    pub fn with_selectable(mut self, selectable: bool) -> Self {
+        self.set_selectable(selectable);
+        self
+    }
+
+    /// Set whether the text can be selected.
+    ///
+    /// When enabled, dragging the mouse selects text and double-clicking
+    /// selects a word. The selection is painted with the
+    /// [`SELECTED_TEXT_BACKGROUND_COLOR`] from the [`Env`], and
+    /// <kbd>Cmd</kbd>/<kbd>Ctrl</kbd>+<kbd>C</kbd> copies it to the clipboard
+    /// as plain text.
+    ///
+    /// Clicking the label gives it keyboard focus so that it can handle the
+    /// copy shortcut, but a selectable label is not otherwise added to the
+    /// focus chain. The selection is cleared when the label loses focus.
+    ///
+    /// [`SELECTED_TEXT_BACKGROUND_COLOR`]: ../theme/constant.SELECTED_TEXT_BACKGROUND_COLOR.html
+    /// [`Env`]: ../struct.Env.html
+    pub fn set_selectable(&mut self, selectable: bool) {
+        self.selectable = selectable;
+        if !selectable {
+            self.selection = Selection::caret(0);
+        }
+    }
+
     /// Set the text color.
     ///
     /// The argument can be either a `Color` or a [`Key<Color>`].
@@ -286,6 +381,40 @@ impl<T: TextStorage> RawLabel<T> {
         let text_metrics = self.layout.layout_metrics();
         text_metrics.size.height - text_metrics.first_baseline
     }
+
+    /// Pass the state of the hovered or pressed link to the layout, which
+    /// needs to be rebuilt if it changed.
+    fn update_link_state(&mut self) {
+        let state = match (self.pressed_link, self.hovered_link) {
+            (Some(index), _) => Some((index, LinkState::Pressed)),
+            (None, Some(index)) => Some((index, LinkState::Hovered)),
+            (None, None) => None,
+        };
+        self.layout.set_link_state(state);
+    }
+
+    fn activate_link(&self, index: Option<usize>, ctx: &mut EventCtx, env: &Env) {
+        let link = index.and_then(|idx| self.layout.text()?.links().get(idx));
+        if let Some(link) = link {
+            link.activate(ctx, env);
+        }
+    }
+
+    /// The currently selected text, if any.
+    fn selected_text(&self) -> Option<&str> {
+        if self.selection.is_caret() {
+            return None;
+        }
+        self.layout
+            .text()
+            .and_then(|text| text.as_str().get(self.selection.range()))
+    }
+
+    fn copy_selection(&self, ctx: &EventCtx) {
+        if let Some(text) = self.selected_text() {
+            ctx.clipboard().put_string(text);
+        }
+    }
 }
 
 impl<T: TextStorage> Label<T> {
@@ -419,6 +548,16 @@ impl<T: Data> Label<T> {
         self
     }
 
+    /// Builder-style method to set whether the text can be selected.
+    ///
+    /// See [`RawLabel::set_selectable`] for more information.
+    ///
+    /// [`RawLabel::set_selectable`]: struct.RawLabel.html#method.set_selectable
+    pub fn with_selectable(mut self, selectable: bool) -> Self {
+        self.label.set_selectable(selectable);
+        self
+    }
+
     /// Draw this label's text at the provided `Point`, without internal padding.
     ///
     /// This is a convenience for widgets that want to use Label as a way
@@ -486,8 +625,11 @@ impl<T: Data> LabelText<T> {
 }
 
 impl<T: Data> Widget<T> for Label<T> {
-    #[instrument(name = "Label", level = "trace", skip(self, _ctx, _event, _data, _env))]
-    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut T, _env: &Env) {}
+    #[instrument(name = "Label", level = "trace", skip(self, ctx, event, _data, env))]
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, env: &Env) {
+        let mut text = self.current_text.clone();
+        self.label.event(ctx, event, &mut text, env);
+    }
 
     #[instrument(name = "Label", level = "trace", skip(self, ctx, event, data, env))]
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
@@ -536,29 +678,108 @@ impl<T: Data> Widget<T> for Label<T> {
 }
 
 impl<T: TextStorage> Widget<T> for RawLabel<T> {
-    #[instrument(
-        name = "RawLabel",
-        level = "trace",
-        skip(self, ctx, event, _data, _env)
-    )]
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, _env: &Env) {
+    #[instrument(name = "RawLabel", level = "trace", skip(self, ctx, event, _data, env))]
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, env: &Env) {
         match event {
+            Event::KeyDown(key) if ctx.is_focused() => {
+                let link_count = self.layout.text().map_or(0, |text| text.links().len());
+                let focused = self.focused_link.filter(|idx| *idx < link_count);
+                match &key.key {
+                    _ if self.selectable && HotKey::new(SysMods::Cmd, "c").matches(key) => {
+                        self.copy_selection(ctx);
+                    }
+                    KbKey::Tab if key.mods.shift() => match focused {
+                        Some(idx) if idx > 0 => self.focused_link = Some(idx - 1),
+                        _ => ctx.focus_prev(),
+                    },
+                    KbKey::Tab => match focused {
+                        Some(idx) if idx + 1 < link_count => self.focused_link = Some(idx + 1),
+                        None if link_count > 0 => self.focused_link = Some(0),
+                        _ => ctx.focus_next(),
+                    },
+                    KbKey::Enter => self.activate_link(focused, ctx, env),
+                    KbKey::Character(c) if c == " " => self.activate_link(focused, ctx, env),
+                    _ => return,
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            Event::Command(cmd)
+                if cmd.is(commands::COPY) && ctx.is_focused() && self.selected_text().is_some() =>
+            {
+                self.copy_selection(ctx);
+                ctx.set_handled();
+            }
+            Event::MouseDown(event) => {
+                // Account for the padding
+                let pos = event.pos - Vec2::new(LABEL_X_PADDING, 0.0);
+                if self.link_hover {
+                    let pressed_link = self.layout.link_index_for_pos(pos);
+                    if pressed_link != self.pressed_link {
+                        self.pressed_link = pressed_link;
+                        self.update_link_state();
+                        ctx.request_layout();
+                    }
+                }
+                if self.selectable && event.button.is_left() {
+                    let offset = self.layout.text_position_for_point(pos);
+                    self.selection = match self.layout.text() {
+                        Some(text) if event.count == 2 => {
+                            let range = crate::text::word_range_for_pos(text.as_str(), offset);
+                            Selection::new(range.start, range.end)
+                        }
+                        _ => Selection::caret(offset),
+                    };
+                    ctx.set_active(true);
+                    ctx.request_focus();
+                    ctx.request_paint();
+                }
+            }
             Event::MouseUp(event) => {
                 // Account for the padding
                 let pos = event.pos - Vec2::new(LABEL_X_PADDING, 0.0);
-                if let Some(link) = self.layout.link_for_pos(pos) {
-                    ctx.submit_command(link.command.clone());
+                if ctx.is_active() {
+                    ctx.set_active(false);
+                }
+                // Finishing a drag-selection over a link shouldn't follow it.
+                if self.selection.is_caret() {
+                    if let Some(link) = self.layout.link_for_pos(pos) {
+                        link.activate(ctx, env);
+                    }
+                }
+                if self.pressed_link.take().is_some() {
+                    self.update_link_state();
+                    ctx.request_layout();
                 }
             }
-            Event::MouseMove(event) => {
+            Event::MouseMove(event) if self.link_hover || self.selectable => {
                 // Account for the padding
                 let pos = event.pos - Vec2::new(LABEL_X_PADDING, 0.0);
+                if self.selectable && ctx.is_active() {
+                    let offset = self.layout.text_position_for_point(pos);
+                    if offset != self.selection.active {
+                        self.selection.active = offset;
+                        ctx.request_paint();
+                    }
+                }
+                let hovered_link = if self.link_hover {
+                    self.layout.link_index_for_pos(pos)
+                } else {
+                    None
+                };
 
-                if self.layout.link_for_pos(pos).is_some() {
+                if hovered_link.is_some() {
                     ctx.set_cursor(&Cursor::Pointer);
+                } else if self.selectable {
+                    ctx.set_cursor(&Cursor::IBeam);
                 } else {
                     ctx.clear_cursor();
                 }
+                if hovered_link != self.hovered_link {
+                    self.hovered_link = hovered_link;
+                    self.update_link_state();
+                    ctx.request_layout();
+                }
             }
             _ => {}
         }
@@ -579,6 +800,30 @@ impl<T: TextStorage> Widget<T> for RawLabel<T> {
                 self.layout.set_text_color(color);
                 ctx.request_layout();
             }
+            LifeCycle::HotChanged(false) if self.hovered_link.is_some() => {
+                self.hovered_link = None;
+                self.pressed_link = None;
+                self.update_link_state();
+                ctx.request_layout();
+            }
+            LifeCycle::BuildFocusChain if !data.links().is_empty() => {
+                ctx.register_for_focus();
+            }
+            LifeCycle::FocusChanged(focused) => {
+                // Focus gained by clicking to select text shouldn't highlight a link.
+                let link_count = data.links().len();
+                self.focused_link = if !*focused || ctx.is_active() || link_count == 0 {
+                    None
+                } else if ctx.is_focus_reversed() {
+                    Some(link_count - 1)
+                } else {
+                    Some(0)
+                };
+                if !focused {
+                    self.selection = Selection::caret(0);
+                }
+                ctx.request_paint();
+            }
             _ => {}
         }
     }
@@ -591,6 +836,12 @@ impl<T: TextStorage> Widget<T> for RawLabel<T> {
     fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, _env: &Env) {
         if !old_data.same(data) {
             self.layout.set_text(data.clone());
+            self.hovered_link = None;
+            self.pressed_link = None;
+            self.selection = Selection::caret(0);
+            if old_data.links().is_empty() != data.links().is_empty() {
+                ctx.children_changed();
+            }
             ctx.request_layout();
         }
         if self.layout.needs_rebuild_after_update(ctx) {
@@ -620,16 +871,47 @@ impl<T: TextStorage> Widget<T> for RawLabel<T> {
         size
     }
 
-    #[instrument(name = "RawLabel", level = "trace", skip(self, ctx, _data, _env))]
-    fn paint(&mut self, ctx: &mut PaintCtx, _data: &T, _env: &Env) {
+    #[instrument(name = "RawLabel", level = "trace", skip(self, ctx, _data, env))]
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &T, env: &Env) {
         let origin = Point::new(LABEL_X_PADDING, 0.0);
         let label_size = ctx.size();
 
         if self.line_break_mode == LineBreaking::Clip {
             ctx.clip(label_size.to_rect());
         }
+        if !self.selection.is_caret() {
+            let color = env.get(crate::theme::SELECTED_TEXT_BACKGROUND_COLOR);
+            for rect in self.layout.rects_for_range(self.selection.range()) {
+                ctx.fill(rect + origin.to_vec2(), &color);
+            }
+        }
+        if let Some(index) = self.hovered_link {
+            let color = env.get(crate::theme::LINK_HOVER_COLOR);
+            for rect in self.layout.link_rects(index) {
+                ctx.fill(rect + origin.to_vec2(), &color);
+            }
+        }
+        if let Some(index) = self.focused_link.filter(|_| ctx.is_focused()) {
+            let color = env.get(crate::theme::PRIMARY_LIGHT);
+            for rect in self.layout.link_rects(index) {
+                ctx.stroke(rect.inset(-1.0) + origin.to_vec2(), &color, 1.0);
+            }
+        }
         self.draw_at(ctx, origin)
     }
+
+    fn debug_state(&self, data: &T) -> DebugState {
+        let mut other_values = HashMap::new();
+        if let Some(text) = self.selected_text() {
+            other_values.insert("selection".to_string(), text.to_string());
+        }
+        DebugState {
+            display_name: self.short_type_name().to_string(),
+            main_value: data.as_str().to_string(),
+            other_values,
+            ..Default::default()
+        }
+    }
 }
 
 impl<T: TextStorage> Default for RawLabel<T> {
@@ -110,6 +110,22 @@ impl<T: Data, W: Widget<T>> Widget<T> for Padding<T, W> {
         my_size
     }
 
+    fn intrinsic_size(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &T,
+        env: &Env,
+    ) -> Size {
+        let insets = self.insets.resolve(env);
+        let hpad = insets.x0 + insets.x1;
+        let vpad = insets.y0 + insets.y1;
+
+        let child_bc = bc.shrink((hpad, vpad));
+        let size = self.child.intrinsic_size(ctx, &child_bc, data, env);
+        Size::new(size.width + hpad, size.height + vpad)
+    }
+
     #[instrument(name = "Padding", level = "trace", skip(self, ctx, data, env))]
     fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
         self.child.paint(ctx, data, env);
@@ -181,6 +181,20 @@ impl<T: Data> Widget<T> for SizedBox<T> {
         size
     }
 
+    fn intrinsic_size(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &T,
+        env: &Env,
+    ) -> Size {
+        let child_bc = self.child_constraints(bc);
+        match self.child.as_mut() {
+            Some(child) => child.intrinsic_size(ctx, &child_bc, data, env),
+            None => bc.constrain((self.width.unwrap_or(0.0), self.height.unwrap_or(0.0))),
+        }
+    }
+
     #[instrument(name = "SizedBox", level = "trace", skip(self, ctx, data, env))]
     fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
         if let Some(ref mut child) = self.child {
@@ -21,23 +21,35 @@ use crate::debug_state::DebugState;
 use crate::kurbo::Insets;
 use crate::piet::TextLayout as _;
 use crate::text::{
-    EditableText, ImeInvalidation, Selection, TextComponent, TextLayout, TextStorage,
+    EditableText, ImeInvalidation, Selection, TabBehavior, TextComponent, TextLayout, TextStorage,
 };
 use crate::widget::prelude::*;
 use crate::widget::{Padding, Scroll, WidgetWrapper};
 use crate::{
-    theme, ArcStr, Color, Command, FontDescriptor, HotKey, KeyEvent, KeyOrValue, Point, Rect,
-    SysMods, TextAlignment, TimerToken, Vec2,
+    theme, ArcStr, Color, Command, FontDescriptor, HotKey, KeyEvent, KeyOrValue, MouseEvent, Point,
+    Rect, Selector, SysMods, Target, TextAlignment, TimerToken, Vec2,
 };
 
 use super::LabelText;
 
 const CURSOR_BLINK_DURATION: Duration = Duration::from_millis(500);
+/// How long the border is highlighted after input is rejected.
+const REJECTED_FLASH_DURATION: Duration = Duration::from_millis(300);
+/// How often the text scrolls while a selection is dragged past the edge.
+const DRAG_SCROLL_INTERVAL: Duration = Duration::from_millis(16);
+/// How far the text scrolls every [`DRAG_SCROLL_INTERVAL`], as a fraction of
+/// the distance from the pointer to the edge of the text box.
+const DRAG_SCROLL_SPEED: f64 = 0.2;
 const MAC_OR_LINUX: bool = cfg!(any(target_os = "macos", target_os = "linux"));
 
 /// When we scroll after editing or movement, we show a little extra of the document.
+///
+/// This is the default for [`TextBox::with_scroll_margin`].
 const SCROLL_TO_INSETS: Insets = Insets::uniform_xy(40.0, 0.0);
 
+/// The character displayed in place of the text of a [`TextBox::protected`].
+const MASK_CHAR: char = '\u{2022}';
+
 /// A widget that allows user text input.
 ///
 /// # Editing values
@@ -55,6 +67,8 @@ pub struct TextBox<T> {
     inner: Scroll<T, Padding<T, TextComponent<T>>>,
     scroll_to_selection_after_layout: bool,
     multiline: bool,
+    /// If set, the text box is as tall as its text, up to this many lines.
+    max_lines: Option<usize>,
     /// true if a click event caused us to gain focus.
     ///
     /// On macOS, if focus happens via click then we set the selection based
@@ -63,12 +77,20 @@ pub struct TextBox<T> {
     was_focused_from_click: bool,
     cursor_on: bool,
     cursor_timer: TimerToken,
+    rejected_timer: TimerToken,
+    /// The most recent mouse event of a drag selection, if one is in progress.
+    drag_mouse: Option<MouseEvent>,
+    drag_scroll_timer: TimerToken,
     /// if `true` (the default), this textbox will attempt to change focus on tab.
     ///
     /// You can override this in a controller if you want to customize tab
     /// behaviour.
     pub handles_tab_notifications: bool,
     text_pos: Point,
+    /// The space kept around the cursor when scrolling to it.
+    scroll_margin: Insets,
+    /// The selection most recently reported with [`TextBox::SELECTION_CHANGED`].
+    notified_selection: Selection,
 }
 
 impl<T: EditableText + TextStorage> TextBox<T> {
@@ -91,11 +113,17 @@ impl<T: EditableText + TextStorage> TextBox<T> {
             placeholder_text: placeholder_text.into(),
             placeholder_layout,
             multiline: false,
+            max_lines: None,
             was_focused_from_click: false,
             cursor_on: false,
             cursor_timer: TimerToken::INVALID,
+            rejected_timer: TimerToken::INVALID,
+            drag_mouse: None,
+            drag_scroll_timer: TimerToken::INVALID,
             handles_tab_notifications: true,
             text_pos: Point::ZERO,
+            scroll_margin: SCROLL_TO_INSETS,
+            notified_selection: Selection::caret(0),
         }
     }
 
@@ -110,6 +138,38 @@ impl<T: EditableText + TextStorage> TextBox<T> {
         this
     }
 
+    /// Builder-style method to make a multi-line `TextBox` grow with its text.
+    ///
+    /// The `TextBox` is as tall as its text, from one line up to `max_lines`
+    /// lines; after that, the text scrolls. This is only useful with
+    /// [`TextBox::multiline`], and when the parent does not give the
+    /// `TextBox` a fixed height.
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = Some(max_lines.max(1));
+        self
+    }
+
+    /// Builder-style method to set the space kept between the cursor and the
+    /// edges of the `TextBox` when the text scrolls to show the cursor.
+    ///
+    /// The default is 40 on the left and right, and 0 above and below.
+    pub fn with_scroll_margin(mut self, margin: impl Into<Insets>) -> Self {
+        self.set_scroll_margin(margin);
+        self
+    }
+
+    /// Create a new `TextBox` for entering passwords or other secrets.
+    ///
+    /// Each grapheme of the text is displayed as a bullet (`•`), and the
+    /// text cannot be copied or cut, although it can be pasted into. The
+    /// text is also withheld from the platform's input method. Use
+    /// [`set_mask_char`] to change the mask, or to show the text.
+    ///
+    /// [`set_mask_char`]: TextBox::set_mask_char
+    pub fn protected() -> Self {
+        TextBox::new().with_mask_char(MASK_CHAR)
+    }
+
     /// If `true` (and this is a [`multiline`] text box) lines will be wrapped
     /// at the maximum layout width.
     ///
@@ -121,6 +181,26 @@ impl<T: EditableText + TextStorage> TextBox<T> {
         self.inner.set_horizontal_scroll_enabled(!wrap_lines);
         self
     }
+
+    /// Returns `true` if there is selected text that [`commands::COPY`]
+    /// would copy.
+    ///
+    /// Text cannot be copied from a [`TextBox::protected`] text box. The
+    /// [`TextBox::SELECTION_CHANGED`] notification is sent whenever this may
+    /// have changed, so that menu items can be enabled or disabled.
+    ///
+    /// [`commands::COPY`]: crate::commands::COPY
+    pub fn can_copy(&self) -> bool {
+        self.text().can_read() && !self.text().is_composing() && self.text().borrow().can_copy()
+    }
+
+    /// Returns `true` if there is selected text that [`commands::CUT`]
+    /// would remove.
+    ///
+    /// [`commands::CUT`]: crate::commands::CUT
+    pub fn can_cut(&self) -> bool {
+        self.text().can_write() && self.can_copy()
+    }
 }
 
 impl<T> TextBox<T> {
@@ -157,6 +237,59 @@ impl<T> TextBox<T> {
         self
     }
 
+    /// Builder-style method to display `mask` in place of each grapheme of
+    /// the text.
+    ///
+    /// See [`set_mask_char`] for more information.
+    ///
+    /// [`set_mask_char`]: TextBox::set_mask_char
+    pub fn with_mask_char(mut self, mask: char) -> Self {
+        self.set_mask_char(Some(mask));
+        self
+    }
+
+    /// Builder-style method to restrict the text that can be entered.
+    ///
+    /// `filter` is called with each grapheme the user types, pastes, or
+    /// commits with an input method, and the graphemes it returns `false`
+    /// for are not inserted. When this happens the border of the `TextBox`
+    /// briefly changes to [`theme::TEXTBOX_REJECTED_BORDER_COLOR`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use druid::widget::TextBox;
+    ///
+    /// let digits = TextBox::<String>::new()
+    ///     .with_input_filter(|g| g.chars().all(|c| c.is_ascii_digit()));
+    /// ```
+    pub fn with_input_filter(mut self, filter: impl Fn(&str) -> bool + 'static) -> Self {
+        self.set_input_filter(filter);
+        self
+    }
+
+    /// Builder-style method to limit the length of the text, in graphemes.
+    ///
+    /// Typed, pasted and composed text that would make the text longer than
+    /// `max_length` is cut short, the border of the `TextBox` briefly changes
+    /// to [`theme::TEXTBOX_REJECTED_BORDER_COLOR`], and the
+    /// [`TextComponent::TEXT_LENGTH_LIMIT`] notification is sent, which can
+    /// be handled by an ancestor to show the limit to the user.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.set_max_length(Some(max_length));
+        self
+    }
+
+    /// Builder-style method to set what happens when the user presses the
+    /// tab key.
+    ///
+    /// By default, tab and shift-tab move the focus; a code editor might
+    /// instead use [`TabBehavior::Insert`] to insert tabs or spaces.
+    pub fn with_tab_behavior(mut self, behavior: TabBehavior) -> Self {
+        self.set_tab_behavior(behavior);
+        self
+    }
+
     /// Builder-style method for setting the font.
     ///
     /// The argument can be a [`FontDescriptor`] or a [`Key<FontDescriptor>`]
@@ -260,6 +393,74 @@ impl<T> TextBox<T> {
         self.text_mut().borrow_mut().layout.set_text_color(color);
     }
 
+    /// Set a character to display in place of each grapheme of the text, or
+    /// `None` to display the text itself.
+    ///
+    /// While the text is masked it cannot be copied or cut, and it is not
+    /// provided to the platform's input method. The selection is kept when
+    /// the mask changes, so this can be used to show and hide a password.
+    ///
+    /// If you change this property, you are responsible for calling
+    /// [`request_layout`] to ensure the text box is updated.
+    ///
+    /// [`request_layout`]: ../struct.EventCtx.html#method.request_layout
+    pub fn set_mask_char(&mut self, mask: Option<char>) {
+        if !self.text().can_write() {
+            tracing::warn!("set_mask_char called with IME lock held.");
+            return;
+        }
+        self.text_mut().borrow_mut().layout.set_mask(mask);
+    }
+
+    /// Set the space kept between the cursor and the edges of the `TextBox`
+    /// when the text scrolls to show the cursor.
+    ///
+    /// See [`with_scroll_margin`] for more information.
+    ///
+    /// [`with_scroll_margin`]: TextBox::with_scroll_margin
+    pub fn set_scroll_margin(&mut self, margin: impl Into<Insets>) {
+        self.scroll_margin = margin.into();
+    }
+
+    /// Restrict the text that can be entered.
+    ///
+    /// See [`with_input_filter`] for more information.
+    ///
+    /// [`with_input_filter`]: TextBox::with_input_filter
+    pub fn set_input_filter(&mut self, filter: impl Fn(&str) -> bool + 'static) {
+        if !self.text().can_write() {
+            tracing::warn!("set_input_filter called with IME lock held.");
+            return;
+        }
+        self.text_mut().borrow_mut().set_input_filter(filter);
+    }
+
+    /// Limit the length of the text, in graphemes, or remove the limit.
+    ///
+    /// See [`with_max_length`] for more information.
+    ///
+    /// [`with_max_length`]: TextBox::with_max_length
+    pub fn set_max_length(&mut self, max_length: Option<usize>) {
+        if !self.text().can_write() {
+            tracing::warn!("set_max_length called with IME lock held.");
+            return;
+        }
+        self.text_mut().borrow_mut().set_max_length(max_length);
+    }
+
+    /// Set what happens when the user presses the tab key.
+    ///
+    /// See [`with_tab_behavior`] for more information.
+    ///
+    /// [`with_tab_behavior`]: TextBox::with_tab_behavior
+    pub fn set_tab_behavior(&mut self, behavior: TabBehavior) {
+        if !self.text().can_write() {
+            tracing::warn!("set_tab_behavior called with IME lock held.");
+            return;
+        }
+        self.text_mut().borrow_mut().set_tab_behavior(behavior);
+    }
+
     /// The point, relative to the origin, where this text box draws its
     /// [`TextLayout`].
     ///
@@ -272,6 +473,31 @@ impl<T> TextBox<T> {
     }
 }
 
+impl TextBox<()> {
+    /// Set the selection of the `TextBox`.
+    ///
+    /// Offsets past the end of the text are moved to the end, and offsets
+    /// inside a grapheme are moved to the start of that grapheme. The command
+    /// is handled by the focused `TextBox`, or by the `TextBox` it is sent
+    /// to, which does not need to be focused; it is ignored while an input
+    /// method is composing text.
+    ///
+    /// To select all of the text, send [`commands::SELECT_ALL`] to the
+    /// `TextBox`.
+    ///
+    /// [`commands::SELECT_ALL`]: crate::commands::SELECT_ALL
+    pub const SET_SELECTION: Selector<Selection> =
+        Selector::new("druid-builtin.textbox-set-selection");
+
+    /// A notification sent by the `TextBox` when an event changes its
+    /// selection, with the new selection.
+    ///
+    /// This can be used to enable or disable actions that depend on the
+    /// selection, such as cut and copy.
+    pub const SELECTION_CHANGED: Selector<Selection> =
+        Selector::new("druid-builtin.textbox-selection-changed");
+}
+
 impl<T: Data> TextBox<T> {
     /// Builder-style method to set the `TextBox`'s placeholder text.
     pub fn with_placeholder(mut self, placeholder: impl Into<LabelText<T>>) -> Self {
@@ -304,11 +530,42 @@ impl<T> TextBox<T> {
         self.inner.child_mut().wrapped_mut()
     }
 
+    fn flash_rejected(&mut self, ctx: &mut EventCtx) {
+        self.rejected_timer = ctx.request_timer(REJECTED_FLASH_DURATION);
+        ctx.request_paint();
+    }
+
     fn reset_cursor_blink(&mut self, token: TimerToken) {
         self.cursor_on = true;
         self.cursor_timer = token;
     }
 
+    /// The distance to scroll on the next drag scroll tick.
+    ///
+    /// This is zero unless a selection is being dragged and the pointer is
+    /// outside of the text box.
+    fn drag_scroll_delta(&self, size: Size) -> Vec2 {
+        let pos = match &self.drag_mouse {
+            Some(mouse) => mouse.pos,
+            None => return Vec2::ZERO,
+        };
+        let overshoot = |pos: f64, max: f64| {
+            if pos < 0.0 {
+                pos
+            } else if pos > max {
+                pos - max
+            } else {
+                0.0
+            }
+        };
+        Vec2::new(overshoot(pos.x, size.width), overshoot(pos.y, size.height)) * DRAG_SCROLL_SPEED
+    }
+
+    fn stop_drag_scroll(&mut self) {
+        self.drag_mouse = None;
+        self.drag_scroll_timer = TimerToken::INVALID;
+    }
+
     fn should_draw_cursor(&self) -> bool {
         if cfg!(target_os = "macos") && self.text().can_read() {
             self.cursor_on && self.text().borrow().selection().is_caret()
@@ -323,7 +580,8 @@ impl<T: TextStorage + EditableText> TextBox<T> {
         let text = self.text().borrow();
         let layout = text.layout.layout().unwrap();
 
-        let hit = layout.hit_test_text_position(text.selection().active);
+        let offset = text.layout.layout_offset(text.selection().active);
+        let hit = layout.hit_test_text_position(offset);
         let line = layout.line_metric(hit.line).unwrap();
         let y0 = line.y_offset;
         let y1 = y0 + line.height;
@@ -332,13 +590,22 @@ impl<T: TextStorage + EditableText> TextBox<T> {
         Rect::new(x, y0, x, y1)
     }
 
+    /// The height of the first `lines` lines of the text, or `None` if the
+    /// text is not that long.
+    fn text_height_for_lines(&self, lines: usize) -> Option<f64> {
+        let session = self.text().borrow();
+        let layout = session.layout.layout()?;
+        let line = layout.line_metric(lines.checked_sub(1)?)?;
+        Some(line.y_offset + line.height)
+    }
+
     fn scroll_to_selection_end(&mut self) {
         let rect = self.rect_for_selection_end();
         let view_rect = self.inner.viewport_rect();
         let is_visible =
             view_rect.contains(rect.origin()) && view_rect.contains(Point::new(rect.x1, rect.y1));
         if !is_visible {
-            self.inner.scroll_to(rect + SCROLL_TO_INSETS);
+            self.inner.scroll_to(rect + self.scroll_margin);
         }
     }
 
@@ -360,9 +627,7 @@ impl<T: TextStorage + EditableText> TextBox<T> {
                 Some(sys::PASTE.to(ctx.window_id()))
             }
             key if HotKey::new(SysMods::Cmd, "z").matches(key) => Some(sys::UNDO.to(our_id)),
-            key if HotKey::new(SysMods::CmdShift, "Z").matches(key) && !cfg!(windows) => {
-                Some(sys::REDO.to(our_id))
-            }
+            key if HotKey::new(SysMods::CmdShift, "Z").matches(key) => Some(sys::REDO.to(our_id)),
             key if HotKey::new(SysMods::Cmd, "y").matches(key) && cfg!(windows) => {
                 Some(sys::REDO.to(our_id))
             }
@@ -398,6 +663,14 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
                     ctx.request_paint();
                     ctx.set_handled();
                 }
+                cmd if cmd.is(TextComponent::INPUT_REJECTED) => {
+                    self.flash_rejected(ctx);
+                    ctx.set_handled();
+                }
+                // not handled, so that the app can also show the limit.
+                cmd if cmd.is(TextComponent::TEXT_LENGTH_LIMIT) => {
+                    self.flash_rejected(ctx);
+                }
                 cmd if cmd.is(TextComponent::CANCEL) => {
                     ctx.resign_focus();
                     ctx.request_paint();
@@ -417,11 +690,36 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
                         ctx.request_focus();
                         self.was_focused_from_click = true;
                         self.reset_cursor_blink(ctx.request_timer(CURSOR_BLINK_DURATION));
+                        self.drag_mouse = Some(mouse.clone());
                     } else {
                         ctx.set_handled();
                     }
                 }
             }
+            Event::MouseMove(mouse) if self.drag_mouse.is_some() => {
+                self.drag_mouse = Some(mouse.clone());
+                let delta = self.drag_scroll_delta(ctx.size());
+                if delta != Vec2::ZERO && self.drag_scroll_timer == TimerToken::INVALID {
+                    self.drag_scroll_timer = ctx.request_timer(DRAG_SCROLL_INTERVAL);
+                }
+            }
+            Event::MouseUp(_) => self.stop_drag_scroll(),
+            Event::Timer(id) if *id == self.drag_scroll_timer => {
+                self.drag_scroll_timer = TimerToken::INVALID;
+                let delta = self.drag_scroll_delta(ctx.size());
+                if self.text().can_write() && self.inner.scroll_by(delta) {
+                    // extend the selection to the text that is now under the pointer
+                    if let Some(mouse) = self.drag_mouse.clone() {
+                        self.inner.event(ctx, &Event::MouseMove(mouse), data, env);
+                    }
+                    self.drag_scroll_timer = ctx.request_timer(DRAG_SCROLL_INTERVAL);
+                    ctx.request_paint();
+                }
+            }
+            Event::Timer(id) if *id == self.rejected_timer => {
+                self.rejected_timer = TimerToken::INVALID;
+                ctx.request_paint();
+            }
             Event::Timer(id) => {
                 if !ctx.is_disabled() {
                     if *id == self.cursor_timer && ctx.has_focus() {
@@ -459,6 +757,23 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
             Event::Command(cmd)
                 if !self.text().is_composing()
                     && ctx.is_focused()
+                    && (cmd.is(crate::commands::UNDO) || cmd.is(crate::commands::REDO)) =>
+            {
+                let mut session = self.text_mut().borrow_mut();
+                let inval = if cmd.is(crate::commands::UNDO) {
+                    session.undo(data)
+                } else {
+                    session.redo(data)
+                };
+                drop(session);
+                if let Some(inval) = inval {
+                    ctx.invalidate_text_input(inval);
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd)
+                if !self.text().is_composing()
+                    && (ctx.is_focused() || cmd.target() == Target::Widget(ctx.widget_id()))
                     && cmd.is(crate::commands::SELECT_ALL) =>
             {
                 if let Some(inval) = self
@@ -470,22 +785,56 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
                 }
                 ctx.set_handled();
             }
+            Event::Command(cmd)
+                if !self.text().is_composing()
+                    && (ctx.is_focused() || cmd.target() == Target::Widget(ctx.widget_id()))
+                    && cmd.is(TextBox::SET_SELECTION) =>
+            {
+                let selection = *cmd.get_unchecked(TextBox::SET_SELECTION);
+                let inval = self
+                    .text_mut()
+                    .borrow_mut()
+                    .set_selection_in(data, selection);
+                if let Some(inval) = inval {
+                    ctx.invalidate_text_input(inval);
+                    self.scroll_to_selection_end();
+                    ctx.request_paint();
+                }
+                ctx.set_handled();
+            }
             Event::Paste(ref item) if self.text().can_write() => {
                 if let Some(string) = item.get_string() {
                     let text = if self.multiline {
-                        &string
+                        string
                     } else {
-                        string.lines().next().unwrap_or("")
+                        // a single line text box gets the lines joined by spaces
+                        string.lines().collect::<Vec<_>>().join(" ")
                     };
-                    if !text.is_empty() {
-                        let inval = self.text_mut().borrow_mut().insert_text(data, text);
+                    let filtered = self.text().borrow().filter_input(&text).into_owned();
+                    let truncated = self.text().borrow().truncate_input(data, &filtered);
+                    if truncated.len() != text.len() {
+                        self.flash_rejected(ctx);
+                    }
+                    if truncated.len() != filtered.len() {
+                        ctx.submit_notification(TextComponent::TEXT_LENGTH_LIMIT);
+                    }
+                    if !truncated.is_empty() {
+                        let inval = self.text_mut().borrow_mut().insert_text(data, truncated);
                         ctx.invalidate_text_input(inval);
                     }
                 }
             }
             _ => (),
         }
-        self.inner.event(ctx, event, data, env)
+        self.inner.event(ctx, event, data, env);
+
+        if self.text().can_read() {
+            let selection = self.text().borrow().selection();
+            if selection != self.notified_selection {
+                self.notified_selection = selection;
+                ctx.submit_notification(TextBox::SELECTION_CHANGED.with(selection));
+            }
+        }
     }
 
     #[instrument(name = "TextBox", level = "trace", skip(self, ctx, event, data, env))]
@@ -508,6 +857,9 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
                     ctx.invalidate_text_input(ImeInvalidation::SelectionChanged);
                 }
                 self.text_mut().has_focus = true;
+                if self.text().can_read() {
+                    self.scroll_to_selection_end();
+                }
                 self.reset_cursor_blink(ctx.request_timer(CURSOR_BLINK_DURATION));
                 self.was_focused_from_click = false;
                 ctx.request_paint();
@@ -525,6 +877,7 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
                 }
                 self.cursor_timer = TimerToken::INVALID;
                 self.was_focused_from_click = false;
+                self.stop_drag_scroll();
                 ctx.request_paint();
             }
             _ => (),
@@ -547,9 +900,13 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
             ctx.request_layout();
         }
         if self.text().can_write() {
-            if let Some(ime_invalidation) = self.text_mut().borrow_mut().pending_ime_invalidation()
-            {
+            let invalidation = self.text_mut().borrow_mut().pending_ime_invalidation();
+            if let Some(ime_invalidation) = invalidation {
                 ctx.invalidate_text_input(ime_invalidation);
+                // the text or selection was changed by the app; keep the cursor
+                // in view.
+                self.scroll_to_selection_after_layout = true;
+                ctx.request_layout();
             }
         }
     }
@@ -566,7 +923,21 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
         let min_size = bc.constrain((min_width, 0.0));
         let child_bc = BoxConstraints::new(min_size, bc.max());
 
-        let size = self.inner.layout(ctx, &child_bc, data, env);
+        let mut size = self.inner.layout(ctx, &child_bc, data, env);
+        // if the text is taller than the maximum number of lines, lay out again
+        // so that it scrolls.
+        if let Some(max_lines) = self.max_lines {
+            if let Some(height) = self.text_height_for_lines(max_lines) {
+                let max_height = (height + textbox_insets.y_value())
+                    .max(bc.min().height)
+                    .min(bc.max().height);
+                if size.height > max_height {
+                    let max_size = Size::new(bc.max().width, max_height);
+                    let child_bc = BoxConstraints::new(min_size, max_size);
+                    size = self.inner.layout(ctx, &child_bc, data, env);
+                }
+            }
+        }
 
         let text_metrics = if !self.text().can_read() || data.is_empty() {
             self.placeholder_layout.layout_metrics()
@@ -606,7 +977,9 @@ impl<T: TextStorage + EditableText> Widget<T> for TextBox<T> {
 
         let is_focused = ctx.is_focused();
 
-        let border_color = if is_focused {
+        let border_color = if self.rejected_timer != TimerToken::INVALID {
+            env.get(theme::TEXTBOX_REJECTED_BORDER_COLOR)
+        } else if is_focused {
             env.get(theme::PRIMARY_LIGHT)
         } else {
             env.get(theme::BORDER_DARK)
@@ -170,6 +170,39 @@ pub trait Widget<T> {
     /// [`set_origin`]: struct.WidgetPod.html#method.set_origin
     fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size;
 
+    /// Compute the size this widget would have under the given constraints,
+    /// without laying it out.
+    ///
+    /// This lets a container measure its children before deciding on the
+    /// constraints it gives them in [`layout`]; for example, a grid can
+    /// size each column to fit its widest child. Containers should measure
+    /// their children with [`WidgetPod::intrinsic_size`].
+    ///
+    /// The returned size *must* be the same as the size [`layout`] would
+    /// return with the same constraints, data, and env. An implementation
+    /// should only report this size, and not change the position or size of
+    /// the widget or of its children.
+    ///
+    /// The default implementation calls [`layout`]. When a widget is measured
+    /// through [`WidgetPod::intrinsic_size`], the layout state of it and of
+    /// its descendants, such as their sizes and origins, is restored
+    /// afterwards, and calls to [`WidgetPod::set_origin`] are ignored. A
+    /// container that overrides this should use the intrinsic sizes of its
+    /// children.
+    ///
+    /// [`layout`]: Widget::layout
+    /// [`WidgetPod::intrinsic_size`]: crate::WidgetPod::intrinsic_size
+    /// [`WidgetPod::set_origin`]: crate::WidgetPod::set_origin
+    fn intrinsic_size(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &T,
+        env: &Env,
+    ) -> Size {
+        self.layout(ctx, bc, data, env)
+    }
+
     /// Paint the widget appearance.
     ///
     /// The [`PaintCtx`] derefs to something that implements the [`RenderContext`]
@@ -276,6 +309,16 @@ impl<T> Widget<T> for Box<dyn Widget<T>> {
         self.deref_mut().layout(ctx, bc, data, env)
     }
 
+    fn intrinsic_size(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &T,
+        env: &Env,
+    ) -> Size {
+        self.deref_mut().intrinsic_size(ctx, bc, data, env)
+    }
+
     fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
         self.deref_mut().paint(ctx, data, env);
     }
@@ -305,11 +305,15 @@ impl<T: Data> InnerAppState<T> {
     /// our handlers `destroy()` method, at which point we can do our cleanup.
     fn request_close_window(&mut self, window_id: WindowId) {
         if let Some(win) = self.windows.get_mut(window_id) {
-            win.handle.close();
+            win.close();
         }
     }
 
     /// Requests the platform to close all windows.
+    ///
+    /// The windows are not marked as closing, so that on platforms that ask
+    /// the handler to close each window, it still receives
+    /// [`Event::WindowCloseRequested`] and [`Event::WindowDisconnected`].
     fn request_close_all_windows(&mut self) {
         for win in self.windows.iter_mut() {
             win.handle.close();
@@ -328,6 +332,12 @@ impl<T: Data> InnerAppState<T> {
         }
     }
 
+    fn set_window_title(&mut self, title: &str, id: WindowId) {
+        if let Some(win) = self.windows.get_mut(id) {
+            win.set_title(title);
+        }
+    }
+
     fn prepare_paint(&mut self, window_id: WindowId) {
         if let Some(win) = self.windows.get_mut(window_id) {
             win.prepare_paint(&mut self.command_queue, &mut self.data, &self.env);
@@ -362,6 +372,11 @@ impl<T: Data> InnerAppState<T> {
                 }
                 if let Some(w) = self.windows.get_mut(id) {
                     return if cmd.is(sys_cmd::CLOSE_WINDOW) {
+                        // some platforms request the close again while the
+                        // window is going away; the widgets are only asked once.
+                        if w.is_closing() {
+                            return Handled::Yes;
+                        }
                         let handled = w.event(
                             &mut self.command_queue,
                             Event::WindowCloseRequested,
@@ -675,6 +690,7 @@ impl<T: Data> AppState<T> {
             T::Window(id) if cmd.is(sys_cmd::SHOW_OPEN_PANEL) => self.show_open_panel(cmd, id),
             T::Window(id) if cmd.is(sys_cmd::SHOW_SAVE_PANEL) => self.show_save_panel(cmd, id),
             T::Window(id) if cmd.is(sys_cmd::CONFIGURE_WINDOW) => self.configure_window(cmd, id),
+            T::Window(id) if cmd.is(sys_cmd::SET_WINDOW_TITLE) => self.set_window_title(cmd, id),
             T::Window(id) if cmd.is(sys_cmd::CLOSE_WINDOW) => {
                 if !self.inner.borrow_mut().dispatch_cmd(cmd).is_handled() {
                     self.request_close_window(id);
@@ -814,6 +830,12 @@ impl<T: Data> AppState<T> {
         }
     }
 
+    fn set_window_title(&mut self, cmd: Command, id: WindowId) {
+        if let Some(title) = cmd.get(sys_cmd::SET_WINDOW_TITLE) {
+            self.inner.borrow_mut().set_window_title(title, id);
+        }
+    }
+
     fn do_paste(&mut self, window_id: WindowId) {
         let event = Event::Paste(self.inner.borrow().app.clipboard());
         self.inner.borrow_mut().do_window_event(window_id, event);
@@ -59,12 +59,16 @@ pub struct Window<T> {
     pub(crate) last_anim: Option<Instant>,
     pub(crate) last_mouse_pos: Option<Point>,
     pub(crate) focus: Option<WidgetId>,
+    /// `true` while a focus change caused by [`FocusChange::Previous`] is being routed.
+    focus_reversed: bool,
     pub(crate) handle: WindowHandle,
     pub(crate) timers: HashMap<TimerToken, WidgetId>,
     pub(crate) transparent: bool,
     pub(crate) ime_handlers: Vec<(TextFieldToken, TextFieldRegistration)>,
     ext_handle: ExtEventSink,
     pub(crate) ime_focus_change: Option<Option<TextFieldToken>>,
+    /// `true` once the window has been asked to close, and has not vetoed it.
+    closing: bool,
 }
 
 impl<T> Window<T> {
@@ -87,11 +91,13 @@ impl<T> Window<T> {
             last_anim: None,
             last_mouse_pos: None,
             focus: None,
+            focus_reversed: false,
             handle,
             timers: HashMap::new(),
             ext_handle,
             ime_handlers: Vec::new(),
             ime_focus_change: None,
+            closing: false,
         }
     }
 }
@@ -334,6 +340,7 @@ impl<T: Data> Window<T> {
         let mut widget_state = WidgetState::new(self.root.id(), Some(self.size));
         let mut state =
             ContextState::new::<T>(queue, &self.ext_handle, &self.handle, self.id, self.focus);
+        state.focus_reversed = self.focus_reversed;
         let mut ctx = LifeCycleCtx {
             state: &mut state,
             widget_state: &mut widget_state,
@@ -444,6 +451,7 @@ impl<T: Data> Window<T> {
             state: &mut state,
             widget_state: &mut widget_state,
             mouse_pos: self.last_mouse_pos,
+            measuring: false,
         };
         let bc = match self.size_policy {
             WindowSizePolicy::User => BoxConstraints::tight(self.size),
@@ -529,6 +537,37 @@ impl<T: Data> Window<T> {
         self.root.widget().debug_state(data)
     }
 
+    /// Set the title of this window, in response to [`SET_WINDOW_TITLE`].
+    ///
+    /// This replaces the title the window was created with; if that was a
+    /// localized or dynamic title, it will no longer be updated.
+    ///
+    /// [`SET_WINDOW_TITLE`]: crate::commands::SET_WINDOW_TITLE
+    pub(crate) fn set_title(&mut self, title: impl Into<String>) {
+        let title = title.into();
+        self.handle.set_title(&title);
+        self.title = LabelText::from(title);
+    }
+
+    /// Close this window, once a [`CLOSE_WINDOW`] request has not been vetoed.
+    ///
+    /// The window is marked as closing, so that close requests the platform
+    /// makes while the window goes away are granted without asking the
+    /// widgets again.
+    ///
+    /// [`CLOSE_WINDOW`]: crate::commands::CLOSE_WINDOW
+    pub(crate) fn close(&mut self) {
+        self.closing = true;
+        self.handle.close();
+    }
+
+    /// Returns `true` if [`close`] has been called on this window.
+    ///
+    /// [`close`]: Window::close
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing
+    }
+
     pub(crate) fn update_title(&mut self, data: &T, env: &Env) {
         if self.title.resolve(data, env) {
             self.handle.set_title(&self.title.display_text());
@@ -569,11 +608,14 @@ impl<T: Data> Window<T> {
     ) {
         if let Some(focus_req) = widget_state.request_focus.take() {
             let old = self.focus;
+            let reversed = matches!(focus_req, FocusChange::Previous);
             let new = self.widget_for_focus_request(focus_req);
             // Only send RouteFocusChanged in case there's actual change
             if old != new {
                 let event = LifeCycle::Internal(InternalLifeCycle::RouteFocusChanged { old, new });
+                self.focus_reversed = reversed;
                 self.lifecycle(queue, &event, data, env, false);
+                self.focus_reversed = false;
                 self.focus = new;
                 // check if the newly focused widget has an IME session, and
                 // notify the system if so.